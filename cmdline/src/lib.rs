@@ -0,0 +1,124 @@
+#![no_std]
+
+use core::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamError {
+    MissingValue,
+    InvalidValue,
+    Duplicate,
+}
+
+impl fmt::Display for ParamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingValue => f.write_str("missing value"),
+            Self::InvalidValue => f.write_str("invalid value"),
+            Self::Duplicate => f.write_str("given more than once"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Arg<'a> {
+    pub key: &'a str,
+    pub value: Option<&'a str>,
+}
+
+pub struct Args<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Iterator for Args<'a> {
+    type Item = Arg<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = self
+            .rest
+            .trim_start_matches(|c: char| c.is_ascii_whitespace());
+
+        let mut quoted = false;
+        let mut split = None;
+        let mut end = rest.len();
+        for (i, c) in rest.char_indices() {
+            match c {
+                '"' => quoted = !quoted,
+                '=' if !quoted && split.is_none() => split = Some(i),
+                c if !quoted && c.is_ascii_whitespace() => {
+                    end = i;
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        let arg = &rest[..end];
+        self.rest = &rest[end..];
+        if arg.is_empty() {
+            return None;
+        }
+
+        Some(match split {
+            Some(split) => Arg {
+                key: unquote(&arg[..split]),
+                value: Some(unquote(&arg[split + 1..])),
+            },
+            None => Arg {
+                key: unquote(arg),
+                value: None,
+            },
+        })
+    }
+}
+
+pub fn parse(cmdline: &str) -> Args<'_> {
+    Args { rest: cmdline }
+}
+
+fn unquote(s: &str) -> &str {
+    s.strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(s)
+}
+
+// A flag without a value turns it on.
+pub fn parse_bool(value: Option<&str>) -> Result<bool, ParamError> {
+    match value {
+        None | Some("1" | "y" | "yes" | "on" | "true") => Ok(true),
+        Some("0" | "n" | "no" | "off" | "false") => Ok(false),
+        Some(_) => Err(ParamError::InvalidValue),
+    }
+}
+
+pub fn parse_usize(value: Option<&str>) -> Result<usize, ParamError> {
+    let value = value.ok_or(ParamError::MissingValue)?;
+    let (digits, radix) = match value.strip_prefix("0x") {
+        Some(hex) => (hex, 16),
+        None => (value, 10),
+    };
+    usize::from_str_radix(digits, radix).map_err(|_| ParamError::InvalidValue)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Size(pub usize);
+
+impl Size {
+    #[inline]
+    pub const fn bytes(self) -> usize {
+        self.0
+    }
+
+    pub fn parse(value: Option<&str>) -> Result<Self, ParamError> {
+        let value = value.ok_or(ParamError::MissingValue)?;
+        let (digits, shift) = match value.as_bytes().last() {
+            Some(b'k' | b'K') => (&value[..value.len() - 1], 10),
+            Some(b'm' | b'M') => (&value[..value.len() - 1], 20),
+            Some(b'g' | b'G') => (&value[..value.len() - 1], 30),
+            _ => (value, 0),
+        };
+        parse_usize(Some(digits))?
+            .checked_mul(1 << shift)
+            .map(Size)
+            .ok_or(ParamError::InvalidValue)
+    }
+}
@@ -0,0 +1,67 @@
+use cmdline::*;
+
+fn args(cmdline: &str) -> Vec<(&str, Option<&str>)> {
+    parse(cmdline).map(|arg| (arg.key, arg.value)).collect()
+}
+
+#[test]
+fn keys_and_flags() {
+    assert_eq!(
+        args(" \t log=debug  nosmep smp=2 "),
+        [("log", Some("debug")), ("nosmep", None), ("smp", Some("2"))]
+    );
+    assert_eq!(args(""), []);
+    assert_eq!(args("   "), []);
+}
+
+#[test]
+fn quoting() {
+    assert_eq!(
+        args(r#"init="/bin/sh -l" "odd key"=1 x="a=b" y=a=b z="""#),
+        [
+            ("init", Some("/bin/sh -l")),
+            ("odd key", Some("1")),
+            ("x", Some("a=b")),
+            ("y", Some("a=b")),
+            ("z", Some("")),
+        ]
+    );
+    // An unterminated quote runs to the end of the line.
+    assert_eq!(args(r#"a="b c"#), [("a", Some(r#""b c"#))]);
+}
+
+#[test]
+fn bools() {
+    assert_eq!(parse_bool(None), Ok(true));
+    assert_eq!(parse_bool(Some("yes")), Ok(true));
+    assert_eq!(parse_bool(Some("1")), Ok(true));
+    assert_eq!(parse_bool(Some("off")), Ok(false));
+    assert_eq!(parse_bool(Some("false")), Ok(false));
+    assert_eq!(parse_bool(Some("maybe")), Err(ParamError::InvalidValue));
+}
+
+#[test]
+fn usizes() {
+    assert_eq!(parse_usize(Some("42")), Ok(42));
+    assert_eq!(parse_usize(Some("0x2a")), Ok(42));
+    assert_eq!(parse_usize(None), Err(ParamError::MissingValue));
+    assert_eq!(parse_usize(Some("")), Err(ParamError::InvalidValue));
+    assert_eq!(parse_usize(Some("-1")), Err(ParamError::InvalidValue));
+    assert_eq!(parse_usize(Some("0x")), Err(ParamError::InvalidValue));
+}
+
+#[test]
+fn sizes() {
+    assert_eq!(Size::parse(Some("512")), Ok(Size(512)));
+    assert_eq!(Size::parse(Some("4k")), Ok(Size(4 << 10)));
+    assert_eq!(Size::parse(Some("256M")), Ok(Size(256 << 20)));
+    assert_eq!(Size::parse(Some("2G")), Ok(Size(2 << 30)));
+    assert_eq!(Size::parse(Some("0x10K")), Ok(Size(16 << 10)));
+    assert_eq!(Size::parse(Some("M")), Err(ParamError::InvalidValue));
+    assert_eq!(Size::parse(Some("4T")), Err(ParamError::InvalidValue));
+    assert_eq!(
+        Size::parse(Some("18446744073709551615G")),
+        Err(ParamError::InvalidValue)
+    );
+    assert_eq!(Size::parse(None), Err(ParamError::MissingValue));
+}
@@ -0,0 +1,168 @@
+use crate::{Disk, FsError};
+
+use super::{
+    superblock::{GroupDesc, DESC_SIZE},
+    Ext2,
+};
+
+const SCAN_CHUNK: usize = 64;
+const ZERO_CHUNK: usize = 512;
+
+impl<D: Disk> Ext2<D> {
+    pub(super) fn group(&mut self, group: u32) -> Result<GroupDesc, FsError> {
+        if group >= self.sb.groups {
+            return Err(FsError::Corrupt("group out of range"));
+        }
+        let mut desc = GroupDesc([0; DESC_SIZE as usize]);
+        self.disk.read_at(self.sb.desc_offset(group), &mut desc.0)?;
+        Ok(desc)
+    }
+
+    fn write_group(&mut self, group: u32, desc: &GroupDesc) -> Result<(), FsError> {
+        self.disk.write_at(self.sb.desc_offset(group), &desc.0)
+    }
+
+    // The first clear bit in `start..count` of a bitmap block.
+    fn find_zero(&mut self, bitmap: u32, start: u32, count: u32) -> Result<Option<u32>, FsError> {
+        let base = bitmap as u64 * self.sb.block_size as u64;
+        let mut buf = [0u8; SCAN_CHUNK];
+        let mut byte = (start / 8) as usize;
+        let end = count.div_ceil(8) as usize;
+        while byte < end {
+            let len = (end - byte).min(SCAN_CHUNK);
+            self.disk.read_at(base + byte as u64, &mut buf[..len])?;
+            for (idx, &bits) in buf[..len].iter().enumerate() {
+                if bits == 0xff {
+                    continue;
+                }
+                for bit in 0..8 {
+                    let found = ((byte + idx) * 8 + bit) as u32;
+                    if bits & (1 << bit) == 0 && found >= start && found < count {
+                        return Ok(Some(found));
+                    }
+                }
+            }
+            byte += len;
+        }
+        Ok(None)
+    }
+
+    // Flips a bitmap bit and fails if it already had the new value.
+    fn set_bit(&mut self, bitmap: u32, bit: u32, value: bool) -> Result<(), FsError> {
+        let offset = bitmap as u64 * self.sb.block_size as u64 + (bit / 8) as u64;
+        let mask = 1 << (bit % 8);
+        let mut byte = [0];
+        self.disk.read_at(offset, &mut byte)?;
+        if (byte[0] & mask != 0) == value {
+            return Err(FsError::Corrupt("bitmap out of sync"));
+        }
+        byte[0] ^= mask;
+        self.disk.write_at(offset, &byte)
+    }
+
+    // Allocates a zeroed block, preferring `goal` and the blocks after it.
+    pub(super) fn alloc_block(&mut self, goal: u32) -> Result<u32, FsError> {
+        if self.sb.free_blocks == 0 {
+            return Err(FsError::NoSpace);
+        }
+        let goal = goal.clamp(self.sb.first_data_block, self.sb.blocks_count - 1);
+        let first = (goal - self.sb.first_data_block) / self.sb.blocks_per_group;
+        let first_bit = (goal - self.sb.first_data_block) % self.sb.blocks_per_group;
+
+        // The goal group is visited twice, so that the bits before the goal
+        // are tried last.
+        for step in 0..=self.sb.groups {
+            let group = (first + step) % self.sb.groups;
+            let mut desc = self.group(group)?;
+            if desc.free_blocks() == 0 {
+                continue;
+            }
+            let start = if step == 0 { first_bit } else { 0 };
+            let count = self.sb.group_blocks(group);
+            let Some(bit) = self.find_zero(desc.block_bitmap(), start, count)? else {
+                continue;
+            };
+            self.set_bit(desc.block_bitmap(), bit, true)?;
+            desc.set_free_blocks(desc.free_blocks() - 1);
+            self.write_group(group, &desc)?;
+            self.sb.free_blocks -= 1;
+            self.sb_dirty = true;
+
+            let block = self.sb.first_data_block + group * self.sb.blocks_per_group + bit;
+            self.zero_block(block)?;
+            return Ok(block);
+        }
+        Err(FsError::NoSpace)
+    }
+
+    fn zero_block(&mut self, block: u32) -> Result<(), FsError> {
+        let zeros = [0; ZERO_CHUNK];
+        let offset = block as u64 * self.sb.block_size as u64;
+        for chunk in (0..self.sb.block_size as u64).step_by(ZERO_CHUNK) {
+            self.disk.write_at(offset + chunk, &zeros)?;
+        }
+        Ok(())
+    }
+
+    pub(super) fn free_block(&mut self, block: u32) -> Result<(), FsError> {
+        if block < self.sb.first_data_block || block >= self.sb.blocks_count {
+            return Err(FsError::Corrupt("block out of range"));
+        }
+        let group = (block - self.sb.first_data_block) / self.sb.blocks_per_group;
+        let bit = (block - self.sb.first_data_block) % self.sb.blocks_per_group;
+        let mut desc = self.group(group)?;
+        self.set_bit(desc.block_bitmap(), bit, false)?;
+        desc.set_free_blocks(desc.free_blocks() + 1);
+        self.write_group(group, &desc)?;
+        self.sb.free_blocks += 1;
+        self.sb_dirty = true;
+        Ok(())
+    }
+
+    // New inodes go into the group of `near`, or the next one with room.
+    pub(super) fn alloc_inode(&mut self, near: u32, dir: bool) -> Result<u32, FsError> {
+        if self.sb.free_inodes == 0 {
+            return Err(FsError::NoSpace);
+        }
+        let first = (near - 1) / self.sb.inodes_per_group;
+        for step in 0..self.sb.groups {
+            let group = (first + step) % self.sb.groups;
+            let mut desc = self.group(group)?;
+            if desc.free_inodes() == 0 {
+                continue;
+            }
+            let base = group * self.sb.inodes_per_group;
+            // Inodes below the first usable one are reserved.
+            let start = self.sb.first_ino.saturating_sub(base + 1);
+            let count = self.sb.inodes_per_group.min(self.sb.inodes_count - base);
+            let Some(bit) = self.find_zero(desc.inode_bitmap(), start, count)? else {
+                continue;
+            };
+            self.set_bit(desc.inode_bitmap(), bit, true)?;
+            desc.set_free_inodes(desc.free_inodes() - 1);
+            if dir {
+                desc.set_used_dirs(desc.used_dirs() + 1);
+            }
+            self.write_group(group, &desc)?;
+            self.sb.free_inodes -= 1;
+            self.sb_dirty = true;
+            return Ok(base + bit + 1);
+        }
+        Err(FsError::NoSpace)
+    }
+
+    pub(super) fn free_inode(&mut self, ino: u32, dir: bool) -> Result<(), FsError> {
+        let group = (ino - 1) / self.sb.inodes_per_group;
+        let bit = (ino - 1) % self.sb.inodes_per_group;
+        let mut desc = self.group(group)?;
+        self.set_bit(desc.inode_bitmap(), bit, false)?;
+        desc.set_free_inodes(desc.free_inodes() + 1);
+        if dir {
+            desc.set_used_dirs(desc.used_dirs().saturating_sub(1));
+        }
+        self.write_group(group, &desc)?;
+        self.sb.free_inodes += 1;
+        self.sb_dirty = true;
+        Ok(())
+    }
+}
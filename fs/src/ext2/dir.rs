@@ -0,0 +1,276 @@
+use core::str;
+
+use crate::{read_u16, read_u32, Disk, FileType, FsError, Name, NAME_MAX};
+
+use super::{
+    inode::{Inode, FLAG_INDEX},
+    Ext2,
+};
+
+const HEADER_SIZE: usize = 8;
+const OFFSET_REC_LEN: u64 = 4;
+
+pub const FT_UNKNOWN: u8 = 0;
+pub const FT_REG_FILE: u8 = 1;
+pub const FT_DIR: u8 = 2;
+pub const FT_SYMLINK: u8 = 7;
+
+#[inline]
+fn entry_size(name_len: usize) -> usize {
+    (HEADER_SIZE + name_len).next_multiple_of(4)
+}
+
+pub fn file_type(kind: FileType) -> u8 {
+    match kind {
+        FileType::File => FT_REG_FILE,
+        FileType::Dir => FT_DIR,
+        FileType::Symlink => FT_SYMLINK,
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Dirent {
+    pub inode: u32,
+    pub rec_len: u16,
+    pub file_type: u8,
+    name: [u8; NAME_MAX],
+    name_len: u8,
+}
+
+impl Dirent {
+    #[inline]
+    pub fn name(&self) -> &[u8] {
+        &self.name[..self.name_len as usize]
+    }
+
+    #[inline]
+    pub fn is_dot(&self) -> bool {
+        self.name() == b"." || self.name() == b".."
+    }
+
+    pub fn display_name(&self) -> Name {
+        let mut name = Name::new();
+        match str::from_utf8(self.name()) {
+            Ok(s) => {
+                let _ = name.push_str(s);
+            }
+            Err(_) => {
+                for &byte in self.name() {
+                    let c = if byte.is_ascii() {
+                        byte as char
+                    } else {
+                        char::REPLACEMENT_CHARACTER
+                    };
+                    if name.push(c).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+        name
+    }
+}
+
+// A directory entry found by name, with the entry before it in the same
+// block which absorbs its space on removal.
+pub struct Found {
+    pub dirent: Dirent,
+    pub pos: u64,
+    pub prev: Option<u64>,
+}
+
+impl<D: Disk> Ext2<D> {
+    // The volume offset of byte `pos` of a directory.
+    fn dir_offset(&mut self, dir: &Inode, pos: u64) -> Result<u64, FsError> {
+        let block_size = self.sb.block_size as u64;
+        match self.map_block(dir, pos / block_size)? {
+            0 => Err(FsError::Corrupt("hole in directory")),
+            block => Ok(block as u64 * block_size + pos % block_size),
+        }
+    }
+
+    pub(super) fn read_dirent(&mut self, dir: &Inode, pos: u64) -> Result<Dirent, FsError> {
+        let offset = self.dir_offset(dir, pos)?;
+        let mut header = [0; HEADER_SIZE];
+        self.disk.read_at(offset, &mut header)?;
+
+        let rec_len = read_u16(&header, 4);
+        // Without the file type feature the name length takes both bytes.
+        let (name_len, file_type) = if self.sb.has_filetype() {
+            (header[6] as usize, header[7])
+        } else {
+            (read_u16(&header, 6) as usize, FT_UNKNOWN)
+        };
+        let within = pos % self.sb.block_size as u64;
+        if rec_len < HEADER_SIZE as u16
+            || rec_len & 3 != 0
+            || within + rec_len as u64 > self.sb.block_size as u64
+            || name_len > NAME_MAX
+            || entry_size(name_len) > rec_len as usize
+        {
+            return Err(FsError::Corrupt("bad directory entry"));
+        }
+
+        let mut dirent = Dirent {
+            inode: read_u32(&header, 0),
+            rec_len,
+            file_type,
+            name: [0; NAME_MAX],
+            name_len: name_len as u8,
+        };
+        self.disk
+            .read_at(offset + HEADER_SIZE as u64, &mut dirent.name[..name_len])?;
+        Ok(dirent)
+    }
+
+    fn write_dirent(
+        &mut self,
+        dir: &Inode,
+        pos: u64,
+        inode: u32,
+        rec_len: u16,
+        name: &[u8],
+        file_type: u8,
+    ) -> Result<(), FsError> {
+        let offset = self.dir_offset(dir, pos)?;
+        let mut header = [0; HEADER_SIZE];
+        header[..4].copy_from_slice(&inode.to_le_bytes());
+        header[4..6].copy_from_slice(&rec_len.to_le_bytes());
+        if self.sb.has_filetype() {
+            header[6] = name.len() as u8;
+            header[7] = file_type;
+        } else {
+            header[6..8].copy_from_slice(&(name.len() as u16).to_le_bytes());
+        }
+        self.disk.write_at(offset, &header)?;
+        self.disk.write_at(offset + HEADER_SIZE as u64, name)
+    }
+
+    fn set_rec_len(&mut self, dir: &Inode, pos: u64, rec_len: u16) -> Result<(), FsError> {
+        let offset = self.dir_offset(dir, pos)?;
+        self.disk
+            .write_at(offset + OFFSET_REC_LEN, &rec_len.to_le_bytes())
+    }
+
+    pub(super) fn find(&mut self, dir: &Inode, name: &[u8]) -> Result<Option<Found>, FsError> {
+        let block_size = self.sb.block_size as u64;
+        let size = dir.size();
+        let mut pos = 0;
+        let mut prev = None;
+        while pos < size {
+            if pos % block_size == 0 {
+                prev = None;
+            }
+            let dirent = self.read_dirent(dir, pos)?;
+            if dirent.inode != 0 && dirent.name() == name {
+                return Ok(Some(Found { dirent, pos, prev }));
+            }
+            prev = Some(pos);
+            pos += dirent.rec_len as u64;
+        }
+        Ok(None)
+    }
+
+    pub(super) fn is_empty_dir(&mut self, dir: &Inode) -> Result<bool, FsError> {
+        let size = dir.size();
+        let mut pos = 0;
+        while pos < size {
+            let dirent = self.read_dirent(dir, pos)?;
+            if dirent.inode != 0 && !dirent.is_dot() {
+                return Ok(false);
+            }
+            pos += dirent.rec_len as u64;
+        }
+        Ok(true)
+    }
+
+    fn drop_index(&mut self, ino: u32, dir: &mut Inode) -> Result<(), FsError> {
+        if dir.flags() & FLAG_INDEX != 0 {
+            dir.set_flags(dir.flags() & !FLAG_INDEX);
+            self.write_inode(ino, dir)?;
+        }
+        Ok(())
+    }
+
+    // Puts the entry into the first gap large enough, or a new block.
+    pub(super) fn add_dirent(
+        &mut self,
+        ino: u32,
+        dir: &mut Inode,
+        name: &[u8],
+        child: u32,
+        file_type: u8,
+    ) -> Result<(), FsError> {
+        self.drop_index(ino, dir)?;
+        let needed = entry_size(name.len());
+        let size = dir.size();
+        let mut pos = 0;
+        while pos < size {
+            let dirent = self.read_dirent(dir, pos)?;
+            let used = if dirent.inode == 0 {
+                0
+            } else {
+                entry_size(dirent.name().len())
+            };
+            let rec_len = dirent.rec_len as usize;
+            if rec_len - used >= needed {
+                if used != 0 {
+                    self.set_rec_len(dir, pos, used as u16)?;
+                }
+                let at = pos + used as u64;
+                return self.write_dirent(dir, at, child, (rec_len - used) as u16, name, file_type);
+            }
+            pos += rec_len as u64;
+        }
+
+        let block_size = self.sb.block_size;
+        self.map_block_alloc(ino, dir, size / block_size as u64)?;
+        dir.set_size(size + block_size as u64);
+        dir.touch(self.now());
+        self.write_inode(ino, dir)?;
+        // A record can't span 64 KiB blocks, the length wraps to 0 like
+        // e2fsprogs does it.
+        self.write_dirent(dir, size, child, block_size as u16, name, file_type)
+    }
+
+    pub(super) fn remove_dirent(
+        &mut self,
+        ino: u32,
+        dir: &mut Inode,
+        found: &Found,
+    ) -> Result<(), FsError> {
+        self.drop_index(ino, dir)?;
+        match found.prev {
+            Some(prev) => {
+                let prev_len = self.read_dirent(dir, prev)?.rec_len;
+                self.set_rec_len(dir, prev, prev_len + found.dirent.rec_len)
+            }
+            None => {
+                let offset = self.dir_offset(dir, found.pos)?;
+                self.disk.write_at(offset, &0u32.to_le_bytes())
+            }
+        }
+    }
+
+    // Writes "." and ".." into the first block of a new directory.
+    pub(super) fn init_dir(
+        &mut self,
+        ino: u32,
+        dir: &mut Inode,
+        parent: u32,
+    ) -> Result<(), FsError> {
+        let block_size = self.sb.block_size;
+        self.map_block_alloc(ino, dir, 0)?;
+        dir.set_size(block_size as u64);
+        let dot_len = entry_size(1) as u16;
+        self.write_dirent(dir, 0, ino, dot_len, b".", FT_DIR)?;
+        self.write_dirent(
+            dir,
+            dot_len as u64,
+            parent,
+            block_size as u16 - dot_len,
+            b"..",
+            FT_DIR,
+        )
+    }
+}
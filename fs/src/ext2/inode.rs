@@ -0,0 +1,423 @@
+use crate::{read_u16, read_u32, Disk, FileType, FsError};
+
+use super::Ext2;
+
+pub const BASE_SIZE: usize = 128;
+// Extra fields in use by e2fsprogs for inodes larger than the base size.
+pub const EXTRA_ISIZE: u16 = 32;
+
+const OFFSET_MODE: usize = 0;
+const OFFSET_UID: usize = 2;
+const OFFSET_SIZE: usize = 4;
+const OFFSET_ATIME: usize = 8;
+const OFFSET_CTIME: usize = 12;
+const OFFSET_MTIME: usize = 16;
+const OFFSET_DTIME: usize = 20;
+const OFFSET_GID: usize = 24;
+const OFFSET_LINKS: usize = 26;
+const OFFSET_BLOCKS: usize = 28;
+const OFFSET_FLAGS: usize = 32;
+const OFFSET_BLOCK: usize = 40;
+const OFFSET_FILE_ACL: usize = 104;
+const OFFSET_SIZE_HIGH: usize = 108;
+const OFFSET_UID_HIGH: usize = 120;
+const OFFSET_GID_HIGH: usize = 122;
+
+pub const S_IFMT: u16 = 0xf000;
+pub const S_IFDIR: u16 = 0x4000;
+pub const S_IFREG: u16 = 0x8000;
+pub const S_IFLNK: u16 = 0xa000;
+pub const PERMISSIONS: u16 = 0o7777;
+
+// Hashed directory indexes are not kept up to date and are dropped when a
+// directory changes.
+pub const FLAG_INDEX: u32 = 0x1000;
+
+pub const DIRECT_BLOCKS: usize = 12;
+const INDIRECT: usize = 12;
+const DOUBLE_INDIRECT: usize = 13;
+const TRIPLE_INDIRECT: usize = 14;
+pub const BLOCK_POINTERS: usize = 15;
+// Links with shorter targets keep them in the block pointers.
+pub const FAST_SYMLINK_MAX: usize = BLOCK_POINTERS * 4 - 1;
+
+const XATTR_MAGIC: u32 = 0xea02_0000;
+const XATTR_REFCOUNT: u64 = 4;
+
+#[derive(Clone, Copy)]
+pub struct Inode(pub [u8; BASE_SIZE]);
+
+impl Inode {
+    pub const EMPTY: Self = Self([0; BASE_SIZE]);
+
+    fn set_u16(&mut self, offset: usize, value: u16) {
+        self.0[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn set_u32(&mut self, offset: usize, value: u32) {
+        self.0[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    #[inline]
+    pub fn mode(&self) -> u16 {
+        read_u16(&self.0, OFFSET_MODE)
+    }
+
+    #[inline]
+    pub fn set_mode(&mut self, mode: u16) {
+        self.set_u16(OFFSET_MODE, mode);
+    }
+
+    pub fn kind(&self) -> FileType {
+        match self.mode() & S_IFMT {
+            S_IFDIR => FileType::Dir,
+            S_IFLNK => FileType::Symlink,
+            _ => FileType::File,
+        }
+    }
+
+    #[inline]
+    pub fn is_regular(&self) -> bool {
+        self.mode() & S_IFMT == S_IFREG
+    }
+
+    #[inline]
+    pub fn uid(&self) -> u32 {
+        read_u16(&self.0, OFFSET_UID) as u32 | (read_u16(&self.0, OFFSET_UID_HIGH) as u32) << 16
+    }
+
+    #[inline]
+    pub fn gid(&self) -> u32 {
+        read_u16(&self.0, OFFSET_GID) as u32 | (read_u16(&self.0, OFFSET_GID_HIGH) as u32) << 16
+    }
+
+    // The high half of the size is only meaningful for regular files.
+    pub fn size(&self) -> u64 {
+        let low = read_u32(&self.0, OFFSET_SIZE) as u64;
+        if self.is_regular() {
+            low | (read_u32(&self.0, OFFSET_SIZE_HIGH) as u64) << 32
+        } else {
+            low
+        }
+    }
+
+    pub fn set_size(&mut self, size: u64) {
+        self.set_u32(OFFSET_SIZE, size as u32);
+        if self.is_regular() {
+            self.set_u32(OFFSET_SIZE_HIGH, (size >> 32) as u32);
+        }
+    }
+
+    #[inline]
+    pub fn links(&self) -> u16 {
+        read_u16(&self.0, OFFSET_LINKS)
+    }
+
+    #[inline]
+    pub fn set_links(&mut self, links: u16) {
+        self.set_u16(OFFSET_LINKS, links);
+    }
+
+    // In 512 byte sectors, including indirect and attribute blocks.
+    #[inline]
+    pub fn sectors(&self) -> u32 {
+        read_u32(&self.0, OFFSET_BLOCKS)
+    }
+
+    #[inline]
+    pub fn set_sectors(&mut self, sectors: u32) {
+        self.set_u32(OFFSET_BLOCKS, sectors);
+    }
+
+    #[inline]
+    pub fn flags(&self) -> u32 {
+        read_u32(&self.0, OFFSET_FLAGS)
+    }
+
+    #[inline]
+    pub fn set_flags(&mut self, flags: u32) {
+        self.set_u32(OFFSET_FLAGS, flags);
+    }
+
+    #[inline]
+    pub fn block(&self, idx: usize) -> u32 {
+        read_u32(&self.0, OFFSET_BLOCK + idx * 4)
+    }
+
+    #[inline]
+    pub fn set_block(&mut self, idx: usize, block: u32) {
+        self.set_u32(OFFSET_BLOCK + idx * 4, block);
+    }
+
+    #[inline]
+    pub fn inline_data(&self) -> &[u8] {
+        &self.0[OFFSET_BLOCK..OFFSET_BLOCK + BLOCK_POINTERS * 4]
+    }
+
+    #[inline]
+    pub fn inline_data_mut(&mut self) -> &mut [u8] {
+        &mut self.0[OFFSET_BLOCK..OFFSET_BLOCK + BLOCK_POINTERS * 4]
+    }
+
+    #[inline]
+    pub fn file_acl(&self) -> u32 {
+        read_u32(&self.0, OFFSET_FILE_ACL)
+    }
+
+    #[inline]
+    pub fn mtime(&self) -> u32 {
+        read_u32(&self.0, OFFSET_MTIME)
+    }
+
+    pub fn touch(&mut self, now: u32) {
+        self.set_u32(OFFSET_CTIME, now);
+        self.set_u32(OFFSET_MTIME, now);
+    }
+
+    pub fn new(mode: u16, links: u16, now: u32) -> Self {
+        let mut inode = Self::EMPTY;
+        inode.set_mode(mode);
+        inode.set_links(links);
+        inode.set_u32(OFFSET_ATIME, now);
+        inode.touch(now);
+        inode
+    }
+
+    #[inline]
+    pub fn set_dtime(&mut self, now: u32) {
+        self.set_u32(OFFSET_DTIME, now);
+    }
+}
+
+impl<D: Disk> Ext2<D> {
+    #[inline]
+    fn pointers_per_block(&self) -> u64 {
+        self.sb.block_size as u64 / 4
+    }
+
+    #[inline]
+    pub(super) fn sectors_per_block(&self) -> u32 {
+        self.sb.block_size / 512
+    }
+
+    // Fast links have no data blocks, only possibly an attribute block.
+    pub(super) fn is_fast_symlink(&self, inode: &Inode) -> bool {
+        let xattr = if inode.file_acl() != 0 {
+            self.sectors_per_block()
+        } else {
+            0
+        };
+        inode.kind() == FileType::Symlink && inode.sectors() == xattr
+    }
+
+    fn inode_offset(&mut self, ino: u32) -> Result<u64, FsError> {
+        if ino == 0 || ino > self.sb.inodes_count {
+            return Err(FsError::NotFound);
+        }
+        let group = (ino - 1) / self.sb.inodes_per_group;
+        let index = (ino - 1) % self.sb.inodes_per_group;
+        let table = self.group(group)?.inode_table();
+        Ok(table as u64 * self.sb.block_size as u64 + index as u64 * self.sb.inode_size as u64)
+    }
+
+    pub(super) fn read_inode(&mut self, ino: u32) -> Result<Inode, FsError> {
+        let offset = self.inode_offset(ino)?;
+        let mut inode = Inode::EMPTY;
+        self.disk.read_at(offset, &mut inode.0)?;
+        Ok(inode)
+    }
+
+    pub(super) fn write_inode(&mut self, ino: u32, inode: &Inode) -> Result<(), FsError> {
+        let offset = self.inode_offset(ino)?;
+        self.disk.write_at(offset, &inode.0)
+    }
+
+    // Writes a new inode along with cleared extra space.
+    pub(super) fn init_inode(&mut self, ino: u32, inode: &Inode) -> Result<(), FsError> {
+        let offset = self.inode_offset(ino)?;
+        self.disk.write_at(offset, &inode.0)?;
+        let extra = self.sb.inode_size as usize - BASE_SIZE;
+        if extra == 0 {
+            return Ok(());
+        }
+        let zeros = [0; BASE_SIZE];
+        for chunk in (0..extra).step_by(BASE_SIZE) {
+            let at = offset + (BASE_SIZE + chunk) as u64;
+            self.disk
+                .write_at(at, &zeros[..(extra - chunk).min(BASE_SIZE)])?;
+        }
+        self.disk
+            .write_at(offset + BASE_SIZE as u64, &EXTRA_ISIZE.to_le_bytes())
+    }
+
+    fn read_pointer(&mut self, block: u32, idx: u64) -> Result<u32, FsError> {
+        let mut buf = [0; 4];
+        let offset = block as u64 * self.sb.block_size as u64 + idx * 4;
+        self.disk.read_at(offset, &mut buf)?;
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn write_pointer(&mut self, block: u32, idx: u64, value: u32) -> Result<(), FsError> {
+        let offset = block as u64 * self.sb.block_size as u64 + idx * 4;
+        self.disk.write_at(offset, &value.to_le_bytes())
+    }
+
+    // The block pointer slot of logical block `index` and the indices into
+    // the indirect blocks below it.
+    fn block_path(&self, index: u64) -> Result<(usize, [u64; 3], usize), FsError> {
+        let per = self.pointers_per_block();
+        let mut index = index;
+        if index < DIRECT_BLOCKS as u64 {
+            return Ok((index as usize, [0; 3], 0));
+        }
+        index -= DIRECT_BLOCKS as u64;
+        if index < per {
+            return Ok((INDIRECT, [index, 0, 0], 1));
+        }
+        index -= per;
+        if index < per * per {
+            return Ok((DOUBLE_INDIRECT, [index / per, index % per, 0], 2));
+        }
+        index -= per * per;
+        if index < per * per * per {
+            let path = [index / (per * per), index / per % per, index % per];
+            return Ok((TRIPLE_INDIRECT, path, 3));
+        }
+        Err(FsError::FileTooLarge)
+    }
+
+    // The block holding logical block `index`, 0 for a hole.
+    pub(super) fn map_block(&mut self, inode: &Inode, index: u64) -> Result<u32, FsError> {
+        let (slot, path, depth) = self.block_path(index)?;
+        let mut block = inode.block(slot);
+        for &idx in &path[..depth] {
+            if block == 0 {
+                break;
+            }
+            block = self.read_pointer(block, idx)?;
+        }
+        Ok(block)
+    }
+
+    // Like `map_block`, but fills holes along the way with new blocks.
+    pub(super) fn map_block_alloc(
+        &mut self,
+        ino: u32,
+        inode: &mut Inode,
+        index: u64,
+    ) -> Result<u32, FsError> {
+        let (slot, path, depth) = self.block_path(index)?;
+        let goal = self.sb.first_data_block
+            + (ino - 1) / self.sb.inodes_per_group * self.sb.blocks_per_group;
+
+        let mut block = inode.block(slot);
+        if block == 0 {
+            block = self.alloc_block(goal)?;
+            inode.set_block(slot, block);
+            inode.set_sectors(inode.sectors() + self.sectors_per_block());
+        }
+        for &idx in &path[..depth] {
+            let next = self.read_pointer(block, idx)?;
+            if next != 0 {
+                block = next;
+                continue;
+            }
+            let next = self.alloc_block(block + 1)?;
+            inode.set_sectors(inode.sectors() + self.sectors_per_block());
+            self.write_pointer(block, idx, next)?;
+            block = next;
+        }
+        Ok(block)
+    }
+
+    // Frees the blocks from logical block `from` on.
+    pub(super) fn truncate_blocks(&mut self, inode: &mut Inode, from: u64) -> Result<(), FsError> {
+        for slot in (from as usize).min(DIRECT_BLOCKS)..DIRECT_BLOCKS {
+            let block = inode.block(slot);
+            if block != 0 {
+                self.release(inode, block)?;
+                inode.set_block(slot, 0);
+            }
+        }
+
+        let per = self.pointers_per_block();
+        let mut base = DIRECT_BLOCKS as u64;
+        let mut span = per;
+        for (level, slot) in [INDIRECT, DOUBLE_INDIRECT, TRIPLE_INDIRECT]
+            .into_iter()
+            .enumerate()
+        {
+            let block = inode.block(slot);
+            if block != 0
+                && self.truncate_tree(inode, block, level as u32 + 1, from.saturating_sub(base))?
+            {
+                inode.set_block(slot, 0);
+            }
+            base += span;
+            span *= per;
+        }
+        Ok(())
+    }
+
+    // Frees the part of an indirect tree from logical block `from` on and
+    // returns whether the tree went away entirely.
+    fn truncate_tree(
+        &mut self,
+        inode: &mut Inode,
+        block: u32,
+        level: u32,
+        from: u64,
+    ) -> Result<bool, FsError> {
+        let per = self.pointers_per_block();
+        let span = per.pow(level - 1);
+        for idx in from / span..per {
+            let child = self.read_pointer(block, idx)?;
+            if child == 0 {
+                continue;
+            }
+            let child_from = from.saturating_sub(idx * span);
+            let gone = if level == 1 {
+                self.release(inode, child)?;
+                true
+            } else {
+                self.truncate_tree(inode, child, level - 1, child_from)?
+            };
+            if gone {
+                self.write_pointer(block, idx, 0)?;
+            }
+        }
+        if from == 0 {
+            self.release(inode, block)?;
+        }
+        Ok(from == 0)
+    }
+
+    fn release(&mut self, inode: &mut Inode, block: u32) -> Result<(), FsError> {
+        self.free_block(block)?;
+        let sectors = inode.sectors().saturating_sub(self.sectors_per_block());
+        inode.set_sectors(sectors);
+        Ok(())
+    }
+
+    // Drops the reference to a shared extended attribute block.
+    pub(super) fn release_xattr(&mut self, inode: &mut Inode) -> Result<(), FsError> {
+        let block = inode.file_acl();
+        if block == 0 {
+            return Ok(());
+        }
+        let offset = block as u64 * self.sb.block_size as u64;
+        let mut header = [0; 8];
+        self.disk.read_at(offset, &mut header)?;
+        let refcount = read_u32(&header, XATTR_REFCOUNT as usize);
+        if read_u32(&header, 0) == XATTR_MAGIC && refcount > 1 {
+            self.disk
+                .write_at(offset + XATTR_REFCOUNT, &(refcount - 1).to_le_bytes())?;
+            let sectors = inode.sectors().saturating_sub(self.sectors_per_block());
+            inode.set_sectors(sectors);
+        } else {
+            self.release(inode, block)?;
+        }
+        inode.set_u32(OFFSET_FILE_ACL, 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,437 @@
+use crate::{DirEntry, Disk, FileSystem, FileType, FsError, Ino, Metadata, NAME_MAX, PATH_MAX};
+
+use inode::{Inode, FAST_SYMLINK_MAX, PERMISSIONS, S_IFDIR, S_IFLNK, S_IFREG};
+use superblock::{Superblock, RO_COMPAT_LARGE_FILE};
+
+mod alloc;
+mod dir;
+mod inode;
+mod superblock;
+
+const ROOT_INO: u32 = 2;
+// Files past 2 GiB need the large file feature.
+const LARGE_FILE_SIZE: u64 = i32::MAX as u64;
+
+// Inodes are the ext2 inode numbers. Hashed directory indexes are ignored
+// and dropped on changes, so directories are always scanned linearly.
+pub struct Ext2<D> {
+    disk: D,
+    sb: Superblock,
+    sb_dirty: bool,
+    clock: Option<fn() -> u64>,
+}
+
+fn validate(name: &str) -> Result<(), FsError> {
+    if name.len() > NAME_MAX {
+        return Err(FsError::NameTooLong);
+    }
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\0']) {
+        return Err(FsError::InvalidName);
+    }
+    Ok(())
+}
+
+impl<D: Disk> Ext2<D> {
+    pub fn mount(mut disk: D) -> Result<Self, FsError> {
+        let sb = Superblock::read(&mut disk)?;
+        let mut ext2 = Self {
+            disk,
+            sb,
+            sb_dirty: false,
+            clock: None,
+        };
+        let (_, root) = ext2.load(ROOT_INO as Ino)?;
+        if root.kind() != FileType::Dir {
+            return Err(FsError::Corrupt("root is not a directory"));
+        }
+        Ok(ext2)
+    }
+
+    // Without a clock, timestamps stay at the last write time of the volume.
+    // e2fsck takes small deletion times for orphan list links.
+    pub fn with_clock(mut self, clock: fn() -> u64) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    #[inline]
+    pub fn block_size(&self) -> u32 {
+        self.sb.block_size
+    }
+
+    #[inline]
+    pub fn free_blocks(&self) -> u32 {
+        self.sb.free_blocks
+    }
+
+    #[inline]
+    pub fn free_inodes(&self) -> u32 {
+        self.sb.free_inodes
+    }
+
+    pub fn into_disk(self) -> D {
+        self.disk
+    }
+
+    #[inline]
+    fn now(&self) -> u32 {
+        self.clock.map_or(self.sb.wtime, |clock| clock() as u32)
+    }
+
+    fn check_writable(&self) -> Result<(), FsError> {
+        if self.disk.is_read_only() || self.sb.read_only {
+            Err(FsError::ReadOnly)
+        } else {
+            Ok(())
+        }
+    }
+
+    // An inode in use, which it stops being once its last link is gone.
+    fn load(&mut self, ino: Ino) -> Result<(u32, Inode), FsError> {
+        let ino = u32::try_from(ino).map_err(|_| FsError::NotFound)?;
+        let inode = self.read_inode(ino)?;
+        if inode.links() == 0 {
+            return Err(FsError::NotFound);
+        }
+        Ok((ino, inode))
+    }
+
+    fn load_dir(&mut self, ino: Ino) -> Result<(u32, Inode), FsError> {
+        let (ino, inode) = self.load(ino)?;
+        if inode.kind() != FileType::Dir {
+            return Err(FsError::NotDir);
+        }
+        Ok((ino, inode))
+    }
+
+    fn load_file(&mut self, ino: Ino) -> Result<(u32, Inode), FsError> {
+        let (ino, inode) = self.load(ino)?;
+        match inode.kind() {
+            FileType::File => Ok((ino, inode)),
+            FileType::Dir => Err(FsError::IsDir),
+            FileType::Symlink => Err(FsError::Unsupported),
+        }
+    }
+
+    // Frees everything an inode holds once it has no links left.
+    fn destroy(&mut self, ino: u32, inode: &mut Inode) -> Result<(), FsError> {
+        if !self.is_fast_symlink(inode) {
+            self.truncate_blocks(inode, 0)?;
+        }
+        self.release_xattr(inode)?;
+        inode.set_links(0);
+        inode.set_dtime(self.now());
+        self.write_inode(ino, inode)?;
+        self.free_inode(ino, inode.kind() == FileType::Dir)
+    }
+
+    // Creates an inode of `kind` and links it into `dir`. Links get
+    // `target` as their contents.
+    fn add(&mut self, dir: Ino, name: &str, kind: FileType, target: &str) -> Result<Ino, FsError> {
+        self.check_writable()?;
+        validate(name)?;
+        let (dir_ino, mut dir) = self.load_dir(dir)?;
+        if self.find(&dir, name.as_bytes())?.is_some() {
+            return Err(FsError::Exists);
+        }
+
+        let now = self.now();
+        let ino = self.alloc_inode(dir_ino, kind == FileType::Dir)?;
+        let mut inode = match kind {
+            FileType::File => Inode::new(S_IFREG | 0o644, 1, now),
+            FileType::Dir => Inode::new(S_IFDIR | 0o755, 2, now),
+            FileType::Symlink => Inode::new(S_IFLNK | 0o777, 1, now),
+        };
+        let result = self
+            .fill(ino, &mut inode, dir_ino, target)
+            .and_then(|()| self.init_inode(ino, &inode))
+            .and_then(|()| {
+                self.add_dirent(
+                    dir_ino,
+                    &mut dir,
+                    name.as_bytes(),
+                    ino,
+                    dir::file_type(kind),
+                )
+            });
+        if let Err(err) = result {
+            self.destroy(ino, &mut inode)?;
+            return Err(err);
+        }
+
+        if kind == FileType::Dir {
+            dir.set_links(dir.links() + 1);
+        }
+        dir.touch(now);
+        self.write_inode(dir_ino, &dir)?;
+        Ok(ino as Ino)
+    }
+
+    fn fill(
+        &mut self,
+        ino: u32,
+        inode: &mut Inode,
+        parent: u32,
+        target: &str,
+    ) -> Result<(), FsError> {
+        match inode.kind() {
+            FileType::File => Ok(()),
+            FileType::Dir => self.init_dir(ino, inode, parent),
+            FileType::Symlink if target.len() <= FAST_SYMLINK_MAX => {
+                inode.inline_data_mut()[..target.len()].copy_from_slice(target.as_bytes());
+                inode.set_size(target.len() as u64);
+                Ok(())
+            }
+            FileType::Symlink => {
+                let block = self.map_block_alloc(ino, inode, 0)?;
+                let offset = block as u64 * self.sb.block_size as u64;
+                self.disk.write_at(offset, target.as_bytes())?;
+                inode.set_size(target.len() as u64);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<D: Disk> FileSystem for Ext2<D> {
+    fn name(&self) -> &'static str {
+        "ext2"
+    }
+
+    fn root(&self) -> Ino {
+        ROOT_INO as Ino
+    }
+
+    fn lookup(&mut self, dir: Ino, name: &str) -> Result<Ino, FsError> {
+        let (_, dir) = self.load_dir(dir)?;
+        if name.len() > NAME_MAX {
+            return Err(FsError::NameTooLong);
+        }
+        match self.find(&dir, name.as_bytes())? {
+            Some(found) => Ok(found.dirent.inode as Ino),
+            None => Err(FsError::NotFound),
+        }
+    }
+
+    fn metadata(&mut self, ino: Ino) -> Result<Metadata, FsError> {
+        let (_, inode) = self.load(ino)?;
+        Ok(Metadata {
+            ino,
+            kind: inode.kind(),
+            size: inode.size(),
+            mode: inode.mode() & PERMISSIONS,
+            links: inode.links(),
+            uid: inode.uid(),
+            gid: inode.gid(),
+            mtime: inode.mtime() as u64,
+        })
+    }
+
+    fn read(&mut self, ino: Ino, offset: u64, buf: &mut [u8]) -> Result<usize, FsError> {
+        let (_, inode) = self.load_file(ino)?;
+        let size = inode.size();
+        if offset >= size || buf.is_empty() {
+            return Ok(0);
+        }
+        let len = ((size - offset) as usize).min(buf.len());
+        let block_size = self.sb.block_size as u64;
+        let mut done = 0;
+        while done < len {
+            let pos = offset + done as u64;
+            let within = pos % block_size;
+            let chunk = ((block_size - within) as usize).min(len - done);
+            let dst = &mut buf[done..done + chunk];
+            match self.map_block(&inode, pos / block_size)? {
+                0 => dst.fill(0),
+                block => self.disk.read_at(block as u64 * block_size + within, dst)?,
+            }
+            done += chunk;
+        }
+        Ok(len)
+    }
+
+    // A write that runs out of space part way stores what fit.
+    fn write(&mut self, ino: Ino, offset: u64, buf: &[u8]) -> Result<usize, FsError> {
+        self.check_writable()?;
+        let (ino, mut inode) = self.load_file(ino)?;
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        offset
+            .checked_add(buf.len() as u64)
+            .ok_or(FsError::FileTooLarge)?;
+
+        let block_size = self.sb.block_size as u64;
+        let mut done = 0;
+        let mut result = Ok(());
+        while done < buf.len() {
+            let pos = offset + done as u64;
+            let within = pos % block_size;
+            let chunk = ((block_size - within) as usize).min(buf.len() - done);
+            result = self
+                .map_block_alloc(ino, &mut inode, pos / block_size)
+                .and_then(|block| {
+                    let at = block as u64 * block_size + within;
+                    self.disk.write_at(at, &buf[done..done + chunk])
+                });
+            if result.is_err() {
+                break;
+            }
+            done += chunk;
+        }
+
+        let written = offset + done as u64;
+        if written > inode.size() {
+            inode.set_size(written);
+            if written > LARGE_FILE_SIZE && self.sb.feature_ro_compat & RO_COMPAT_LARGE_FILE == 0 {
+                self.sb.feature_ro_compat |= RO_COMPAT_LARGE_FILE;
+                self.sb_dirty = true;
+            }
+        }
+        inode.touch(self.now());
+        self.write_inode(ino, &inode)?;
+        match result {
+            Err(err) if done == 0 => Err(err),
+            _ => Ok(done),
+        }
+    }
+
+    fn truncate(&mut self, ino: Ino, size: u64) -> Result<(), FsError> {
+        self.check_writable()?;
+        let (ino, mut inode) = self.load_file(ino)?;
+        let block_size = self.sb.block_size as u64;
+        if size < inode.size() {
+            self.truncate_blocks(&mut inode, size.div_ceil(block_size))?;
+            // Growing again later has to read zeros past the old end.
+            let within = size % block_size;
+            if within != 0 {
+                let block = self.map_block(&inode, size / block_size)?;
+                if block != 0 {
+                    let zeros = [0; 512];
+                    let mut at = within;
+                    while at < block_size {
+                        let chunk = (block_size - at).min(zeros.len() as u64);
+                        let offset = block as u64 * block_size + at;
+                        self.disk.write_at(offset, &zeros[..chunk as usize])?;
+                        at += chunk;
+                    }
+                }
+            }
+        } else if size > LARGE_FILE_SIZE && self.sb.feature_ro_compat & RO_COMPAT_LARGE_FILE == 0 {
+            self.sb.feature_ro_compat |= RO_COMPAT_LARGE_FILE;
+            self.sb_dirty = true;
+        }
+        inode.set_size(size);
+        inode.touch(self.now());
+        self.write_inode(ino, &inode)
+    }
+
+    fn read_dir(&mut self, dir: Ino, pos: u64) -> Result<Option<(DirEntry, u64)>, FsError> {
+        let (_, dir) = self.load_dir(dir)?;
+        let size = dir.size();
+        let mut pos = pos;
+        while pos < size {
+            let dirent = self.read_dirent(&dir, pos)?;
+            pos += dirent.rec_len as u64;
+            if dirent.inode == 0 || dirent.is_dot() {
+                continue;
+            }
+            let kind = match dirent.file_type {
+                dir::FT_REG_FILE => FileType::File,
+                dir::FT_DIR => FileType::Dir,
+                dir::FT_SYMLINK => FileType::Symlink,
+                _ => self.read_inode(dirent.inode)?.kind(),
+            };
+            let entry = DirEntry {
+                ino: dirent.inode as Ino,
+                kind,
+                name: dirent.display_name(),
+            };
+            return Ok(Some((entry, pos)));
+        }
+        Ok(None)
+    }
+
+    fn create(&mut self, dir: Ino, name: &str, kind: FileType) -> Result<Ino, FsError> {
+        if kind == FileType::Symlink {
+            return Err(FsError::Unsupported);
+        }
+        self.add(dir, name, kind, "")
+    }
+
+    fn remove(&mut self, dir: Ino, name: &str) -> Result<(), FsError> {
+        self.check_writable()?;
+        if name == "." || name == ".." {
+            return Err(FsError::InvalidName);
+        }
+        let (dir_ino, mut dir) = self.load_dir(dir)?;
+        let found = self.find(&dir, name.as_bytes())?.ok_or(FsError::NotFound)?;
+        let (ino, mut inode) = self.load(found.dirent.inode as Ino)?;
+        let is_dir = inode.kind() == FileType::Dir;
+        if is_dir && !self.is_empty_dir(&inode)? {
+            return Err(FsError::NotEmpty);
+        }
+
+        self.remove_dirent(dir_ino, &mut dir, &found)?;
+        let now = self.now();
+        if is_dir {
+            dir.set_links(dir.links().saturating_sub(1));
+        }
+        dir.touch(now);
+        self.write_inode(dir_ino, &dir)?;
+
+        // Directories hold a link to themselves through ".".
+        let links = if is_dir {
+            0
+        } else {
+            inode.links().saturating_sub(1)
+        };
+        if links == 0 {
+            self.destroy(ino, &mut inode)
+        } else {
+            inode.set_links(links);
+            self.write_inode(ino, &inode)
+        }
+    }
+
+    fn sync(&mut self) -> Result<(), FsError> {
+        if self.sb_dirty {
+            self.sb.wtime = self.now();
+            self.sb.write(&mut self.disk)?;
+            self.sb_dirty = false;
+        }
+        self.disk.flush()
+    }
+
+    fn symlink(&mut self, dir: Ino, name: &str, target: &str) -> Result<Ino, FsError> {
+        if target.is_empty() {
+            return Err(FsError::InvalidName);
+        }
+        if target.len() >= PATH_MAX || target.len() >= self.sb.block_size as usize {
+            return Err(FsError::NameTooLong);
+        }
+        self.add(dir, name, FileType::Symlink, target)
+    }
+
+    fn read_link(&mut self, ino: Ino, buf: &mut [u8]) -> Result<usize, FsError> {
+        let (_, inode) = self.load(ino)?;
+        if inode.kind() != FileType::Symlink {
+            return Err(FsError::Unsupported);
+        }
+        let len = (inode.size() as usize).min(buf.len());
+        if self.is_fast_symlink(&inode) {
+            let data = inode.inline_data();
+            let len = len.min(data.len());
+            buf[..len].copy_from_slice(&data[..len]);
+            return Ok(len);
+        }
+        match self.map_block(&inode, 0)? {
+            0 => Err(FsError::Corrupt("symlink without data")),
+            block => {
+                let offset = block as u64 * self.sb.block_size as u64;
+                self.disk.read_at(offset, &mut buf[..len])?;
+                Ok(len)
+            }
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use crate::{read_u16, read_u32, Disk, FsError};
+
+pub const SUPERBLOCK_OFFSET: u64 = 1024;
+const SUPERBLOCK_SIZE: usize = 1024;
+const MAGIC: u16 = 0xef53;
+
+const OFFSET_INODES_COUNT: usize = 0;
+const OFFSET_BLOCKS_COUNT: usize = 4;
+const OFFSET_FREE_BLOCKS: usize = 12;
+const OFFSET_FREE_INODES: usize = 16;
+const OFFSET_FIRST_DATA_BLOCK: usize = 20;
+const OFFSET_LOG_BLOCK_SIZE: usize = 24;
+const OFFSET_BLOCKS_PER_GROUP: usize = 32;
+const OFFSET_INODES_PER_GROUP: usize = 40;
+const OFFSET_WTIME: usize = 48;
+const OFFSET_MAGIC: usize = 56;
+const OFFSET_REV_LEVEL: usize = 76;
+const OFFSET_FIRST_INO: usize = 84;
+const OFFSET_INODE_SIZE: usize = 88;
+const OFFSET_FEATURE_INCOMPAT: usize = 96;
+const OFFSET_FEATURE_RO_COMPAT: usize = 100;
+
+const REV_GOOD_OLD: u32 = 0;
+const GOOD_OLD_FIRST_INO: u32 = 11;
+const GOOD_OLD_INODE_SIZE: u32 = 128;
+const MAX_LOG_BLOCK_SIZE: u32 = 6;
+
+pub const INCOMPAT_FILETYPE: u32 = 0x0002;
+const INCOMPAT_SUPPORTED: u32 = INCOMPAT_FILETYPE;
+
+const RO_COMPAT_SPARSE_SUPER: u32 = 0x0001;
+pub const RO_COMPAT_LARGE_FILE: u32 = 0x0002;
+const RO_COMPAT_BTREE_DIR: u32 = 0x0004;
+const RO_COMPAT_SUPPORTED: u32 =
+    RO_COMPAT_SPARSE_SUPER | RO_COMPAT_LARGE_FILE | RO_COMPAT_BTREE_DIR;
+
+pub const DESC_SIZE: u64 = 32;
+const DESC_BLOCK_BITMAP: usize = 0;
+const DESC_INODE_BITMAP: usize = 4;
+const DESC_INODE_TABLE: usize = 8;
+const DESC_FREE_BLOCKS: usize = 12;
+const DESC_FREE_INODES: usize = 14;
+const DESC_USED_DIRS: usize = 16;
+
+// The fields in use, counts are kept here and written back on sync.
+#[derive(Debug, Clone, Copy)]
+pub struct Superblock {
+    pub inodes_count: u32,
+    pub blocks_count: u32,
+    pub free_blocks: u32,
+    pub free_inodes: u32,
+    pub first_data_block: u32,
+    pub block_size: u32,
+    pub blocks_per_group: u32,
+    pub inodes_per_group: u32,
+    pub first_ino: u32,
+    pub inode_size: u32,
+    pub groups: u32,
+    pub wtime: u32,
+    pub feature_incompat: u32,
+    pub feature_ro_compat: u32,
+    // Set when the volume uses features that are only safe to read.
+    pub read_only: bool,
+}
+
+impl Superblock {
+    pub fn read(disk: &mut dyn Disk) -> Result<Self, FsError> {
+        let mut raw = [0; SUPERBLOCK_SIZE];
+        disk.read_at(SUPERBLOCK_OFFSET, &mut raw)?;
+        if read_u16(&raw, OFFSET_MAGIC) != MAGIC {
+            return Err(FsError::Corrupt("bad superblock magic"));
+        }
+
+        let log_block_size = read_u32(&raw, OFFSET_LOG_BLOCK_SIZE);
+        if log_block_size > MAX_LOG_BLOCK_SIZE {
+            return Err(FsError::Corrupt("bad block size"));
+        }
+        let block_size = 1024 << log_block_size;
+        let (first_ino, inode_size, incompat, ro_compat) =
+            if read_u32(&raw, OFFSET_REV_LEVEL) == REV_GOOD_OLD {
+                (GOOD_OLD_FIRST_INO, GOOD_OLD_INODE_SIZE, 0, 0)
+            } else {
+                (
+                    read_u32(&raw, OFFSET_FIRST_INO),
+                    read_u16(&raw, OFFSET_INODE_SIZE) as u32,
+                    read_u32(&raw, OFFSET_FEATURE_INCOMPAT),
+                    read_u32(&raw, OFFSET_FEATURE_RO_COMPAT),
+                )
+            };
+        if incompat & !INCOMPAT_SUPPORTED != 0 {
+            return Err(FsError::Unsupported);
+        }
+
+        let sb = Self {
+            inodes_count: read_u32(&raw, OFFSET_INODES_COUNT),
+            blocks_count: read_u32(&raw, OFFSET_BLOCKS_COUNT),
+            free_blocks: read_u32(&raw, OFFSET_FREE_BLOCKS),
+            free_inodes: read_u32(&raw, OFFSET_FREE_INODES),
+            first_data_block: read_u32(&raw, OFFSET_FIRST_DATA_BLOCK),
+            block_size,
+            blocks_per_group: read_u32(&raw, OFFSET_BLOCKS_PER_GROUP),
+            inodes_per_group: read_u32(&raw, OFFSET_INODES_PER_GROUP),
+            first_ino,
+            inode_size,
+            groups: 0,
+            wtime: read_u32(&raw, OFFSET_WTIME),
+            feature_incompat: incompat,
+            feature_ro_compat: ro_compat,
+            read_only: ro_compat & !RO_COMPAT_SUPPORTED != 0,
+        };
+        let bits = block_size * 8;
+        if sb.blocks_per_group == 0
+            || sb.blocks_per_group > bits
+            || sb.inodes_per_group == 0
+            || sb.inodes_per_group > bits
+            || sb.first_data_block >= sb.blocks_count
+            || !(GOOD_OLD_INODE_SIZE..=block_size).contains(&inode_size)
+            || !inode_size.is_power_of_two()
+        {
+            return Err(FsError::Corrupt("bad superblock"));
+        }
+        let groups = (sb.blocks_count - sb.first_data_block).div_ceil(sb.blocks_per_group);
+        if sb.inodes_count as u64 > groups as u64 * sb.inodes_per_group as u64 || sb.first_ino < 3 {
+            return Err(FsError::Corrupt("bad superblock"));
+        }
+        if sb.blocks_count as u64 * block_size as u64 > disk.size() {
+            return Err(FsError::Corrupt("volume larger than disk"));
+        }
+        Ok(Self { groups, ..sb })
+    }
+
+    pub fn write(&self, disk: &mut dyn Disk) -> Result<(), FsError> {
+        let fields = [
+            (OFFSET_FREE_BLOCKS, self.free_blocks),
+            (OFFSET_FREE_INODES, self.free_inodes),
+            (OFFSET_WTIME, self.wtime),
+        ];
+        for (offset, value) in fields {
+            disk.write_at(SUPERBLOCK_OFFSET + offset as u64, &value.to_le_bytes())?;
+        }
+        // Only a dynamic revision superblock has feature flags.
+        if self.feature_ro_compat != 0 {
+            let offset = SUPERBLOCK_OFFSET + OFFSET_FEATURE_RO_COMPAT as u64;
+            disk.write_at(offset, &self.feature_ro_compat.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn has_filetype(&self) -> bool {
+        self.feature_incompat & INCOMPAT_FILETYPE != 0
+    }
+
+    // The last group may be shorter than the others.
+    pub fn group_blocks(&self, group: u32) -> u32 {
+        let start = self.first_data_block + group * self.blocks_per_group;
+        (self.blocks_count - start).min(self.blocks_per_group)
+    }
+
+    #[inline]
+    pub fn desc_offset(&self, group: u32) -> u64 {
+        (self.first_data_block as u64 + 1) * self.block_size as u64 + group as u64 * DESC_SIZE
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct GroupDesc(pub [u8; DESC_SIZE as usize]);
+
+impl GroupDesc {
+    #[inline]
+    pub fn block_bitmap(&self) -> u32 {
+        read_u32(&self.0, DESC_BLOCK_BITMAP)
+    }
+
+    #[inline]
+    pub fn inode_bitmap(&self) -> u32 {
+        read_u32(&self.0, DESC_INODE_BITMAP)
+    }
+
+    #[inline]
+    pub fn inode_table(&self) -> u32 {
+        read_u32(&self.0, DESC_INODE_TABLE)
+    }
+
+    #[inline]
+    pub fn free_blocks(&self) -> u16 {
+        read_u16(&self.0, DESC_FREE_BLOCKS)
+    }
+
+    #[inline]
+    pub fn free_inodes(&self) -> u16 {
+        read_u16(&self.0, DESC_FREE_INODES)
+    }
+
+    #[inline]
+    pub fn used_dirs(&self) -> u16 {
+        read_u16(&self.0, DESC_USED_DIRS)
+    }
+
+    #[inline]
+    pub fn set_free_blocks(&mut self, count: u16) {
+        self.0[DESC_FREE_BLOCKS..DESC_FREE_BLOCKS + 2].copy_from_slice(&count.to_le_bytes());
+    }
+
+    #[inline]
+    pub fn set_free_inodes(&mut self, count: u16) {
+        self.0[DESC_FREE_INODES..DESC_FREE_INODES + 2].copy_from_slice(&count.to_le_bytes());
+    }
+
+    #[inline]
+    pub fn set_used_dirs(&mut self, count: u16) {
+        self.0[DESC_USED_DIRS..DESC_USED_DIRS + 2].copy_from_slice(&count.to_le_bytes());
+    }
+}
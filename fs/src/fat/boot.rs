@@ -0,0 +1,159 @@
+use crate::{Disk, FsError};
+
+use crate::{read_u16, read_u32};
+
+const BOOT_SIGNATURE: u16 = 0xaa55;
+const BPB_BYTES_PER_SECTOR: usize = 11;
+const BPB_SECTORS_PER_CLUSTER: usize = 13;
+const BPB_RESERVED_SECTORS: usize = 14;
+const BPB_FATS: usize = 16;
+const BPB_ROOT_ENTRIES: usize = 17;
+const BPB_TOTAL_SECTORS_16: usize = 19;
+const BPB_FAT_SIZE_16: usize = 22;
+const BPB_TOTAL_SECTORS_32: usize = 32;
+const BPB_FAT_SIZE_32: usize = 36;
+const BPB_EXT_FLAGS: usize = 40;
+const BPB_ROOT_CLUSTER: usize = 44;
+const BPB_FSINFO: usize = 48;
+const BOOT_SIGNATURE_OFFSET: usize = 510;
+
+// Mirroring is off and only the active FAT is used.
+const EXT_FLAGS_NO_MIRROR: u16 = 1 << 7;
+const EXT_FLAGS_ACTIVE: u16 = 0xf;
+
+const FAT12_MAX_CLUSTERS: u32 = 4085;
+const FAT16_MAX_CLUSTERS: u32 = 65525;
+
+pub const DIR_ENTRY_SIZE: u32 = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatType {
+    Fat12,
+    Fat16,
+    Fat32,
+}
+
+// Sizes are in bytes and offsets are from the start of the volume.
+#[derive(Debug, Clone, Copy)]
+pub struct Layout {
+    pub kind: FatType,
+    pub sector_size: u32,
+    pub cluster_size: u32,
+    pub fat_offset: u64,
+    pub fat_size: u64,
+    pub fats: u32,
+    pub active_fat: Option<u32>,
+    pub root_offset: u64,
+    pub root_entries: u32,
+    pub root_cluster: u32,
+    pub data_offset: u64,
+    pub clusters: u32,
+    pub fsinfo: Option<u64>,
+}
+
+impl Layout {
+    pub fn read(disk: &mut dyn Disk) -> Result<Self, FsError> {
+        let mut sector = [0; 512];
+        disk.read_at(0, &mut sector)?;
+        if read_u16(&sector, BOOT_SIGNATURE_OFFSET) != BOOT_SIGNATURE {
+            return Err(FsError::Corrupt("missing boot signature"));
+        }
+
+        let sector_size = read_u16(&sector, BPB_BYTES_PER_SECTOR) as u32;
+        let sectors_per_cluster = sector[BPB_SECTORS_PER_CLUSTER] as u32;
+        let reserved = read_u16(&sector, BPB_RESERVED_SECTORS) as u32;
+        let fats = sector[BPB_FATS] as u32;
+        let root_entries = read_u16(&sector, BPB_ROOT_ENTRIES) as u32;
+        let total = match read_u16(&sector, BPB_TOTAL_SECTORS_16) {
+            0 => read_u32(&sector, BPB_TOTAL_SECTORS_32),
+            total => total as u32,
+        };
+        let fat_sectors = match read_u16(&sector, BPB_FAT_SIZE_16) {
+            0 => read_u32(&sector, BPB_FAT_SIZE_32),
+            size => size as u32,
+        };
+        if !(512..=4096).contains(&sector_size)
+            || !sector_size.is_power_of_two()
+            || !sectors_per_cluster.is_power_of_two()
+            || reserved == 0
+            || fats == 0
+            || fat_sectors == 0
+        {
+            return Err(FsError::Corrupt("bad bios parameter block"));
+        }
+
+        let root_sectors = (root_entries * DIR_ENTRY_SIZE).div_ceil(sector_size);
+        let data_sector = reserved as u64 + fats as u64 * fat_sectors as u64 + root_sectors as u64;
+        if data_sector >= total as u64 || total as u64 * sector_size as u64 > disk.size() {
+            return Err(FsError::Corrupt("volume larger than disk"));
+        }
+        let clusters = (total - data_sector as u32) / sectors_per_cluster;
+        let kind = if clusters < FAT12_MAX_CLUSTERS {
+            FatType::Fat12
+        } else if clusters < FAT16_MAX_CLUSTERS {
+            FatType::Fat16
+        } else {
+            FatType::Fat32
+        };
+        let min_fat_size = match kind {
+            FatType::Fat12 => (clusters as u64 + 2) * 3 / 2,
+            FatType::Fat16 => (clusters as u64 + 2) * 2,
+            FatType::Fat32 => (clusters as u64 + 2) * 4,
+        };
+        if (fat_sectors as u64 * sector_size as u64) < min_fat_size {
+            return Err(FsError::Corrupt("fat too small"));
+        }
+
+        let (root_cluster, fsinfo, active_fat) = if kind == FatType::Fat32 {
+            if root_entries != 0 {
+                return Err(FsError::Corrupt("fixed root directory on fat32"));
+            }
+            let flags = read_u16(&sector, BPB_EXT_FLAGS);
+            let active = if flags & EXT_FLAGS_NO_MIRROR != 0 {
+                Some((flags & EXT_FLAGS_ACTIVE) as u32)
+            } else {
+                None
+            };
+            let fsinfo = match read_u16(&sector, BPB_FSINFO) as u32 {
+                0 | 0xffff => None,
+                fsinfo if fsinfo < reserved => Some(fsinfo as u64 * sector_size as u64),
+                _ => None,
+            };
+            (read_u32(&sector, BPB_ROOT_CLUSTER), fsinfo, active)
+        } else {
+            if root_entries == 0 {
+                return Err(FsError::Corrupt("missing root directory"));
+            }
+            (0, None, None)
+        };
+
+        let sector_size64 = sector_size as u64;
+        let fat_offset = reserved as u64 * sector_size64;
+        let fat_size = fat_sectors as u64 * sector_size64;
+        Ok(Self {
+            kind,
+            sector_size,
+            cluster_size: sectors_per_cluster * sector_size,
+            fat_offset,
+            fat_size,
+            fats,
+            active_fat: active_fat.filter(|&fat| fat < fats),
+            root_offset: fat_offset + fats as u64 * fat_size,
+            root_entries,
+            root_cluster,
+            data_offset: data_sector * sector_size64,
+            clusters,
+            fsinfo,
+        })
+    }
+
+    #[inline]
+    pub fn is_cluster(&self, cluster: u32) -> bool {
+        cluster >= 2 && cluster - 2 < self.clusters
+    }
+
+    #[inline]
+    pub fn cluster_offset(&self, cluster: u32) -> u64 {
+        self.data_offset + (cluster - 2) as u64 * self.cluster_size as u64
+    }
+}
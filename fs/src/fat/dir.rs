@@ -0,0 +1,485 @@
+use crate::{read_u16, read_u32, FsError, Name, NAME_MAX};
+
+pub const ATTR_READ_ONLY: u8 = 0x01;
+pub const ATTR_HIDDEN: u8 = 0x02;
+pub const ATTR_SYSTEM: u8 = 0x04;
+pub const ATTR_VOLUME_ID: u8 = 0x08;
+pub const ATTR_DIRECTORY: u8 = 0x10;
+pub const ATTR_ARCHIVE: u8 = 0x20;
+pub const ATTR_LFN: u8 = ATTR_READ_ONLY | ATTR_HIDDEN | ATTR_SYSTEM | ATTR_VOLUME_ID;
+const ATTR_LFN_MASK: u8 = 0x3f;
+
+const ENTRY_END: u8 = 0x00;
+pub const ENTRY_DELETED: u8 = 0xe5;
+// A leading 0xe5 in a real name is stored as 0x05.
+const ENTRY_KANJI: u8 = 0x05;
+
+const NTRES_LOWER_BASE: u8 = 0x08;
+const NTRES_LOWER_EXT: u8 = 0x10;
+
+const OFFSET_ATTR: usize = 11;
+const OFFSET_NTRES: usize = 12;
+const OFFSET_CTIME: usize = 14;
+const OFFSET_CDATE: usize = 16;
+const OFFSET_ADATE: usize = 18;
+const OFFSET_CLUSTER_HIGH: usize = 20;
+const OFFSET_MTIME: usize = 22;
+const OFFSET_MDATE: usize = 24;
+const OFFSET_CLUSTER_LOW: usize = 26;
+const OFFSET_SIZE: usize = 28;
+
+const LFN_LAST: u8 = 0x40;
+const LFN_ORDER_MASK: u8 = 0x1f;
+const LFN_CHECKSUM: usize = 13;
+const LFN_CHARS: [usize; 13] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+pub const LFN_CHARS_PER_ENTRY: usize = 13;
+pub const LFN_MAX_UNITS: usize = 255;
+pub const LFN_MAX_ENTRIES: usize = LFN_MAX_UNITS.div_ceil(LFN_CHARS_PER_ENTRY);
+
+const SHORT_BASE: usize = 8;
+const SHORT_LEN: usize = 11;
+const SHORT_SPECIAL: &[u8] = b"$%'-_@~`!(){}^#&";
+const INVALID: &[char] = &['"', '*', '/', ':', '<', '>', '?', '\\', '|'];
+const MAX_TAIL: u32 = 999_999;
+
+const SECS_PER_DAY: u64 = 86400;
+// Days from 0000-03-01 to 1970-01-01, the epoch of `civil_from_days`.
+const UNIX_EPOCH_DAYS: i64 = 719_468;
+const FAT_EPOCH_YEAR: i64 = 1980;
+pub const FAT_EPOCH_DATE: u16 = (1 << 5) | 1;
+
+#[derive(Clone, Copy)]
+pub struct RawEntry(pub [u8; 32]);
+
+impl RawEntry {
+    pub const EMPTY: Self = Self([0; 32]);
+
+    #[inline]
+    pub fn is_end(&self) -> bool {
+        self.0[0] == ENTRY_END
+    }
+
+    #[inline]
+    pub fn is_deleted(&self) -> bool {
+        self.0[0] == ENTRY_DELETED
+    }
+
+    #[inline]
+    pub fn is_free(&self) -> bool {
+        self.is_end() || self.is_deleted()
+    }
+
+    #[inline]
+    pub fn attr(&self) -> u8 {
+        self.0[OFFSET_ATTR]
+    }
+
+    #[inline]
+    pub fn is_lfn(&self) -> bool {
+        self.attr() & ATTR_LFN_MASK == ATTR_LFN
+    }
+
+    #[inline]
+    pub fn is_dir(&self) -> bool {
+        self.attr() & ATTR_DIRECTORY != 0
+    }
+
+    #[inline]
+    pub fn is_volume_label(&self) -> bool {
+        !self.is_lfn() && self.attr() & ATTR_VOLUME_ID != 0
+    }
+
+    #[inline]
+    pub fn set_archive(&mut self) {
+        self.0[OFFSET_ATTR] |= ATTR_ARCHIVE;
+    }
+
+    #[inline]
+    pub fn short_name(&self) -> [u8; SHORT_LEN] {
+        let mut name = [0; SHORT_LEN];
+        name.copy_from_slice(&self.0[..SHORT_LEN]);
+        name
+    }
+
+    #[inline]
+    pub fn is_dot(&self) -> bool {
+        &self.0[..SHORT_LEN] == b".          " || &self.0[..SHORT_LEN] == b"..         "
+    }
+
+    #[inline]
+    pub fn cluster(&self) -> u32 {
+        ((read_u16(&self.0, OFFSET_CLUSTER_HIGH) as u32) << 16)
+            | read_u16(&self.0, OFFSET_CLUSTER_LOW) as u32
+    }
+
+    pub fn set_cluster(&mut self, cluster: u32) {
+        self.0[OFFSET_CLUSTER_HIGH..OFFSET_CLUSTER_HIGH + 2]
+            .copy_from_slice(&((cluster >> 16) as u16).to_le_bytes());
+        self.0[OFFSET_CLUSTER_LOW..OFFSET_CLUSTER_LOW + 2]
+            .copy_from_slice(&(cluster as u16).to_le_bytes());
+    }
+
+    #[inline]
+    pub fn size(&self) -> u32 {
+        read_u32(&self.0, OFFSET_SIZE)
+    }
+
+    #[inline]
+    pub fn set_size(&mut self, size: u32) {
+        self.0[OFFSET_SIZE..OFFSET_SIZE + 4].copy_from_slice(&size.to_le_bytes());
+    }
+
+    #[inline]
+    pub fn mtime(&self) -> u64 {
+        to_unix(
+            read_u16(&self.0, OFFSET_MDATE),
+            read_u16(&self.0, OFFSET_MTIME),
+        )
+    }
+
+    pub fn set_mtime(&mut self, secs: u64) {
+        let (date, time) = from_unix(secs);
+        self.0[OFFSET_MDATE..OFFSET_MDATE + 2].copy_from_slice(&date.to_le_bytes());
+        self.0[OFFSET_MTIME..OFFSET_MTIME + 2].copy_from_slice(&time.to_le_bytes());
+        self.0[OFFSET_ADATE..OFFSET_ADATE + 2].copy_from_slice(&date.to_le_bytes());
+    }
+
+    pub fn new(short: &[u8; SHORT_LEN], ntres: u8, attr: u8, cluster: u32, secs: u64) -> Self {
+        let mut entry = Self::EMPTY;
+        entry.0[..SHORT_LEN].copy_from_slice(short);
+        entry.0[OFFSET_ATTR] = attr;
+        entry.0[OFFSET_NTRES] = ntres;
+        let (date, time) = from_unix(secs);
+        entry.0[OFFSET_CDATE..OFFSET_CDATE + 2].copy_from_slice(&date.to_le_bytes());
+        entry.0[OFFSET_CTIME..OFFSET_CTIME + 2].copy_from_slice(&time.to_le_bytes());
+        entry.set_mtime(secs);
+        entry.set_cluster(cluster);
+        entry
+    }
+
+    // The 8.3 name as shown to users, lowercased where the NT flags say so.
+    pub fn display_name(&self) -> Name {
+        let ntres = self.0[OFFSET_NTRES];
+        let mut name = Name::new();
+        let push = |name: &mut Name, bytes: &[u8], lower: bool| {
+            for (idx, &byte) in bytes.iter().enumerate() {
+                let byte = if idx == 0 && byte == ENTRY_KANJI {
+                    ENTRY_DELETED
+                } else {
+                    byte
+                };
+                let byte = if lower {
+                    byte.to_ascii_lowercase()
+                } else {
+                    byte
+                };
+                let _ = name.push(byte as char);
+            }
+        };
+        let base = trim_spaces(&self.0[..SHORT_BASE]);
+        let ext = trim_spaces(&self.0[SHORT_BASE..SHORT_LEN]);
+        push(&mut name, base, ntres & NTRES_LOWER_BASE != 0);
+        if !ext.is_empty() {
+            let _ = name.push('.');
+            push(&mut name, ext, ntres & NTRES_LOWER_EXT != 0);
+        }
+        name
+    }
+
+    #[inline]
+    pub fn lfn_order(&self) -> u8 {
+        self.0[0] & LFN_ORDER_MASK
+    }
+
+    #[inline]
+    pub fn lfn_is_last(&self) -> bool {
+        self.0[0] & LFN_LAST != 0
+    }
+
+    #[inline]
+    pub fn lfn_checksum(&self) -> u8 {
+        self.0[LFN_CHECKSUM]
+    }
+
+    pub fn lfn_units(&self) -> [u16; LFN_CHARS_PER_ENTRY] {
+        LFN_CHARS.map(|offset| read_u16(&self.0, offset))
+    }
+
+    // Entry `order` counts from one and holds units `(order - 1) * 13..`,
+    // padded with a terminator and then 0xffff.
+    pub fn lfn(order: u8, last: bool, checksum: u8, units: &[u16]) -> Self {
+        let mut entry = Self::EMPTY;
+        entry.0[0] = if last { order | LFN_LAST } else { order };
+        entry.0[OFFSET_ATTR] = ATTR_LFN;
+        entry.0[LFN_CHECKSUM] = checksum;
+        let start = (order as usize - 1) * LFN_CHARS_PER_ENTRY;
+        for (idx, offset) in LFN_CHARS.iter().enumerate() {
+            let unit = match units.get(start + idx) {
+                Some(&unit) => unit,
+                None if start + idx == units.len() => 0,
+                None => 0xffff,
+            };
+            entry.0[*offset..*offset + 2].copy_from_slice(&unit.to_le_bytes());
+        }
+        entry
+    }
+}
+
+fn trim_spaces(bytes: &[u8]) -> &[u8] {
+    let len = bytes
+        .iter()
+        .rposition(|&b| b != b' ')
+        .map_or(0, |idx| idx + 1);
+    &bytes[..len]
+}
+
+pub fn checksum(short: &[u8; SHORT_LEN]) -> u8 {
+    short
+        .iter()
+        .fold(0u8, |sum, &byte| sum.rotate_right(1).wrapping_add(byte))
+}
+
+// Long names are gathered backwards, the entry with the highest order comes
+// first on disk.
+pub struct LongName {
+    units: [u16; LFN_MAX_ENTRIES * LFN_CHARS_PER_ENTRY],
+    checksum: u8,
+    next: u8,
+    valid: bool,
+}
+
+impl LongName {
+    pub const fn new() -> Self {
+        Self {
+            units: [0; LFN_MAX_ENTRIES * LFN_CHARS_PER_ENTRY],
+            checksum: 0,
+            next: 0,
+            valid: false,
+        }
+    }
+
+    #[inline]
+    pub fn reset(&mut self) {
+        self.valid = false;
+    }
+
+    pub fn push(&mut self, entry: &RawEntry) {
+        let order = entry.lfn_order();
+        if entry.lfn_is_last() {
+            self.valid = order != 0 && order as usize <= LFN_MAX_ENTRIES;
+            self.checksum = entry.lfn_checksum();
+            self.units.fill(0xffff);
+        } else if !self.valid || order != self.next || entry.lfn_checksum() != self.checksum {
+            self.valid = false;
+        }
+        if self.valid {
+            let start = (order as usize - 1) * LFN_CHARS_PER_ENTRY;
+            self.units[start..start + LFN_CHARS_PER_ENTRY].copy_from_slice(&entry.lfn_units());
+            self.next = order - 1;
+        }
+    }
+
+    // The name belongs to `short` only if the whole sequence was seen.
+    pub fn take(&mut self, short: &RawEntry) -> Option<Name> {
+        let valid = self.valid && self.next == 0 && self.checksum == checksum(&short.short_name());
+        self.valid = false;
+        if !valid {
+            return None;
+        }
+        let len = self
+            .units
+            .iter()
+            .position(|&unit| unit == 0 || unit == 0xffff)
+            .unwrap_or(self.units.len());
+        let mut name = Name::new();
+        for c in char::decode_utf16(self.units[..len].iter().copied()) {
+            name.push(c.unwrap_or(char::REPLACEMENT_CHARACTER)).ok()?;
+        }
+        Some(name)
+    }
+}
+
+// Checks a name for creation and returns its UTF-16 length.
+pub fn validate(name: &str) -> Result<usize, FsError> {
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(FsError::InvalidName);
+    }
+    if name.len() > NAME_MAX {
+        return Err(FsError::NameTooLong);
+    }
+    if name
+        .chars()
+        .any(|c| (c as u32) < 0x20 || INVALID.contains(&c))
+        || name.ends_with('.')
+        || name.ends_with(' ')
+    {
+        return Err(FsError::InvalidName);
+    }
+    let units = name.encode_utf16().count();
+    if units > LFN_MAX_UNITS {
+        return Err(FsError::NameTooLong);
+    }
+    Ok(units)
+}
+
+pub fn eq_ignore_case(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.chars()
+            .zip(b.chars())
+            .all(|(a, b)| a.to_uppercase().eq(b.to_uppercase()))
+}
+
+#[inline]
+fn is_short_char(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || SHORT_SPECIAL.contains(&byte) || byte >= 0x80
+}
+
+// How a name is stored: either directly as an 8.3 name, possibly lowercased
+// through the NT flags, or as a long name with a generated 8.3 alias.
+pub enum ShortName {
+    Exact([u8; SHORT_LEN], u8),
+    Basis([u8; SHORT_LEN], usize),
+}
+
+pub fn short_name(name: &str) -> ShortName {
+    if let Some((short, ntres)) = exact_short_name(name) {
+        return ShortName::Exact(short, ntres);
+    }
+
+    let name = name.trim_start_matches('.');
+    let (base, ext) = match name.rfind('.') {
+        Some(idx) => (&name[..idx], &name[idx + 1..]),
+        None => (name, ""),
+    };
+    let mut short = [b' '; SHORT_LEN];
+    let fill = |dst: &mut [u8], src: &str| {
+        let mut len = 0;
+        for c in src.chars().filter(|&c| c != ' ' && c != '.') {
+            if len == dst.len() {
+                break;
+            }
+            dst[len] = if c.is_ascii() && is_short_char(c as u8) {
+                c.to_ascii_uppercase() as u8
+            } else {
+                b'_'
+            };
+            len += 1;
+        }
+        len
+    };
+    let base_len = fill(&mut short[..SHORT_BASE], base).max(1);
+    if short[0] == b' ' {
+        short[0] = b'_';
+    }
+    fill(&mut short[SHORT_BASE..], ext);
+    ShortName::Basis(short, base_len)
+}
+
+fn exact_short_name(name: &str) -> Option<([u8; SHORT_LEN], u8)> {
+    let (base, ext) = match name.find('.') {
+        Some(idx) => (&name[..idx], &name[idx + 1..]),
+        None => (name, ""),
+    };
+    if base.is_empty()
+        || base.len() > SHORT_BASE
+        || ext.len() > SHORT_LEN - SHORT_BASE
+        || ext.contains('.')
+        || !name
+            .bytes()
+            .all(|b| b == b'.' || (b.is_ascii() && is_short_char(b)))
+    {
+        return None;
+    }
+
+    let case = |part: &str, flag: u8| -> Option<u8> {
+        let lower = part.bytes().any(|b| b.is_ascii_lowercase());
+        let upper = part.bytes().any(|b| b.is_ascii_uppercase());
+        match (lower, upper) {
+            (true, true) => None,
+            (true, false) => Some(flag),
+            _ => Some(0),
+        }
+    };
+    let ntres = case(base, NTRES_LOWER_BASE)? | case(ext, NTRES_LOWER_EXT)?;
+
+    let mut short = [b' '; SHORT_LEN];
+    short[..base.len()].copy_from_slice(base.as_bytes());
+    short[SHORT_BASE..SHORT_BASE + ext.len()].copy_from_slice(ext.as_bytes());
+    short.make_ascii_uppercase();
+    if short[0] == ENTRY_DELETED {
+        short[0] = ENTRY_KANJI;
+    }
+    Some((short, ntres))
+}
+
+// Applies the numeric tail "~n" to a basis name.
+pub fn with_tail(basis: &[u8; SHORT_LEN], base_len: usize, n: u32) -> Option<[u8; SHORT_LEN]> {
+    if n == 0 || n > MAX_TAIL {
+        return None;
+    }
+    let mut digits = [0; 7];
+    let mut len = 0;
+    let mut value = n;
+    while value > 0 {
+        digits[len] = b'0' + (value % 10) as u8;
+        value /= 10;
+        len += 1;
+    }
+    let tail = len + 1;
+    let keep = base_len.min(SHORT_BASE - tail);
+
+    let mut short = *basis;
+    short[keep] = b'~';
+    for idx in 0..len {
+        short[keep + 1 + idx] = digits[len - 1 - idx];
+    }
+    short[keep + tail..SHORT_BASE].fill(b' ');
+    Some(short)
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - UNIX_EPOCH_DAYS
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + UNIX_EPOCH_DAYS;
+    let era = days.div_euclid(146_097);
+    let doe = days - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// FAT stores local time in two second steps, it is treated as UTC.
+pub fn to_unix(date: u16, time: u16) -> u64 {
+    let year = FAT_EPOCH_YEAR + (date >> 9) as i64;
+    let month = ((date >> 5) & 0xf).clamp(1, 12) as i64;
+    let day = (date & 0x1f).max(1) as i64;
+    let days = days_from_civil(year, month, day) as u64;
+    let secs =
+        (time >> 11) as u64 * 3600 + ((time >> 5) & 0x3f) as u64 * 60 + (time & 0x1f) as u64 * 2;
+    days * SECS_PER_DAY + secs
+}
+
+pub fn from_unix(secs: u64) -> (u16, u16) {
+    let (year, month, day) = civil_from_days((secs / SECS_PER_DAY) as i64);
+    if year < FAT_EPOCH_YEAR {
+        return (FAT_EPOCH_DATE, 0);
+    }
+    let year = (year - FAT_EPOCH_YEAR).min(127);
+    let date = ((year as u16) << 9) | ((month as u16) << 5) | day as u16;
+    let secs = secs % SECS_PER_DAY;
+    let time = (((secs / 3600) as u16) << 11)
+        | ((((secs / 60) % 60) as u16) << 5)
+        | ((secs % 60) / 2) as u16;
+    (date, time)
+}
@@ -0,0 +1,755 @@
+use crate::{read_u32, DirEntry, Disk, FileSystem, FileType, FsError, Ino, Metadata, Name};
+
+use dir::{LongName, RawEntry, ShortName};
+
+pub use boot::{FatType, Layout};
+
+mod boot;
+mod dir;
+mod table;
+
+const ROOT_INO: Ino = 0;
+const ENTRY_SIZE: u64 = boot::DIR_ENTRY_SIZE as u64;
+// Directories are limited to 65536 entries by the spec.
+const DIR_MAX_ENTRIES: u32 = 65536;
+const ZERO_CHUNK: usize = 512;
+
+const FSINFO_LEAD_SIG: u32 = 0x4161_5252;
+const FSINFO_STRUCT_SIG: u32 = 0x6141_7272;
+const FSINFO_TRAIL_SIG: u32 = 0xaa55_0000;
+const FSINFO_STRUCT_SIG_OFFSET: usize = 484;
+const FSINFO_FREE_OFFSET: usize = 488;
+const FSINFO_NEXT_OFFSET: usize = 492;
+const FSINFO_TRAIL_SIG_OFFSET: usize = 508;
+const FSINFO_UNKNOWN: u32 = 0xffff_ffff;
+
+const SHORT_DOT: &[u8; 11] = b".          ";
+const SHORT_DOTDOT: &[u8; 11] = b"..         ";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Dir {
+    // The fixed root directory of FAT12 and FAT16.
+    Root,
+    Chain(u32),
+}
+
+struct Cursor {
+    dir: Dir,
+    index: u32,
+    cluster: u32,
+    cluster_index: u32,
+}
+
+impl Cursor {
+    fn new(dir: Dir, index: u32) -> Self {
+        Self {
+            dir,
+            index,
+            cluster: match dir {
+                Dir::Root => 0,
+                Dir::Chain(first) => first,
+            },
+            cluster_index: 0,
+        }
+    }
+}
+
+struct Found {
+    entry: RawEntry,
+    name: Name,
+    offset: u64,
+    // The first slot of the entry, including its long name.
+    first: u32,
+    index: u32,
+}
+
+// Inodes are the volume offsets of the short directory entries, the root
+// directory has none and uses 0.
+pub struct Fat<D> {
+    disk: D,
+    layout: Layout,
+    free: Option<u32>,
+    next_free: u32,
+    fsinfo_dirty: bool,
+    clock: Option<fn() -> u64>,
+}
+
+impl<D: Disk> Fat<D> {
+    pub fn mount(mut disk: D) -> Result<Self, FsError> {
+        let layout = Layout::read(&mut disk)?;
+        let mut fat = Self {
+            disk,
+            layout,
+            free: None,
+            next_free: 2,
+            fsinfo_dirty: false,
+            clock: None,
+        };
+        fat.read_fsinfo()?;
+        if fat.free.is_none() {
+            fat.free = Some(fat.count_free()?);
+        }
+        Ok(fat)
+    }
+
+    // Timestamps are left at the FAT epoch without a clock.
+    pub fn with_clock(mut self, clock: fn() -> u64) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    #[inline]
+    pub fn kind(&self) -> FatType {
+        self.layout.kind
+    }
+
+    #[inline]
+    pub fn cluster_size(&self) -> u32 {
+        self.layout.cluster_size
+    }
+
+    #[inline]
+    pub fn free_clusters(&self) -> u32 {
+        self.free.unwrap_or(0)
+    }
+
+    pub fn into_disk(self) -> D {
+        self.disk
+    }
+
+    fn read_fsinfo(&mut self) -> Result<(), FsError> {
+        let Some(offset) = self.layout.fsinfo else {
+            return Ok(());
+        };
+        let mut sector = [0; 512];
+        self.disk.read_at(offset, &mut sector)?;
+        if read_u32(&sector, 0) != FSINFO_LEAD_SIG
+            || read_u32(&sector, FSINFO_STRUCT_SIG_OFFSET) != FSINFO_STRUCT_SIG
+            || read_u32(&sector, FSINFO_TRAIL_SIG_OFFSET) != FSINFO_TRAIL_SIG
+        {
+            self.layout.fsinfo = None;
+            return Ok(());
+        }
+        let free = read_u32(&sector, FSINFO_FREE_OFFSET);
+        if free != FSINFO_UNKNOWN && free <= self.layout.clusters {
+            self.free = Some(free);
+        }
+        let next = read_u32(&sector, FSINFO_NEXT_OFFSET);
+        if self.layout.is_cluster(next) {
+            self.next_free = next;
+        }
+        Ok(())
+    }
+
+    fn write_fsinfo(&mut self) -> Result<(), FsError> {
+        if let Some(offset) = self.layout.fsinfo {
+            let free = self.free.unwrap_or(FSINFO_UNKNOWN);
+            let next = if self.layout.is_cluster(self.next_free) {
+                self.next_free
+            } else {
+                FSINFO_UNKNOWN
+            };
+            let fields = offset + FSINFO_FREE_OFFSET as u64;
+            let mut buf = [0; 8];
+            buf[..4].copy_from_slice(&free.to_le_bytes());
+            buf[4..].copy_from_slice(&next.to_le_bytes());
+            self.disk.write_at(fields, &buf)?;
+        }
+        self.fsinfo_dirty = false;
+        Ok(())
+    }
+
+    #[inline]
+    fn now(&self) -> u64 {
+        self.clock.map_or(0, |clock| clock())
+    }
+
+    fn check_writable(&self) -> Result<(), FsError> {
+        if self.disk.is_read_only() {
+            Err(FsError::ReadOnly)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn read_entry(&mut self, offset: u64) -> Result<RawEntry, FsError> {
+        let mut entry = RawEntry::EMPTY;
+        self.disk.read_at(offset, &mut entry.0)?;
+        Ok(entry)
+    }
+
+    #[inline]
+    fn write_entry(&mut self, offset: u64, entry: &RawEntry) -> Result<(), FsError> {
+        self.disk.write_at(offset, &entry.0)
+    }
+
+    // The entry behind an inode, which is gone once its file was removed.
+    fn dir_entry(&mut self, ino: Ino) -> Result<RawEntry, FsError> {
+        if ino < self.layout.root_offset || ino & (ENTRY_SIZE - 1) != 0 || ino >= self.disk.size() {
+            return Err(FsError::NotFound);
+        }
+        let entry = self.read_entry(ino)?;
+        if entry.is_free() || entry.is_lfn() || entry.is_volume_label() {
+            return Err(FsError::NotFound);
+        }
+        Ok(entry)
+    }
+
+    fn root_dir(&self) -> Dir {
+        match self.layout.kind {
+            FatType::Fat32 => Dir::Chain(self.layout.root_cluster),
+            _ => Dir::Root,
+        }
+    }
+
+    // ".." entries pointing at the root hold cluster 0.
+    fn cluster_dir(&self, cluster: u32) -> Result<Dir, FsError> {
+        if cluster == 0 {
+            Ok(self.root_dir())
+        } else if self.layout.is_cluster(cluster) {
+            Ok(Dir::Chain(cluster))
+        } else {
+            Err(FsError::Corrupt("bad directory cluster"))
+        }
+    }
+
+    fn dir(&mut self, ino: Ino) -> Result<Dir, FsError> {
+        if ino == ROOT_INO {
+            return Ok(self.root_dir());
+        }
+        let entry = self.dir_entry(ino)?;
+        if !entry.is_dir() {
+            return Err(FsError::NotDir);
+        }
+        self.cluster_dir(entry.cluster())
+    }
+
+    fn parent_cluster(&self, dir: Dir) -> u32 {
+        match dir {
+            Dir::Chain(cluster) if self.root_dir() != dir => cluster,
+            _ => 0,
+        }
+    }
+
+    // The volume offset of the slot at the cursor, or None past the end of
+    // the directory.
+    fn slot(&mut self, cursor: &mut Cursor) -> Result<Option<u64>, FsError> {
+        if cursor.index >= DIR_MAX_ENTRIES {
+            return Ok(None);
+        }
+        match cursor.dir {
+            Dir::Root => Ok((cursor.index < self.layout.root_entries)
+                .then(|| self.layout.root_offset + cursor.index as u64 * ENTRY_SIZE)),
+            Dir::Chain(first) => {
+                let per_cluster = self.layout.cluster_size / ENTRY_SIZE as u32;
+                let target = cursor.index / per_cluster;
+                if target < cursor.cluster_index {
+                    cursor.cluster = first;
+                    cursor.cluster_index = 0;
+                }
+                while cursor.cluster_index < target {
+                    match self.next_cluster(cursor.cluster)? {
+                        Some(next) => {
+                            cursor.cluster = next;
+                            cursor.cluster_index += 1;
+                        }
+                        None => return Ok(None),
+                    }
+                }
+                let within = (cursor.index % per_cluster) as u64 * ENTRY_SIZE;
+                Ok(Some(self.layout.cluster_offset(cursor.cluster) + within))
+            }
+        }
+    }
+
+    fn next_entry(&mut self, cursor: &mut Cursor) -> Result<Option<Found>, FsError> {
+        let mut long = LongName::new();
+        let mut first = cursor.index;
+        loop {
+            let index = cursor.index;
+            let Some(offset) = self.slot(cursor)? else {
+                return Ok(None);
+            };
+            let entry = self.read_entry(offset)?;
+            cursor.index += 1;
+            if entry.is_end() {
+                return Ok(None);
+            }
+            if entry.is_deleted() || entry.is_volume_label() {
+                long.reset();
+                continue;
+            }
+            if entry.is_lfn() {
+                if entry.lfn_is_last() {
+                    first = index;
+                }
+                long.push(&entry);
+                continue;
+            }
+            let (name, first) = match long.take(&entry) {
+                Some(name) => (name, first),
+                None => (entry.display_name(), index),
+            };
+            return Ok(Some(Found {
+                entry,
+                name,
+                offset,
+                first,
+                index,
+            }));
+        }
+    }
+
+    // Names match case insensitively against both the long and short name.
+    fn find(&mut self, dir: Dir, name: &str) -> Result<Option<Found>, FsError> {
+        let mut cursor = Cursor::new(dir, 0);
+        while let Some(found) = self.next_entry(&mut cursor)? {
+            if found.entry.is_dot() {
+                continue;
+            }
+            if dir::eq_ignore_case(found.name.as_str(), name)
+                || dir::eq_ignore_case(found.entry.display_name().as_str(), name)
+            {
+                return Ok(Some(found));
+            }
+        }
+        Ok(None)
+    }
+
+    fn short_exists(&mut self, dir: Dir, short: &[u8; 11]) -> Result<bool, FsError> {
+        let mut cursor = Cursor::new(dir, 0);
+        while let Some(found) = self.next_entry(&mut cursor)? {
+            if &found.entry.short_name() == short {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    // Directories only know the cluster of their parent, so its inode is
+    // found by looking for the directory in the grandparent.
+    fn dir_ino(&mut self, cluster: u32) -> Result<Ino, FsError> {
+        let dir = self.cluster_dir(cluster)?;
+        if dir == self.root_dir() {
+            return Ok(ROOT_INO);
+        }
+        let dotdot = self.read_entry(self.layout.cluster_offset(cluster) + ENTRY_SIZE)?;
+        if &dotdot.short_name() != SHORT_DOTDOT {
+            return Err(FsError::Corrupt("missing .. entry"));
+        }
+        let parent = self.cluster_dir(dotdot.cluster())?;
+        let mut cursor = Cursor::new(parent, 0);
+        while let Some(found) = self.next_entry(&mut cursor)? {
+            if found.entry.is_dir() && !found.entry.is_dot() && found.entry.cluster() == cluster {
+                return Ok(found.offset);
+            }
+        }
+        Err(FsError::Corrupt("orphaned directory"))
+    }
+
+    fn is_empty(&mut self, dir: Dir) -> Result<bool, FsError> {
+        let mut cursor = Cursor::new(dir, 0);
+        while let Some(found) = self.next_entry(&mut cursor)? {
+            if !found.entry.is_dot() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    // Finds `count` consecutive free slots, growing the directory by a
+    // cluster at a time if needed.
+    fn alloc_slots(&mut self, dir: Dir, count: u32) -> Result<u32, FsError> {
+        let mut cursor = Cursor::new(dir, 0);
+        let mut start = 0;
+        let mut run = 0;
+        loop {
+            let index = cursor.index;
+            match self.slot(&mut cursor)? {
+                Some(offset) => {
+                    cursor.index += 1;
+                    if !self.read_entry(offset)?.is_free() {
+                        run = 0;
+                        continue;
+                    }
+                    if run == 0 {
+                        start = index;
+                    }
+                    run += 1;
+                    if run == count {
+                        return Ok(start);
+                    }
+                }
+                None => match dir {
+                    Dir::Chain(first) if index < DIR_MAX_ENTRIES => {
+                        let (last, _) = self.last_cluster(first)?;
+                        self.alloc_cluster(Some(last), true)?;
+                    }
+                    _ => return Err(FsError::NoSpace),
+                },
+            }
+        }
+    }
+
+    fn slot_offset(&mut self, dir: Dir, index: u32) -> Result<u64, FsError> {
+        self.slot(&mut Cursor::new(dir, index))?
+            .ok_or(FsError::Corrupt("directory shrank"))
+    }
+
+    // Calls `f` with the volume offset, position in the range and length of
+    // each piece of a file range that is backed by a single cluster.
+    fn for_each_extent(
+        &mut self,
+        first: u32,
+        offset: u64,
+        len: usize,
+        mut f: impl FnMut(&mut D, u64, usize, usize) -> Result<(), FsError>,
+    ) -> Result<(), FsError> {
+        let cluster_size = self.layout.cluster_size as u64;
+        let mut cluster = self
+            .nth_cluster(first, offset / cluster_size)?
+            .ok_or(FsError::Corrupt("cluster chain shorter than file"))?;
+        let mut done = 0;
+        let mut pos = offset;
+        while done < len {
+            let within = pos % cluster_size;
+            let chunk = ((cluster_size - within) as usize).min(len - done);
+            f(
+                &mut self.disk,
+                self.layout.cluster_offset(cluster) + within,
+                done,
+                chunk,
+            )?;
+            done += chunk;
+            pos += chunk as u64;
+            if done < len {
+                cluster = self
+                    .next_cluster(cluster)?
+                    .ok_or(FsError::Corrupt("cluster chain shorter than file"))?;
+            }
+        }
+        Ok(())
+    }
+
+    // Makes the chain of `entry` long enough to hold `end` bytes.
+    fn extend(&mut self, entry: &mut RawEntry, end: u64) -> Result<(), FsError> {
+        let needed = end.div_ceil(self.layout.cluster_size as u64);
+        if needed == 0 {
+            return Ok(());
+        }
+        let (mut last, mut count) = if entry.cluster() == 0 {
+            let first = self.alloc_cluster(None, false)?;
+            entry.set_cluster(first);
+            (first, 1)
+        } else {
+            self.last_cluster(entry.cluster())?
+        };
+        while count < needed {
+            last = self.alloc_cluster(Some(last), false)?;
+            count += 1;
+        }
+        Ok(())
+    }
+
+    fn zero(&mut self, first: u32, from: u64, to: u64) -> Result<(), FsError> {
+        if from >= to {
+            return Ok(());
+        }
+        let zeros = [0; ZERO_CHUNK];
+        self.for_each_extent(first, from, (to - from) as usize, |disk, offset, _, len| {
+            for chunk in (0..len).step_by(ZERO_CHUNK) {
+                let size = (len - chunk).min(ZERO_CHUNK);
+                disk.write_at(offset + chunk as u64, &zeros[..size])?;
+            }
+            Ok(())
+        })
+    }
+
+    fn file_entry(&mut self, ino: Ino) -> Result<RawEntry, FsError> {
+        if ino == ROOT_INO {
+            return Err(FsError::IsDir);
+        }
+        let entry = self.dir_entry(ino)?;
+        if entry.is_dir() {
+            return Err(FsError::IsDir);
+        }
+        if entry.size() != 0 && !self.layout.is_cluster(entry.cluster()) {
+            return Err(FsError::Corrupt("bad file cluster"));
+        }
+        Ok(entry)
+    }
+
+    // Grows or shrinks a file, the entry is written back even on failure
+    // so that allocated clusters stay reachable.
+    fn resize(&mut self, ino: Ino, entry: &mut RawEntry, size: u64) -> Result<(), FsError> {
+        let old = entry.size() as u64;
+        let result = if size > old {
+            self.extend(entry, size)
+                .and_then(|()| self.zero(entry.cluster(), old, size))
+        } else if size == 0 && entry.cluster() != 0 {
+            let first = entry.cluster();
+            entry.set_cluster(0);
+            self.free_chain(first)
+        } else if size < old {
+            let cluster_size = self.layout.cluster_size as u64;
+            match self.nth_cluster(entry.cluster(), (size - 1) / cluster_size)? {
+                Some(last) => self.truncate_chain(last),
+                None => Err(FsError::Corrupt("cluster chain shorter than file")),
+            }
+        } else {
+            Ok(())
+        };
+        if result.is_ok() {
+            entry.set_size(size as u32);
+        }
+        entry.set_mtime(self.now());
+        self.write_entry(ino, entry)?;
+        result
+    }
+}
+
+impl<D: Disk> FileSystem for Fat<D> {
+    fn name(&self) -> &'static str {
+        "fat"
+    }
+
+    fn root(&self) -> Ino {
+        ROOT_INO
+    }
+
+    fn lookup(&mut self, dir: Ino, name: &str) -> Result<Ino, FsError> {
+        let dir_ino = dir;
+        let dir = self.dir(dir)?;
+        match name {
+            "." => Ok(dir_ino),
+            ".." => match dir {
+                Dir::Chain(cluster) if dir != self.root_dir() => {
+                    let dotdot =
+                        self.read_entry(self.layout.cluster_offset(cluster) + ENTRY_SIZE)?;
+                    self.dir_ino(dotdot.cluster())
+                }
+                _ => Ok(ROOT_INO),
+            },
+            _ => match self.find(dir, name)? {
+                Some(found) => Ok(found.offset),
+                None => Err(FsError::NotFound),
+            },
+        }
+    }
+
+    fn metadata(&mut self, ino: Ino) -> Result<Metadata, FsError> {
+        let (kind, size, read_only, mtime) = if ino == ROOT_INO {
+            (FileType::Dir, 0, false, 0)
+        } else {
+            let entry = self.dir_entry(ino)?;
+            let read_only = entry.attr() & dir::ATTR_READ_ONLY != 0;
+            if entry.is_dir() {
+                let (_, clusters) = match self.cluster_dir(entry.cluster())? {
+                    Dir::Chain(first) => self.last_cluster(first)?,
+                    Dir::Root => (0, 0),
+                };
+                let size = clusters * self.layout.cluster_size as u64;
+                (FileType::Dir, size, read_only, entry.mtime())
+            } else {
+                (
+                    FileType::File,
+                    entry.size() as u64,
+                    read_only,
+                    entry.mtime(),
+                )
+            }
+        };
+        let mode = match (kind, read_only) {
+            (FileType::Dir, false) => 0o755,
+            (FileType::Dir, true) => 0o555,
+            (_, false) => 0o644,
+            (_, true) => 0o444,
+        };
+        Ok(Metadata {
+            ino,
+            kind,
+            size,
+            mode,
+            links: 1,
+            uid: 0,
+            gid: 0,
+            mtime,
+        })
+    }
+
+    fn read(&mut self, ino: Ino, offset: u64, buf: &mut [u8]) -> Result<usize, FsError> {
+        let entry = self.file_entry(ino)?;
+        let size = entry.size() as u64;
+        if offset >= size || buf.is_empty() {
+            return Ok(0);
+        }
+        let len = ((size - offset) as usize).min(buf.len());
+        self.for_each_extent(entry.cluster(), offset, len, |disk, at, pos, chunk| {
+            disk.read_at(at, &mut buf[pos..pos + chunk])
+        })?;
+        Ok(len)
+    }
+
+    fn write(&mut self, ino: Ino, offset: u64, buf: &[u8]) -> Result<usize, FsError> {
+        self.check_writable()?;
+        let mut entry = self.file_entry(ino)?;
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let end = offset
+            .checked_add(buf.len() as u64)
+            .filter(|&end| end <= u32::MAX as u64)
+            .ok_or(FsError::FileTooLarge)?;
+        let old = entry.size() as u64;
+        let grown = if end > old {
+            self.extend(&mut entry, end)
+                .and_then(|()| self.zero(entry.cluster(), old, offset))
+        } else {
+            Ok(())
+        };
+        let result = grown.and_then(|()| {
+            self.for_each_extent(
+                entry.cluster(),
+                offset,
+                buf.len(),
+                |disk, at, pos, chunk| disk.write_at(at, &buf[pos..pos + chunk]),
+            )
+        });
+        if result.is_ok() && end > old {
+            entry.set_size(end as u32);
+        }
+        entry.set_archive();
+        entry.set_mtime(self.now());
+        self.write_entry(ino, &entry)?;
+        result.map(|()| buf.len())
+    }
+
+    fn truncate(&mut self, ino: Ino, size: u64) -> Result<(), FsError> {
+        self.check_writable()?;
+        let mut entry = self.file_entry(ino)?;
+        if size > u32::MAX as u64 {
+            return Err(FsError::FileTooLarge);
+        }
+        self.resize(ino, &mut entry, size)
+    }
+
+    fn read_dir(&mut self, dir: Ino, pos: u64) -> Result<Option<(DirEntry, u64)>, FsError> {
+        let dir = self.dir(dir)?;
+        let Ok(index) = u32::try_from(pos) else {
+            return Ok(None);
+        };
+        let mut cursor = Cursor::new(dir, index);
+        while let Some(found) = self.next_entry(&mut cursor)? {
+            if found.entry.is_dot() {
+                continue;
+            }
+            let kind = if found.entry.is_dir() {
+                FileType::Dir
+            } else {
+                FileType::File
+            };
+            let entry = DirEntry {
+                ino: found.offset,
+                kind,
+                name: found.name,
+            };
+            return Ok(Some((entry, found.index as u64 + 1)));
+        }
+        Ok(None)
+    }
+
+    fn create(&mut self, dir: Ino, name: &str, kind: FileType) -> Result<Ino, FsError> {
+        self.check_writable()?;
+        if kind == FileType::Symlink {
+            return Err(FsError::Unsupported);
+        }
+        let dir = self.dir(dir)?;
+        let units = dir::validate(name)?;
+        if self.find(dir, name)?.is_some() {
+            return Err(FsError::Exists);
+        }
+
+        let (short, ntres, long) = match dir::short_name(name) {
+            ShortName::Exact(short, ntres) => (short, ntres, false),
+            ShortName::Basis(basis, base_len) => {
+                let mut n = 1;
+                loop {
+                    let short = dir::with_tail(&basis, base_len, n).ok_or(FsError::Exists)?;
+                    if !self.short_exists(dir, &short)? {
+                        break (short, 0, true);
+                    }
+                    n += 1;
+                }
+            }
+        };
+        let long_entries = if long {
+            units.div_ceil(dir::LFN_CHARS_PER_ENTRY) as u32
+        } else {
+            0
+        };
+        let start = self.alloc_slots(dir, long_entries + 1)?;
+
+        let now = self.now();
+        let (attr, cluster) = match kind {
+            FileType::Dir => {
+                let cluster = self.alloc_cluster(None, true)?;
+                let offset = self.layout.cluster_offset(cluster);
+                let dot = RawEntry::new(SHORT_DOT, 0, dir::ATTR_DIRECTORY, cluster, now);
+                let parent = self.parent_cluster(dir);
+                let dotdot = RawEntry::new(SHORT_DOTDOT, 0, dir::ATTR_DIRECTORY, parent, now);
+                self.write_entry(offset, &dot)?;
+                self.write_entry(offset + ENTRY_SIZE, &dotdot)?;
+                (dir::ATTR_DIRECTORY, cluster)
+            }
+            _ => (dir::ATTR_ARCHIVE, 0),
+        };
+
+        if long {
+            let mut utf16 = [0u16; dir::LFN_MAX_UNITS];
+            for (dst, unit) in utf16.iter_mut().zip(name.encode_utf16()) {
+                *dst = unit;
+            }
+            let checksum = dir::checksum(&short);
+            for idx in 0..long_entries {
+                let order = (long_entries - idx) as u8;
+                let entry = RawEntry::lfn(order, idx == 0, checksum, &utf16[..units]);
+                let offset = self.slot_offset(dir, start + idx)?;
+                self.write_entry(offset, &entry)?;
+            }
+        }
+        let offset = self.slot_offset(dir, start + long_entries)?;
+        self.write_entry(offset, &RawEntry::new(&short, ntres, attr, cluster, now))?;
+        Ok(offset)
+    }
+
+    fn remove(&mut self, dir: Ino, name: &str) -> Result<(), FsError> {
+        self.check_writable()?;
+        let dir = self.dir(dir)?;
+        if name == "." || name == ".." {
+            return Err(FsError::InvalidName);
+        }
+        let found = self.find(dir, name)?.ok_or(FsError::NotFound)?;
+        let cluster = found.entry.cluster();
+        if found.entry.is_dir() {
+            let sub = self.cluster_dir(cluster)?;
+            if !self.is_empty(sub)? {
+                return Err(FsError::NotEmpty);
+            }
+        }
+        if cluster != 0 {
+            self.free_chain(cluster)?;
+        }
+        for index in found.first..=found.index {
+            let offset = self.slot_offset(dir, index)?;
+            self.disk.write_at(offset, &[dir::ENTRY_DELETED])?;
+        }
+        Ok(())
+    }
+
+    fn sync(&mut self) -> Result<(), FsError> {
+        if self.fsinfo_dirty {
+            self.write_fsinfo()?;
+        }
+        self.disk.flush()
+    }
+}
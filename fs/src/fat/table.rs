@@ -0,0 +1,243 @@
+use crate::{Disk, FsError};
+
+use super::{Fat, FatType};
+
+const FREE: u32 = 0;
+const FAT32_MASK: u32 = 0x0fff_ffff;
+const SCAN_CHUNK: usize = 512;
+
+impl FatType {
+    #[inline]
+    fn end_of_chain(self) -> u32 {
+        match self {
+            Self::Fat12 => 0xfff,
+            Self::Fat16 => 0xffff,
+            Self::Fat32 => 0x0fff_ffff,
+        }
+    }
+
+    #[inline]
+    fn is_end(self, value: u32) -> bool {
+        value >= self.end_of_chain() & !0x7
+    }
+}
+
+impl<D: Disk> Fat<D> {
+    fn entry_offset(&self, fat: u32, cluster: u32) -> u64 {
+        let base = self.layout.fat_offset + fat as u64 * self.layout.fat_size;
+        base + match self.layout.kind {
+            FatType::Fat12 => cluster as u64 * 3 / 2,
+            FatType::Fat16 => cluster as u64 * 2,
+            FatType::Fat32 => cluster as u64 * 4,
+        }
+    }
+
+    pub(super) fn entry(&mut self, cluster: u32) -> Result<u32, FsError> {
+        let offset = self.entry_offset(self.layout.active_fat.unwrap_or(0), cluster);
+        Ok(match self.layout.kind {
+            FatType::Fat12 => {
+                let mut buf = [0; 2];
+                self.disk.read_at(offset, &mut buf)?;
+                let value = u16::from_le_bytes(buf) as u32;
+                if cluster & 1 != 0 {
+                    value >> 4
+                } else {
+                    value & 0xfff
+                }
+            }
+            FatType::Fat16 => {
+                let mut buf = [0; 2];
+                self.disk.read_at(offset, &mut buf)?;
+                u16::from_le_bytes(buf) as u32
+            }
+            FatType::Fat32 => {
+                let mut buf = [0; 4];
+                self.disk.read_at(offset, &mut buf)?;
+                u32::from_le_bytes(buf) & FAT32_MASK
+            }
+        })
+    }
+
+    // Every copy is kept in sync unless the volume says only one is active.
+    pub(super) fn set_entry(&mut self, cluster: u32, value: u32) -> Result<(), FsError> {
+        let fats = match self.layout.active_fat {
+            Some(fat) => fat..fat + 1,
+            None => 0..self.layout.fats,
+        };
+        for fat in fats {
+            let offset = self.entry_offset(fat, cluster);
+            match self.layout.kind {
+                FatType::Fat12 => {
+                    let mut buf = [0; 2];
+                    self.disk.read_at(offset, &mut buf)?;
+                    let old = u16::from_le_bytes(buf);
+                    let new = if cluster & 1 != 0 {
+                        (old & 0x000f) | ((value as u16) << 4)
+                    } else {
+                        (old & 0xf000) | (value as u16 & 0xfff)
+                    };
+                    self.disk.write_at(offset, &new.to_le_bytes())?;
+                }
+                FatType::Fat16 => self.disk.write_at(offset, &(value as u16).to_le_bytes())?,
+                // The top four bits are reserved and preserved.
+                FatType::Fat32 => {
+                    let mut buf = [0; 4];
+                    self.disk.read_at(offset, &mut buf)?;
+                    let new = (u32::from_le_bytes(buf) & !FAT32_MASK) | (value & FAT32_MASK);
+                    self.disk.write_at(offset, &new.to_le_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Returns the cluster after `cluster`, or None at the end of the chain.
+    pub(super) fn next_cluster(&mut self, cluster: u32) -> Result<Option<u32>, FsError> {
+        let next = self.entry(cluster)?;
+        if self.layout.kind.is_end(next) {
+            Ok(None)
+        } else if self.layout.is_cluster(next) {
+            Ok(Some(next))
+        } else {
+            Err(FsError::Corrupt("bad cluster chain"))
+        }
+    }
+
+    // The cluster `n` links after `first`, or None if the chain is shorter.
+    pub(super) fn nth_cluster(&mut self, first: u32, n: u64) -> Result<Option<u32>, FsError> {
+        let mut cluster = first;
+        for _ in 0..n {
+            match self.next_cluster(cluster)? {
+                Some(next) => cluster = next,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(cluster))
+    }
+
+    pub(super) fn last_cluster(&mut self, first: u32) -> Result<(u32, u64), FsError> {
+        let mut cluster = first;
+        let mut count = 1;
+        while let Some(next) = self.next_cluster(cluster)? {
+            cluster = next;
+            count += 1;
+            if count > self.layout.clusters as u64 {
+                return Err(FsError::Corrupt("cluster chain loops"));
+            }
+        }
+        Ok((cluster, count))
+    }
+
+    // Allocates a cluster, linking it after `prev` if given. Directory
+    // clusters must start out zeroed so that they read as empty.
+    pub(super) fn alloc_cluster(&mut self, prev: Option<u32>, zero: bool) -> Result<u32, FsError> {
+        if self.free == Some(0) {
+            return Err(FsError::NoSpace);
+        }
+        let first = 2;
+        let end = self.layout.clusters + 2;
+        let start = if self.layout.is_cluster(self.next_free) {
+            self.next_free
+        } else {
+            first
+        };
+        let mut cluster = start;
+        loop {
+            if self.entry(cluster)? == FREE {
+                break;
+            }
+            cluster = if cluster + 1 == end {
+                first
+            } else {
+                cluster + 1
+            };
+            if cluster == start {
+                self.free = Some(0);
+                return Err(FsError::NoSpace);
+            }
+        }
+
+        if zero {
+            let offset = self.layout.cluster_offset(cluster);
+            let zeros = [0; 512];
+            for chunk in (0..self.layout.cluster_size as u64).step_by(zeros.len()) {
+                self.disk.write_at(offset + chunk, &zeros)?;
+            }
+        }
+        self.set_entry(cluster, self.layout.kind.end_of_chain())?;
+        if let Some(prev) = prev {
+            self.set_entry(prev, cluster)?;
+        }
+        self.free = self.free.map(|free| free - 1);
+        self.next_free = cluster + 1;
+        self.fsinfo_dirty = true;
+        Ok(cluster)
+    }
+
+    pub(super) fn free_chain(&mut self, first: u32) -> Result<(), FsError> {
+        let mut cluster = Some(first);
+        let mut count = 0;
+        while let Some(current) = cluster {
+            cluster = self.next_cluster(current)?;
+            self.set_entry(current, FREE)?;
+            self.free = self.free.map(|free| free + 1);
+            count += 1;
+            if count > self.layout.clusters {
+                return Err(FsError::Corrupt("cluster chain loops"));
+            }
+        }
+        self.fsinfo_dirty = true;
+        Ok(())
+    }
+
+    // Cuts the chain after `last`, freeing what followed.
+    pub(super) fn truncate_chain(&mut self, last: u32) -> Result<(), FsError> {
+        let next = self.next_cluster(last)?;
+        self.set_entry(last, self.layout.kind.end_of_chain())?;
+        match next {
+            Some(next) => self.free_chain(next),
+            None => Ok(()),
+        }
+    }
+
+    pub(super) fn count_free(&mut self) -> Result<u32, FsError> {
+        let end = self.layout.clusters + 2;
+        if self.layout.kind == FatType::Fat12 {
+            let mut free = 0;
+            for cluster in 2..end {
+                if self.entry(cluster)? == FREE {
+                    free += 1;
+                }
+            }
+            return Ok(free);
+        }
+
+        let width = if self.layout.kind == FatType::Fat16 {
+            2
+        } else {
+            4
+        };
+        let base = self.entry_offset(self.layout.active_fat.unwrap_or(0), 0);
+        let mut buf = [0; SCAN_CHUNK];
+        let mut free = 0;
+        let mut cluster = 0;
+        while cluster < end {
+            let count = ((end - cluster) as usize).min(SCAN_CHUNK / width);
+            let buf = &mut buf[..count * width];
+            self.disk
+                .read_at(base + cluster as u64 * width as u64, buf)?;
+            for (idx, entry) in buf.chunks(width).enumerate() {
+                let value = if width == 2 {
+                    u16::from_le_bytes([entry[0], entry[1]]) as u32
+                } else {
+                    u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]) & FAT32_MASK
+                };
+                if value == FREE && cluster + idx as u32 >= 2 {
+                    free += 1;
+                }
+            }
+            cluster += count as u32;
+        }
+        Ok(free)
+    }
+}
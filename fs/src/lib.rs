@@ -0,0 +1,306 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use core::{fmt, str};
+
+pub use ext2::Ext2;
+pub use fat::Fat;
+
+pub mod ext2;
+pub mod fat;
+
+pub const NAME_MAX: usize = 255;
+pub const PATH_MAX: usize = 1024;
+
+// Symbolic links followed while resolving a single path.
+const MAX_LINKS: usize = 40;
+
+pub type Ino = u64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsError {
+    Io,
+    NotFound,
+    Exists,
+    NotDir,
+    IsDir,
+    NotEmpty,
+    NoSpace,
+    FileTooLarge,
+    NameTooLong,
+    InvalidName,
+    Loop,
+    ReadOnly,
+    Unsupported,
+    Corrupt(&'static str),
+}
+
+impl fmt::Display for FsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io => f.write_str("i/o error"),
+            Self::NotFound => f.write_str("no such file or directory"),
+            Self::Exists => f.write_str("file exists"),
+            Self::NotDir => f.write_str("not a directory"),
+            Self::IsDir => f.write_str("is a directory"),
+            Self::NotEmpty => f.write_str("directory not empty"),
+            Self::NoSpace => f.write_str("no space left on device"),
+            Self::FileTooLarge => f.write_str("file too large"),
+            Self::NameTooLong => f.write_str("file name too long"),
+            Self::InvalidName => f.write_str("invalid file name"),
+            Self::Loop => f.write_str("too many levels of symbolic links"),
+            Self::ReadOnly => f.write_str("read only filesystem"),
+            Self::Unsupported => f.write_str("operation not supported"),
+            Self::Corrupt(what) => write!(f, "corrupt filesystem: {}", what),
+        }
+    }
+}
+
+// Byte addressed storage under a filesystem. Accesses past `size` fail.
+pub trait Disk {
+    fn size(&self) -> u64;
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<(), FsError>;
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> Result<(), FsError>;
+
+    fn flush(&mut self) -> Result<(), FsError> {
+        Ok(())
+    }
+
+    fn is_read_only(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    File,
+    Dir,
+    Symlink,
+}
+
+// Times are seconds since the Unix epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Metadata {
+    pub ino: Ino,
+    pub kind: FileType,
+    pub size: u64,
+    pub mode: u16,
+    pub links: u16,
+    pub uid: u32,
+    pub gid: u32,
+    pub mtime: u64,
+}
+
+#[derive(Clone, Copy)]
+pub struct Name {
+    buf: [u8; NAME_MAX],
+    len: u8,
+}
+
+impl Name {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; NAME_MAX],
+            len: 0,
+        }
+    }
+
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        str::from_utf8(&self.buf[..self.len as usize]).unwrap_or("?")
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push_str(&mut self, s: &str) -> Result<(), FsError> {
+        let end = self.len as usize + s.len();
+        if end > NAME_MAX {
+            return Err(FsError::NameTooLong);
+        }
+        self.buf[self.len as usize..end].copy_from_slice(s.as_bytes());
+        self.len = end as u8;
+        Ok(())
+    }
+
+    pub fn push(&mut self, c: char) -> Result<(), FsError> {
+        self.push_str(c.encode_utf8(&mut [0; 4]))
+    }
+}
+
+impl Default for Name {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DirEntry {
+    pub ino: Ino,
+    pub kind: FileType,
+    pub name: Name,
+}
+
+// Directory listings leave out "." and "..", while `lookup` resolves both.
+// `read_dir` returns the entry at or after `pos` along with the position to
+// continue from. `create` makes files and directories, symbolic links are
+// made with `symlink`.
+pub trait FileSystem {
+    fn name(&self) -> &'static str;
+    fn root(&self) -> Ino;
+    fn lookup(&mut self, dir: Ino, name: &str) -> Result<Ino, FsError>;
+    fn metadata(&mut self, ino: Ino) -> Result<Metadata, FsError>;
+    fn read(&mut self, ino: Ino, offset: u64, buf: &mut [u8]) -> Result<usize, FsError>;
+    fn write(&mut self, ino: Ino, offset: u64, buf: &[u8]) -> Result<usize, FsError>;
+    fn truncate(&mut self, ino: Ino, size: u64) -> Result<(), FsError>;
+    fn read_dir(&mut self, dir: Ino, pos: u64) -> Result<Option<(DirEntry, u64)>, FsError>;
+    fn create(&mut self, dir: Ino, name: &str, kind: FileType) -> Result<Ino, FsError>;
+    fn remove(&mut self, dir: Ino, name: &str) -> Result<(), FsError>;
+    fn sync(&mut self) -> Result<(), FsError>;
+
+    fn symlink(&mut self, _dir: Ino, _name: &str, _target: &str) -> Result<Ino, FsError> {
+        Err(FsError::Unsupported)
+    }
+
+    // Copies the target of a link into `buf` and returns its length.
+    fn read_link(&mut self, _ino: Ino, _buf: &mut [u8]) -> Result<usize, FsError> {
+        Err(FsError::Unsupported)
+    }
+}
+
+#[inline]
+pub(crate) fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+#[inline]
+pub(crate) fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        buf[offset],
+        buf[offset + 1],
+        buf[offset + 2],
+        buf[offset + 3],
+    ])
+}
+
+// Splits a path into the parent directory and the last component.
+pub fn split(path: &str) -> (&str, &str) {
+    let path = path.trim_end_matches('/');
+    match path.rfind('/') {
+        Some(idx) => (&path[..idx], &path[idx + 1..]),
+        None => ("", path),
+    }
+}
+
+// Walks `path` from `start`, which is used for relative paths. Absolute
+// paths start at the root. Symbolic links are followed, the target taking
+// the place of the link in the rest of the path.
+pub fn resolve(fs: &mut dyn FileSystem, start: Ino, path: &str) -> Result<Ino, FsError> {
+    walk(fs, start, path, true)
+}
+
+// Like `resolve`, but returns a link in the last component itself.
+pub fn resolve_nofollow(fs: &mut dyn FileSystem, start: Ino, path: &str) -> Result<Ino, FsError> {
+    walk(fs, start, path, false)
+}
+
+fn walk(fs: &mut dyn FileSystem, start: Ino, path: &str, follow: bool) -> Result<Ino, FsError> {
+    let mut buf = [0; PATH_MAX];
+    let mut len = path.len();
+    buf.get_mut(..len)
+        .ok_or(FsError::NameTooLong)?
+        .copy_from_slice(path.as_bytes());
+    let mut pos = 0;
+    let mut links = 0;
+    let mut ino = if path.starts_with('/') {
+        fs.root()
+    } else {
+        start
+    };
+
+    loop {
+        while pos < len && buf[pos] == b'/' {
+            pos += 1;
+        }
+        if pos == len {
+            return Ok(ino);
+        }
+        let end = buf[pos..len]
+            .iter()
+            .position(|&b| b == b'/')
+            .map_or(len, |idx| pos + idx);
+        let component = str::from_utf8(&buf[pos..end]).map_err(|_| FsError::InvalidName)?;
+        if fs.metadata(ino)?.kind != FileType::Dir {
+            return Err(FsError::NotDir);
+        }
+        let next = fs.lookup(ino, component)?;
+        pos = end;
+
+        let last = buf[pos..len].iter().all(|&b| b == b'/');
+        if (follow || !last) && fs.metadata(next)?.kind == FileType::Symlink {
+            links += 1;
+            if links > MAX_LINKS {
+                return Err(FsError::Loop);
+            }
+            let mut target = [0; PATH_MAX];
+            let target_len = fs.read_link(next, &mut target)?;
+            if target_len == 0 {
+                return Err(FsError::NotFound);
+            }
+            let rest = len - pos;
+            if target_len + 1 + rest > PATH_MAX {
+                return Err(FsError::NameTooLong);
+            }
+            target[target_len] = b'/';
+            target[target_len + 1..target_len + 1 + rest].copy_from_slice(&buf[pos..len]);
+            buf = target;
+            len = target_len + 1 + rest;
+            pos = 0;
+            // Relative targets start from the directory holding the link.
+            if buf[0] == b'/' {
+                ino = fs.root();
+            }
+            continue;
+        }
+        ino = next;
+    }
+}
+
+#[cfg(feature = "std")]
+impl Disk for std::vec::Vec<u8> {
+    fn size(&self) -> u64 {
+        self.len() as u64
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<(), FsError> {
+        let start = usize::try_from(offset).map_err(|_| FsError::Io)?;
+        let src = self.get(start..start + buf.len()).ok_or(FsError::Io)?;
+        buf.copy_from_slice(src);
+        Ok(())
+    }
+
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> Result<(), FsError> {
+        let start = usize::try_from(offset).map_err(|_| FsError::Io)?;
+        let dst = self.get_mut(start..start + buf.len()).ok_or(FsError::Io)?;
+        dst.copy_from_slice(buf);
+        Ok(())
+    }
+}
@@ -0,0 +1,58 @@
+use std::{
+    env,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use fs::{FileSystem, FileType, Ino};
+
+pub const MIB: u64 = 1024 * 1024;
+
+pub fn temp(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("fs-test-{}-{}", name, std::process::id()))
+}
+
+// Runs a host tool with `path` as its last argument. The tests are
+// worthless without it, so a missing tool is a failure rather than a skip.
+pub fn run(program: &str, args: &[&str], path: &Path) {
+    match Command::new(program).args(args).arg(path).output() {
+        Ok(output) => assert!(output.status.success(), "{}: {:?}", program, output),
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            panic!("{} not found, it is needed to run these tests", program)
+        }
+        Err(err) => panic!("{}: {}", program, err),
+    }
+}
+
+// Formats a blank image of `size` bytes with a host mkfs.
+pub fn mkfs(name: &str, program: &str, args: &[&str], size: u64) -> Vec<u8> {
+    let path = temp(name).with_extension("img");
+    std::fs::File::create(&path).unwrap().set_len(size).unwrap();
+    run(program, args, &path);
+    let image = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    image
+}
+
+pub fn fsck(name: &str, program: &str, args: &[&str], image: &[u8]) {
+    let path = temp(name).with_extension("fsck.img");
+    std::fs::write(&path, image).unwrap();
+    run(program, args, &path);
+    std::fs::remove_file(&path).unwrap();
+}
+
+pub fn list(fs: &mut dyn FileSystem, dir: Ino) -> Vec<(String, FileType)> {
+    let mut names = Vec::new();
+    let mut pos = 0;
+    while let Some((entry, next)) = fs.read_dir(dir, pos).unwrap() {
+        names.push((entry.name.as_str().to_string(), entry.kind));
+        pos = next;
+    }
+    names.sort_by(|a, b| a.0.cmp(&b.0));
+    names
+}
+
+pub fn pattern(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 7 + i / 251) as u8).collect()
+}
@@ -0,0 +1,321 @@
+use std::{
+    io::{Seek, SeekFrom, Write},
+    os::unix::fs::symlink,
+    path::Path,
+};
+
+use fs::*;
+
+use common::{list, pattern, temp, MIB};
+
+mod common;
+
+const NOW: u64 = 1_700_000_000;
+// Past the direct, indirect and double indirect blocks with 1 KiB blocks.
+const SPARSE_SIZE: u64 = 70_000_004;
+
+fn mkfs(name: &str, args: &[&str], size: u64, root: Option<&Path>) -> Vec<u8> {
+    let mut all = vec!["-q", "-F"];
+    all.extend_from_slice(args);
+    if let Some(root) = root {
+        all.extend(["-d", root.to_str().unwrap()]);
+    }
+    common::mkfs(&format!("ext2-{}", name), "mke2fs", &all, size)
+}
+
+fn ext2(name: &str, block_size: u32, inode_size: u32) -> Vec<u8> {
+    let block_size = block_size.to_string();
+    let inode_size = inode_size.to_string();
+    let args = ["-t", "ext2", "-b", &block_size, "-I", &inode_size];
+    mkfs(name, &args, 16 * MIB, None)
+}
+
+fn fsck(name: &str, image: &[u8]) {
+    common::fsck(&format!("ext2-{}", name), "e2fsck", &["-fn"], image);
+}
+
+fn read_all(fs: &mut dyn FileSystem, ino: Ino) -> Vec<u8> {
+    let size = fs.metadata(ino).unwrap().size as usize;
+    let mut buf = vec![0xaa; size + 16];
+    assert_eq!(fs.read(ino, 0, &mut buf).unwrap(), size);
+    buf.truncate(size);
+    buf
+}
+
+fn read_link(fs: &mut dyn FileSystem, ino: Ino) -> String {
+    let mut buf = [0; PATH_MAX];
+    let len = fs.read_link(ino, &mut buf).unwrap();
+    String::from_utf8(buf[..len].to_vec()).unwrap()
+}
+
+fn clock() -> u64 {
+    NOW
+}
+
+#[test]
+fn mount() {
+    let image = ext2("mount", 1024, 128);
+    let mut fs = Ext2::mount(image).unwrap();
+    assert_eq!(fs.block_size(), 1024);
+    assert!(fs.free_blocks() > 0 && fs.free_inodes() > 0);
+
+    let root = fs.root();
+    let meta = fs.metadata(root).unwrap();
+    assert_eq!(meta.kind, FileType::Dir);
+    assert_eq!(meta.mode, 0o755);
+    assert_eq!(
+        list(&mut fs, root),
+        [("lost+found".to_string(), FileType::Dir)]
+    );
+    assert_eq!(fs.lookup(root, ".."), Ok(root));
+    assert_eq!(fs.lookup(root, "missing"), Err(FsError::NotFound));
+    assert_eq!(fs.read(root, 0, &mut [0; 4]), Err(FsError::IsDir));
+}
+
+#[test]
+fn rejects_unsupported() {
+    assert!(Ext2::mount(vec![0u8; 64 * 1024]).is_err());
+    let image = mkfs("ext4", &["-t", "ext4"], 16 * MIB, None);
+    assert_eq!(Ext2::mount(image).err(), Some(FsError::Unsupported));
+}
+
+#[test]
+fn read_populated() {
+    let dir = temp("ext2-tree");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("sub/deeper")).unwrap();
+    std::fs::write(dir.join("small.txt"), b"hello ext2\n").unwrap();
+    std::fs::write(dir.join("sub/deeper/big"), pattern(300 * 1024)).unwrap();
+    let mut sparse = std::fs::File::create(dir.join("sparse")).unwrap();
+    sparse.write_all(b"head").unwrap();
+    sparse.seek(SeekFrom::Start(SPARSE_SIZE - 4)).unwrap();
+    sparse.write_all(b"tail").unwrap();
+    drop(sparse);
+    let long_target = format!("sub/{}/../deeper/big", "x".repeat(80));
+    symlink("small.txt", dir.join("fast")).unwrap();
+    symlink(&long_target, dir.join("slow")).unwrap();
+    symlink("/sub/deeper", dir.join("link-dir")).unwrap();
+
+    let args = ["-t", "ext2", "-b", "1024"];
+    let image = mkfs("populated", &args, 16 * MIB, Some(&dir));
+    std::fs::remove_dir_all(&dir).unwrap();
+    let mut fs = Ext2::mount(image).unwrap();
+    let root = fs.root();
+    assert_eq!(
+        list(&mut fs, root),
+        [
+            ("fast".to_string(), FileType::Symlink),
+            ("link-dir".to_string(), FileType::Symlink),
+            ("lost+found".to_string(), FileType::Dir),
+            ("slow".to_string(), FileType::Symlink),
+            ("small.txt".to_string(), FileType::File),
+            ("sparse".to_string(), FileType::File),
+            ("sub".to_string(), FileType::Dir),
+        ]
+    );
+
+    let small = resolve(&mut fs, root, "/small.txt").unwrap();
+    assert_eq!(read_all(&mut fs, small), b"hello ext2\n");
+    let big = resolve(&mut fs, root, "sub/deeper/big").unwrap();
+    assert_eq!(read_all(&mut fs, big), pattern(300 * 1024));
+    let mut buf = vec![0; 5000];
+    assert_eq!(fs.read(big, 270 * 1024 - 100, &mut buf).unwrap(), 5000);
+    assert_eq!(buf, pattern(300 * 1024)[270 * 1024 - 100..][..5000]);
+
+    let sparse = fs.lookup(root, "sparse").unwrap();
+    assert_eq!(fs.metadata(sparse).unwrap().size, SPARSE_SIZE);
+    let mut buf = [0xaa; 12];
+    assert_eq!(fs.read(sparse, SPARSE_SIZE - 8, &mut buf).unwrap(), 8);
+    assert_eq!(&buf[..8], b"\0\0\0\0tail");
+    assert_eq!(fs.read(sparse, 0, &mut buf).unwrap(), 12);
+    assert_eq!(&buf, b"head\0\0\0\0\0\0\0\0");
+
+    let fast = fs.lookup(root, "fast").unwrap();
+    assert_eq!(read_link(&mut fs, fast), "small.txt");
+    assert_eq!(fs.read(fast, 0, &mut buf), Err(FsError::Unsupported));
+    let slow = fs.lookup(root, "slow").unwrap();
+    assert_eq!(read_link(&mut fs, slow), long_target);
+    assert_eq!(resolve(&mut fs, root, "fast"), Ok(small));
+    assert_eq!(resolve_nofollow(&mut fs, root, "fast"), Ok(fast));
+    assert_eq!(resolve(&mut fs, root, "link-dir/big"), Ok(big));
+    assert_eq!(
+        resolve(&mut fs, root, "slow").err(),
+        Some(FsError::NotFound)
+    );
+    assert_eq!(resolve(&mut fs, root, "small.txt/x"), Err(FsError::NotDir));
+}
+
+#[test]
+fn read_write() {
+    for (block_size, inode_size) in [(1024, 128), (1024, 256), (4096, 128), (4096, 256)] {
+        let name = format!("rw-{}-{}", block_size, inode_size);
+        let image = ext2(&name, block_size, inode_size);
+        let mut fs = Ext2::mount(image).unwrap().with_clock(clock);
+        let (free_blocks, free_inodes) = (fs.free_blocks(), fs.free_inodes());
+        let root = fs.root();
+
+        let file = fs.create(root, "file", FileType::File).unwrap();
+        assert_eq!(
+            fs.create(root, "file", FileType::File),
+            Err(FsError::Exists)
+        );
+        let data = pattern(600 * 1024);
+        assert_eq!(fs.write(file, 0, &data).unwrap(), data.len());
+        assert_eq!(fs.write(file, 1000, b"overwrite").unwrap(), 9);
+        let mut expected = data.clone();
+        expected[1000..1009].copy_from_slice(b"overwrite");
+        assert_eq!(read_all(&mut fs, file), expected);
+        let meta = fs.metadata(file).unwrap();
+        assert_eq!((meta.mode, meta.links, meta.mtime), (0o644, 1, NOW));
+
+        fs.truncate(file, 5000).unwrap();
+        fs.truncate(file, 9000).unwrap();
+        expected.truncate(5000);
+        expected.resize(9000, 0);
+        assert_eq!(read_all(&mut fs, file), expected);
+
+        // A write far past the end leaves a hole.
+        let sparse = fs.create(root, "sparse", FileType::File).unwrap();
+        assert_eq!(fs.write(sparse, 3 * MIB, b"end").unwrap(), 3);
+        let mut buf = [0xaa; 8];
+        assert_eq!(fs.read(sparse, 3 * MIB - 5, &mut buf).unwrap(), 8);
+        assert_eq!(&buf, b"\0\0\0\0\0end");
+
+        let dir = fs.create(root, "dir", FileType::Dir).unwrap();
+        let inner = fs.create(dir, "inner", FileType::File).unwrap();
+        fs.write(inner, 0, b"inside").unwrap();
+        assert_eq!(fs.metadata(root).unwrap().links, 4);
+        assert_eq!(fs.metadata(dir).unwrap().links, 2);
+        assert_eq!(fs.lookup(dir, ".."), Ok(root));
+        assert_eq!(resolve(&mut fs, root, "/dir/inner"), Ok(inner));
+        assert_eq!(fs.remove(root, "dir"), Err(FsError::NotEmpty));
+        assert_eq!(
+            fs.create(root, "a/b", FileType::File),
+            Err(FsError::InvalidName)
+        );
+        assert_eq!(
+            fs.create(root, &"x".repeat(256), FileType::File),
+            Err(FsError::NameTooLong)
+        );
+
+        fs.sync().unwrap();
+        let image = fs.into_disk();
+        fsck(&name, &image);
+
+        let mut fs = Ext2::mount(image).unwrap();
+        let file = fs.lookup(root, "file").unwrap();
+        assert_eq!(read_all(&mut fs, file), expected);
+        fs.remove(dir, "inner").unwrap();
+        fs.remove(root, "dir").unwrap();
+        fs.remove(root, "file").unwrap();
+        fs.remove(root, "sparse").unwrap();
+        assert_eq!(fs.lookup(root, "file"), Err(FsError::NotFound));
+        assert_eq!(fs.metadata(file), Err(FsError::NotFound));
+        assert_eq!(fs.metadata(root).unwrap().links, 3);
+        assert_eq!(
+            (fs.free_blocks(), fs.free_inodes()),
+            (free_blocks, free_inodes)
+        );
+        fs.sync().unwrap();
+        fsck(&name, &fs.into_disk());
+    }
+}
+
+#[test]
+fn large_directory() {
+    let image = ext2("large-dir", 1024, 128);
+    let mut fs = Ext2::mount(image).unwrap();
+    let root = fs.root();
+    let dir = fs.create(root, "many", FileType::Dir).unwrap();
+    let names: Vec<String> = (0..300)
+        .map(|i| format!("entry number {:03} with a long name", i))
+        .collect();
+    for name in &names {
+        fs.create(dir, name, FileType::File).unwrap();
+    }
+    assert!(fs.metadata(dir).unwrap().size > 8 * 1024);
+    for name in names.iter().step_by(2) {
+        fs.remove(dir, name).unwrap();
+    }
+    let left: Vec<String> = list(&mut fs, dir)
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    let expected: Vec<String> = names.iter().skip(1).step_by(2).cloned().collect();
+    assert_eq!(left, expected);
+
+    // Freed slots get reused before the directory grows.
+    let size = fs.metadata(dir).unwrap().size;
+    fs.create(dir, "reused", FileType::File).unwrap();
+    assert_eq!(fs.metadata(dir).unwrap().size, size);
+    fs.sync().unwrap();
+    fsck("large-dir", &fs.into_disk());
+}
+
+#[test]
+fn symlinks() {
+    for block_size in [1024, 4096] {
+        let name = format!("symlinks-{}", block_size);
+        let image = ext2(&name, block_size, 256);
+        let mut fs = Ext2::mount(image).unwrap();
+        let root = fs.root();
+        let dir = fs.create(root, "dir", FileType::Dir).unwrap();
+        let file = fs.create(dir, "file", FileType::File).unwrap();
+
+        let fast = fs.symlink(root, "fast", "dir/file").unwrap();
+        let long_target = format!("{}/../dir/file", "y".repeat(100));
+        let slow = fs.symlink(root, "slow", &long_target).unwrap();
+        fs.symlink(dir, "up", "..").unwrap();
+        fs.symlink(root, "loop-a", "loop-b").unwrap();
+        fs.symlink(root, "loop-b", "/loop-a").unwrap();
+
+        assert_eq!(fs.metadata(fast).unwrap().kind, FileType::Symlink);
+        assert_eq!(fs.metadata(slow).unwrap().size, long_target.len() as u64);
+        assert_eq!(read_link(&mut fs, fast), "dir/file");
+        assert_eq!(read_link(&mut fs, slow), long_target);
+        assert_eq!(resolve(&mut fs, root, "fast"), Ok(file));
+        assert_eq!(resolve(&mut fs, root, "dir/up/dir/up/fast"), Ok(file));
+        assert_eq!(resolve(&mut fs, root, "loop-a"), Err(FsError::Loop));
+        assert_eq!(
+            fs.symlink(root, "huge", &"z".repeat(block_size as usize)),
+            Err(FsError::NameTooLong)
+        );
+        assert_eq!(fs.read_link(file, &mut [0; 16]), Err(FsError::Unsupported));
+
+        fs.sync().unwrap();
+        let image = fs.into_disk();
+        fsck(&name, &image);
+
+        let mut fs = Ext2::mount(image).unwrap();
+        for name in ["fast", "slow", "loop-a", "loop-b"] {
+            fs.remove(root, name).unwrap();
+        }
+        fs.remove(dir, "up").unwrap();
+        assert_eq!(resolve(&mut fs, root, "dir/file"), Ok(file));
+        fs.sync().unwrap();
+        fsck(&name, &fs.into_disk());
+    }
+}
+
+#[test]
+fn no_space() {
+    let args = ["-t", "ext2", "-b", "1024", "-m", "0"];
+    let image = mkfs("no-space", &args, MIB, None);
+    let mut fs = Ext2::mount(image).unwrap();
+    let root = fs.root();
+    let file = fs.create(root, "fill", FileType::File).unwrap();
+    let data = pattern(2 * MIB as usize);
+    let written = fs.write(file, 0, &data).unwrap();
+    assert!(written > 0 && written < data.len());
+    assert_eq!(fs.free_blocks(), 0);
+    assert_eq!(
+        fs.write(file, written as u64, b"more"),
+        Err(FsError::NoSpace)
+    );
+    assert_eq!(read_all(&mut fs, file), data[..written]);
+
+    fs.remove(root, "fill").unwrap();
+    assert!(fs.free_blocks() > 0);
+    fs.sync().unwrap();
+    fsck("no-space", &fs.into_disk());
+}
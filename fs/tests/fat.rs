@@ -0,0 +1,272 @@
+use fs::fat::FatType;
+use fs::*;
+
+use common::{list, pattern, MIB};
+
+mod common;
+
+// No label is given, it would take up an entry in the root directory.
+fn mkfs(name: &str, bits: u8, size: u64) -> Vec<u8> {
+    common::mkfs(name, "mkfs.fat", &["-F", &bits.to_string()], size)
+}
+
+fn fsck(name: &str, image: &[u8]) {
+    common::fsck(name, "fsck.fat", &["-n"], image);
+}
+
+fn images(name: &str) -> Vec<(FatType, Vec<u8>)> {
+    [
+        (FatType::Fat12, 12, 2 * MIB),
+        (FatType::Fat16, 16, 16 * MIB),
+        (FatType::Fat32, 32, 64 * MIB),
+    ]
+    .into_iter()
+    .map(|(kind, bits, size)| (kind, mkfs(&format!("{}-{}", name, bits), bits, size)))
+    .collect()
+}
+
+#[test]
+fn mount() {
+    for (kind, image) in images("mount") {
+        let mut fat = Fat::mount(image).unwrap();
+        assert_eq!(fat.kind(), kind);
+        assert!(fat.free_clusters() > 0);
+
+        let root = fat.root();
+        let meta = fat.metadata(root).unwrap();
+        assert_eq!(meta.kind, FileType::Dir);
+        assert!(list(&mut fat, root).is_empty());
+        assert_eq!(fat.lookup(root, "missing"), Err(FsError::NotFound));
+        assert_eq!(fat.lookup(root, ".."), Ok(root));
+    }
+}
+
+#[test]
+fn rejects_garbage() {
+    assert!(Fat::mount(vec![0u8; 64 * 1024]).is_err());
+}
+
+#[test]
+fn rejects_oversized_fats() {
+    // 255 FATs of 2^25 sectors each overflow 32 bits.
+    let mut image = vec![0u8; 64 * 1024];
+    image[11..13].copy_from_slice(&512u16.to_le_bytes());
+    image[13] = 1;
+    image[14..16].copy_from_slice(&1u16.to_le_bytes());
+    image[16] = 255;
+    image[32..36].copy_from_slice(&128u32.to_le_bytes());
+    image[36..40].copy_from_slice(&(1u32 << 25).to_le_bytes());
+    image[510..512].copy_from_slice(&0xaa55u16.to_le_bytes());
+    assert!(Fat::mount(image).is_err());
+}
+
+#[test]
+fn long_names() {
+    for (kind, image) in images("lfn") {
+        let mut fat = Fat::mount(image).unwrap();
+        let root = fat.root();
+        let names = [
+            "A file with a long name.txt",
+            "README",
+            "notes.md",
+            "MixedCase.Txt",
+            "ünïcödé ✓",
+            "long name two.txt",
+        ];
+        for name in names {
+            fat.create(root, name, FileType::File).unwrap();
+        }
+        assert_eq!(
+            fat.create(root, "readme", FileType::File),
+            Err(FsError::Exists)
+        );
+        assert_eq!(
+            fat.create(root, "bad:name", FileType::File),
+            Err(FsError::InvalidName)
+        );
+        assert_eq!(
+            fat.create(root, &"x".repeat(256), FileType::File),
+            Err(FsError::NameTooLong)
+        );
+
+        fat.sync().unwrap();
+        let image = fat.into_disk();
+        fsck(&format!("lfn-{:?}", kind), &image);
+        let mut fat = Fat::mount(image).unwrap();
+
+        let mut expected: Vec<_> = names
+            .iter()
+            .map(|name| (name.to_string(), FileType::File))
+            .collect();
+        expected.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(list(&mut fat, root), expected);
+
+        let ino = fat.lookup(root, "a FILE with a long NAME.TXT").unwrap();
+        assert_eq!(fat.lookup(root, "AFILEW~1.TXT"), Ok(ino));
+        assert!(fat.lookup(root, "LONGNA~1.TXT").is_ok());
+        assert!(fat.lookup(root, "ünïcödé ✓").is_ok());
+    }
+}
+
+#[test]
+fn read_write() {
+    for (kind, image) in images("rw") {
+        let mut fat = Fat::mount(image).unwrap();
+        let root = fat.root();
+        let free = fat.free_clusters();
+        let cluster_size = fat.cluster_size() as usize;
+
+        let ino = fat.create(root, "data.bin", FileType::File).unwrap();
+        let data = pattern(cluster_size * 5 + 123);
+        assert_eq!(fat.write(ino, 0, &data[..1000]), Ok(1000));
+        assert_eq!(fat.write(ino, 1000, &data[1000..]), Ok(data.len() - 1000));
+        assert_eq!(fat.metadata(ino).unwrap().size, data.len() as u64);
+
+        let sparse = fat.create(root, "sparse", FileType::File).unwrap();
+        fat.write(sparse, cluster_size as u64 * 3 + 10, b"tail")
+            .unwrap();
+
+        fat.sync().unwrap();
+        let image = fat.into_disk();
+        fsck(&format!("rw-{:?}", kind), &image);
+        let mut fat = Fat::mount(image).unwrap();
+
+        let mut buf = vec![0; data.len() + 100];
+        assert_eq!(fat.read(ino, 0, &mut buf), Ok(data.len()));
+        assert_eq!(&buf[..data.len()], &data[..]);
+        let mut buf = [0; 300];
+        let offset = cluster_size - 100;
+        assert_eq!(fat.read(ino, offset as u64, &mut buf), Ok(300));
+        assert_eq!(&buf[..], &data[offset..offset + 300]);
+        assert_eq!(fat.read(ino, data.len() as u64, &mut buf), Ok(0));
+
+        let mut buf = vec![0xff; cluster_size * 3 + 14];
+        assert_eq!(fat.read(sparse, 0, &mut buf), Ok(buf.len()));
+        assert!(buf[..cluster_size * 3 + 10].iter().all(|&b| b == 0));
+        assert_eq!(&buf[cluster_size * 3 + 10..], b"tail");
+
+        let used = (data.len().div_ceil(cluster_size) + 4) as u32;
+        assert_eq!(fat.free_clusters(), free - used);
+
+        fat.truncate(ino, 10).unwrap();
+        assert_eq!(fat.metadata(ino).unwrap().size, 10);
+        assert_eq!(fat.free_clusters(), free - 5);
+        fat.truncate(ino, 0).unwrap();
+        fat.truncate(ino, 20).unwrap();
+        let mut buf = [0xff; 20];
+        assert_eq!(fat.read(ino, 0, &mut buf), Ok(20));
+        assert_eq!(buf, [0; 20]);
+
+        fat.remove(root, "data.bin").unwrap();
+        fat.remove(root, "SPARSE").unwrap();
+        assert_eq!(fat.free_clusters(), free);
+        assert_eq!(fat.metadata(ino), Err(FsError::NotFound));
+        assert_eq!(fat.remove(root, "sparse"), Err(FsError::NotFound));
+        assert!(list(&mut fat, root).is_empty());
+
+        fat.sync().unwrap();
+        let image = fat.into_disk();
+        fsck(&format!("rw-empty-{:?}", kind), &image);
+        assert_eq!(Fat::mount(image).unwrap().free_clusters(), free);
+    }
+}
+
+#[test]
+fn directories() {
+    for (kind, image) in images("dirs") {
+        let mut fat = Fat::mount(image).unwrap();
+        let root = fat.root();
+        let free = fat.free_clusters();
+
+        let docs = fat.create(root, "Documents", FileType::Dir).unwrap();
+        let nested = fat.create(docs, "nested dir", FileType::Dir).unwrap();
+        let file = fat.create(nested, "file.txt", FileType::File).unwrap();
+        fat.write(file, 0, b"hello").unwrap();
+
+        assert_eq!(fat.lookup(nested, ".."), Ok(docs));
+        assert_eq!(fat.lookup(docs, ".."), Ok(root));
+        assert_eq!(fat.lookup(nested, "."), Ok(nested));
+        assert_eq!(
+            resolve(&mut fat, root, "/documents/Nested Dir/FILE.TXT"),
+            Ok(file)
+        );
+        assert_eq!(resolve(&mut fat, nested, "../../Documents"), Ok(docs));
+        assert_eq!(
+            resolve(&mut fat, root, "/Documents/nested dir/file.txt/x"),
+            Err(FsError::NotDir)
+        );
+        assert_eq!(fat.read(docs, 0, &mut [0; 4]), Err(FsError::IsDir));
+
+        // Enough entries to spill over into more clusters.
+        let count = fat.cluster_size() as usize / 32 * 2;
+        for i in 0..count {
+            fat.create(nested, &format!("entry number {}", i), FileType::File)
+                .unwrap();
+        }
+        assert_eq!(list(&mut fat, nested).len(), count + 1);
+
+        fat.sync().unwrap();
+        let image = fat.into_disk();
+        fsck(&format!("dirs-{:?}", kind), &image);
+        let mut fat = Fat::mount(image).unwrap();
+
+        assert_eq!(fat.remove(root, "documents"), Err(FsError::NotEmpty));
+        for i in 0..count {
+            fat.remove(nested, &format!("entry number {}", i)).unwrap();
+        }
+        assert_eq!(
+            list(&mut fat, nested),
+            vec![("file.txt".to_string(), FileType::File)]
+        );
+        fat.remove(nested, "file.txt").unwrap();
+        fat.remove(docs, "nested dir").unwrap();
+        fat.remove(root, "documents").unwrap();
+        assert!(list(&mut fat, root).is_empty());
+        assert_eq!(fat.free_clusters(), free);
+    }
+}
+
+#[test]
+fn fixed_root_fills_up() {
+    let image = mkfs("root", 12, 2 * MIB);
+    let mut fat = Fat::mount(image).unwrap();
+    let root = fat.root();
+    let mut created = 0;
+    loop {
+        match fat.create(root, &format!("F{}", created), FileType::File) {
+            Ok(_) => created += 1,
+            Err(err) => {
+                assert_eq!(err, FsError::NoSpace);
+                break;
+            }
+        }
+    }
+    assert_eq!(created, 224);
+}
+
+#[test]
+fn fsinfo() {
+    let image = mkfs("fsinfo", 32, 64 * MIB);
+    let mut fat = Fat::mount(image).unwrap();
+    let free = fat.free_clusters();
+    let ino = fat.create(fat.root(), "big", FileType::File).unwrap();
+    fat.write(ino, 0, &pattern(fat.cluster_size() as usize * 8))
+        .unwrap();
+    fat.sync().unwrap();
+
+    let image = fat.into_disk();
+    let stored = u32::from_le_bytes(image[512 + 488..512 + 492].try_into().unwrap());
+    assert_eq!(stored, free - 8);
+    assert_eq!(Fat::mount(image).unwrap().free_clusters(), free - 8);
+}
+
+#[test]
+fn timestamps() {
+    for (_, image) in images("time") {
+        // 2021-03-04 05:06:08 UTC
+        let mut fat = Fat::mount(image).unwrap().with_clock(|| 1_614_834_368);
+        let ino = fat.create(fat.root(), "stamped", FileType::File).unwrap();
+        fat.write(ino, 0, b"x").unwrap();
+        assert_eq!(fat.metadata(ino).unwrap().mtime, 1_614_834_368);
+    }
+}
@@ -7,12 +7,14 @@ pub mod frame;
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AllocError {
     NoMemory,
+    Uninitialized,
 }
 
 impl Display for AllocError {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Self::NoMemory => f.write_str("no memory"),
+            Self::Uninitialized => f.write_str("allocator not initialized"),
         }
     }
 }
@@ -23,7 +23,7 @@ impl<F: FrameAlloc> PageMapper<F> {
     }
 
     pub fn current(allocator: F) -> PageMapper<F> {
-        let root = unsafe { &mut *(read_cr3() as *mut PageTable<_>) }.init();
+        let root = unsafe { &mut *((read_cr3() & !PAGE_MASK) as *mut PageTable<_>) };
         PageMapper { root, allocator }
     }
 
@@ -264,6 +264,8 @@ impl PageFlags {
     pub const PRESENT: PageFlags = Self::new(1 << 0);
     pub const WRITE: PageFlags = Self::new(1 << 1);
     pub const USER: PageFlags = Self::new(1 << 2);
+    pub const WRITE_THROUGH: PageFlags = Self::new(1 << 3);
+    pub const NO_CACHE: PageFlags = Self::new(1 << 4);
 
     #[inline]
     const fn new(val: usize) -> Self {
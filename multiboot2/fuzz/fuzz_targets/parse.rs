@@ -0,0 +1,48 @@
+#![no_main]
+
+use std::hint::black_box;
+
+use libfuzzer_sys::fuzz_target;
+use multiboot2::*;
+
+fn visit(boot_info: &BootInfo) {
+    for tag in boot_info.tags() {
+        black_box(tag.unwrap());
+    }
+
+    boot_info.find_tags::<CmdlineTag>().for_each(|tag| {
+        black_box(tag.get());
+    });
+    boot_info.find_tags::<ModuleTag>().for_each(|tag| {
+        black_box(tag.cmdline());
+    });
+    boot_info.find_tags::<MemoryMapTag>().for_each(|tag| {
+        tag.areas().for_each(|area| {
+            black_box(area);
+        });
+    });
+    boot_info.find_tags::<EfiMemoryMapTag>().for_each(|tag| {
+        tag.descriptors().for_each(|desc| {
+            black_box(desc);
+        });
+    });
+    boot_info.find_tags::<ElfSectionsTag>().for_each(|tag| {
+        tag.sections().for_each(|section| {
+            black_box(section);
+        });
+        black_box(tag.string_table());
+    });
+    boot_info.find_tags::<FramebufferTag>().for_each(|tag| {
+        if let Some(FramebufferType::Indexed { palette }) = tag.buffer_type() {
+            palette.iter().for_each(|color| {
+                black_box(color);
+            });
+        }
+    });
+}
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(boot_info) = BootInfo::from_bytes(data) {
+        visit(&boot_info);
+    }
+});
@@ -0,0 +1,81 @@
+use crate::{BootInfoError, RawTag, Tag, TagType};
+
+#[derive(Debug, Clone, Copy)]
+pub struct BootDeviceTag {
+    biosdev: u32,
+    partition: u32,
+    sub_partition: u32,
+}
+
+impl BootDeviceTag {
+    #[inline]
+    pub fn bios_device(&self) -> u32 {
+        self.biosdev
+    }
+
+    #[inline]
+    pub fn partition(&self) -> Option<u32> {
+        Some(self.partition).filter(|&p| p != u32::MAX)
+    }
+
+    #[inline]
+    pub fn sub_partition(&self) -> Option<u32> {
+        Some(self.sub_partition).filter(|&p| p != u32::MAX)
+    }
+}
+
+impl<'a> Tag<'a> for BootDeviceTag {
+    const TYPE: TagType = TagType::BootDevice;
+
+    fn parse(tag: RawTag<'a>) -> Result<Self, BootInfoError> {
+        Ok(Self {
+            biosdev: tag.u32(8)?,
+            partition: tag.u32(12)?,
+            sub_partition: tag.u32(16)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct NetworkTag<'a> {
+    dhcp_ack: &'a [u8],
+}
+
+impl<'a> NetworkTag<'a> {
+    #[inline]
+    pub fn dhcp_ack(&self) -> &'a [u8] {
+        self.dhcp_ack
+    }
+}
+
+impl<'a> Tag<'a> for NetworkTag<'a> {
+    const TYPE: TagType = TagType::Network;
+
+    fn parse(tag: RawTag<'a>) -> Result<Self, BootInfoError> {
+        Ok(Self {
+            dhcp_ack: tag.payload(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LoadBaseAddrTag {
+    load_base_addr: u32,
+}
+
+impl LoadBaseAddrTag {
+    #[inline]
+    pub fn addr(&self) -> usize {
+        self.load_base_addr as usize
+    }
+}
+
+impl<'a> Tag<'a> for LoadBaseAddrTag {
+    const TYPE: TagType = TagType::LoadBaseAddr;
+
+    fn parse(tag: RawTag<'a>) -> Result<Self, BootInfoError> {
+        Ok(Self {
+            load_base_addr: tag.u32(8)?,
+        })
+    }
+}
@@ -0,0 +1,267 @@
+use crate::{
+    ApmTag, ColorDescriptor, ColorField, EfiMemoryDesc, ElfSection, MemoryArea, Rsdp, TagType,
+    XsdpExt, HEADER_SIZE, TAG_ALIGN,
+};
+
+#[derive(Debug, Clone)]
+pub struct BootInfoBuilder {
+    bytes: Vec<u8>,
+}
+
+impl BootInfoBuilder {
+    pub fn new() -> Self {
+        Self {
+            bytes: Vec::from([0; HEADER_SIZE]),
+        }
+    }
+
+    pub fn cmdline(self, cmdline: &str) -> Self {
+        self.tag(TagType::Cmdline, |buf| push_str(buf, cmdline))
+    }
+
+    pub fn bootloader(self, name: &str) -> Self {
+        self.tag(TagType::Bootloader, |buf| push_str(buf, name))
+    }
+
+    pub fn module(self, start: u32, end: u32, cmdline: &str) -> Self {
+        self.tag(TagType::Module, |buf| {
+            buf.extend(start.to_le_bytes());
+            buf.extend(end.to_le_bytes());
+            push_str(buf, cmdline);
+        })
+    }
+
+    pub fn basic_mem_info(self, lower_kb: u32, upper_kb: u32) -> Self {
+        self.tag(TagType::BasicMemInfo, |buf| {
+            buf.extend(lower_kb.to_le_bytes());
+            buf.extend(upper_kb.to_le_bytes());
+        })
+    }
+
+    pub fn boot_device(self, bios_device: u32, partition: Option<u32>, sub: Option<u32>) -> Self {
+        self.tag(TagType::BootDevice, |buf| {
+            buf.extend(bios_device.to_le_bytes());
+            buf.extend(partition.unwrap_or(u32::MAX).to_le_bytes());
+            buf.extend(sub.unwrap_or(u32::MAX).to_le_bytes());
+        })
+    }
+
+    pub fn memory_map(self, areas: &[MemoryArea]) -> Self {
+        self.tag(TagType::Mmap, |buf| {
+            buf.extend(24u32.to_le_bytes());
+            buf.extend(0u32.to_le_bytes());
+            for area in areas {
+                buf.extend(area.base_addr.to_le_bytes());
+                buf.extend(area.length.to_le_bytes());
+                buf.extend(u32::from(area.typ).to_le_bytes());
+                buf.extend(0u32.to_le_bytes());
+            }
+        })
+    }
+
+    pub fn vbe_info(
+        self,
+        mode: u16,
+        interface: (u16, u16, u16),
+        control_info: &[u8; 512],
+        mode_info: &[u8; 256],
+    ) -> Self {
+        self.tag(TagType::VbeInfo, |buf| {
+            buf.extend(mode.to_le_bytes());
+            buf.extend(interface.0.to_le_bytes());
+            buf.extend(interface.1.to_le_bytes());
+            buf.extend(interface.2.to_le_bytes());
+            buf.extend(control_info);
+            buf.extend(mode_info);
+        })
+    }
+
+    pub fn framebuffer_indexed(self, mode: FramebufferMode, palette: &[ColorDescriptor]) -> Self {
+        self.tag(TagType::Framebuffer, |buf| {
+            mode.push(buf, 0);
+            buf.extend((palette.len() as u16).to_le_bytes());
+            for color in palette {
+                buf.extend([color.red, color.green, color.blue]);
+            }
+        })
+    }
+
+    pub fn framebuffer_rgb(
+        self,
+        mode: FramebufferMode,
+        red: ColorField,
+        green: ColorField,
+        blue: ColorField,
+    ) -> Self {
+        self.tag(TagType::Framebuffer, |buf| {
+            mode.push(buf, 1);
+            for field in [red, green, blue] {
+                buf.extend([field.position, field.size]);
+            }
+        })
+    }
+
+    pub fn framebuffer_text(self, mode: FramebufferMode) -> Self {
+        self.tag(TagType::Framebuffer, |buf| mode.push(buf, 2))
+    }
+
+    pub fn elf_sections(self, shndx: u32, sections: &[ElfSection]) -> Self {
+        self.tag(TagType::ElfSections, |buf| {
+            buf.extend((sections.len() as u32).to_le_bytes());
+            buf.extend(64u32.to_le_bytes());
+            buf.extend(shndx.to_le_bytes());
+            for section in sections {
+                buf.extend(section.name_index.to_le_bytes());
+                buf.extend(section.typ.to_le_bytes());
+                buf.extend(section.flags.to_le_bytes());
+                buf.extend(section.addr.to_le_bytes());
+                buf.extend(section.offset.to_le_bytes());
+                buf.extend(section.size.to_le_bytes());
+                buf.extend(section.link.to_le_bytes());
+                buf.extend(section.info.to_le_bytes());
+                buf.extend(section.addr_align.to_le_bytes());
+                buf.extend(section.entry_size.to_le_bytes());
+            }
+        })
+    }
+
+    pub fn apm(self, apm: &ApmTag) -> Self {
+        self.tag(TagType::Apm, |buf| {
+            buf.extend(apm.version.to_le_bytes());
+            buf.extend(apm.cseg.to_le_bytes());
+            buf.extend(apm.offset.to_le_bytes());
+            buf.extend(apm.cseg_16.to_le_bytes());
+            buf.extend(apm.dseg.to_le_bytes());
+            buf.extend(apm.flags.to_le_bytes());
+            buf.extend(apm.cseg_len.to_le_bytes());
+            buf.extend(apm.cseg_16_len.to_le_bytes());
+            buf.extend(apm.dseg_len.to_le_bytes());
+        })
+    }
+
+    pub fn efi32(self, system_table: u32) -> Self {
+        self.tag(TagType::Efi32, |buf| buf.extend(system_table.to_le_bytes()))
+    }
+
+    pub fn efi64(self, system_table: u64) -> Self {
+        self.tag(TagType::Efi64, |buf| buf.extend(system_table.to_le_bytes()))
+    }
+
+    pub fn smbios(self, version: (u8, u8), tables: &[u8]) -> Self {
+        self.tag(TagType::Smbios, |buf| {
+            buf.extend([version.0, version.1, 0, 0, 0, 0, 0, 0]);
+            buf.extend(tables);
+        })
+    }
+
+    pub fn acpi_v1(self, rsdp: &Rsdp) -> Self {
+        self.tag(TagType::AcpiV1, |buf| push_rsdp(buf, rsdp))
+    }
+
+    pub fn acpi_v2(self, rsdp: &Rsdp, ext: &XsdpExt) -> Self {
+        self.tag(TagType::AcpiV2, |buf| {
+            push_rsdp(buf, rsdp);
+            buf.extend(ext.length.to_le_bytes());
+            buf.extend(ext.xsdt_addr.to_le_bytes());
+            buf.extend([ext.ext_checksum, 0, 0, 0]);
+        })
+    }
+
+    pub fn network(self, dhcp_ack: &[u8]) -> Self {
+        self.tag(TagType::Network, |buf| buf.extend(dhcp_ack))
+    }
+
+    pub fn efi_memory_map(self, desc_version: u32, descs: &[EfiMemoryDesc]) -> Self {
+        self.tag(TagType::EfiMmap, |buf| {
+            buf.extend(40u32.to_le_bytes());
+            buf.extend(desc_version.to_le_bytes());
+            for desc in descs {
+                buf.extend(desc.typ.to_le_bytes());
+                buf.extend(0u32.to_le_bytes());
+                buf.extend(desc.phys_start.to_le_bytes());
+                buf.extend(desc.virt_start.to_le_bytes());
+                buf.extend(desc.num_pages.to_le_bytes());
+                buf.extend(desc.attribute.to_le_bytes());
+            }
+        })
+    }
+
+    pub fn efi_boot_services(self) -> Self {
+        self.tag(TagType::EfiBs, |_| {})
+    }
+
+    pub fn efi32_image_handle(self, handle: u32) -> Self {
+        self.tag(TagType::Efi32Ih, |buf| buf.extend(handle.to_le_bytes()))
+    }
+
+    pub fn efi64_image_handle(self, handle: u64) -> Self {
+        self.tag(TagType::Efi64Ih, |buf| buf.extend(handle.to_le_bytes()))
+    }
+
+    pub fn load_base_addr(self, addr: u32) -> Self {
+        self.tag(TagType::LoadBaseAddr, |buf| buf.extend(addr.to_le_bytes()))
+    }
+
+    pub fn raw(self, typ: TagType, payload: &[u8]) -> Self {
+        self.tag(typ, |buf| buf.extend(payload))
+    }
+
+    pub fn build(self) -> Vec<u8> {
+        let mut bytes = self.tag(TagType::End, |_| {}).bytes;
+        let size = bytes.len() as u32;
+        bytes[..4].copy_from_slice(&size.to_le_bytes());
+        bytes
+    }
+
+    fn tag(mut self, typ: TagType, f: impl FnOnce(&mut Vec<u8>)) -> Self {
+        let start = self.bytes.len();
+        self.bytes.extend(u32::from(typ).to_le_bytes());
+        self.bytes.extend(0u32.to_le_bytes());
+        f(&mut self.bytes);
+
+        let size = (self.bytes.len() - start) as u32;
+        self.bytes[start + 4..start + 8].copy_from_slice(&size.to_le_bytes());
+        while self.bytes.len() & (TAG_ALIGN - 1) != 0 {
+            self.bytes.push(0);
+        }
+        self
+    }
+}
+
+impl Default for BootInfoBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FramebufferMode {
+    pub addr: u64,
+    pub pitch: u32,
+    pub width: u32,
+    pub height: u32,
+    pub bpp: u8,
+}
+
+impl FramebufferMode {
+    fn push(&self, buf: &mut Vec<u8>, buffer_type: u8) {
+        buf.extend(self.addr.to_le_bytes());
+        buf.extend(self.pitch.to_le_bytes());
+        buf.extend(self.width.to_le_bytes());
+        buf.extend(self.height.to_le_bytes());
+        buf.extend([self.bpp, buffer_type, 0, 0]);
+    }
+}
+
+fn push_str(buf: &mut Vec<u8>, s: &str) {
+    buf.extend(s.as_bytes());
+    buf.push(0);
+}
+
+fn push_rsdp(buf: &mut Vec<u8>, rsdp: &Rsdp) {
+    buf.extend(rsdp.signature);
+    buf.push(rsdp.checksum);
+    buf.extend(rsdp.oem_id);
+    buf.push(rsdp.revision);
+    buf.extend(rsdp.rsdt_addr.to_le_bytes());
+}
@@ -0,0 +1,132 @@
+use core::{
+    slice::{self, ChunksExact},
+    str,
+};
+
+use crate::{BootInfoError, RawTag, Tag, TagType};
+
+#[derive(Debug, Clone, Copy)]
+pub struct ElfSectionsTag<'a> {
+    entry_size: usize,
+    shndx: u32,
+    sections: &'a [u8],
+}
+
+impl<'a> ElfSectionsTag<'a> {
+    const SECTION_SIZE: usize = 64;
+
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.sections.len() / self.entry_size
+    }
+
+    pub fn sections(&self) -> ElfSectionIter<'a> {
+        ElfSectionIter {
+            sections: self.sections.chunks_exact(self.entry_size),
+        }
+    }
+
+    pub fn string_table(&self) -> Option<ElfSection> {
+        self.sections().nth(self.shndx as usize)
+    }
+
+    /// # Safety
+    ///
+    /// The string table section must be mapped at its load address.
+    pub unsafe fn section_name(&self, section: &ElfSection) -> Option<&'a str> {
+        let strtab = self.string_table()?;
+        let strtab = slice::from_raw_parts(strtab.addr as *const u8, strtab.size as usize);
+        let name = strtab.get(section.name_index as usize..)?;
+        let len = name.iter().position(|&c| c == 0)?;
+        str::from_utf8(&name[..len]).ok()
+    }
+}
+
+impl<'a> Tag<'a> for ElfSectionsTag<'a> {
+    const TYPE: TagType = TagType::ElfSections;
+
+    fn parse(tag: RawTag<'a>) -> Result<Self, BootInfoError> {
+        let num = tag.u32(8)? as usize;
+        let entry_size = tag.u32(12)? as usize;
+        if entry_size < Self::SECTION_SIZE {
+            return Err(tag.bad());
+        }
+        let len = num.checked_mul(entry_size).ok_or(tag.bad())?;
+        Ok(Self {
+            entry_size,
+            shndx: tag.u32(16)?,
+            sections: tag.slice(20, len)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ElfSection {
+    pub name_index: u32,
+    pub typ: u32,
+    pub flags: u64,
+    pub addr: u64,
+    pub offset: u64,
+    pub size: u64,
+    pub link: u32,
+    pub info: u32,
+    pub addr_align: u64,
+    pub entry_size: u64,
+}
+
+impl ElfSection {
+    pub const WRITE: u64 = 0x1;
+    pub const ALLOC: u64 = 0x2;
+    pub const EXECUTE: u64 = 0x4;
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        let u64_at =
+            |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+        Self {
+            name_index: u32_at(0),
+            typ: u32_at(4),
+            flags: u64_at(8),
+            addr: u64_at(16),
+            offset: u64_at(24),
+            size: u64_at(32),
+            link: u32_at(40),
+            info: u32_at(44),
+            addr_align: u64_at(48),
+            entry_size: u64_at(56),
+        }
+    }
+
+    #[inline]
+    pub fn start_addr(&self) -> usize {
+        self.addr as usize
+    }
+
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.size as usize
+    }
+
+    #[inline]
+    pub fn end_addr(&self) -> usize {
+        self.start_addr() + self.size()
+    }
+
+    #[inline]
+    pub fn is_allocated(&self) -> bool {
+        self.flags & Self::ALLOC != 0
+    }
+}
+
+pub struct ElfSectionIter<'a> {
+    sections: ChunksExact<'a, u8>,
+}
+
+impl<'a> Iterator for ElfSectionIter<'a> {
+    type Item = ElfSection;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.sections.next().map(ElfSection::from_bytes)
+    }
+}
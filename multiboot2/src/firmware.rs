@@ -0,0 +1,272 @@
+use crate::{BootInfoError, RawTag, Tag, TagType};
+
+#[derive(Debug, Clone, Copy)]
+pub struct ApmTag {
+    pub version: u16,
+    pub cseg: u16,
+    pub offset: u32,
+    pub cseg_16: u16,
+    pub dseg: u16,
+    pub flags: u16,
+    pub cseg_len: u16,
+    pub cseg_16_len: u16,
+    pub dseg_len: u16,
+}
+
+impl<'a> Tag<'a> for ApmTag {
+    const TYPE: TagType = TagType::Apm;
+
+    fn parse(tag: RawTag<'a>) -> Result<Self, BootInfoError> {
+        Ok(Self {
+            version: tag.u16(8)?,
+            cseg: tag.u16(10)?,
+            offset: tag.u32(12)?,
+            cseg_16: tag.u16(16)?,
+            dseg: tag.u16(18)?,
+            flags: tag.u16(20)?,
+            cseg_len: tag.u16(22)?,
+            cseg_16_len: tag.u16(24)?,
+            dseg_len: tag.u16(26)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Efi32Tag {
+    pointer: u32,
+}
+
+impl Efi32Tag {
+    #[inline]
+    pub fn system_table(&self) -> usize {
+        self.pointer as usize
+    }
+}
+
+impl<'a> Tag<'a> for Efi32Tag {
+    const TYPE: TagType = TagType::Efi32;
+
+    fn parse(tag: RawTag<'a>) -> Result<Self, BootInfoError> {
+        Ok(Self {
+            pointer: tag.u32(8)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Efi64Tag {
+    pointer: u64,
+}
+
+impl Efi64Tag {
+    #[inline]
+    pub fn system_table(&self) -> usize {
+        self.pointer as usize
+    }
+}
+
+impl<'a> Tag<'a> for Efi64Tag {
+    const TYPE: TagType = TagType::Efi64;
+
+    fn parse(tag: RawTag<'a>) -> Result<Self, BootInfoError> {
+        Ok(Self {
+            pointer: tag.u64(8)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EfiBootServicesTag;
+
+impl<'a> Tag<'a> for EfiBootServicesTag {
+    const TYPE: TagType = TagType::EfiBs;
+
+    fn parse(_tag: RawTag<'a>) -> Result<Self, BootInfoError> {
+        Ok(Self)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Efi32ImageHandleTag {
+    pointer: u32,
+}
+
+impl Efi32ImageHandleTag {
+    #[inline]
+    pub fn image_handle(&self) -> usize {
+        self.pointer as usize
+    }
+}
+
+impl<'a> Tag<'a> for Efi32ImageHandleTag {
+    const TYPE: TagType = TagType::Efi32Ih;
+
+    fn parse(tag: RawTag<'a>) -> Result<Self, BootInfoError> {
+        Ok(Self {
+            pointer: tag.u32(8)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Efi64ImageHandleTag {
+    pointer: u64,
+}
+
+impl Efi64ImageHandleTag {
+    #[inline]
+    pub fn image_handle(&self) -> usize {
+        self.pointer as usize
+    }
+}
+
+impl<'a> Tag<'a> for Efi64ImageHandleTag {
+    const TYPE: TagType = TagType::Efi64Ih;
+
+    fn parse(tag: RawTag<'a>) -> Result<Self, BootInfoError> {
+        Ok(Self {
+            pointer: tag.u64(8)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SmbiosTag<'a> {
+    major: u8,
+    minor: u8,
+    tables: &'a [u8],
+}
+
+impl<'a> SmbiosTag<'a> {
+    #[inline]
+    pub fn version(&self) -> (u8, u8) {
+        (self.major, self.minor)
+    }
+
+    #[inline]
+    pub fn tables(&self) -> &'a [u8] {
+        self.tables
+    }
+}
+
+impl<'a> Tag<'a> for SmbiosTag<'a> {
+    const TYPE: TagType = TagType::Smbios;
+
+    fn parse(tag: RawTag<'a>) -> Result<Self, BootInfoError> {
+        Ok(Self {
+            major: tag.u8(8)?,
+            minor: tag.u8(9)?,
+            tables: tag.tail(16)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rsdp {
+    pub signature: [u8; 8],
+    pub checksum: u8,
+    pub oem_id: [u8; 6],
+    pub revision: u8,
+    pub rsdt_addr: u32,
+}
+
+impl Rsdp {
+    const SIZE: usize = 20;
+
+    fn parse(tag: &RawTag, offset: usize) -> Result<Self, BootInfoError> {
+        Ok(Self {
+            signature: tag.array(offset)?,
+            checksum: tag.u8(offset + 8)?,
+            oem_id: tag.array(offset + 9)?,
+            revision: tag.u8(offset + 15)?,
+            rsdt_addr: tag.u32(offset + 16)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XsdpExt {
+    pub length: u32,
+    pub xsdt_addr: u64,
+    pub ext_checksum: u8,
+}
+
+impl XsdpExt {
+    fn parse(tag: &RawTag, offset: usize) -> Result<Self, BootInfoError> {
+        Ok(Self {
+            length: tag.u32(offset)?,
+            xsdt_addr: tag.u64(offset + 4)?,
+            ext_checksum: tag.u8(offset + 12)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AcpiV1Tag {
+    rsdp: Rsdp,
+    rsdp_addr: usize,
+}
+
+impl AcpiV1Tag {
+    #[inline]
+    pub fn rsdp(&self) -> &Rsdp {
+        &self.rsdp
+    }
+
+    #[inline]
+    pub fn rsdp_addr(&self) -> usize {
+        self.rsdp_addr
+    }
+}
+
+impl<'a> Tag<'a> for AcpiV1Tag {
+    const TYPE: TagType = TagType::AcpiV1;
+
+    fn parse(tag: RawTag<'a>) -> Result<Self, BootInfoError> {
+        Ok(Self {
+            rsdp: Rsdp::parse(&tag, 8)?,
+            rsdp_addr: tag.payload().as_ptr() as usize,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AcpiV2Tag {
+    rsdp: Rsdp,
+    ext: XsdpExt,
+    rsdp_addr: usize,
+}
+
+impl AcpiV2Tag {
+    #[inline]
+    pub fn rsdp(&self) -> &Rsdp {
+        &self.rsdp
+    }
+
+    #[inline]
+    pub fn ext(&self) -> &XsdpExt {
+        &self.ext
+    }
+
+    #[inline]
+    pub fn xsdt_addr(&self) -> usize {
+        self.ext.xsdt_addr as usize
+    }
+
+    #[inline]
+    pub fn rsdp_addr(&self) -> usize {
+        self.rsdp_addr
+    }
+}
+
+impl<'a> Tag<'a> for AcpiV2Tag {
+    const TYPE: TagType = TagType::AcpiV2;
+
+    fn parse(tag: RawTag<'a>) -> Result<Self, BootInfoError> {
+        Ok(Self {
+            rsdp: Rsdp::parse(&tag, 8)?,
+            ext: XsdpExt::parse(&tag, 8 + Rsdp::SIZE)?,
+            rsdp_addr: tag.payload().as_ptr() as usize,
+        })
+    }
+}
@@ -0,0 +1,219 @@
+use crate::{BootInfoError, RawTag, Tag, TagType};
+
+#[derive(Debug, Clone, Copy)]
+pub struct FramebufferTag<'a> {
+    addr: u64,
+    pitch: u32,
+    width: u32,
+    height: u32,
+    bpp: u8,
+    buffer_type: Option<FramebufferType<'a>>,
+}
+
+impl<'a> FramebufferTag<'a> {
+    #[inline]
+    pub fn addr(&self) -> usize {
+        self.addr as usize
+    }
+
+    #[inline]
+    pub fn pitch(&self) -> usize {
+        self.pitch as usize
+    }
+
+    #[inline]
+    pub fn width(&self) -> usize {
+        self.width as usize
+    }
+
+    #[inline]
+    pub fn height(&self) -> usize {
+        self.height as usize
+    }
+
+    #[inline]
+    pub fn bpp(&self) -> usize {
+        self.bpp as usize
+    }
+
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.pitch() * self.height()
+    }
+
+    #[inline]
+    pub fn buffer_type(&self) -> Option<FramebufferType<'a>> {
+        self.buffer_type
+    }
+}
+
+impl<'a> Tag<'a> for FramebufferTag<'a> {
+    const TYPE: TagType = TagType::Framebuffer;
+
+    fn parse(tag: RawTag<'a>) -> Result<Self, BootInfoError> {
+        let buffer_type = match tag.u8(29)? {
+            0 => {
+                let num_colors = tag.u16(32)? as usize;
+                Some(FramebufferType::Indexed {
+                    palette: Palette {
+                        bytes: tag.slice(34, num_colors * 3)?,
+                    },
+                })
+            }
+            1 => {
+                let fields: [u8; 6] = tag.array(32)?;
+                Some(FramebufferType::Rgb {
+                    red: ColorField::new(fields[0], fields[1]),
+                    green: ColorField::new(fields[2], fields[3]),
+                    blue: ColorField::new(fields[4], fields[5]),
+                })
+            }
+            2 => Some(FramebufferType::Text),
+            _ => None,
+        };
+
+        Ok(Self {
+            addr: tag.u64(8)?,
+            pitch: tag.u32(16)?,
+            width: tag.u32(20)?,
+            height: tag.u32(24)?,
+            bpp: tag.u8(28)?,
+            buffer_type,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramebufferType<'a> {
+    Indexed {
+        palette: Palette<'a>,
+    },
+    Rgb {
+        red: ColorField,
+        green: ColorField,
+        blue: ColorField,
+    },
+    Text,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Palette<'a> {
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.bytes.len() / 3
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn get(&self, idx: usize) -> Option<ColorDescriptor> {
+        self.bytes
+            .get(idx * 3..idx * 3 + 3)
+            .map(ColorDescriptor::from_bytes)
+    }
+
+    pub fn iter(&self) -> PaletteIter<'a> {
+        PaletteIter {
+            palette: *self,
+            idx: 0,
+        }
+    }
+}
+
+pub struct PaletteIter<'a> {
+    palette: Palette<'a>,
+    idx: usize,
+}
+
+impl<'a> Iterator for PaletteIter<'a> {
+    type Item = ColorDescriptor;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let color = self.palette.get(self.idx)?;
+        self.idx += 1;
+        Some(color)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorDescriptor {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+impl ColorDescriptor {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            red: bytes[0],
+            green: bytes[1],
+            blue: bytes[2],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorField {
+    pub position: u8,
+    pub size: u8,
+}
+
+impl ColorField {
+    #[inline]
+    pub const fn new(position: u8, size: u8) -> Self {
+        Self { position, size }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct VbeInfoTag<'a> {
+    mode: u16,
+    interface_seg: u16,
+    interface_off: u16,
+    interface_len: u16,
+    control_info: &'a [u8; 512],
+    mode_info: &'a [u8; 256],
+}
+
+impl<'a> VbeInfoTag<'a> {
+    #[inline]
+    pub fn mode(&self) -> u16 {
+        self.mode
+    }
+
+    #[inline]
+    pub fn interface(&self) -> (u16, u16, u16) {
+        (self.interface_seg, self.interface_off, self.interface_len)
+    }
+
+    #[inline]
+    pub fn control_info(&self) -> &'a [u8; 512] {
+        self.control_info
+    }
+
+    #[inline]
+    pub fn mode_info(&self) -> &'a [u8; 256] {
+        self.mode_info
+    }
+}
+
+impl<'a> Tag<'a> for VbeInfoTag<'a> {
+    const TYPE: TagType = TagType::VbeInfo;
+
+    fn parse(tag: RawTag<'a>) -> Result<Self, BootInfoError> {
+        Ok(Self {
+            mode: tag.u16(8)?,
+            interface_seg: tag.u16(10)?,
+            interface_off: tag.u16(12)?,
+            interface_len: tag.u16(14)?,
+            control_info: tag.slice(16, 512)?.try_into().unwrap(),
+            mode_info: tag.slice(528, 256)?.try_into().unwrap(),
+        })
+    }
+}
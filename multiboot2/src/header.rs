@@ -0,0 +1,369 @@
+use core::mem;
+
+pub const HEADER_MAGIC: u32 = 0xe85250d6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum Architecture {
+    I386 = 0,
+    Mips32 = 4,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum HeaderTagType {
+    End = 0,
+    InformationRequest = 1,
+    Address = 2,
+    EntryAddress = 3,
+    ConsoleFlags = 4,
+    Framebuffer = 5,
+    ModuleAlign = 6,
+    EfiBs = 7,
+    EntryAddressEfi32 = 8,
+    EntryAddressEfi64 = 9,
+    Relocatable = 10,
+}
+
+pub const TAG_OPTIONAL: u16 = 1;
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C, align(8))]
+pub struct Header<T> {
+    magic: u32,
+    arch: Architecture,
+    length: u32,
+    checksum: u32,
+    tags: T,
+    end: EndTag,
+}
+
+impl<T> Header<T> {
+    pub const fn new(arch: Architecture, tags: T) -> Self {
+        let length = mem::size_of::<Self>() as u32;
+        Self {
+            magic: HEADER_MAGIC,
+            arch,
+            length,
+            checksum: 0u32
+                .wrapping_sub(HEADER_MAGIC)
+                .wrapping_sub(arch as u32)
+                .wrapping_sub(length),
+            tags,
+            end: EndTag::new(),
+        }
+    }
+
+    #[inline]
+    pub const fn arch(&self) -> Architecture {
+        self.arch
+    }
+
+    #[inline]
+    pub const fn length(&self) -> u32 {
+        self.length
+    }
+
+    #[inline]
+    pub const fn checksum(&self) -> u32 {
+        self.checksum
+    }
+
+    #[inline]
+    pub const fn tags(&self) -> &T {
+        &self.tags
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct TagHeader {
+    typ: HeaderTagType,
+    flags: u16,
+    size: u32,
+}
+
+impl TagHeader {
+    const fn new(typ: HeaderTagType, size: usize) -> Self {
+        Self {
+            typ,
+            flags: 0,
+            size: size as u32,
+        }
+    }
+
+    #[inline]
+    pub const fn typ(&self) -> HeaderTagType {
+        self.typ
+    }
+
+    #[inline]
+    pub const fn is_optional(&self) -> bool {
+        self.flags & TAG_OPTIONAL != 0
+    }
+
+    #[inline]
+    pub const fn size(&self) -> u32 {
+        self.size
+    }
+}
+
+macro_rules! optional {
+    ($tag:ty) => {
+        impl $tag {
+            pub const fn optional(mut self) -> Self {
+                self.header.flags |= TAG_OPTIONAL;
+                self
+            }
+
+            #[inline]
+            pub const fn header(&self) -> &TagHeader {
+                &self.header
+            }
+        }
+    };
+}
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C, align(8))]
+pub struct EndTag {
+    header: TagHeader,
+}
+
+impl EndTag {
+    pub const fn new() -> Self {
+        Self {
+            header: TagHeader::new(HeaderTagType::End, 8),
+        }
+    }
+}
+
+impl Default for EndTag {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C, align(8))]
+pub struct InformationRequestTag<const N: usize> {
+    header: TagHeader,
+    requests: [u32; N],
+}
+
+impl<const N: usize> InformationRequestTag<N> {
+    pub const fn new(requests: [u32; N]) -> Self {
+        Self {
+            header: TagHeader::new(HeaderTagType::InformationRequest, 8 + N * 4),
+            requests,
+        }
+    }
+
+    pub const fn optional(mut self) -> Self {
+        self.header.flags |= TAG_OPTIONAL;
+        self
+    }
+
+    #[inline]
+    pub const fn header(&self) -> &TagHeader {
+        &self.header
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C, align(8))]
+pub struct AddressTag {
+    header: TagHeader,
+    header_addr: u32,
+    load_addr: u32,
+    load_end_addr: u32,
+    bss_end_addr: u32,
+}
+
+impl AddressTag {
+    pub const fn new(
+        header_addr: u32,
+        load_addr: u32,
+        load_end_addr: u32,
+        bss_end_addr: u32,
+    ) -> Self {
+        Self {
+            header: TagHeader::new(HeaderTagType::Address, 24),
+            header_addr,
+            load_addr,
+            load_end_addr,
+            bss_end_addr,
+        }
+    }
+}
+
+optional!(AddressTag);
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C, align(8))]
+pub struct EntryAddressTag {
+    header: TagHeader,
+    entry_addr: u32,
+    reserved: u32,
+}
+
+impl EntryAddressTag {
+    pub const fn new(entry_addr: u32) -> Self {
+        Self {
+            header: TagHeader::new(HeaderTagType::EntryAddress, 12),
+            entry_addr,
+            reserved: 0,
+        }
+    }
+
+    pub const fn efi32(entry_addr: u32) -> Self {
+        Self {
+            header: TagHeader::new(HeaderTagType::EntryAddressEfi32, 12),
+            entry_addr,
+            reserved: 0,
+        }
+    }
+
+    pub const fn efi64(entry_addr: u32) -> Self {
+        Self {
+            header: TagHeader::new(HeaderTagType::EntryAddressEfi64, 12),
+            entry_addr,
+            reserved: 0,
+        }
+    }
+}
+
+optional!(EntryAddressTag);
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C, align(8))]
+pub struct ConsoleFlagsTag {
+    header: TagHeader,
+    flags: u32,
+    reserved: u32,
+}
+
+impl ConsoleFlagsTag {
+    pub const CONSOLE_REQUIRED: u32 = 0x1;
+    pub const EGA_TEXT_SUPPORTED: u32 = 0x2;
+
+    pub const fn new(flags: u32) -> Self {
+        Self {
+            header: TagHeader::new(HeaderTagType::ConsoleFlags, 12),
+            flags,
+            reserved: 0,
+        }
+    }
+}
+
+optional!(ConsoleFlagsTag);
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C, align(8))]
+pub struct FramebufferTag {
+    header: TagHeader,
+    width: u32,
+    height: u32,
+    depth: u32,
+    reserved: u32,
+}
+
+impl FramebufferTag {
+    pub const fn new(width: u32, height: u32, depth: u32) -> Self {
+        Self {
+            header: TagHeader::new(HeaderTagType::Framebuffer, 20),
+            width,
+            height,
+            depth,
+            reserved: 0,
+        }
+    }
+
+    #[inline]
+    pub const fn mode(&self) -> (u32, u32, u32) {
+        (self.width, self.height, self.depth)
+    }
+}
+
+optional!(FramebufferTag);
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C, align(8))]
+pub struct ModuleAlignTag {
+    header: TagHeader,
+}
+
+impl ModuleAlignTag {
+    pub const fn new() -> Self {
+        Self {
+            header: TagHeader::new(HeaderTagType::ModuleAlign, 8),
+        }
+    }
+}
+
+impl Default for ModuleAlignTag {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+optional!(ModuleAlignTag);
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C, align(8))]
+pub struct EfiBootServicesTag {
+    header: TagHeader,
+}
+
+impl EfiBootServicesTag {
+    pub const fn new() -> Self {
+        Self {
+            header: TagHeader::new(HeaderTagType::EfiBs, 8),
+        }
+    }
+}
+
+impl Default for EfiBootServicesTag {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+optional!(EfiBootServicesTag);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum RelocPreference {
+    None = 0,
+    Lowest = 1,
+    Highest = 2,
+}
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C, align(8))]
+pub struct RelocatableTag {
+    header: TagHeader,
+    min_addr: u32,
+    max_addr: u32,
+    align: u32,
+    preference: RelocPreference,
+}
+
+impl RelocatableTag {
+    pub const fn new(
+        min_addr: u32,
+        max_addr: u32,
+        align: u32,
+        preference: RelocPreference,
+    ) -> Self {
+        Self {
+            header: TagHeader::new(HeaderTagType::Relocatable, 24),
+            min_addr,
+            max_addr,
+            align,
+            preference,
+        }
+    }
+}
+
+optional!(RelocatableTag);
@@ -1,34 +1,124 @@
-#![no_std]
-
-use core::{marker::PhantomData, mem, slice, str};
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use core::{fmt, slice, str};
+
+pub use boot::*;
+#[cfg(feature = "builder")]
+pub use builder::*;
+pub use elf::*;
+pub use firmware::*;
+pub use framebuffer::*;
+pub use memory::*;
+pub use string::*;
+
+pub mod boot;
+#[cfg(feature = "builder")]
+pub mod builder;
+pub mod elf;
+pub mod firmware;
+pub mod framebuffer;
+pub mod header;
+pub mod memory;
+pub mod string;
 
 pub const MAGIC: u64 = 0x36d76289;
 
-pub fn init(magic: u64, info: *const u8) -> Option<&'static BootInfo> {
-    if magic == MAGIC {
-        Some(unsafe { &*(info as *mut BootInfo) })
-    } else {
-        None
+const TAG_ALIGN: usize = 8;
+const HEADER_SIZE: usize = 8;
+
+/// # Safety
+///
+/// `info` must point to boot information left in memory by a multiboot2 loader.
+pub unsafe fn init(magic: u64, info: *const u8) -> Result<BootInfo<'static>, BootInfoError> {
+    if magic != MAGIC {
+        return Err(BootInfoError::BadMagic(magic));
+    }
+    if info.is_null() || info as usize & (TAG_ALIGN - 1) != 0 {
+        return Err(BootInfoError::Misaligned(info as usize));
+    }
+
+    let size = (info as *const u32).read() as usize;
+    if size < HEADER_SIZE {
+        return Err(BootInfoError::Truncated {
+            offset: 0,
+            len: HEADER_SIZE,
+        });
+    }
+
+    BootInfo::from_bytes(slice::from_raw_parts(info, size))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootInfoError {
+    BadMagic(u64),
+    Misaligned(usize),
+    Truncated { offset: usize, len: usize },
+    OverlappingTag { offset: usize, size: usize },
+    BadTag { typ: TagType, offset: usize },
+    MissingEndTag,
+}
+
+impl fmt::Display for BootInfoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::BadMagic(magic) => write!(f, "bad magic {:#x}", magic),
+            Self::Misaligned(addr) => write!(f, "boot info at {:#x} is not 8-byte aligned", addr),
+            Self::Truncated { offset, len } => {
+                write!(f, "{} bytes at offset {} run past the end", len, offset)
+            }
+            Self::OverlappingTag { offset, size } => {
+                write!(
+                    f,
+                    "tag at offset {} with size {} overlaps its neighbours",
+                    offset, size
+                )
+            }
+            Self::BadTag { typ, offset } => {
+                write!(f, "malformed {:?} tag at offset {}", typ, offset)
+            }
+            Self::MissingEndTag => f.write_str("missing end tag"),
+        }
     }
 }
 
-#[derive(Debug)]
-#[repr(C)]
-pub struct BootInfo {
-    size: u32,
-    reserved: u32,
-    tags: TagBase,
+#[derive(Debug, Clone, Copy)]
+pub struct BootInfo<'a> {
+    bytes: &'a [u8],
 }
 
-impl BootInfo {
+impl<'a> BootInfo<'a> {
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, BootInfoError> {
+        let size = read_u32(bytes, 0)? as usize;
+        if size < HEADER_SIZE || size > bytes.len() {
+            return Err(BootInfoError::Truncated {
+                offset: 0,
+                len: size.max(HEADER_SIZE),
+            });
+        }
+
+        let boot_info = Self {
+            bytes: &bytes[..size],
+        };
+
+        let mut iter = boot_info.tags();
+        for tag in &mut iter {
+            validate_tag(tag?)?;
+        }
+        if iter.finished {
+            Ok(boot_info)
+        } else {
+            Err(BootInfoError::MissingEndTag)
+        }
+    }
+
     #[inline]
     pub fn start_addr(&self) -> usize {
-        self as *const _ as usize
+        self.bytes.as_ptr() as usize
     }
 
     #[inline]
     pub fn size(&self) -> usize {
-        self.size as usize
+        self.bytes.len()
     }
 
     #[inline]
@@ -37,202 +127,290 @@ impl BootInfo {
     }
 
     #[inline]
-    pub const fn tags(&self) -> TagIter {
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    #[inline]
+    pub fn tags(&self) -> TagIter<'a> {
         TagIter {
-            current: &self.tags as *const TagBase,
-            phantom: PhantomData {},
+            bytes: self.bytes,
+            offset: HEADER_SIZE,
+            finished: false,
         }
     }
 
-    pub fn find_tag<T: Tag>(&self, typ: TagType) -> Option<&T> {
-        unsafe { mem::transmute(self.tags().find(|&x| x.typ == typ)) }
+    pub fn find_tag<T: Tag<'a>>(&self) -> Option<T> {
+        self.find_tags().next()
     }
-}
 
-#[derive(Debug)]
-#[repr(C)]
-pub struct TagBase {
-    typ: TagType,
-    size: u32,
+    pub fn find_tags<T: Tag<'a>>(&self) -> impl Iterator<Item = T> + 'a {
+        self.tags()
+            .map_while(Result::ok)
+            .filter(|tag| tag.typ() == T::TYPE)
+            .filter_map(|tag| T::parse(tag).ok())
+    }
 }
 
-pub trait Tag {}
+pub trait Tag<'a>: Sized {
+    const TYPE: TagType;
 
-pub struct TagIter<'a> {
-    current: *const TagBase,
-    phantom: PhantomData<&'a TagBase>,
+    fn parse(tag: RawTag<'a>) -> Result<Self, BootInfoError>;
 }
 
-impl<'a> Iterator for TagIter<'a> {
-    type Item = &'a TagBase;
+#[derive(Debug, Clone, Copy)]
+pub struct RawTag<'a> {
+    typ: TagType,
+    offset: usize,
+    bytes: &'a [u8],
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        match unsafe { &*self.current } {
-            &TagBase {
-                typ: TagType::End,
-                size: 8,
-            } => None,
-            tag => {
-                self.current =
-                    ((self.current as usize + tag.size as usize + 7) & !7) as *const TagBase;
-                Some(tag)
-            }
-        }
+impl<'a> RawTag<'a> {
+    #[inline]
+    pub fn typ(&self) -> TagType {
+        self.typ
     }
-}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[repr(u32)]
-pub enum TagType {
-    End = 0,
-    Cmdline = 1,
-    Bootloader = 2,
-    Module = 3,
-    BasicMemInfo = 4,
-    BootDevice = 5,
-    Mmap = 6,
-    VbeInfo = 7,
-    Framebuffer = 8,
-    ElfSections = 9,
-    Apm = 10,
-    Efi32 = 11,
-    Efi64 = 12,
-    Smbios = 13,
-    AcpiV1 = 14,
-    AcpiV2 = 15,
-    Network = 16,
-    EfiMmap = 17,
-    EfiBs = 18,
-    Efi32Ih = 19,
-    Efi64Ih = 20,
-    LoadBaseAddr = 21,
-}
-
-#[derive(Debug)]
-#[repr(C)]
-pub struct CmdlineTag {
-    typ: TagType,
-    size: u32,
-    cmdline: u8,
-}
+    #[inline]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
 
-impl CmdlineTag {
-    pub fn get(&self) -> &'static str {
-        unsafe {
-            str::from_utf8_unchecked(slice::from_raw_parts(
-                &self.cmdline,
-                self.size as usize - mem::size_of::<TagBase>(),
-            ))
-        }
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.bytes.len()
     }
-}
 
-impl Tag for CmdlineTag {}
+    #[inline]
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
 
-#[derive(Debug)]
-#[repr(C)]
-pub struct BootloaderTag {
-    typ: TagType,
-    size: u32,
-    bootloader: u8,
-}
+    #[inline]
+    pub fn payload(&self) -> &'a [u8] {
+        &self.bytes[HEADER_SIZE..]
+    }
 
-impl BootloaderTag {
-    pub fn get(&self) -> &'static str {
-        unsafe {
-            str::from_utf8_unchecked(slice::from_raw_parts(
-                &self.bootloader,
-                self.size as usize - mem::size_of::<TagBase>(),
-            ))
+    pub fn bad(&self) -> BootInfoError {
+        BootInfoError::BadTag {
+            typ: self.typ,
+            offset: self.offset,
         }
     }
-}
 
-impl Tag for BootloaderTag {}
+    pub fn u8(&self, offset: usize) -> Result<u8, BootInfoError> {
+        self.bytes.get(offset).copied().ok_or(self.bad())
+    }
 
-#[derive(Debug)]
-#[repr(C)]
-pub struct MemoryMapTag {
-    typ: TagType,
-    size: u32,
-    entry_size: u32,
-    entry_version: u32,
-    areas: MemoryArea,
-}
-
-impl MemoryMapTag {
-    pub fn areas(&self) -> MemoryAreaIter {
-        let start = &self.areas as *const _ as usize;
-        let end = self as *const _ as usize + self.size as usize;
-        MemoryAreaIter {
-            current_area: start,
-            last_area: end,
-            entry_size: self.entry_size as usize,
-            phantom: PhantomData {},
-        }
+    pub fn u16(&self, offset: usize) -> Result<u16, BootInfoError> {
+        self.array(offset).map(u16::from_le_bytes)
     }
-}
 
-impl Tag for MemoryMapTag {}
+    pub fn u32(&self, offset: usize) -> Result<u32, BootInfoError> {
+        self.array(offset).map(u32::from_le_bytes)
+    }
 
-#[derive(Debug)]
-#[repr(C)]
-pub struct MemoryArea {
-    pub base_addr: u64,
-    pub length: u64,
-    pub typ: AreaType,
-    reserved: u32,
-}
+    pub fn u64(&self, offset: usize) -> Result<u64, BootInfoError> {
+        self.array(offset).map(u64::from_le_bytes)
+    }
 
-impl MemoryArea {
-    #[inline]
-    pub fn start_addr(&self) -> usize {
-        self.base_addr as usize
+    pub fn array<const N: usize>(&self, offset: usize) -> Result<[u8; N], BootInfoError> {
+        self.slice(offset, N).map(|bytes| bytes.try_into().unwrap())
     }
 
-    #[inline]
-    pub fn size(&self) -> usize {
-        self.length as usize
+    pub fn slice(&self, offset: usize, len: usize) -> Result<&'a [u8], BootInfoError> {
+        offset
+            .checked_add(len)
+            .and_then(|end| self.bytes.get(offset..end))
+            .ok_or(self.bad())
     }
 
-    #[inline]
-    pub fn end_addr(&self) -> usize {
-        self.start_addr() + self.size()
+    pub fn tail(&self, offset: usize) -> Result<&'a [u8], BootInfoError> {
+        self.bytes.get(offset..).ok_or(self.bad())
     }
 
-    #[inline]
-    pub fn typ(&self) -> AreaType {
-        self.typ
+    pub fn str(&self, offset: usize) -> Result<&'a str, BootInfoError> {
+        self.tail(offset).map(c_str)
     }
 }
 
-pub struct MemoryAreaIter<'a> {
-    current_area: usize,
-    last_area: usize,
-    entry_size: usize,
-    phantom: PhantomData<&'a MemoryArea>,
+pub struct TagIter<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    finished: bool,
 }
 
-impl<'a> Iterator for MemoryAreaIter<'a> {
-    type Item = &'a MemoryArea;
+impl<'a> Iterator for TagIter<'a> {
+    type Item = Result<RawTag<'a>, BootInfoError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.current_area < self.last_area {
-            let area = unsafe { &*(self.current_area as *const MemoryArea) };
-            self.current_area += self.entry_size;
-            Some(area)
-        } else {
-            None
+        if self.finished || self.offset >= self.bytes.len() {
+            return None;
         }
+
+        let offset = self.offset;
+        let header = read_u32(self.bytes, offset)
+            .and_then(|typ| Ok((typ, read_u32(self.bytes, offset + 4)?)));
+        let (typ, size) = match header {
+            Ok((typ, size)) => (TagType::from(typ), size as usize),
+            Err(err) => {
+                self.offset = self.bytes.len();
+                return Some(Err(err));
+            }
+        };
+
+        if size < HEADER_SIZE {
+            self.offset = self.bytes.len();
+            return Some(Err(BootInfoError::OverlappingTag { offset, size }));
+        }
+        let Some(bytes) = self.bytes.get(offset..offset + size) else {
+            self.offset = self.bytes.len();
+            return Some(Err(BootInfoError::Truncated { offset, len: size }));
+        };
+
+        if typ == TagType::End {
+            self.finished = true;
+            return if size == HEADER_SIZE {
+                None
+            } else {
+                Some(Err(BootInfoError::BadTag { typ, offset }))
+            };
+        }
+
+        self.offset = (offset + size + TAG_ALIGN - 1) & !(TAG_ALIGN - 1);
+        Some(Ok(RawTag { typ, offset, bytes }))
     }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[repr(u32)]
-pub enum AreaType {
-    Available = 1,
-    Reserved = 2,
-    AcpiAvailable = 3,
-    ReservedHibernate = 4,
-    Defective = 5,
+pub enum TagType {
+    End,
+    Cmdline,
+    Bootloader,
+    Module,
+    BasicMemInfo,
+    BootDevice,
+    Mmap,
+    VbeInfo,
+    Framebuffer,
+    ElfSections,
+    Apm,
+    Efi32,
+    Efi64,
+    Smbios,
+    AcpiV1,
+    AcpiV2,
+    Network,
+    EfiMmap,
+    EfiBs,
+    Efi32Ih,
+    Efi64Ih,
+    LoadBaseAddr,
+    Unknown(u32),
+}
+
+impl From<u32> for TagType {
+    fn from(val: u32) -> Self {
+        match val {
+            0 => Self::End,
+            1 => Self::Cmdline,
+            2 => Self::Bootloader,
+            3 => Self::Module,
+            4 => Self::BasicMemInfo,
+            5 => Self::BootDevice,
+            6 => Self::Mmap,
+            7 => Self::VbeInfo,
+            8 => Self::Framebuffer,
+            9 => Self::ElfSections,
+            10 => Self::Apm,
+            11 => Self::Efi32,
+            12 => Self::Efi64,
+            13 => Self::Smbios,
+            14 => Self::AcpiV1,
+            15 => Self::AcpiV2,
+            16 => Self::Network,
+            17 => Self::EfiMmap,
+            18 => Self::EfiBs,
+            19 => Self::Efi32Ih,
+            20 => Self::Efi64Ih,
+            21 => Self::LoadBaseAddr,
+            val => Self::Unknown(val),
+        }
+    }
+}
+
+impl From<TagType> for u32 {
+    fn from(typ: TagType) -> Self {
+        match typ {
+            TagType::End => 0,
+            TagType::Cmdline => 1,
+            TagType::Bootloader => 2,
+            TagType::Module => 3,
+            TagType::BasicMemInfo => 4,
+            TagType::BootDevice => 5,
+            TagType::Mmap => 6,
+            TagType::VbeInfo => 7,
+            TagType::Framebuffer => 8,
+            TagType::ElfSections => 9,
+            TagType::Apm => 10,
+            TagType::Efi32 => 11,
+            TagType::Efi64 => 12,
+            TagType::Smbios => 13,
+            TagType::AcpiV1 => 14,
+            TagType::AcpiV2 => 15,
+            TagType::Network => 16,
+            TagType::EfiMmap => 17,
+            TagType::EfiBs => 18,
+            TagType::Efi32Ih => 19,
+            TagType::Efi64Ih => 20,
+            TagType::LoadBaseAddr => 21,
+            TagType::Unknown(val) => val,
+        }
+    }
+}
+
+fn validate_tag(tag: RawTag) -> Result<(), BootInfoError> {
+    fn check<'a, T: Tag<'a>>(tag: RawTag<'a>) -> Result<(), BootInfoError> {
+        T::parse(tag).map(|_| ())
+    }
+
+    match tag.typ() {
+        TagType::Cmdline => check::<CmdlineTag>(tag),
+        TagType::Bootloader => check::<BootloaderTag>(tag),
+        TagType::Module => check::<ModuleTag>(tag),
+        TagType::BasicMemInfo => check::<BasicMemInfoTag>(tag),
+        TagType::BootDevice => check::<BootDeviceTag>(tag),
+        TagType::Mmap => check::<MemoryMapTag>(tag),
+        TagType::VbeInfo => check::<VbeInfoTag>(tag),
+        TagType::Framebuffer => check::<FramebufferTag>(tag),
+        TagType::ElfSections => check::<ElfSectionsTag>(tag),
+        TagType::Apm => check::<ApmTag>(tag),
+        TagType::Efi32 => check::<Efi32Tag>(tag),
+        TagType::Efi64 => check::<Efi64Tag>(tag),
+        TagType::Smbios => check::<SmbiosTag>(tag),
+        TagType::AcpiV1 => check::<AcpiV1Tag>(tag),
+        TagType::AcpiV2 => check::<AcpiV2Tag>(tag),
+        TagType::Network => check::<NetworkTag>(tag),
+        TagType::EfiMmap => check::<EfiMemoryMapTag>(tag),
+        TagType::EfiBs => check::<EfiBootServicesTag>(tag),
+        TagType::Efi32Ih => check::<Efi32ImageHandleTag>(tag),
+        TagType::Efi64Ih => check::<Efi64ImageHandleTag>(tag),
+        TagType::LoadBaseAddr => check::<LoadBaseAddrTag>(tag),
+        TagType::End | TagType::Unknown(_) => Ok(()),
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, BootInfoError> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        .ok_or(BootInfoError::Truncated { offset, len: 4 })
+}
+
+fn c_str(bytes: &[u8]) -> &str {
+    let len = bytes.iter().position(|&c| c == 0).unwrap_or(bytes.len());
+    match str::from_utf8(&bytes[..len]) {
+        Ok(s) => s,
+        Err(err) => unsafe { str::from_utf8_unchecked(&bytes[..err.valid_up_to()]) },
+    }
 }
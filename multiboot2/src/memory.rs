@@ -0,0 +1,243 @@
+use core::slice::ChunksExact;
+
+use crate::{BootInfoError, RawTag, Tag, TagType};
+
+#[derive(Debug, Clone, Copy)]
+pub struct BasicMemInfoTag {
+    mem_lower: u32,
+    mem_upper: u32,
+}
+
+impl BasicMemInfoTag {
+    #[inline]
+    pub fn lower_kb(&self) -> usize {
+        self.mem_lower as usize
+    }
+
+    #[inline]
+    pub fn upper_kb(&self) -> usize {
+        self.mem_upper as usize
+    }
+}
+
+impl<'a> Tag<'a> for BasicMemInfoTag {
+    const TYPE: TagType = TagType::BasicMemInfo;
+
+    fn parse(tag: RawTag<'a>) -> Result<Self, BootInfoError> {
+        Ok(Self {
+            mem_lower: tag.u32(8)?,
+            mem_upper: tag.u32(12)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryMapTag<'a> {
+    entry_size: usize,
+    entry_version: u32,
+    entries: &'a [u8],
+}
+
+impl<'a> MemoryMapTag<'a> {
+    const AREA_SIZE: usize = 24;
+
+    #[inline]
+    pub fn entry_version(&self) -> u32 {
+        self.entry_version
+    }
+
+    pub fn areas(&self) -> MemoryAreaIter<'a> {
+        MemoryAreaIter {
+            entries: self.entries.chunks_exact(self.entry_size),
+        }
+    }
+}
+
+impl<'a> Tag<'a> for MemoryMapTag<'a> {
+    const TYPE: TagType = TagType::Mmap;
+
+    fn parse(tag: RawTag<'a>) -> Result<Self, BootInfoError> {
+        let entry_size = tag.u32(8)? as usize;
+        if entry_size < Self::AREA_SIZE {
+            return Err(tag.bad());
+        }
+        Ok(Self {
+            entry_size,
+            entry_version: tag.u32(12)?,
+            entries: tag.tail(16)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryArea {
+    pub base_addr: u64,
+    pub length: u64,
+    pub typ: AreaType,
+}
+
+impl MemoryArea {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            base_addr: u64::from_le_bytes(bytes[0..8].try_into().unwrap()),
+            length: u64::from_le_bytes(bytes[8..16].try_into().unwrap()),
+            typ: u32::from_le_bytes(bytes[16..20].try_into().unwrap()).into(),
+        }
+    }
+
+    #[inline]
+    pub fn start_addr(&self) -> usize {
+        self.base_addr as usize
+    }
+
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.length as usize
+    }
+
+    #[inline]
+    pub fn end_addr(&self) -> usize {
+        self.start_addr() + self.size()
+    }
+
+    #[inline]
+    pub fn typ(&self) -> AreaType {
+        self.typ
+    }
+}
+
+pub struct MemoryAreaIter<'a> {
+    entries: ChunksExact<'a, u8>,
+}
+
+impl<'a> Iterator for MemoryAreaIter<'a> {
+    type Item = MemoryArea;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next().map(MemoryArea::from_bytes)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AreaType {
+    Available,
+    Reserved,
+    AcpiAvailable,
+    ReservedHibernate,
+    Defective,
+    Unknown(u32),
+}
+
+impl From<u32> for AreaType {
+    fn from(val: u32) -> Self {
+        match val {
+            1 => Self::Available,
+            2 => Self::Reserved,
+            3 => Self::AcpiAvailable,
+            4 => Self::ReservedHibernate,
+            5 => Self::Defective,
+            val => Self::Unknown(val),
+        }
+    }
+}
+
+impl From<AreaType> for u32 {
+    fn from(typ: AreaType) -> Self {
+        match typ {
+            AreaType::Available => 1,
+            AreaType::Reserved => 2,
+            AreaType::AcpiAvailable => 3,
+            AreaType::ReservedHibernate => 4,
+            AreaType::Defective => 5,
+            AreaType::Unknown(val) => val,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EfiMemoryMapTag<'a> {
+    desc_size: usize,
+    desc_version: u32,
+    descs: &'a [u8],
+}
+
+impl<'a> EfiMemoryMapTag<'a> {
+    const DESC_SIZE: usize = 40;
+
+    #[inline]
+    pub fn desc_version(&self) -> u32 {
+        self.desc_version
+    }
+
+    pub fn descriptors(&self) -> EfiMemoryDescIter<'a> {
+        EfiMemoryDescIter {
+            descs: self.descs.chunks_exact(self.desc_size),
+        }
+    }
+}
+
+impl<'a> Tag<'a> for EfiMemoryMapTag<'a> {
+    const TYPE: TagType = TagType::EfiMmap;
+
+    fn parse(tag: RawTag<'a>) -> Result<Self, BootInfoError> {
+        let desc_size = tag.u32(8)? as usize;
+        if desc_size < Self::DESC_SIZE {
+            return Err(tag.bad());
+        }
+        Ok(Self {
+            desc_size,
+            desc_version: tag.u32(12)?,
+            descs: tag.tail(16)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EfiMemoryDesc {
+    pub typ: u32,
+    pub phys_start: u64,
+    pub virt_start: u64,
+    pub num_pages: u64,
+    pub attribute: u64,
+}
+
+impl EfiMemoryDesc {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let u64_at =
+            |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+        Self {
+            typ: u32::from_le_bytes(bytes[0..4].try_into().unwrap()),
+            phys_start: u64_at(8),
+            virt_start: u64_at(16),
+            num_pages: u64_at(24),
+            attribute: u64_at(32),
+        }
+    }
+
+    #[inline]
+    pub fn start_addr(&self) -> usize {
+        self.phys_start as usize
+    }
+
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.num_pages as usize * 4096
+    }
+
+    #[inline]
+    pub fn end_addr(&self) -> usize {
+        self.start_addr() + self.size()
+    }
+}
+
+pub struct EfiMemoryDescIter<'a> {
+    descs: ChunksExact<'a, u8>,
+}
+
+impl<'a> Iterator for EfiMemoryDescIter<'a> {
+    type Item = EfiMemoryDesc;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.descs.next().map(EfiMemoryDesc::from_bytes)
+    }
+}
@@ -0,0 +1,89 @@
+use crate::{BootInfoError, RawTag, Tag, TagType};
+
+#[derive(Debug, Clone, Copy)]
+pub struct CmdlineTag<'a> {
+    cmdline: &'a str,
+}
+
+impl<'a> CmdlineTag<'a> {
+    #[inline]
+    pub fn get(&self) -> &'a str {
+        self.cmdline
+    }
+}
+
+impl<'a> Tag<'a> for CmdlineTag<'a> {
+    const TYPE: TagType = TagType::Cmdline;
+
+    fn parse(tag: RawTag<'a>) -> Result<Self, BootInfoError> {
+        Ok(Self {
+            cmdline: tag.str(8)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BootloaderTag<'a> {
+    name: &'a str,
+}
+
+impl<'a> BootloaderTag<'a> {
+    #[inline]
+    pub fn get(&self) -> &'a str {
+        self.name
+    }
+}
+
+impl<'a> Tag<'a> for BootloaderTag<'a> {
+    const TYPE: TagType = TagType::Bootloader;
+
+    fn parse(tag: RawTag<'a>) -> Result<Self, BootInfoError> {
+        Ok(Self { name: tag.str(8)? })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ModuleTag<'a> {
+    mod_start: u32,
+    mod_end: u32,
+    cmdline: &'a str,
+}
+
+impl<'a> ModuleTag<'a> {
+    #[inline]
+    pub fn start_addr(&self) -> usize {
+        self.mod_start as usize
+    }
+
+    #[inline]
+    pub fn end_addr(&self) -> usize {
+        self.mod_end as usize
+    }
+
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.end_addr() - self.start_addr()
+    }
+
+    #[inline]
+    pub fn cmdline(&self) -> &'a str {
+        self.cmdline
+    }
+}
+
+impl<'a> Tag<'a> for ModuleTag<'a> {
+    const TYPE: TagType = TagType::Module;
+
+    fn parse(tag: RawTag<'a>) -> Result<Self, BootInfoError> {
+        let mod_start = tag.u32(8)?;
+        let mod_end = tag.u32(12)?;
+        if mod_end < mod_start {
+            return Err(tag.bad());
+        }
+        Ok(Self {
+            mod_start,
+            mod_end,
+            cmdline: tag.str(16)?,
+        })
+    }
+}
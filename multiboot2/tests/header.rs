@@ -0,0 +1,87 @@
+use std::mem;
+
+use multiboot2::header::*;
+
+#[repr(C)]
+struct Tags {
+    info: InformationRequestTag<3>,
+    framebuffer: FramebufferTag,
+    module_align: ModuleAlignTag,
+    efi_bs: EfiBootServicesTag,
+    entry: EntryAddressTag,
+    relocatable: RelocatableTag,
+}
+
+static HEADER: Header<Tags> = Header::new(
+    Architecture::I386,
+    Tags {
+        info: InformationRequestTag::new([1, 6, 8]).optional(),
+        framebuffer: FramebufferTag::new(1024, 768, 32).optional(),
+        module_align: ModuleAlignTag::new(),
+        efi_bs: EfiBootServicesTag::new(),
+        entry: EntryAddressTag::efi64(0x10_0000),
+        relocatable: RelocatableTag::new(0x10_0000, 0x1000_0000, 0x1000, RelocPreference::Lowest),
+    },
+);
+
+#[test]
+fn checksum() {
+    let sum = HEADER_MAGIC
+        .wrapping_add(HEADER.arch() as u32)
+        .wrapping_add(HEADER.length())
+        .wrapping_add(HEADER.checksum());
+    assert_eq!(sum, 0);
+    assert_eq!(HEADER.length() as usize, mem::size_of::<Header<Tags>>());
+}
+
+#[test]
+fn tags_are_aligned() {
+    assert_eq!(mem::align_of::<Header<Tags>>(), 8);
+    assert_eq!(mem::size_of::<Header<Tags>>() % 8, 0);
+
+    let start = &HEADER as *const _ as usize;
+    let tags = HEADER.tags();
+    let headers = [
+        (tags.info.header(), &tags.info as *const _ as usize),
+        (
+            tags.framebuffer.header(),
+            &tags.framebuffer as *const _ as usize,
+        ),
+        (
+            tags.module_align.header(),
+            &tags.module_align as *const _ as usize,
+        ),
+        (tags.efi_bs.header(), &tags.efi_bs as *const _ as usize),
+        (tags.entry.header(), &tags.entry as *const _ as usize),
+        (
+            tags.relocatable.header(),
+            &tags.relocatable as *const _ as usize,
+        ),
+    ];
+
+    let mut offset = 16;
+    for (header, addr) in headers {
+        assert_eq!(addr - start, offset);
+        offset += (header.size() as usize + 7) & !7;
+    }
+    assert_eq!(offset + 8, HEADER.length() as usize);
+}
+
+#[test]
+fn tag_headers() {
+    let tags = HEADER.tags();
+    assert_eq!(tags.info.header().typ(), HeaderTagType::InformationRequest);
+    assert_eq!(tags.info.header().size(), 20);
+    assert!(tags.info.header().is_optional());
+
+    assert_eq!(tags.framebuffer.header().typ(), HeaderTagType::Framebuffer);
+    assert_eq!(tags.framebuffer.header().size(), 20);
+    assert_eq!(tags.framebuffer.mode(), (1024, 768, 32));
+
+    assert_eq!(tags.module_align.header().size(), 8);
+    assert!(!tags.module_align.header().is_optional());
+    assert_eq!(tags.efi_bs.header().typ(), HeaderTagType::EfiBs);
+    assert_eq!(tags.entry.header().typ(), HeaderTagType::EntryAddressEfi64);
+    assert_eq!(tags.entry.header().size(), 12);
+    assert_eq!(tags.relocatable.header().size(), 24);
+}
@@ -0,0 +1,451 @@
+use multiboot2::*;
+
+fn parse(bytes: &[u8]) -> BootInfo<'_> {
+    BootInfo::from_bytes(bytes).unwrap()
+}
+
+fn framebuffer_mode() -> FramebufferMode {
+    FramebufferMode {
+        addr: 0xfd00_0000,
+        pitch: 4096,
+        width: 1024,
+        height: 768,
+        bpp: 32,
+    }
+}
+
+#[test]
+fn empty() {
+    let bytes = BootInfoBuilder::new().build();
+    assert_eq!(bytes.len(), 16);
+
+    let boot_info = parse(&bytes);
+    assert_eq!(boot_info.size(), 16);
+    assert_eq!(boot_info.tags().count(), 0);
+}
+
+#[test]
+fn tags_are_aligned() {
+    let bytes = BootInfoBuilder::new()
+        .cmdline("a")
+        .bootloader("GRUB 2.06")
+        .raw(TagType::Unknown(0x1234), &[1, 2, 3])
+        .build();
+    assert_eq!(bytes.len() % 8, 0);
+
+    for tag in parse(&bytes).tags() {
+        assert_eq!(tag.unwrap().offset() % 8, 0);
+    }
+}
+
+#[test]
+fn strings() {
+    let bytes = BootInfoBuilder::new()
+        .cmdline("log=debug console=ttyS0")
+        .bootloader("GRUB 2.06")
+        .module(0x20_0000, 0x20_1000, "font")
+        .module(0x30_0000, 0x30_0000, "")
+        .build();
+    let boot_info = parse(&bytes);
+
+    assert_eq!(
+        boot_info.find_tag::<CmdlineTag>().unwrap().get(),
+        "log=debug console=ttyS0"
+    );
+    assert_eq!(
+        boot_info.find_tag::<BootloaderTag>().unwrap().get(),
+        "GRUB 2.06"
+    );
+
+    let modules: Vec<ModuleTag> = boot_info.find_tags().collect();
+    assert_eq!(modules.len(), 2);
+    assert_eq!(modules[0].start_addr(), 0x20_0000);
+    assert_eq!(modules[0].end_addr(), 0x20_1000);
+    assert_eq!(modules[0].size(), 0x1000);
+    assert_eq!(modules[0].cmdline(), "font");
+    assert_eq!(modules[1].size(), 0);
+    assert_eq!(modules[1].cmdline(), "");
+}
+
+#[test]
+fn memory() {
+    let areas = [
+        MemoryArea {
+            base_addr: 0,
+            length: 0x9fc00,
+            typ: AreaType::Available,
+        },
+        MemoryArea {
+            base_addr: 0x10_0000,
+            length: 0x1fee_0000,
+            typ: AreaType::Available,
+        },
+        MemoryArea {
+            base_addr: 0xfffc_0000,
+            length: 0x4_0000,
+            typ: AreaType::Reserved,
+        },
+        MemoryArea {
+            base_addr: 0x1_0000_0000,
+            length: 0x1000,
+            typ: AreaType::Unknown(20),
+        },
+    ];
+    let descs = [EfiMemoryDesc {
+        typ: 7,
+        phys_start: 0x10_0000,
+        virt_start: 0,
+        num_pages: 16,
+        attribute: 0xf,
+    }];
+    let bytes = BootInfoBuilder::new()
+        .basic_mem_info(639, 523_136)
+        .memory_map(&areas)
+        .efi_memory_map(1, &descs)
+        .build();
+    let boot_info = parse(&bytes);
+
+    let mem_info: BasicMemInfoTag = boot_info.find_tag().unwrap();
+    assert_eq!(mem_info.lower_kb(), 639);
+    assert_eq!(mem_info.upper_kb(), 523_136);
+
+    let mmap: MemoryMapTag = boot_info.find_tag().unwrap();
+    assert!(mmap.areas().eq(areas));
+    assert_eq!(mmap.areas().nth(1).unwrap().end_addr(), 0x1ffe_0000);
+
+    let efi_mmap: EfiMemoryMapTag = boot_info.find_tag().unwrap();
+    assert_eq!(efi_mmap.desc_version(), 1);
+    assert!(efi_mmap.descriptors().eq(descs));
+    assert_eq!(efi_mmap.descriptors().next().unwrap().size(), 16 * 4096);
+}
+
+#[test]
+fn framebuffer() {
+    let red = ColorField::new(16, 8);
+    let green = ColorField::new(8, 8);
+    let blue = ColorField::new(0, 8);
+    let bytes = BootInfoBuilder::new()
+        .framebuffer_rgb(framebuffer_mode(), red, green, blue)
+        .build();
+    let fb: FramebufferTag = parse(&bytes).find_tag().unwrap();
+    assert_eq!(fb.addr(), 0xfd00_0000);
+    assert_eq!(fb.pitch(), 4096);
+    assert_eq!(fb.width(), 1024);
+    assert_eq!(fb.height(), 768);
+    assert_eq!(fb.bpp(), 32);
+    assert_eq!(fb.size(), 4096 * 768);
+    assert_eq!(
+        fb.buffer_type(),
+        Some(FramebufferType::Rgb { red, green, blue })
+    );
+
+    let palette = [
+        ColorDescriptor {
+            red: 0,
+            green: 0,
+            blue: 0,
+        },
+        ColorDescriptor {
+            red: 0xaa,
+            green: 0x55,
+            blue: 0x00,
+        },
+    ];
+    let bytes = BootInfoBuilder::new()
+        .framebuffer_indexed(framebuffer_mode(), &palette)
+        .build();
+    let fb: FramebufferTag = parse(&bytes).find_tag().unwrap();
+    let Some(FramebufferType::Indexed { palette: parsed }) = fb.buffer_type() else {
+        panic!("expected indexed framebuffer");
+    };
+    assert_eq!(parsed.len(), 2);
+    assert_eq!(parsed.get(1), Some(palette[1]));
+    assert_eq!(parsed.get(2), None);
+    assert!(parsed.iter().eq(palette));
+
+    let bytes = BootInfoBuilder::new()
+        .framebuffer_text(framebuffer_mode())
+        .build();
+    let fb: FramebufferTag = parse(&bytes).find_tag().unwrap();
+    assert_eq!(fb.buffer_type(), Some(FramebufferType::Text));
+}
+
+#[test]
+fn vbe_info() {
+    let control_info = [0x11; 512];
+    let mode_info = [0x22; 256];
+    let bytes = BootInfoBuilder::new()
+        .vbe_info(0x118, (0xc000, 0x1234, 0x40), &control_info, &mode_info)
+        .build();
+    let vbe: VbeInfoTag = parse(&bytes).find_tag().unwrap();
+    assert_eq!(vbe.mode(), 0x118);
+    assert_eq!(vbe.interface(), (0xc000, 0x1234, 0x40));
+    assert_eq!(vbe.control_info(), &control_info);
+    assert_eq!(vbe.mode_info(), &mode_info);
+}
+
+#[test]
+fn elf_sections() {
+    let sections = [
+        ElfSection {
+            name_index: 0,
+            typ: 0,
+            flags: 0,
+            addr: 0,
+            offset: 0,
+            size: 0,
+            link: 0,
+            info: 0,
+            addr_align: 0,
+            entry_size: 0,
+        },
+        ElfSection {
+            name_index: 1,
+            typ: 1,
+            flags: ElfSection::ALLOC | ElfSection::EXECUTE,
+            addr: 0x10_0000,
+            offset: 0x1000,
+            size: 0x8000,
+            link: 0,
+            info: 0,
+            addr_align: 16,
+            entry_size: 0,
+        },
+    ];
+    let bytes = BootInfoBuilder::new().elf_sections(0, &sections).build();
+    let elf: ElfSectionsTag = parse(&bytes).find_tag().unwrap();
+    assert_eq!(elf.count(), 2);
+    assert!(elf.sections().eq(sections));
+    assert_eq!(elf.string_table(), Some(sections[0]));
+
+    let text = elf.sections().nth(1).unwrap();
+    assert!(text.is_allocated());
+    assert_eq!(text.end_addr(), 0x10_8000);
+}
+
+#[test]
+fn firmware() {
+    let apm = ApmTag {
+        version: 0x102,
+        cseg: 0xf000,
+        offset: 0x1234,
+        cseg_16: 0xf000,
+        dseg: 0x40,
+        flags: 3,
+        cseg_len: 0xffff,
+        cseg_16_len: 0xffff,
+        dseg_len: 0x100,
+    };
+    let rsdp = Rsdp {
+        signature: *b"RSD PTR ",
+        checksum: 0x42,
+        oem_id: *b"BOCHS ",
+        revision: 2,
+        rsdt_addr: 0x7fe_0000,
+    };
+    let ext = XsdpExt {
+        length: 36,
+        xsdt_addr: 0x7fe_1000,
+        ext_checksum: 0x17,
+    };
+    let bytes = BootInfoBuilder::new()
+        .apm(&apm)
+        .efi32(0x1000)
+        .efi64(0x1_0000_2000)
+        .efi_boot_services()
+        .efi32_image_handle(0x3000)
+        .efi64_image_handle(0x1_0000_4000)
+        .smbios((3, 2), b"_SM3_tables")
+        .acpi_v1(&rsdp)
+        .acpi_v2(&rsdp, &ext)
+        .build();
+    let boot_info = parse(&bytes);
+
+    let parsed: ApmTag = boot_info.find_tag().unwrap();
+    assert_eq!(parsed.version, apm.version);
+    assert_eq!(parsed.offset, apm.offset);
+    assert_eq!(parsed.dseg_len, apm.dseg_len);
+
+    assert_eq!(
+        boot_info.find_tag::<Efi32Tag>().unwrap().system_table(),
+        0x1000
+    );
+    assert_eq!(
+        boot_info.find_tag::<Efi64Tag>().unwrap().system_table(),
+        0x1_0000_2000
+    );
+    assert!(boot_info.find_tag::<EfiBootServicesTag>().is_some());
+    assert_eq!(
+        boot_info
+            .find_tag::<Efi32ImageHandleTag>()
+            .unwrap()
+            .image_handle(),
+        0x3000
+    );
+    assert_eq!(
+        boot_info
+            .find_tag::<Efi64ImageHandleTag>()
+            .unwrap()
+            .image_handle(),
+        0x1_0000_4000
+    );
+
+    let smbios: SmbiosTag = boot_info.find_tag().unwrap();
+    assert_eq!(smbios.version(), (3, 2));
+    assert_eq!(smbios.tables(), b"_SM3_tables");
+
+    let acpi_v1: AcpiV1Tag = boot_info.find_tag().unwrap();
+    assert_eq!(acpi_v1.rsdp(), &rsdp);
+    let addr = acpi_v1.rsdp_addr() - boot_info.start_addr();
+    assert_eq!(&bytes[addr..addr + 8], b"RSD PTR ");
+
+    let acpi_v2: AcpiV2Tag = boot_info.find_tag().unwrap();
+    assert_eq!(acpi_v2.rsdp(), &rsdp);
+    assert_eq!(acpi_v2.ext(), &ext);
+    assert_eq!(acpi_v2.xsdt_addr(), 0x7fe_1000);
+}
+
+#[test]
+fn boot() {
+    let bytes = BootInfoBuilder::new()
+        .boot_device(0x80, Some(1), None)
+        .network(&[0x02, 0x01, 0x06])
+        .load_base_addr(0x20_0000)
+        .build();
+    let boot_info = parse(&bytes);
+
+    let dev: BootDeviceTag = boot_info.find_tag().unwrap();
+    assert_eq!(dev.bios_device(), 0x80);
+    assert_eq!(dev.partition(), Some(1));
+    assert_eq!(dev.sub_partition(), None);
+
+    let net: NetworkTag = boot_info.find_tag().unwrap();
+    assert_eq!(net.dhcp_ack(), &[0x02, 0x01, 0x06]);
+
+    let base: LoadBaseAddrTag = boot_info.find_tag().unwrap();
+    assert_eq!(base.addr(), 0x20_0000);
+}
+
+#[test]
+fn unknown_tags_are_skipped() {
+    let bytes = BootInfoBuilder::new()
+        .raw(TagType::Unknown(0x99), &[0xff; 13])
+        .cmdline("quiet")
+        .build();
+    let boot_info = parse(&bytes);
+
+    let types: Vec<TagType> = boot_info.tags().map(|tag| tag.unwrap().typ()).collect();
+    assert_eq!(types, [TagType::Unknown(0x99), TagType::Cmdline]);
+    assert_eq!(boot_info.find_tag::<CmdlineTag>().unwrap().get(), "quiet");
+}
+
+#[test]
+fn invalid_utf8_is_truncated() {
+    let bytes = BootInfoBuilder::new()
+        .raw(TagType::Cmdline, b"abc\xffdef\0")
+        .build();
+    assert_eq!(parse(&bytes).find_tag::<CmdlineTag>().unwrap().get(), "abc");
+}
+
+#[test]
+fn too_short() {
+    assert_eq!(
+        BootInfo::from_bytes(&[16, 0]).unwrap_err(),
+        BootInfoError::Truncated { offset: 0, len: 4 }
+    );
+
+    let bytes = BootInfoBuilder::new().cmdline("quiet").build();
+    assert_eq!(
+        BootInfo::from_bytes(&bytes[..bytes.len() - 8]).unwrap_err(),
+        BootInfoError::Truncated {
+            offset: 0,
+            len: bytes.len(),
+        }
+    );
+}
+
+#[test]
+fn missing_end_tag() {
+    let mut bytes = BootInfoBuilder::new().cmdline("quiet").build();
+    bytes.truncate(bytes.len() - 8);
+    let size = bytes.len() as u32;
+    bytes[..4].copy_from_slice(&size.to_le_bytes());
+
+    assert_eq!(
+        BootInfo::from_bytes(&bytes).unwrap_err(),
+        BootInfoError::MissingEndTag
+    );
+}
+
+#[test]
+fn bad_tag_sizes() {
+    let mut bytes = BootInfoBuilder::new().cmdline("quiet").build();
+    bytes[12..16].copy_from_slice(&4u32.to_le_bytes());
+    assert_eq!(
+        BootInfo::from_bytes(&bytes).unwrap_err(),
+        BootInfoError::OverlappingTag { offset: 8, size: 4 }
+    );
+
+    bytes[12..16].copy_from_slice(&0x1000u32.to_le_bytes());
+    assert_eq!(
+        BootInfo::from_bytes(&bytes).unwrap_err(),
+        BootInfoError::Truncated {
+            offset: 8,
+            len: 0x1000,
+        }
+    );
+
+    let mut bytes = BootInfoBuilder::new().build();
+    bytes[12..16].copy_from_slice(&16u32.to_le_bytes());
+    assert!(BootInfo::from_bytes(&bytes).is_err());
+}
+
+#[test]
+fn malformed_tags() {
+    let bytes = BootInfoBuilder::new()
+        .raw(TagType::Mmap, &[8, 0, 0, 0, 0, 0, 0, 0])
+        .build();
+    assert_eq!(
+        BootInfo::from_bytes(&bytes).unwrap_err(),
+        BootInfoError::BadTag {
+            typ: TagType::Mmap,
+            offset: 8,
+        }
+    );
+
+    let bytes = BootInfoBuilder::new()
+        .raw(TagType::Module, &[0, 0x10, 0, 0, 0, 0, 0, 0])
+        .build();
+    assert!(matches!(
+        BootInfo::from_bytes(&bytes),
+        Err(BootInfoError::BadTag {
+            typ: TagType::Module,
+            ..
+        })
+    ));
+
+    let bytes = BootInfoBuilder::new()
+        .raw(TagType::AcpiV2, &[0; 20])
+        .build();
+    assert!(BootInfo::from_bytes(&bytes).is_err());
+}
+
+#[test]
+fn init_checks_magic_and_alignment() {
+    let bytes = BootInfoBuilder::new().build();
+    assert_eq!(
+        unsafe { multiboot2::init(0x2badb002, bytes.as_ptr()) }.unwrap_err(),
+        BootInfoError::BadMagic(0x2badb002)
+    );
+    assert_eq!(
+        unsafe { multiboot2::init(MAGIC, 0x1001 as *const u8) }.unwrap_err(),
+        BootInfoError::Misaligned(0x1001)
+    );
+
+    let words: Vec<u64> = bytes
+        .chunks(8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        .collect();
+    let boot_info = unsafe { multiboot2::init(MAGIC, words.as_ptr() as *const u8) }.unwrap();
+    assert_eq!(boot_info.as_bytes(), &bytes[..]);
+}
@@ -0,0 +1,248 @@
+use core::time::Duration;
+
+use crate::{
+    ethernet::{MacAddress, ETHERTYPE_IPV4, MAX_FRAME_SIZE},
+    read_u16, write_u16, Ipv4Addr,
+};
+
+pub const ARP_PACKET_SIZE: usize = 28;
+pub const OP_REQUEST: u16 = 1;
+pub const OP_REPLY: u16 = 2;
+
+const HTYPE_ETHERNET: u16 = 1;
+
+const CACHE_SIZE: usize = 32;
+const QUEUE_LEN: usize = 8;
+const REACHABLE_TIME: Duration = Duration::from_secs(60);
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_REQUESTS: u8 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArpPacket {
+    pub op: u16,
+    pub sender_mac: MacAddress,
+    pub sender_ip: Ipv4Addr,
+    pub target_mac: MacAddress,
+    pub target_ip: Ipv4Addr,
+}
+
+impl ArpPacket {
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < ARP_PACKET_SIZE
+            || read_u16(data, 0) != HTYPE_ETHERNET
+            || read_u16(data, 2) != ETHERTYPE_IPV4
+            || data[4] != 6
+            || data[5] != 4
+        {
+            return None;
+        }
+        Some(Self {
+            op: read_u16(data, 6),
+            sender_mac: MacAddress(data[8..14].try_into().unwrap()),
+            sender_ip: Ipv4Addr(data[14..18].try_into().unwrap()),
+            target_mac: MacAddress(data[18..24].try_into().unwrap()),
+            target_ip: Ipv4Addr(data[24..28].try_into().unwrap()),
+        })
+    }
+
+    pub fn write(&self, buf: &mut [u8]) {
+        write_u16(buf, 0, HTYPE_ETHERNET);
+        write_u16(buf, 2, ETHERTYPE_IPV4);
+        buf[4] = 6;
+        buf[5] = 4;
+        write_u16(buf, 6, self.op);
+        buf[8..14].copy_from_slice(&self.sender_mac.0);
+        buf[14..18].copy_from_slice(&self.sender_ip.0);
+        buf[18..24].copy_from_slice(&self.target_mac.0);
+        buf[24..28].copy_from_slice(&self.target_ip.0);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Free,
+    // Requests sent so far, the entry expiring when the next one is due.
+    Incomplete(u8),
+    Reachable,
+}
+
+#[derive(Clone, Copy)]
+struct Entry {
+    iface: usize,
+    addr: Ipv4Addr,
+    mac: MacAddress,
+    state: State,
+    expires: Duration,
+}
+
+impl Entry {
+    const FREE: Entry = Entry {
+        iface: 0,
+        addr: Ipv4Addr::UNSPECIFIED,
+        mac: MacAddress::ZERO,
+        state: State::Free,
+        expires: Duration::ZERO,
+    };
+}
+
+// A frame held back until its next hop is resolved.
+struct Pending {
+    iface: usize,
+    addr: Ipv4Addr,
+    len: usize,
+    frame: [u8; MAX_FRAME_SIZE],
+}
+
+impl Pending {
+    const EMPTY: Pending = Pending {
+        iface: 0,
+        addr: Ipv4Addr::UNSPECIFIED,
+        len: 0,
+        frame: [0; MAX_FRAME_SIZE],
+    };
+}
+
+pub struct ArpCache {
+    entries: [Entry; CACHE_SIZE],
+    queue: [Pending; QUEUE_LEN],
+}
+
+impl ArpCache {
+    pub const fn new() -> Self {
+        Self {
+            entries: [Entry::FREE; CACHE_SIZE],
+            queue: [Pending::EMPTY; QUEUE_LEN],
+        }
+    }
+
+    fn find(&self, iface: usize, addr: Ipv4Addr) -> Option<usize> {
+        self.entries.iter().position(|entry| {
+            entry.state != State::Free && entry.iface == iface && entry.addr == addr
+        })
+    }
+
+    // Free entries go first, then whichever has the least time left.
+    fn allocate(&mut self, now: Duration) -> usize {
+        self.entries
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, entry)| match entry.state {
+                State::Free => (0, Duration::ZERO),
+                State::Reachable if entry.expires <= now => (0, Duration::ZERO),
+                _ => (1, entry.expires),
+            })
+            .map(|(idx, _)| idx)
+            .unwrap()
+    }
+
+    pub fn lookup(&self, iface: usize, addr: Ipv4Addr, now: Duration) -> Option<MacAddress> {
+        let entry = &self.entries[self.find(iface, addr)?];
+        (entry.state == State::Reachable && entry.expires > now).then_some(entry.mac)
+    }
+
+    // Hosts only learn from requests aimed at them, as RFC 826 has it, and
+    // otherwise just refresh entries they already hold. Returns whether the
+    // address is now resolved.
+    pub fn update(
+        &mut self,
+        iface: usize,
+        addr: Ipv4Addr,
+        mac: MacAddress,
+        now: Duration,
+        create: bool,
+    ) -> bool {
+        let idx = match self.find(iface, addr) {
+            Some(idx) => idx,
+            None if create => self.allocate(now),
+            None => return false,
+        };
+        self.entries[idx] = Entry {
+            iface,
+            addr,
+            mac,
+            state: State::Reachable,
+            expires: now + REACHABLE_TIME,
+        };
+        true
+    }
+
+    // Starts resolving `addr`. Returns whether a request should be sent,
+    // which is only the case when nothing is outstanding yet.
+    pub fn resolve(&mut self, iface: usize, addr: Ipv4Addr, now: Duration) -> bool {
+        let idx = match self.find(iface, addr) {
+            Some(idx) if matches!(self.entries[idx].state, State::Incomplete(_)) => return false,
+            Some(idx) => idx,
+            None => self.allocate(now),
+        };
+        self.entries[idx] = Entry {
+            iface,
+            addr,
+            mac: MacAddress::ZERO,
+            state: State::Incomplete(1),
+            expires: now + RETRY_INTERVAL,
+        };
+        true
+    }
+
+    // Frames beyond the queue length are dropped, leaving it to the upper
+    // layers to try again.
+    pub fn enqueue(&mut self, iface: usize, addr: Ipv4Addr, frame: &[u8]) -> bool {
+        match self.queue.iter_mut().find(|pending| pending.len == 0) {
+            Some(pending) => {
+                pending.iface = iface;
+                pending.addr = addr;
+                pending.len = frame.len();
+                pending.frame[..frame.len()].copy_from_slice(frame);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Hands out frames waiting on `addr` one at a time.
+    pub fn dequeue(&mut self, iface: usize, addr: Ipv4Addr) -> Option<&mut [u8]> {
+        let pending = self
+            .queue
+            .iter_mut()
+            .find(|pending| pending.len != 0 && pending.iface == iface && pending.addr == addr)?;
+        let len = core::mem::take(&mut pending.len);
+        Some(&mut pending.frame[..len])
+    }
+
+    // Returns the next address whose request is due again. Addresses that
+    // stay silent are forgotten along with the frames waiting on them.
+    pub fn retry(&mut self, now: Duration) -> Option<(usize, Ipv4Addr)> {
+        for idx in 0..CACHE_SIZE {
+            let entry = self.entries[idx];
+            let State::Incomplete(sent) = entry.state else {
+                continue;
+            };
+            if entry.expires > now {
+                continue;
+            }
+            if sent >= MAX_REQUESTS {
+                self.entries[idx].state = State::Free;
+                while self.dequeue(entry.iface, entry.addr).is_some() {}
+                continue;
+            }
+            self.entries[idx].state = State::Incomplete(sent + 1);
+            self.entries[idx].expires = now + RETRY_INTERVAL;
+            return Some((entry.iface, entry.addr));
+        }
+        None
+    }
+
+    pub fn next_deadline(&self) -> Option<Duration> {
+        self.entries
+            .iter()
+            .filter(|entry| matches!(entry.state, State::Incomplete(_)))
+            .map(|entry| entry.expires)
+            .min()
+    }
+}
+
+impl Default for ArpCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
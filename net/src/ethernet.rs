@@ -0,0 +1,68 @@
+use core::fmt;
+
+use crate::{read_u16, write_u16};
+
+pub const ETH_HEADER_SIZE: usize = 14;
+pub const ETH_MTU: usize = 1500;
+// Without the frame check sequence, which devices add and strip.
+pub const MAX_FRAME_SIZE: usize = ETH_HEADER_SIZE + ETH_MTU;
+pub const MIN_FRAME_SIZE: usize = 60;
+
+pub const ETHERTYPE_IPV4: u16 = 0x0800;
+pub const ETHERTYPE_ARP: u16 = 0x0806;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct MacAddress(pub [u8; 6]);
+
+impl MacAddress {
+    pub const BROADCAST: MacAddress = MacAddress([0xff; 6]);
+    pub const ZERO: MacAddress = MacAddress([0; 6]);
+
+    #[inline]
+    pub fn is_broadcast(&self) -> bool {
+        *self == Self::BROADCAST
+    }
+
+    #[inline]
+    pub fn is_multicast(&self) -> bool {
+        self.0[0] & 1 != 0
+    }
+}
+
+impl fmt::Display for MacAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(
+            f,
+            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            a, b, c, d, e, g
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EthernetHeader {
+    pub dst: MacAddress,
+    pub src: MacAddress,
+    pub ethertype: u16,
+}
+
+impl EthernetHeader {
+    pub fn parse(frame: &[u8]) -> Option<(Self, &[u8])> {
+        if frame.len() < ETH_HEADER_SIZE {
+            return None;
+        }
+        let header = Self {
+            dst: MacAddress(frame[0..6].try_into().unwrap()),
+            src: MacAddress(frame[6..12].try_into().unwrap()),
+            ethertype: read_u16(frame, 12),
+        };
+        Some((header, &frame[ETH_HEADER_SIZE..]))
+    }
+
+    pub fn write(&self, buf: &mut [u8]) {
+        buf[0..6].copy_from_slice(&self.dst.0);
+        buf[6..12].copy_from_slice(&self.src.0);
+        write_u16(buf, 12, self.ethertype);
+    }
+}
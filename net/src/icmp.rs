@@ -0,0 +1,267 @@
+use core::time::Duration;
+
+use crate::{
+    checksum,
+    iface::Interfaces,
+    ipv4::{Ipv4Header, IPV4_HEADER_SIZE, MAX_DATAGRAM, PROTO_ICMP},
+    read_u16, write_u16, Checksum, Ipv4Addr, NetError,
+};
+
+pub const ICMP_HEADER_SIZE: usize = 8;
+// Echo data starts with the time the request was sent.
+pub const MAX_PING_DATA: usize = MAX_DATAGRAM - ICMP_HEADER_SIZE;
+
+const TYPE_ECHO_REPLY: u8 = 0;
+const TYPE_UNREACHABLE: u8 = 3;
+const TYPE_ECHO_REQUEST: u8 = 8;
+
+pub const CODE_PROTOCOL_UNREACHABLE: u8 = 2;
+pub const CODE_PORT_UNREACHABLE: u8 = 3;
+
+const MAX_PING_SOCKETS: usize = 4;
+const REPLY_QUEUE_LEN: usize = 8;
+const TIMESTAMP_SIZE: usize = 8;
+// The bytes of the original datagram quoted back in error messages.
+const QUOTE_SIZE: usize = 8;
+
+const PATTERN: [u8; 256] = {
+    let mut pattern = [0; 256];
+    let mut idx = 0;
+    while idx < 256 {
+        pattern[idx] = idx as u8;
+        idx += 1;
+    }
+    pattern
+};
+const MAX_PARTS: usize = 2 + MAX_PING_DATA.div_ceil(PATTERN.len());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PingHandle(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PingReply {
+    pub from: Ipv4Addr,
+    pub seq: u16,
+    pub len: usize,
+    pub ttl: u8,
+    pub rtt: Duration,
+}
+
+impl PingReply {
+    const EMPTY: PingReply = PingReply {
+        from: Ipv4Addr::UNSPECIFIED,
+        seq: 0,
+        len: 0,
+        ttl: 0,
+        rtt: Duration::ZERO,
+    };
+}
+
+struct PingSocket {
+    open: bool,
+    ident: u16,
+    replies: [PingReply; REPLY_QUEUE_LEN],
+    head: usize,
+    len: usize,
+}
+
+impl PingSocket {
+    const CLOSED: PingSocket = PingSocket {
+        open: false,
+        ident: 0,
+        replies: [PingReply::EMPTY; REPLY_QUEUE_LEN],
+        head: 0,
+        len: 0,
+    };
+}
+
+pub struct Icmp {
+    sockets: [PingSocket; MAX_PING_SOCKETS],
+    next_ident: u16,
+}
+
+impl Icmp {
+    pub(crate) const fn new() -> Self {
+        Self {
+            sockets: [PingSocket::CLOSED; MAX_PING_SOCKETS],
+            next_ident: 1,
+        }
+    }
+
+    fn socket(&mut self, handle: PingHandle) -> Result<&mut PingSocket, NetError> {
+        match self.sockets.get_mut(handle.0) {
+            Some(socket) if socket.open => Ok(socket),
+            _ => Err(NetError::NotConnected),
+        }
+    }
+
+    pub(crate) fn open(&mut self) -> Result<PingHandle, NetError> {
+        let idx = self
+            .sockets
+            .iter()
+            .position(|socket| !socket.open)
+            .ok_or(NetError::Exhausted)?;
+        let ident = self.next_ident;
+        self.next_ident = self.next_ident.wrapping_add(1).max(1);
+        self.sockets[idx] = PingSocket {
+            open: true,
+            ident,
+            ..PingSocket::CLOSED
+        };
+        Ok(PingHandle(idx))
+    }
+
+    pub(crate) fn close(&mut self, handle: PingHandle) {
+        if let Ok(socket) = self.socket(handle) {
+            socket.open = false;
+        }
+    }
+
+    // Sends an echo request carrying `len` bytes of data, at least enough to
+    // hold the timestamp.
+    pub(crate) fn send(
+        &mut self,
+        ifaces: &mut Interfaces,
+        handle: PingHandle,
+        dst: Ipv4Addr,
+        seq: u16,
+        len: usize,
+    ) -> Result<(), NetError> {
+        let ident = self.socket(handle)?.ident;
+        if len > MAX_PING_DATA {
+            return Err(NetError::TooLarge);
+        }
+        let len = len.max(TIMESTAMP_SIZE);
+        let route = ifaces.route(dst)?;
+        let timestamp = (ifaces.now().as_nanos() as u64).to_be_bytes();
+
+        let mut parts: [&[u8]; MAX_PARTS] = [&[]; MAX_PARTS];
+        let mut count = 2;
+        parts[1] = &timestamp;
+        let mut left = len - TIMESTAMP_SIZE;
+        while left > 0 {
+            let chunk = left.min(PATTERN.len());
+            parts[count] = &PATTERN[..chunk];
+            count += 1;
+            left -= chunk;
+        }
+
+        let mut header = [0; ICMP_HEADER_SIZE];
+        header[0] = TYPE_ECHO_REQUEST;
+        write_u16(&mut header, 4, ident);
+        write_u16(&mut header, 6, seq);
+        let mut sum = Checksum::new();
+        for part in &parts[..count] {
+            sum.add(part);
+        }
+        sum.add(&header);
+        write_u16(&mut header, 2, sum.finish());
+        parts[0] = &header;
+        ifaces.send_ip(&route, route.src, dst, PROTO_ICMP, &parts[..count])
+    }
+
+    pub(crate) fn recv(&mut self, handle: PingHandle) -> Result<PingReply, NetError> {
+        let socket = self.socket(handle)?;
+        if socket.len == 0 {
+            return Err(NetError::WouldBlock);
+        }
+        let reply = socket.replies[socket.head];
+        socket.head = (socket.head + 1) % REPLY_QUEUE_LEN;
+        socket.len -= 1;
+        Ok(reply)
+    }
+
+    pub(crate) fn input(
+        &mut self,
+        ifaces: &mut Interfaces,
+        iface: usize,
+        header: &Ipv4Header,
+        data: &[u8],
+    ) {
+        if data.len() < ICMP_HEADER_SIZE || checksum(data) != 0 {
+            return;
+        }
+        match data[0] {
+            // Requests sent to a broadcast address go unanswered.
+            TYPE_ECHO_REQUEST if !ifaces.is_broadcast(iface, header.dst) => {
+                let Ok(route) = ifaces.route(header.src) else {
+                    return;
+                };
+                let mut reply = [0; ICMP_HEADER_SIZE];
+                reply.copy_from_slice(&data[..ICMP_HEADER_SIZE]);
+                reply[0] = TYPE_ECHO_REPLY;
+                write_u16(&mut reply, 2, 0);
+                let mut sum = Checksum::new();
+                sum.add(&reply);
+                sum.add(&data[ICMP_HEADER_SIZE..]);
+                write_u16(&mut reply, 2, sum.finish());
+                let parts = [&reply[..], &data[ICMP_HEADER_SIZE..]];
+                let _ = ifaces.send_ip(&route, header.dst, header.src, PROTO_ICMP, &parts);
+            }
+            TYPE_ECHO_REPLY => {
+                let ident = read_u16(data, 4);
+                let Some(socket) = self
+                    .sockets
+                    .iter_mut()
+                    .find(|socket| socket.open && socket.ident == ident)
+                else {
+                    return;
+                };
+                if socket.len == REPLY_QUEUE_LEN {
+                    return;
+                }
+                let payload = &data[ICMP_HEADER_SIZE..];
+                let rtt = match payload.get(..TIMESTAMP_SIZE) {
+                    Some(sent) => {
+                        let sent = u64::from_be_bytes(sent.try_into().unwrap());
+                        ifaces.now().saturating_sub(Duration::from_nanos(sent))
+                    }
+                    None => Duration::ZERO,
+                };
+                let idx = (socket.head + socket.len) % REPLY_QUEUE_LEN;
+                socket.replies[idx] = PingReply {
+                    from: header.src,
+                    seq: read_u16(data, 6),
+                    len: payload.len(),
+                    ttl: header.ttl,
+                    rtt,
+                };
+                socket.len += 1;
+            }
+            _ => {}
+        }
+    }
+
+    // Quotes the offending header and the start of its payload back to the
+    // sender. Errors are never sent about broadcasts.
+    pub(crate) fn unreachable(
+        ifaces: &mut Interfaces,
+        iface: usize,
+        header: &Ipv4Header,
+        payload: &[u8],
+        code: u8,
+    ) {
+        if ifaces.is_broadcast(iface, header.dst) || header.src.is_broadcast() {
+            return;
+        }
+        let Ok(route) = ifaces.route(header.src) else {
+            return;
+        };
+        let mut message = [0; ICMP_HEADER_SIZE + IPV4_HEADER_SIZE + QUOTE_SIZE];
+        message[0] = TYPE_UNREACHABLE;
+        message[1] = code;
+        header.write(&mut message[ICMP_HEADER_SIZE..]);
+        let quote = &payload[..payload.len().min(QUOTE_SIZE)];
+        let end = ICMP_HEADER_SIZE + IPV4_HEADER_SIZE + quote.len();
+        message[ICMP_HEADER_SIZE + IPV4_HEADER_SIZE..end].copy_from_slice(quote);
+        let sum = checksum(&message[..end]);
+        write_u16(&mut message, 2, sum);
+        let _ = ifaces.send_ip(
+            &route,
+            header.dst,
+            header.src,
+            PROTO_ICMP,
+            &[&message[..end]],
+        );
+    }
+}
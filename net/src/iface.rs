@@ -0,0 +1,351 @@
+use core::time::Duration;
+
+use crate::{
+    arp::{ArpCache, ArpPacket, ARP_PACKET_SIZE, OP_REPLY, OP_REQUEST},
+    ethernet::{EthernetHeader, ETHERTYPE_ARP, ETHERTYPE_IPV4, ETH_HEADER_SIZE, ETH_MTU},
+    gather,
+    ipv4::{Ipv4Header, DEFAULT_TTL, IPV4_HEADER_SIZE},
+    Clock, Device, Ipv4Addr, Ipv4Cidr, MacAddress, NetError, MAX_FRAME_SIZE,
+};
+
+pub const MAX_INTERFACES: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Iface(pub(crate) usize);
+
+impl Iface {
+    #[inline]
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+struct Interface<'a> {
+    device: &'a dyn Device,
+    cidr: Option<Ipv4Cidr>,
+    gateway: Option<Ipv4Addr>,
+}
+
+impl Interface<'_> {
+    #[inline]
+    fn is_loopback(&self) -> bool {
+        self.cidr.is_some_and(|cidr| cidr.addr.is_loopback())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Route {
+    pub iface: usize,
+    // The address packets on this route are sent from.
+    pub src: Ipv4Addr,
+    pub next_hop: Ipv4Addr,
+}
+
+// The link and network layers below the transports: addressing, routing,
+// neighbour resolution and fragmenting outgoing datagrams.
+pub struct Interfaces<'a> {
+    ifaces: [Option<Interface<'a>>; MAX_INTERFACES],
+    arp: ArpCache,
+    ident: u16,
+    clock: Clock,
+}
+
+impl<'a> Interfaces<'a> {
+    pub(crate) const fn new(clock: Clock) -> Self {
+        Self {
+            ifaces: [const { None }; MAX_INTERFACES],
+            arp: ArpCache::new(),
+            ident: 0,
+            clock,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn now(&self) -> Duration {
+        (self.clock)()
+    }
+
+    pub(crate) fn add(&mut self, device: &'a dyn Device) -> Result<Iface, NetError> {
+        let idx = self
+            .ifaces
+            .iter()
+            .position(Option::is_none)
+            .ok_or(NetError::Exhausted)?;
+        self.ifaces[idx] = Some(Interface {
+            device,
+            cidr: None,
+            gateway: None,
+        });
+        Ok(Iface(idx))
+    }
+
+    fn get(&self, iface: usize) -> &Interface<'a> {
+        self.ifaces[iface].as_ref().unwrap()
+    }
+
+    fn get_mut(&mut self, iface: Iface) -> &mut Interface<'a> {
+        self.ifaces[iface.0].as_mut().unwrap()
+    }
+
+    pub(crate) fn set_address(&mut self, iface: Iface, cidr: Option<Ipv4Cidr>) {
+        self.get_mut(iface).cidr = cidr;
+    }
+
+    pub(crate) fn set_gateway(&mut self, iface: Iface, gateway: Option<Ipv4Addr>) {
+        self.get_mut(iface).gateway = gateway;
+    }
+
+    pub(crate) fn address(&self, iface: Iface) -> Option<Ipv4Cidr> {
+        self.get(iface.0).cidr
+    }
+
+    pub(crate) fn gateway(&self, iface: Iface) -> Option<Ipv4Addr> {
+        self.get(iface.0).gateway
+    }
+
+    pub(crate) fn mtu(&self, iface: usize) -> usize {
+        self.get(iface).device.mtu().min(ETH_MTU)
+    }
+
+    fn configured(&self) -> impl Iterator<Item = (usize, &Interface<'a>, Ipv4Cidr)> {
+        self.ifaces
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, iface)| Some((idx, iface.as_ref()?, iface.as_ref()?.cidr?)))
+    }
+
+    pub(crate) fn is_local(&self, addr: Ipv4Addr) -> bool {
+        self.configured().any(|(_, _, cidr)| cidr.addr == addr)
+    }
+
+    // Whether a datagram arriving on `iface` is meant for this host. Any
+    // local address will do, whichever interface it belongs to.
+    pub(crate) fn accepts(&self, iface: usize, dst: Ipv4Addr) -> bool {
+        if dst.is_broadcast() || self.is_local(dst) {
+            return true;
+        }
+        let iface = self.get(iface);
+        iface.is_loopback() && dst.is_loopback()
+            || iface.cidr.is_some_and(|cidr| cidr.broadcast() == dst)
+    }
+
+    pub(crate) fn is_broadcast(&self, iface: usize, addr: Ipv4Addr) -> bool {
+        addr.is_broadcast()
+            || self
+                .get(iface)
+                .cidr
+                .is_some_and(|cidr| cidr.broadcast() == addr)
+    }
+
+    // Traffic to the host itself goes over the loopback interface, anything
+    // else to the first interface on the destination's network, then to the
+    // first gateway.
+    pub(crate) fn route(&self, dst: Ipv4Addr) -> Result<Route, NetError> {
+        if dst.is_loopback() || self.is_local(dst) {
+            let (iface, _, _) = self
+                .configured()
+                .find(|(_, iface, _)| iface.is_loopback())
+                .ok_or(NetError::Unreachable)?;
+            return Ok(Route {
+                iface,
+                src: dst,
+                next_hop: dst,
+            });
+        }
+        let external = || {
+            self.configured()
+                .filter(|(_, iface, _)| !iface.is_loopback())
+        };
+        let found = if dst.is_broadcast() {
+            external().next().map(|(iface, _, cidr)| (iface, cidr, dst))
+        } else {
+            external()
+                .find(|(_, _, cidr)| cidr.contains(dst))
+                .map(|(iface, _, cidr)| (iface, cidr, dst))
+                .or_else(|| {
+                    external().find_map(|(idx, iface, cidr)| Some((idx, cidr, iface.gateway?)))
+                })
+        };
+        let (iface, cidr, next_hop) = found.ok_or(NetError::Unreachable)?;
+        Ok(Route {
+            iface,
+            src: cidr.addr,
+            next_hop,
+        })
+    }
+
+    // Datagrams larger than the link MTU go out as fragments.
+    pub(crate) fn send_ip(
+        &mut self,
+        route: &Route,
+        src: Ipv4Addr,
+        dst: Ipv4Addr,
+        protocol: u8,
+        parts: &[&[u8]],
+    ) -> Result<(), NetError> {
+        let total = parts.iter().map(|part| part.len()).sum::<usize>();
+        if IPV4_HEADER_SIZE + total > u16::MAX as usize {
+            return Err(NetError::TooLarge);
+        }
+        let mtu = self.mtu(route.iface);
+        let chunk = match IPV4_HEADER_SIZE + total <= mtu {
+            true => total,
+            false => (mtu - IPV4_HEADER_SIZE) & !7,
+        };
+        self.ident = self.ident.wrapping_add(1);
+
+        let mut frame = [0; MAX_FRAME_SIZE];
+        let mut offset = 0;
+        loop {
+            let len = chunk.min(total - offset);
+            let header = Ipv4Header {
+                src,
+                dst,
+                protocol,
+                ttl: DEFAULT_TTL,
+                ident: self.ident,
+                dont_fragment: false,
+                more_fragments: offset + len < total,
+                frag_offset: offset,
+                payload_len: len,
+            };
+            let end = ETH_HEADER_SIZE + IPV4_HEADER_SIZE + len;
+            header.write(&mut frame[ETH_HEADER_SIZE..]);
+            gather(
+                parts,
+                offset,
+                &mut frame[ETH_HEADER_SIZE + IPV4_HEADER_SIZE..end],
+            );
+            self.transmit(
+                route.iface,
+                route.next_hop,
+                ETHERTYPE_IPV4,
+                &mut frame[..end],
+            )?;
+            offset += len;
+            if offset == total {
+                return Ok(());
+            }
+        }
+    }
+
+    // Loopback needs no neighbour resolution, frames just go to the device's
+    // own address.
+    fn transmit(
+        &mut self,
+        iface: usize,
+        next_hop: Ipv4Addr,
+        ethertype: u16,
+        frame: &mut [u8],
+    ) -> Result<(), NetError> {
+        let now = self.now();
+        let interface = self.get(iface);
+        let device = interface.device;
+        let dst = if interface.is_loopback() {
+            device.mac()
+        } else if self.is_broadcast(iface, next_hop) {
+            MacAddress::BROADCAST
+        } else if let Some(mac) = self.arp.lookup(iface, next_hop, now) {
+            mac
+        } else {
+            let header = EthernetHeader {
+                dst: MacAddress::ZERO,
+                src: device.mac(),
+                ethertype,
+            };
+            header.write(frame);
+            self.arp.enqueue(iface, next_hop, frame);
+            if self.arp.resolve(iface, next_hop, now) {
+                self.request(iface, next_hop);
+            }
+            return Ok(());
+        };
+        let header = EthernetHeader {
+            dst,
+            src: device.mac(),
+            ethertype,
+        };
+        header.write(frame);
+        device.transmit(frame)
+    }
+
+    fn send_arp(&self, iface: usize, dst: MacAddress, packet: &ArpPacket) {
+        let mut frame = [0; ETH_HEADER_SIZE + ARP_PACKET_SIZE];
+        let header = EthernetHeader {
+            dst,
+            src: self.get(iface).device.mac(),
+            ethertype: ETHERTYPE_ARP,
+        };
+        header.write(&mut frame);
+        packet.write(&mut frame[ETH_HEADER_SIZE..]);
+        let _ = self.get(iface).device.transmit(&frame);
+    }
+
+    fn request(&self, iface: usize, addr: Ipv4Addr) {
+        let interface = self.get(iface);
+        let Some(cidr) = interface.cidr else {
+            return;
+        };
+        let packet = ArpPacket {
+            op: OP_REQUEST,
+            sender_mac: interface.device.mac(),
+            sender_ip: cidr.addr,
+            target_mac: MacAddress::ZERO,
+            target_ip: addr,
+        };
+        self.send_arp(iface, MacAddress::BROADCAST, &packet);
+    }
+
+    pub(crate) fn arp_input(&mut self, iface: usize, data: &[u8]) {
+        let Some(packet) = ArpPacket::parse(data) else {
+            return;
+        };
+        let interface = self.get(iface);
+        let Some(cidr) = interface.cidr else {
+            return;
+        };
+        let mac = interface.device.mac();
+        let device = interface.device;
+        let for_us = packet.target_ip == cidr.addr;
+
+        // Probes from hosts checking an address is free carry no sender
+        // address worth remembering.
+        if !packet.sender_ip.is_unspecified() {
+            let now = self.now();
+            if self
+                .arp
+                .update(iface, packet.sender_ip, packet.sender_mac, now, for_us)
+            {
+                while let Some(frame) = self.arp.dequeue(iface, packet.sender_ip) {
+                    frame[..6].copy_from_slice(&packet.sender_mac.0);
+                    let _ = device.transmit(frame);
+                }
+            }
+        }
+
+        if for_us && packet.op == OP_REQUEST {
+            let reply = ArpPacket {
+                op: OP_REPLY,
+                sender_mac: mac,
+                sender_ip: cidr.addr,
+                target_mac: packet.sender_mac,
+                target_ip: packet.sender_ip,
+            };
+            self.send_arp(iface, packet.sender_mac, &reply);
+        }
+    }
+
+    pub(crate) fn poll(&mut self, now: Duration) {
+        while let Some((iface, addr)) = self.arp.retry(now) {
+            self.request(iface, addr);
+        }
+    }
+
+    pub(crate) fn next_deadline(&self) -> Option<Duration> {
+        self.arp.next_deadline()
+    }
+
+    pub(crate) fn mac(&self, iface: usize) -> MacAddress {
+        self.get(iface).device.mac()
+    }
+}
@@ -0,0 +1,347 @@
+use core::{fmt, str::FromStr, time::Duration};
+
+use crate::{checksum, read_u16, write_u16};
+
+pub const IPV4_HEADER_SIZE: usize = 20;
+pub const DEFAULT_TTL: u8 = 64;
+
+pub const PROTO_ICMP: u8 = 1;
+pub const PROTO_TCP: u8 = 6;
+pub const PROTO_UDP: u8 = 17;
+
+// Largest payload put back together from fragments, bigger datagrams are
+// dropped.
+pub const MAX_DATAGRAM: usize = 16384;
+
+const FLAG_MF: u16 = 1 << 13;
+const FLAG_DF: u16 = 1 << 14;
+const OFFSET_MASK: u16 = 0x1fff;
+
+const REASSEMBLY_SLOTS: usize = 4;
+const REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(30);
+const BLOCK_SIZE: usize = 8;
+const BLOCKS: usize = MAX_DATAGRAM / BLOCK_SIZE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Ipv4Addr(pub [u8; 4]);
+
+impl Ipv4Addr {
+    pub const UNSPECIFIED: Ipv4Addr = Ipv4Addr([0; 4]);
+    pub const BROADCAST: Ipv4Addr = Ipv4Addr([255; 4]);
+    pub const LOCALHOST: Ipv4Addr = Ipv4Addr([127, 0, 0, 1]);
+
+    #[inline]
+    pub const fn new(a: u8, b: u8, c: u8, d: u8) -> Self {
+        Self([a, b, c, d])
+    }
+
+    #[inline]
+    pub const fn from_u32(value: u32) -> Self {
+        Self(value.to_be_bytes())
+    }
+
+    #[inline]
+    pub const fn to_u32(self) -> u32 {
+        u32::from_be_bytes(self.0)
+    }
+
+    #[inline]
+    pub fn is_unspecified(&self) -> bool {
+        *self == Self::UNSPECIFIED
+    }
+
+    #[inline]
+    pub fn is_broadcast(&self) -> bool {
+        *self == Self::BROADCAST
+    }
+
+    #[inline]
+    pub fn is_multicast(&self) -> bool {
+        self.0[0] & 0xf0 == 224
+    }
+
+    #[inline]
+    pub fn is_loopback(&self) -> bool {
+        self.0[0] == 127
+    }
+}
+
+impl fmt::Display for Ipv4Addr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b, c, d] = self.0;
+        write!(f, "{}.{}.{}.{}", a, b, c, d)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddrParseError;
+
+impl fmt::Display for AddrParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid IPv4 address")
+    }
+}
+
+impl FromStr for Ipv4Addr {
+    type Err = AddrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut addr = [0; 4];
+        let mut parts = s.split('.');
+        for byte in &mut addr {
+            let part = parts.next().ok_or(AddrParseError)?;
+            if part.is_empty() || part.len() > 3 || !part.bytes().all(|c| c.is_ascii_digit()) {
+                return Err(AddrParseError);
+            }
+            *byte = part.parse().map_err(|_| AddrParseError)?;
+        }
+        match parts.next() {
+            Some(_) => Err(AddrParseError),
+            None => Ok(Self(addr)),
+        }
+    }
+}
+
+// An address together with the length of its network prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ipv4Cidr {
+    pub addr: Ipv4Addr,
+    pub prefix_len: u8,
+}
+
+impl Ipv4Cidr {
+    pub const fn new(addr: Ipv4Addr, prefix_len: u8) -> Self {
+        Self { addr, prefix_len }
+    }
+
+    pub fn netmask(&self) -> Ipv4Addr {
+        match self.prefix_len {
+            0 => Ipv4Addr::UNSPECIFIED,
+            len => Ipv4Addr::from_u32(u32::MAX << (32 - len.min(32))),
+        }
+    }
+
+    pub fn network(&self) -> Ipv4Addr {
+        Ipv4Addr::from_u32(self.addr.to_u32() & self.netmask().to_u32())
+    }
+
+    pub fn broadcast(&self) -> Ipv4Addr {
+        Ipv4Addr::from_u32(self.addr.to_u32() | !self.netmask().to_u32())
+    }
+
+    pub fn contains(&self, addr: Ipv4Addr) -> bool {
+        let mask = self.netmask().to_u32();
+        addr.to_u32() & mask == self.addr.to_u32() & mask
+    }
+}
+
+impl fmt::Display for Ipv4Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+impl FromStr for Ipv4Cidr {
+    type Err = AddrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, len) = s.split_once('/').ok_or(AddrParseError)?;
+        let prefix_len = len.parse().map_err(|_| AddrParseError)?;
+        if prefix_len > 32 {
+            return Err(AddrParseError);
+        }
+        Ok(Self::new(addr.parse()?, prefix_len))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ipv4Header {
+    pub src: Ipv4Addr,
+    pub dst: Ipv4Addr,
+    pub protocol: u8,
+    pub ttl: u8,
+    pub ident: u16,
+    pub dont_fragment: bool,
+    pub more_fragments: bool,
+    // In bytes.
+    pub frag_offset: usize,
+    pub payload_len: usize,
+}
+
+impl Ipv4Header {
+    // Options are skipped, as is anything past the total length such as
+    // Ethernet padding.
+    pub fn parse(packet: &[u8]) -> Option<(Self, &[u8])> {
+        if packet.len() < IPV4_HEADER_SIZE || packet[0] >> 4 != 4 {
+            return None;
+        }
+        let header_len = (packet[0] & 0xf) as usize * 4;
+        let total_len = read_u16(packet, 2) as usize;
+        if header_len < IPV4_HEADER_SIZE || total_len < header_len || total_len > packet.len() {
+            return None;
+        }
+        if checksum(&packet[..header_len]) != 0 {
+            return None;
+        }
+        let flags = read_u16(packet, 6);
+        let header = Self {
+            src: Ipv4Addr(packet[12..16].try_into().unwrap()),
+            dst: Ipv4Addr(packet[16..20].try_into().unwrap()),
+            protocol: packet[9],
+            ttl: packet[8],
+            ident: read_u16(packet, 4),
+            dont_fragment: flags & FLAG_DF != 0,
+            more_fragments: flags & FLAG_MF != 0,
+            frag_offset: (flags & OFFSET_MASK) as usize * 8,
+            payload_len: total_len - header_len,
+        };
+        Some((header, &packet[header_len..total_len]))
+    }
+
+    #[inline]
+    pub fn is_fragment(&self) -> bool {
+        self.more_fragments || self.frag_offset != 0
+    }
+
+    // Writes a header without options.
+    pub fn write(&self, buf: &mut [u8]) {
+        let mut flags = (self.frag_offset / 8) as u16 & OFFSET_MASK;
+        if self.dont_fragment {
+            flags |= FLAG_DF;
+        }
+        if self.more_fragments {
+            flags |= FLAG_MF;
+        }
+        buf[0] = 0x45;
+        buf[1] = 0;
+        write_u16(buf, 2, (IPV4_HEADER_SIZE + self.payload_len) as u16);
+        write_u16(buf, 4, self.ident);
+        write_u16(buf, 6, flags);
+        buf[8] = self.ttl;
+        buf[9] = self.protocol;
+        write_u16(buf, 10, 0);
+        buf[12..16].copy_from_slice(&self.src.0);
+        buf[16..20].copy_from_slice(&self.dst.0);
+        let sum = checksum(&buf[..IPV4_HEADER_SIZE]);
+        write_u16(buf, 10, sum);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Key {
+    src: Ipv4Addr,
+    dst: Ipv4Addr,
+    protocol: u8,
+    ident: u16,
+}
+
+struct Fragments {
+    key: Option<Key>,
+    // Known once the last fragment has arrived.
+    len: Option<usize>,
+    received: [u64; BLOCKS / 64],
+    expires: Duration,
+    data: [u8; MAX_DATAGRAM],
+}
+
+impl Fragments {
+    const EMPTY: Fragments = Fragments {
+        key: None,
+        len: None,
+        received: [0; BLOCKS / 64],
+        expires: Duration::ZERO,
+        data: [0; MAX_DATAGRAM],
+    };
+
+    fn is_complete(&self) -> bool {
+        let Some(len) = self.len else {
+            return false;
+        };
+        let blocks = len.div_ceil(BLOCK_SIZE);
+        let full = blocks / 64;
+        self.received[..full].iter().all(|&word| word == u64::MAX)
+            && (blocks % 64 == 0 || self.received[full] == (1 << (blocks % 64)) - 1)
+    }
+}
+
+pub struct Reassembly {
+    slots: [Fragments; REASSEMBLY_SLOTS],
+}
+
+impl Reassembly {
+    pub const fn new() -> Self {
+        Self {
+            slots: [Fragments::EMPTY; REASSEMBLY_SLOTS],
+        }
+    }
+
+    // Returns the whole payload once the last missing piece arrives. When all
+    // slots are busy the datagram closest to timing out is given up on.
+    pub fn insert(&mut self, header: &Ipv4Header, payload: &[u8], now: Duration) -> Option<&[u8]> {
+        let key = Key {
+            src: header.src,
+            dst: header.dst,
+            protocol: header.protocol,
+            ident: header.ident,
+        };
+        let idx = match self.slots.iter().position(|slot| slot.key == Some(key)) {
+            Some(idx) => idx,
+            None => {
+                let idx = self
+                    .slots
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, slot)| (slot.key.is_some(), slot.expires))
+                    .map(|(idx, _)| idx)
+                    .unwrap();
+                let slot = &mut self.slots[idx];
+                slot.key = Some(key);
+                slot.len = None;
+                slot.received = [0; BLOCKS / 64];
+                slot.expires = now + REASSEMBLY_TIMEOUT;
+                idx
+            }
+        };
+
+        let slot = &mut self.slots[idx];
+        let start = header.frag_offset;
+        let end = start + payload.len();
+        // All but the last fragment carry a multiple of eight bytes.
+        let misaligned = header.more_fragments && payload.len() & (BLOCK_SIZE - 1) != 0;
+        let conflicting = match slot.len {
+            Some(len) => end > len || (!header.more_fragments && end != len),
+            None => false,
+        };
+        if end > MAX_DATAGRAM || misaligned || conflicting {
+            slot.key = None;
+            return None;
+        }
+        if !header.more_fragments {
+            slot.len = Some(end);
+        }
+        slot.data[start..end].copy_from_slice(payload);
+        for block in start / BLOCK_SIZE..end.div_ceil(BLOCK_SIZE) {
+            slot.received[block / 64] |= 1 << (block % 64);
+        }
+
+        if !slot.is_complete() {
+            return None;
+        }
+        slot.key = None;
+        Some(&slot.data[..slot.len.unwrap()])
+    }
+
+    pub fn expire(&mut self, now: Duration) {
+        for slot in &mut self.slots {
+            if slot.key.is_some() && slot.expires <= now {
+                slot.key = None;
+            }
+        }
+    }
+}
+
+impl Default for Reassembly {
+    fn default() -> Self {
+        Self::new()
+    }
+}
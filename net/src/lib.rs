@@ -0,0 +1,185 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use core::{fmt, time::Duration};
+
+pub use ethernet::{MacAddress, ETH_HEADER_SIZE, ETH_MTU, MAX_FRAME_SIZE, MIN_FRAME_SIZE};
+pub use icmp::{PingHandle, PingReply};
+pub use iface::Iface;
+pub use ipv4::{Ipv4Addr, Ipv4Cidr};
+pub use stack::Stack;
+pub use tcp::{TcpHandle, TcpState};
+pub use udp::UdpHandle;
+
+pub mod arp;
+pub mod ethernet;
+pub mod icmp;
+pub mod iface;
+pub mod ipv4;
+mod ring;
+mod stack;
+pub mod tcp;
+pub mod udp;
+
+pub type Clock = fn() -> Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetError {
+    WouldBlock,
+    TooLarge,
+    Unreachable,
+    AddrInUse,
+    NotConnected,
+    Refused,
+    Reset,
+    TimedOut,
+    Exhausted,
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WouldBlock => f.write_str("operation would block"),
+            Self::TooLarge => f.write_str("message too long"),
+            Self::Unreachable => f.write_str("network is unreachable"),
+            Self::AddrInUse => f.write_str("address already in use"),
+            Self::NotConnected => f.write_str("socket is not connected"),
+            Self::Refused => f.write_str("connection refused"),
+            Self::Reset => f.write_str("connection reset by peer"),
+            Self::TimedOut => f.write_str("connection timed out"),
+            Self::Exhausted => f.write_str("no buffer space available"),
+        }
+    }
+}
+
+// A link carrying whole Ethernet frames without the frame check sequence.
+pub trait Device: Sync {
+    fn mac(&self) -> MacAddress;
+    fn transmit(&self, frame: &[u8]) -> Result<(), NetError>;
+
+    fn mtu(&self) -> usize {
+        ETH_MTU
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Endpoint {
+    pub addr: Ipv4Addr,
+    pub port: u16,
+}
+
+impl Endpoint {
+    pub const fn new(addr: Ipv4Addr, port: u16) -> Self {
+        Self { addr, port }
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.addr, self.port)
+    }
+}
+
+// Internet checksum, summed in pieces that need not be of even length.
+#[derive(Clone, Copy)]
+pub struct Checksum {
+    sum: u64,
+    odd: bool,
+}
+
+impl Checksum {
+    pub const fn new() -> Self {
+        Self { sum: 0, odd: false }
+    }
+
+    pub fn add(&mut self, mut data: &[u8]) {
+        if self.odd {
+            if let Some((&byte, rest)) = data.split_first() {
+                self.sum += byte as u64;
+                self.odd = false;
+                data = rest;
+            }
+        }
+        for word in data.chunks(2) {
+            match *word {
+                [high, low] => self.sum += u16::from_be_bytes([high, low]) as u64,
+                [high] => {
+                    self.sum += (high as u64) << 8;
+                    self.odd = true;
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    // The header TCP and UDP checksums cover along with the segment.
+    pub fn add_pseudo_header(&mut self, src: Ipv4Addr, dst: Ipv4Addr, protocol: u8, len: usize) {
+        self.add(&src.0);
+        self.add(&dst.0);
+        self.add(&[0, protocol]);
+        self.add(&(len as u16).to_be_bytes());
+    }
+
+    pub fn finish(self) -> u16 {
+        let mut sum = self.sum;
+        while sum >> 16 != 0 {
+            sum = (sum & 0xffff) + (sum >> 16);
+        }
+        !(sum as u16)
+    }
+}
+
+impl Default for Checksum {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn checksum(data: &[u8]) -> u16 {
+    let mut sum = Checksum::new();
+    sum.add(data);
+    sum.finish()
+}
+
+#[inline]
+pub(crate) fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([buf[offset], buf[offset + 1]])
+}
+
+#[inline]
+pub(crate) fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([
+        buf[offset],
+        buf[offset + 1],
+        buf[offset + 2],
+        buf[offset + 3],
+    ])
+}
+
+#[inline]
+pub(crate) fn write_u16(buf: &mut [u8], offset: usize, value: u16) {
+    buf[offset..offset + 2].copy_from_slice(&value.to_be_bytes());
+}
+
+#[inline]
+pub(crate) fn write_u32(buf: &mut [u8], offset: usize, value: u32) {
+    buf[offset..offset + 4].copy_from_slice(&value.to_be_bytes());
+}
+
+// Copies `out.len()` bytes starting `offset` bytes into the concatenation
+// of `parts`.
+pub(crate) fn gather(parts: &[&[u8]], mut offset: usize, out: &mut [u8]) {
+    let mut done = 0;
+    for part in parts {
+        if done == out.len() {
+            break;
+        }
+        if offset >= part.len() {
+            offset -= part.len();
+            continue;
+        }
+        let len = (part.len() - offset).min(out.len() - done);
+        out[done..done + len].copy_from_slice(&part[offset..offset + len]);
+        done += len;
+        offset = 0;
+    }
+}
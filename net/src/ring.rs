@@ -0,0 +1,77 @@
+pub struct Ring<const N: usize> {
+    buf: [u8; N],
+    head: usize,
+    len: usize,
+}
+
+impl<const N: usize> Ring<N> {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline]
+    pub fn free(&self) -> usize {
+        N - self.len
+    }
+
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+
+    // Appends as much of `data` as fits and returns how much that was.
+    pub fn push(&mut self, data: &[u8]) -> usize {
+        let len = data.len().min(self.free());
+        let tail = (self.head + self.len) % N;
+        let first = len.min(N - tail);
+        self.buf[tail..tail + first].copy_from_slice(&data[..first]);
+        self.buf[..len - first].copy_from_slice(&data[first..len]);
+        self.len += len;
+        len
+    }
+
+    // The stored bytes from `offset` on, in at most two pieces because of
+    // wrap around.
+    pub fn slices(&self, offset: usize, len: usize) -> (&[u8], &[u8]) {
+        let len = len.min(self.len.saturating_sub(offset));
+        let start = (self.head + offset) % N;
+        let first = len.min(N - start);
+        (&self.buf[start..start + first], &self.buf[..len - first])
+    }
+
+    pub fn peek(&self, offset: usize, out: &mut [u8]) -> usize {
+        let (first, second) = self.slices(offset, out.len());
+        out[..first.len()].copy_from_slice(first);
+        out[first.len()..first.len() + second.len()].copy_from_slice(second);
+        first.len() + second.len()
+    }
+
+    pub fn consume(&mut self, len: usize) {
+        let len = len.min(self.len);
+        self.head = (self.head + len) % N;
+        self.len -= len;
+        if self.len == 0 {
+            self.head = 0;
+        }
+    }
+
+    pub fn pop(&mut self, out: &mut [u8]) -> usize {
+        let len = self.peek(0, out);
+        self.consume(len);
+        len
+    }
+}
@@ -0,0 +1,235 @@
+use core::time::Duration;
+
+use crate::{
+    ethernet::{EthernetHeader, ETHERTYPE_ARP, ETHERTYPE_IPV4},
+    icmp::{Icmp, CODE_PORT_UNREACHABLE, CODE_PROTOCOL_UNREACHABLE},
+    iface::Interfaces,
+    ipv4::{Ipv4Header, Reassembly, PROTO_ICMP, PROTO_TCP, PROTO_UDP},
+    tcp::Tcp,
+    udp::Udp,
+    Clock, Device, Endpoint, Iface, Ipv4Addr, Ipv4Cidr, NetError, PingHandle, PingReply, TcpHandle,
+    TcpState, UdpHandle,
+};
+
+// Frames are fed in with `input` and `poll` keeps the timers going, nothing
+// runs on its own.
+pub struct Stack<'a> {
+    ifaces: Interfaces<'a>,
+    reassembly: Reassembly,
+    icmp: Icmp,
+    udp: Udp,
+    tcp: Tcp,
+}
+
+impl<'a> Stack<'a> {
+    pub const fn new(clock: Clock) -> Self {
+        Self {
+            ifaces: Interfaces::new(clock),
+            reassembly: Reassembly::new(),
+            icmp: Icmp::new(),
+            udp: Udp::new(),
+            tcp: Tcp::new(),
+        }
+    }
+
+    pub fn add_interface(&mut self, device: &'a dyn Device) -> Result<Iface, NetError> {
+        self.ifaces.add(device)
+    }
+
+    pub fn set_address(&mut self, iface: Iface, cidr: Option<Ipv4Cidr>) {
+        self.ifaces.set_address(iface, cidr);
+    }
+
+    pub fn set_gateway(&mut self, iface: Iface, gateway: Option<Ipv4Addr>) {
+        self.ifaces.set_gateway(iface, gateway);
+    }
+
+    pub fn address(&self, iface: Iface) -> Option<Ipv4Cidr> {
+        self.ifaces.address(iface)
+    }
+
+    pub fn gateway(&self, iface: Iface) -> Option<Ipv4Addr> {
+        self.ifaces.gateway(iface)
+    }
+
+    pub fn input(&mut self, iface: Iface, frame: &[u8]) {
+        let Some((eth, payload)) = EthernetHeader::parse(frame) else {
+            return;
+        };
+        if eth.dst != self.ifaces.mac(iface.0) && !eth.dst.is_broadcast() {
+            return;
+        }
+        match eth.ethertype {
+            ETHERTYPE_ARP => self.ifaces.arp_input(iface.0, payload),
+            ETHERTYPE_IPV4 => self.ip_input(iface.0, payload),
+            _ => {}
+        }
+    }
+
+    fn ip_input(&mut self, iface: usize, packet: &[u8]) {
+        let Some((header, payload)) = Ipv4Header::parse(packet) else {
+            return;
+        };
+        if !self.ifaces.accepts(iface, header.dst) {
+            return;
+        }
+        if !header.is_fragment() {
+            self.deliver(iface, &header, payload);
+            return;
+        }
+
+        // The reassembled payload stays in its slot while the transports
+        // look at it, hence the split borrow.
+        let Self {
+            ifaces,
+            reassembly,
+            icmp,
+            udp,
+            tcp,
+        } = self;
+        let now = ifaces.now();
+        let Some(payload) = reassembly.insert(&header, payload, now) else {
+            return;
+        };
+        let header = Ipv4Header {
+            more_fragments: false,
+            frag_offset: 0,
+            payload_len: payload.len(),
+            ..header
+        };
+        Self::dispatch(ifaces, icmp, udp, tcp, iface, &header, payload);
+    }
+
+    fn deliver(&mut self, iface: usize, header: &Ipv4Header, payload: &[u8]) {
+        Self::dispatch(
+            &mut self.ifaces,
+            &mut self.icmp,
+            &mut self.udp,
+            &mut self.tcp,
+            iface,
+            header,
+            payload,
+        );
+    }
+
+    fn dispatch(
+        ifaces: &mut Interfaces,
+        icmp: &mut Icmp,
+        udp: &mut Udp,
+        tcp: &mut Tcp,
+        iface: usize,
+        header: &Ipv4Header,
+        payload: &[u8],
+    ) {
+        match header.protocol {
+            PROTO_ICMP => icmp.input(ifaces, iface, header, payload),
+            PROTO_UDP => {
+                if !udp.input(header, payload) {
+                    Icmp::unreachable(ifaces, iface, header, payload, CODE_PORT_UNREACHABLE);
+                }
+            }
+            PROTO_TCP => tcp.input(ifaces, iface, header, payload),
+            _ => Icmp::unreachable(ifaces, iface, header, payload, CODE_PROTOCOL_UNREACHABLE),
+        }
+    }
+
+    // Runs whatever timers are due and returns when it wants to be called
+    // again, if at all.
+    pub fn poll(&mut self) -> Option<Duration> {
+        let now = self.ifaces.now();
+        self.ifaces.poll(now);
+        self.reassembly.expire(now);
+        self.tcp.poll(&mut self.ifaces, now);
+        match (self.ifaces.next_deadline(), self.tcp.next_deadline()) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    pub fn ping_open(&mut self) -> Result<PingHandle, NetError> {
+        self.icmp.open()
+    }
+
+    pub fn ping_send(
+        &mut self,
+        handle: PingHandle,
+        dst: Ipv4Addr,
+        seq: u16,
+        len: usize,
+    ) -> Result<(), NetError> {
+        self.icmp.send(&mut self.ifaces, handle, dst, seq, len)
+    }
+
+    pub fn ping_recv(&mut self, handle: PingHandle) -> Result<PingReply, NetError> {
+        self.icmp.recv(handle)
+    }
+
+    pub fn ping_close(&mut self, handle: PingHandle) {
+        self.icmp.close(handle);
+    }
+
+    pub fn udp_bind(&mut self, local: Endpoint) -> Result<UdpHandle, NetError> {
+        self.udp.bind(local)
+    }
+
+    pub fn udp_local(&mut self, handle: UdpHandle) -> Result<Endpoint, NetError> {
+        self.udp.local(handle)
+    }
+
+    pub fn udp_send_to(
+        &mut self,
+        handle: UdpHandle,
+        remote: Endpoint,
+        data: &[u8],
+    ) -> Result<(), NetError> {
+        self.udp.send_to(&mut self.ifaces, handle, remote, data)
+    }
+
+    pub fn udp_recv_from(
+        &mut self,
+        handle: UdpHandle,
+        buf: &mut [u8],
+    ) -> Result<(usize, Endpoint), NetError> {
+        self.udp.recv_from(handle, buf)
+    }
+
+    pub fn udp_close(&mut self, handle: UdpHandle) {
+        self.udp.close(handle);
+    }
+
+    pub fn tcp_listen(&mut self, local: Endpoint, backlog: usize) -> Result<TcpHandle, NetError> {
+        self.tcp.listen(local, backlog)
+    }
+
+    pub fn tcp_connect(&mut self, remote: Endpoint) -> Result<TcpHandle, NetError> {
+        self.tcp.connect(&mut self.ifaces, remote)
+    }
+
+    pub fn tcp_accept(&mut self, handle: TcpHandle) -> Result<TcpHandle, NetError> {
+        self.tcp.accept(handle)
+    }
+
+    pub fn tcp_send(&mut self, handle: TcpHandle, data: &[u8]) -> Result<usize, NetError> {
+        self.tcp.send(&mut self.ifaces, handle, data)
+    }
+
+    pub fn tcp_recv(&mut self, handle: TcpHandle, buf: &mut [u8]) -> Result<usize, NetError> {
+        self.tcp.recv(&mut self.ifaces, handle, buf)
+    }
+
+    pub fn tcp_close(&mut self, handle: TcpHandle) {
+        self.tcp.close(&mut self.ifaces, handle);
+    }
+
+    pub fn tcp_abort(&mut self, handle: TcpHandle) {
+        self.tcp.abort(&mut self.ifaces, handle);
+    }
+
+    pub fn tcp_state(&mut self, handle: TcpHandle) -> Result<TcpState, NetError> {
+        self.tcp.state(handle)
+    }
+
+    pub fn tcp_endpoints(&mut self, handle: TcpHandle) -> Result<(Endpoint, Endpoint), NetError> {
+        self.tcp.endpoints(handle)
+    }
+}
@@ -0,0 +1,1103 @@
+use core::{fmt, time::Duration};
+
+use crate::{
+    iface::Interfaces,
+    ipv4::{Ipv4Header, IPV4_HEADER_SIZE, PROTO_TCP},
+    read_u16, read_u32,
+    ring::Ring,
+    udp::EPHEMERAL_PORTS,
+    write_u16, write_u32, Checksum, Endpoint, Ipv4Addr, NetError,
+};
+
+pub const TCP_HEADER_SIZE: usize = 20;
+
+const MAX_TCP_SOCKETS: usize = 16;
+const BUFFER_SIZE: usize = 4096;
+const MAX_OPTIONS_SIZE: usize = 40;
+
+const FLAG_FIN: u8 = 1 << 0;
+const FLAG_SYN: u8 = 1 << 1;
+const FLAG_RST: u8 = 1 << 2;
+const FLAG_PSH: u8 = 1 << 3;
+const FLAG_ACK: u8 = 1 << 4;
+
+const OPTION_END: u8 = 0;
+const OPTION_NOP: u8 = 1;
+const OPTION_MSS: u8 = 2;
+
+// Assumed when the peer doesn't say, RFC 879.
+const DEFAULT_MSS: usize = 536;
+const INITIAL_RTO: Duration = Duration::from_secs(1);
+const MIN_RTO: Duration = Duration::from_millis(200);
+const MAX_RTO: Duration = Duration::from_secs(60);
+const CLOCK_GRANULARITY: Duration = Duration::from_millis(10);
+const MAX_RETRIES: u8 = 8;
+const MAX_SYN_RETRIES: u8 = 5;
+const DUP_ACK_THRESHOLD: u8 = 3;
+// Twice the maximum segment lifetime.
+const TIME_WAIT: Duration = Duration::from_secs(60);
+const MAX_WINDOW: usize = u16::MAX as usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TcpState {
+    Closed,
+    Listen,
+    SynSent,
+    SynReceived,
+    Established,
+    FinWait1,
+    FinWait2,
+    CloseWait,
+    Closing,
+    LastAck,
+    TimeWait,
+}
+
+impl TcpState {
+    // Both sides' initial sequence numbers are known.
+    #[inline]
+    fn is_synchronized(self) -> bool {
+        !matches!(
+            self,
+            Self::Closed | Self::Listen | Self::SynSent | Self::SynReceived
+        )
+    }
+}
+
+impl fmt::Display for TcpState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Closed => "CLOSED",
+            Self::Listen => "LISTEN",
+            Self::SynSent => "SYN-SENT",
+            Self::SynReceived => "SYN-RECEIVED",
+            Self::Established => "ESTABLISHED",
+            Self::FinWait1 => "FIN-WAIT-1",
+            Self::FinWait2 => "FIN-WAIT-2",
+            Self::CloseWait => "CLOSE-WAIT",
+            Self::Closing => "CLOSING",
+            Self::LastAck => "LAST-ACK",
+            Self::TimeWait => "TIME-WAIT",
+        })
+    }
+}
+
+// Sequence numbers compare modulo 2^32.
+#[inline]
+fn seq_lt(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) < 0
+}
+
+#[inline]
+fn seq_le(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) <= 0
+}
+
+#[inline]
+fn in_window(seq: u32, start: u32, len: u32) -> bool {
+    seq.wrapping_sub(start) < len
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TcpHeader {
+    pub src_port: u16,
+    pub dst_port: u16,
+    pub seq: u32,
+    pub ack: u32,
+    pub flags: u8,
+    pub window: u16,
+    pub mss: Option<u16>,
+}
+
+impl TcpHeader {
+    pub fn parse<'a>(ip: &Ipv4Header, data: &'a [u8]) -> Option<(Self, &'a [u8])> {
+        if data.len() < TCP_HEADER_SIZE {
+            return None;
+        }
+        let header_len = (data[12] >> 4) as usize * 4;
+        if header_len < TCP_HEADER_SIZE || header_len > data.len() {
+            return None;
+        }
+        let mut sum = Checksum::new();
+        sum.add_pseudo_header(ip.src, ip.dst, PROTO_TCP, data.len());
+        sum.add(data);
+        if sum.finish() != 0 {
+            return None;
+        }
+
+        let mut mss = None;
+        let mut options = &data[TCP_HEADER_SIZE..header_len];
+        while let [kind, rest @ ..] = options {
+            match *kind {
+                OPTION_END => break,
+                OPTION_NOP => options = rest,
+                kind => {
+                    let len = *rest.first()? as usize;
+                    if len < 2 || len > options.len() {
+                        return None;
+                    }
+                    if kind == OPTION_MSS && len == 4 {
+                        mss = Some(read_u16(options, 2));
+                    }
+                    options = &options[len..];
+                }
+            }
+        }
+
+        let header = Self {
+            src_port: read_u16(data, 0),
+            dst_port: read_u16(data, 2),
+            seq: read_u32(data, 4),
+            ack: read_u32(data, 8),
+            flags: data[13],
+            window: read_u16(data, 14),
+            mss,
+        };
+        Some((header, &data[header_len..]))
+    }
+
+    #[inline]
+    fn has(&self, flag: u8) -> bool {
+        self.flags & flag != 0
+    }
+
+    // The amount of sequence space taken up, SYN and FIN counting as one.
+    #[inline]
+    fn seq_len(&self, data: &[u8]) -> u32 {
+        data.len() as u32 + self.has(FLAG_SYN) as u32 + self.has(FLAG_FIN) as u32
+    }
+
+    // Returns the length of the header, options included.
+    pub fn write(&self, buf: &mut [u8]) -> usize {
+        let len = match self.mss {
+            Some(_) => TCP_HEADER_SIZE + 4,
+            None => TCP_HEADER_SIZE,
+        };
+        write_u16(buf, 0, self.src_port);
+        write_u16(buf, 2, self.dst_port);
+        write_u32(buf, 4, self.seq);
+        write_u32(buf, 8, self.ack);
+        buf[12] = ((len / 4) as u8) << 4;
+        buf[13] = self.flags;
+        write_u16(buf, 14, self.window);
+        write_u16(buf, 16, 0);
+        write_u16(buf, 18, 0);
+        if let Some(mss) = self.mss {
+            buf[20] = OPTION_MSS;
+            buf[21] = 4;
+            write_u16(buf, 22, mss);
+        }
+        len
+    }
+}
+
+fn emit(
+    ifaces: &mut Interfaces,
+    src: Ipv4Addr,
+    dst: Ipv4Addr,
+    header: &TcpHeader,
+    data: (&[u8], &[u8]),
+) {
+    let Ok(route) = ifaces.route(dst) else {
+        return;
+    };
+    let mut buf = [0; TCP_HEADER_SIZE + MAX_OPTIONS_SIZE];
+    let len = header.write(&mut buf);
+    let mut sum = Checksum::new();
+    sum.add_pseudo_header(src, dst, PROTO_TCP, len + data.0.len() + data.1.len());
+    sum.add(&buf[..len]);
+    sum.add(data.0);
+    sum.add(data.1);
+    write_u16(&mut buf, 16, sum.finish());
+    let _ = ifaces.send_ip(&route, src, dst, PROTO_TCP, &[&buf[..len], data.0, data.1]);
+}
+
+// Answers segments that belong to no connection, RFC 793 page 36.
+fn send_reset(ifaces: &mut Interfaces, ip: &Ipv4Header, header: &TcpHeader, data: &[u8]) {
+    if header.has(FLAG_RST) {
+        return;
+    }
+    let (seq, ack, flags) = match header.has(FLAG_ACK) {
+        true => (header.ack, 0, FLAG_RST),
+        false => (
+            0,
+            header.seq.wrapping_add(header.seq_len(data)),
+            FLAG_RST | FLAG_ACK,
+        ),
+    };
+    let reply = TcpHeader {
+        src_port: header.dst_port,
+        dst_port: header.src_port,
+        seq,
+        ack,
+        flags,
+        window: 0,
+        mss: None,
+    };
+    emit(ifaces, ip.dst, ip.src, &reply, (&[], &[]));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TcpHandle(usize);
+
+struct TcpSocket {
+    state: TcpState,
+    // Held by the user until `close`, the socket lives on after that until
+    // the connection is over.
+    owned: bool,
+    // The listener a connection waits on until it is accepted.
+    parent: Option<usize>,
+    backlog: usize,
+    error: Option<NetError>,
+    local: Endpoint,
+    remote: Endpoint,
+
+    iss: u32,
+    snd_una: u32,
+    snd_nxt: u32,
+    // Highest sequence number sent, `snd_nxt` goes back on retransmission.
+    snd_max: u32,
+    snd_wnd: usize,
+    snd_wl1: u32,
+    snd_wl2: u32,
+    mss: usize,
+    rcv_nxt: u32,
+    // The window last advertised.
+    rcv_wnd: usize,
+
+    fin_queued: bool,
+    fin_sent: bool,
+    fin_acked: bool,
+    fin_received: bool,
+    ack_now: bool,
+    // A zero window is probed with a byte past its edge.
+    probe: bool,
+
+    rto: Duration,
+    srtt: Option<Duration>,
+    rttvar: Duration,
+    // The segment being timed and when it went out, RFC 6298.
+    rtt_seq: Option<(u32, Duration)>,
+    // Retransmission and persist timer, or the end of TIME-WAIT.
+    timer: Option<Duration>,
+    retries: u8,
+    dup_acks: u8,
+    cwnd: usize,
+    ssthresh: usize,
+
+    tx: Ring<BUFFER_SIZE>,
+    rx: Ring<BUFFER_SIZE>,
+}
+
+impl TcpSocket {
+    const CLOSED: TcpSocket = TcpSocket {
+        state: TcpState::Closed,
+        owned: false,
+        parent: None,
+        backlog: 0,
+        error: None,
+        local: Endpoint::new(Ipv4Addr::UNSPECIFIED, 0),
+        remote: Endpoint::new(Ipv4Addr::UNSPECIFIED, 0),
+        iss: 0,
+        snd_una: 0,
+        snd_nxt: 0,
+        snd_max: 0,
+        snd_wnd: 0,
+        snd_wl1: 0,
+        snd_wl2: 0,
+        mss: DEFAULT_MSS,
+        rcv_nxt: 0,
+        rcv_wnd: 0,
+        fin_queued: false,
+        fin_sent: false,
+        fin_acked: false,
+        fin_received: false,
+        ack_now: false,
+        probe: false,
+        rto: INITIAL_RTO,
+        srtt: None,
+        rttvar: Duration::ZERO,
+        rtt_seq: None,
+        timer: None,
+        retries: 0,
+        dup_acks: 0,
+        cwnd: 0,
+        ssthresh: MAX_WINDOW,
+        tx: Ring::new(),
+        rx: Ring::new(),
+    };
+
+    fn reset(&mut self) {
+        *self = TcpSocket::CLOSED;
+    }
+
+    #[inline]
+    fn is_free(&self) -> bool {
+        self.state == TcpState::Closed && !self.owned
+    }
+
+    // Starts the handshake from either side, the SYN goes out with the next
+    // call to `output`.
+    fn open(&mut self, local: Endpoint, remote: Endpoint, iss: u32, mss: usize) {
+        self.local = local;
+        self.remote = remote;
+        self.iss = iss;
+        self.snd_una = iss;
+        self.snd_nxt = iss;
+        self.snd_max = iss;
+        self.mss = mss;
+        self.cwnd = (4 * mss).min((2 * mss).max(4380));
+    }
+
+    fn close_with(&mut self, error: Option<NetError>) {
+        self.state = TcpState::Closed;
+        self.error = self.error.or(error);
+        self.timer = None;
+    }
+
+    // Sends `len` bytes from `offset` into the send buffer, every segment
+    // advertising the current receive window.
+    fn send(&mut self, ifaces: &mut Interfaces, seq: u32, flags: u8, offset: usize, len: usize) {
+        self.rcv_wnd = self.rx.free().min(MAX_WINDOW);
+        let header = TcpHeader {
+            src_port: self.local.port,
+            dst_port: self.remote.port,
+            seq,
+            ack: if flags & FLAG_ACK != 0 {
+                self.rcv_nxt
+            } else {
+                0
+            },
+            flags,
+            window: self.rcv_wnd as u16,
+            mss: (flags & FLAG_SYN != 0).then_some(self.mss as u16),
+        };
+        let data = self.tx.slices(offset, len);
+        emit(ifaces, self.local.addr, self.remote.addr, &header, data);
+    }
+
+    fn abort(&mut self, ifaces: &mut Interfaces, error: Option<NetError>) {
+        if self.state.is_synchronized() || self.state == TcpState::SynReceived {
+            self.send(ifaces, self.snd_nxt, FLAG_RST, 0, 0);
+        }
+        self.close_with(error);
+    }
+
+    #[inline]
+    fn in_flight(&self) -> usize {
+        self.snd_nxt.wrapping_sub(self.snd_una) as usize
+    }
+
+    // Sends whatever the windows allow, then a FIN once the user has closed
+    // and all data is out, then a bare ACK if one is owed and nothing else
+    // carried it.
+    fn output(&mut self, ifaces: &mut Interfaces, now: Duration) {
+        match self.state {
+            TcpState::Closed | TcpState::Listen => return,
+            TcpState::SynSent | TcpState::SynReceived => {
+                if self.snd_nxt == self.iss {
+                    let flags = match self.state {
+                        TcpState::SynSent => FLAG_SYN,
+                        _ => FLAG_SYN | FLAG_ACK,
+                    };
+                    self.send(ifaces, self.iss, flags, 0, 0);
+                    self.snd_nxt = self.iss.wrapping_add(1);
+                    self.snd_max = self.snd_nxt;
+                    self.timer.get_or_insert(now + self.rto);
+                }
+                return;
+            }
+            _ => {}
+        }
+
+        let mut sent = false;
+        // The probe byte isn't counted as sent, so it goes again with the
+        // rest once the window opens whether or not the peer kept it.
+        if self.probe && self.snd_wnd == 0 && !self.fin_sent && self.tx.len() > self.in_flight() {
+            let seq = self.snd_nxt;
+            self.send(ifaces, seq, FLAG_ACK, self.in_flight(), 1);
+            if seq_lt(self.snd_max, seq.wrapping_add(1)) {
+                self.snd_max = seq.wrapping_add(1);
+            }
+            sent = true;
+        }
+        self.probe = false;
+        while !self.fin_sent && !self.fin_acked {
+            let offset = self.in_flight();
+            let unsent = self.tx.len() - offset;
+            let window = self.snd_wnd.min(self.cwnd);
+            let len = unsent.min(self.mss).min(window.saturating_sub(offset));
+            if len == 0 {
+                break;
+            }
+            let mut flags = FLAG_ACK;
+            if len == unsent {
+                flags |= FLAG_PSH;
+            }
+            let seq = self.snd_nxt;
+            self.send(ifaces, seq, flags, offset, len);
+            self.snd_nxt = seq.wrapping_add(len as u32);
+            if seq_lt(self.snd_max, self.snd_nxt) {
+                self.snd_max = self.snd_nxt;
+                if self.rtt_seq.is_none() {
+                    self.rtt_seq = Some((self.snd_nxt, now));
+                }
+            }
+            sent = true;
+        }
+
+        let all_sent = self.in_flight() == self.tx.len();
+        if self.fin_queued && !self.fin_sent && !self.fin_acked && all_sent {
+            self.send(ifaces, self.snd_nxt, FLAG_FIN | FLAG_ACK, 0, 0);
+            self.snd_nxt = self.snd_nxt.wrapping_add(1);
+            if seq_lt(self.snd_max, self.snd_nxt) {
+                self.snd_max = self.snd_nxt;
+            }
+            self.fin_sent = true;
+            self.state = match self.state {
+                TcpState::Established => TcpState::FinWait1,
+                TcpState::CloseWait => TcpState::LastAck,
+                state => state,
+            };
+            sent = true;
+        }
+
+        if self.ack_now && !sent {
+            self.send(ifaces, self.snd_nxt, FLAG_ACK, 0, 0);
+        }
+        self.ack_now = false;
+
+        // Nothing is left to send in FIN-WAIT-2, the wait for the peer's FIN
+        // is bounded like TIME-WAIT so that a vanished peer can't hold on to
+        // the socket.
+        let waiting = self.in_flight() > 0 || (!self.tx.is_empty() && self.snd_wnd == 0);
+        match self.state {
+            TcpState::TimeWait => {}
+            TcpState::FinWait2 => {
+                self.timer.get_or_insert(now + TIME_WAIT);
+            }
+            _ if waiting => {
+                self.timer.get_or_insert(now + self.rto);
+            }
+            _ => self.timer = None,
+        }
+    }
+
+    fn sample_rtt(&mut self, rtt: Duration) {
+        match self.srtt {
+            None => {
+                self.srtt = Some(rtt);
+                self.rttvar = rtt / 2;
+            }
+            Some(srtt) => {
+                let delta = srtt.max(rtt) - srtt.min(rtt);
+                self.rttvar = (self.rttvar * 3 + delta) / 4;
+                self.srtt = Some((srtt * 7 + rtt) / 8);
+            }
+        }
+        let rto = self.srtt.unwrap() + (self.rttvar * 4).max(CLOCK_GRANULARITY);
+        self.rto = rto.clamp(MIN_RTO, MAX_RTO);
+    }
+
+    // Takes acknowledged data off the send buffer and opens the congestion
+    // window, by a segment per ACK in slow start and about one per round
+    // trip after that, RFC 5681.
+    fn acknowledge(&mut self, ack: u32, now: Duration) {
+        let acked = ack.wrapping_sub(self.snd_una) as usize;
+        let data = acked.min(self.tx.len());
+        self.tx.consume(data);
+        if acked > data {
+            self.fin_acked = true;
+        }
+        self.snd_una = ack;
+        if seq_lt(self.snd_nxt, ack) {
+            self.snd_nxt = ack;
+        }
+        self.retries = 0;
+        self.dup_acks = 0;
+        if let Some((seq, sent)) = self.rtt_seq {
+            if seq_le(seq, ack) {
+                self.sample_rtt(now.saturating_sub(sent));
+                self.rtt_seq = None;
+            }
+        }
+        self.cwnd += match self.cwnd < self.ssthresh {
+            true => data.min(self.mss),
+            false => (self.mss * self.mss / self.cwnd).max(1),
+        };
+        self.cwnd = self.cwnd.min(MAX_WINDOW);
+        self.timer = None;
+    }
+
+    // Resends the first unacknowledged segment after three duplicate ACKs
+    // and halves the congestion window, RFC 5681 section 3.2.
+    fn fast_retransmit(&mut self, ifaces: &mut Interfaces) {
+        let flight = self.in_flight();
+        self.ssthresh = (flight / 2).max(2 * self.mss);
+        self.cwnd = self.ssthresh;
+        self.rtt_seq = None;
+        let len = self.mss.min(self.tx.len()).min(flight);
+        if len > 0 {
+            self.send(ifaces, self.snd_una, FLAG_ACK, 0, len);
+        }
+    }
+
+    // Retransmits from the first unacknowledged byte with a doubled timeout,
+    // giving up after too many tries.
+    fn timeout(&mut self, ifaces: &mut Interfaces, now: Duration) {
+        self.timer = None;
+        if matches!(self.state, TcpState::TimeWait | TcpState::FinWait2) {
+            self.close_with(None);
+            return;
+        }
+
+        self.retries += 1;
+        let limit = match self.state.is_synchronized() {
+            true => MAX_RETRIES,
+            false => MAX_SYN_RETRIES,
+        };
+        if self.retries > limit {
+            self.abort(ifaces, Some(NetError::TimedOut));
+            return;
+        }
+        self.rto = (self.rto * 2).min(MAX_RTO);
+        self.rtt_seq = None;
+        if self.state.is_synchronized() {
+            self.ssthresh = (self.in_flight() / 2).max(2 * self.mss);
+            self.cwnd = self.mss;
+            self.probe = self.snd_wnd == 0;
+            if self.snd_nxt != self.snd_una && self.fin_sent && !self.fin_acked {
+                self.fin_sent = false;
+            }
+        }
+        self.snd_nxt = self.snd_una;
+        self.output(ifaces, now);
+    }
+
+    fn syn_acked(&mut self, header: &TcpHeader) {
+        self.state = TcpState::Established;
+        self.snd_una = self.iss.wrapping_add(1);
+        self.snd_wnd = header.window as usize;
+        self.snd_wl1 = header.seq;
+        self.snd_wl2 = header.ack;
+        self.retries = 0;
+        self.timer = None;
+    }
+
+    fn syn_sent_input(
+        &mut self,
+        ifaces: &mut Interfaces,
+        ip: &Ipv4Header,
+        header: &TcpHeader,
+        data: &[u8],
+        now: Duration,
+    ) {
+        let ack_ok = header.has(FLAG_ACK);
+        if ack_ok && (seq_le(header.ack, self.iss) || seq_lt(self.snd_max, header.ack)) {
+            send_reset(ifaces, ip, header, data);
+            return;
+        }
+        if header.has(FLAG_RST) {
+            if ack_ok {
+                self.close_with(Some(NetError::Refused));
+            }
+            return;
+        }
+        if !header.has(FLAG_SYN) {
+            return;
+        }
+
+        self.rcv_nxt = header.seq.wrapping_add(1);
+        self.mss = self.mss.min(header.mss.map_or(DEFAULT_MSS, usize::from));
+        if ack_ok {
+            self.syn_acked(header);
+            self.ack_now = true;
+        } else {
+            // Both ends opened at once, our SYN goes again with an ACK.
+            self.state = TcpState::SynReceived;
+            self.snd_nxt = self.iss;
+            self.timer = None;
+        }
+        self.output(ifaces, now);
+    }
+
+    // Segment processing for every state past SYN-SENT, following the steps
+    // of RFC 793 page 69 on.
+    fn input(
+        &mut self,
+        ifaces: &mut Interfaces,
+        ip: &Ipv4Header,
+        header: &TcpHeader,
+        data: &[u8],
+        now: Duration,
+    ) {
+        if self.state == TcpState::SynSent {
+            self.syn_sent_input(ifaces, ip, header, data, now);
+            return;
+        }
+
+        // The peer didn't get our SYN-ACK and sent its SYN again.
+        if self.state == TcpState::SynReceived
+            && header.has(FLAG_SYN)
+            && !header.has(FLAG_ACK)
+            && header.seq.wrapping_add(1) == self.rcv_nxt
+        {
+            self.snd_nxt = self.iss;
+            self.output(ifaces, now);
+            return;
+        }
+
+        let len = header.seq_len(data);
+        let window = self.rx.free() as u32;
+        let acceptable = match (len, window) {
+            (0, 0) => header.seq == self.rcv_nxt,
+            (0, _) => in_window(header.seq, self.rcv_nxt, window),
+            (_, 0) => false,
+            (len, _) => {
+                in_window(header.seq, self.rcv_nxt, window)
+                    || in_window(header.seq.wrapping_add(len - 1), self.rcv_nxt, window)
+            }
+        };
+        if !acceptable {
+            if !header.has(FLAG_RST) {
+                self.ack_now = true;
+                self.output(ifaces, now);
+            }
+            return;
+        }
+
+        if header.has(FLAG_RST) {
+            let error = match self.state {
+                TcpState::SynReceived if self.parent.is_some() => None,
+                TcpState::SynReceived => Some(NetError::Refused),
+                TcpState::Established
+                | TcpState::FinWait1
+                | TcpState::FinWait2
+                | TcpState::CloseWait => Some(NetError::Reset),
+                _ => None,
+            };
+            self.close_with(error);
+            return;
+        }
+
+        // A SYN in the window gets a challenge ACK instead of resetting the
+        // connection, RFC 5961 section 4.
+        if header.has(FLAG_SYN) {
+            self.ack_now = true;
+            self.output(ifaces, now);
+            return;
+        }
+        if !header.has(FLAG_ACK) {
+            return;
+        }
+
+        if self.state == TcpState::SynReceived {
+            if !(seq_lt(self.snd_una, header.ack) && seq_le(header.ack, self.snd_max)) {
+                send_reset(ifaces, ip, header, data);
+                return;
+            }
+            self.syn_acked(header);
+        }
+
+        if seq_lt(self.snd_max, header.ack) {
+            self.ack_now = true;
+            self.output(ifaces, now);
+            return;
+        }
+        if seq_lt(self.snd_una, header.ack) {
+            self.acknowledge(header.ack, now);
+        } else if header.ack == self.snd_una
+            && data.is_empty()
+            && !header.has(FLAG_FIN)
+            && header.window as usize == self.snd_wnd
+            && self.in_flight() > 0
+        {
+            self.dup_acks += 1;
+            if self.dup_acks == DUP_ACK_THRESHOLD {
+                self.fast_retransmit(ifaces);
+            }
+        }
+        if seq_lt(self.snd_wl1, header.seq)
+            || (self.snd_wl1 == header.seq && seq_le(self.snd_wl2, header.ack))
+        {
+            self.snd_wnd = header.window as usize;
+            self.snd_wl1 = header.seq;
+            self.snd_wl2 = header.ack;
+            // A peer answering probes is alive even if its window stays shut.
+            if self.snd_wnd == 0 {
+                self.retries = 0;
+            }
+        }
+
+        match self.state {
+            TcpState::FinWait1 if self.fin_acked => self.state = TcpState::FinWait2,
+            TcpState::Closing if self.fin_acked => {
+                self.state = TcpState::TimeWait;
+                self.timer = Some(now + TIME_WAIT);
+            }
+            TcpState::LastAck if self.fin_acked => {
+                self.close_with(None);
+                return;
+            }
+            _ => {}
+        }
+
+        // Segments past a gap are dropped and the duplicate ACK tells the
+        // peer where the gap starts.
+        if !data.is_empty()
+            && matches!(
+                self.state,
+                TcpState::Established | TcpState::FinWait1 | TcpState::FinWait2
+            )
+        {
+            if seq_le(header.seq, self.rcv_nxt) {
+                let skip = self.rcv_nxt.wrapping_sub(header.seq) as usize;
+                let pushed = self.rx.push(&data[skip.min(data.len())..]);
+                self.rcv_nxt = self.rcv_nxt.wrapping_add(pushed as u32);
+            }
+            self.ack_now = true;
+        }
+
+        // A FIN only counts once everything before it is in.
+        if header.has(FLAG_FIN) && header.seq.wrapping_add(data.len() as u32) == self.rcv_nxt {
+            self.rcv_nxt = self.rcv_nxt.wrapping_add(1);
+            self.fin_received = true;
+            self.ack_now = true;
+            match self.state {
+                TcpState::Established => self.state = TcpState::CloseWait,
+                TcpState::FinWait1 if self.fin_acked => {
+                    self.state = TcpState::TimeWait;
+                    self.timer = Some(now + TIME_WAIT);
+                }
+                TcpState::FinWait1 => self.state = TcpState::Closing,
+                TcpState::FinWait2 | TcpState::TimeWait => {
+                    self.state = TcpState::TimeWait;
+                    self.timer = Some(now + TIME_WAIT);
+                }
+                _ => {}
+            }
+        }
+
+        self.output(ifaces, now);
+    }
+}
+
+pub struct Tcp {
+    sockets: [TcpSocket; MAX_TCP_SOCKETS],
+    next_port: u16,
+    iss: u32,
+}
+
+impl Tcp {
+    pub(crate) const fn new() -> Self {
+        Self {
+            sockets: [TcpSocket::CLOSED; MAX_TCP_SOCKETS],
+            next_port: *EPHEMERAL_PORTS.start(),
+            iss: 0,
+        }
+    }
+
+    fn socket(&mut self, handle: TcpHandle) -> Result<&mut TcpSocket, NetError> {
+        match self.sockets.get_mut(handle.0) {
+            Some(socket) if socket.owned => Ok(socket),
+            _ => Err(NetError::NotConnected),
+        }
+    }
+
+    // Free sockets first, then connections in TIME-WAIT, oldest first.
+    fn allocate(&mut self) -> Result<usize, NetError> {
+        if let Some(idx) = self.sockets.iter().position(TcpSocket::is_free) {
+            self.sockets[idx].reset();
+            return Ok(idx);
+        }
+        let idx = self
+            .sockets
+            .iter()
+            .enumerate()
+            .filter(|(_, socket)| socket.state == TcpState::TimeWait && !socket.owned)
+            .min_by_key(|(_, socket)| socket.timer)
+            .map(|(idx, _)| idx)
+            .ok_or(NetError::Exhausted)?;
+        self.sockets[idx].reset();
+        Ok(idx)
+    }
+
+    // Clock driven so that numbers don't repeat across restarts, stepped on
+    // for each connection, RFC 793 page 27.
+    fn next_iss(&mut self, now: Duration) -> u32 {
+        self.iss = self.iss.wrapping_add(64000);
+        ((now.as_micros() / 4) as u32).wrapping_add(self.iss)
+    }
+
+    fn port_in_use(&self, port: u16) -> bool {
+        self.sockets
+            .iter()
+            .any(|socket| !socket.is_free() && socket.local.port == port)
+    }
+
+    fn ephemeral_port(&mut self) -> Result<u16, NetError> {
+        for _ in EPHEMERAL_PORTS {
+            let port = self.next_port;
+            self.next_port = match port {
+                65535 => *EPHEMERAL_PORTS.start(),
+                port => port + 1,
+            };
+            if !self.port_in_use(port) {
+                return Ok(port);
+            }
+        }
+        Err(NetError::AddrInUse)
+    }
+
+    fn local_mss(ifaces: &Interfaces, remote: Ipv4Addr) -> usize {
+        let mtu = ifaces
+            .route(remote)
+            .map_or(crate::ETH_MTU, |route| ifaces.mtu(route.iface));
+        mtu - IPV4_HEADER_SIZE - TCP_HEADER_SIZE
+    }
+
+    pub(crate) fn listen(
+        &mut self,
+        local: Endpoint,
+        backlog: usize,
+    ) -> Result<TcpHandle, NetError> {
+        let clash = self.sockets.iter().any(|socket| {
+            socket.state == TcpState::Listen
+                && socket.local.port == local.port
+                && (socket.local.addr == local.addr
+                    || socket.local.addr.is_unspecified()
+                    || local.addr.is_unspecified())
+        });
+        if local.port == 0 || clash {
+            return Err(NetError::AddrInUse);
+        }
+        let idx = self.allocate()?;
+        let socket = &mut self.sockets[idx];
+        socket.state = TcpState::Listen;
+        socket.owned = true;
+        socket.local = local;
+        socket.backlog = backlog.max(1);
+        Ok(TcpHandle(idx))
+    }
+
+    pub(crate) fn connect(
+        &mut self,
+        ifaces: &mut Interfaces,
+        remote: Endpoint,
+    ) -> Result<TcpHandle, NetError> {
+        let route = ifaces.route(remote.addr)?;
+        let port = self.ephemeral_port()?;
+        let now = ifaces.now();
+        let iss = self.next_iss(now);
+        let mss = Self::local_mss(ifaces, remote.addr);
+        let idx = self.allocate()?;
+        let socket = &mut self.sockets[idx];
+        socket.open(Endpoint::new(route.src, port), remote, iss, mss);
+        socket.state = TcpState::SynSent;
+        socket.owned = true;
+        socket.output(ifaces, now);
+        Ok(TcpHandle(idx))
+    }
+
+    pub(crate) fn accept(&mut self, handle: TcpHandle) -> Result<TcpHandle, NetError> {
+        if self.socket(handle)?.state != TcpState::Listen {
+            return Err(NetError::NotConnected);
+        }
+        let idx = self
+            .sockets
+            .iter()
+            .position(|socket| {
+                socket.parent == Some(handle.0) && !socket.owned && socket.state.is_synchronized()
+            })
+            .ok_or(NetError::WouldBlock)?;
+        let socket = &mut self.sockets[idx];
+        socket.owned = true;
+        socket.parent = None;
+        Ok(TcpHandle(idx))
+    }
+
+    pub(crate) fn state(&mut self, handle: TcpHandle) -> Result<TcpState, NetError> {
+        Ok(self.socket(handle)?.state)
+    }
+
+    pub(crate) fn endpoints(
+        &mut self,
+        handle: TcpHandle,
+    ) -> Result<(Endpoint, Endpoint), NetError> {
+        let socket = self.socket(handle)?;
+        Ok((socket.local, socket.remote))
+    }
+
+    pub(crate) fn send(
+        &mut self,
+        ifaces: &mut Interfaces,
+        handle: TcpHandle,
+        data: &[u8],
+    ) -> Result<usize, NetError> {
+        let socket = self.socket(handle)?;
+        match socket.state {
+            TcpState::Established | TcpState::CloseWait if !socket.fin_queued => {}
+            TcpState::SynSent | TcpState::SynReceived => return Err(NetError::WouldBlock),
+            _ => return Err(socket.error.unwrap_or(NetError::NotConnected)),
+        }
+        let len = socket.tx.push(data);
+        if len == 0 && !data.is_empty() {
+            return Err(NetError::WouldBlock);
+        }
+        socket.output(ifaces, ifaces.now());
+        Ok(len)
+    }
+
+    // Returns zero once the peer has closed its side and all its data has
+    // been read.
+    pub(crate) fn recv(
+        &mut self,
+        ifaces: &mut Interfaces,
+        handle: TcpHandle,
+        buf: &mut [u8],
+    ) -> Result<usize, NetError> {
+        let socket = self.socket(handle)?;
+        if socket.rx.is_empty() {
+            return match socket.error {
+                Some(error) => Err(error),
+                None if socket.fin_received => Ok(0),
+                None if socket.state == TcpState::Closed || socket.state == TcpState::Listen => {
+                    Err(NetError::NotConnected)
+                }
+                None => Err(NetError::WouldBlock),
+            };
+        }
+        let len = socket.rx.pop(buf);
+        // Tell the peer once the window has opened by a segment or more, so
+        // a sender stalled on a full buffer gets going again.
+        if socket.rx.free().min(MAX_WINDOW) >= socket.rcv_wnd + socket.mss {
+            socket.ack_now = true;
+            socket.output(ifaces, ifaces.now());
+        }
+        Ok(len)
+    }
+
+    // Closes the user's side. Queued data still goes out before the FIN,
+    // unread data makes it a reset instead, RFC 2525 section 2.17.
+    pub(crate) fn close(&mut self, ifaces: &mut Interfaces, handle: TcpHandle) {
+        let Ok(socket) = self.socket(handle) else {
+            return;
+        };
+        socket.owned = false;
+        match socket.state {
+            TcpState::Listen => {
+                socket.close_with(None);
+                // Connections nobody accepted go down with their listener.
+                for child in &mut self.sockets {
+                    if child.parent == Some(handle.0) && !child.owned {
+                        child.abort(ifaces, None);
+                        child.parent = None;
+                    }
+                }
+            }
+            TcpState::SynSent => socket.close_with(None),
+            TcpState::SynReceived | TcpState::Established | TcpState::CloseWait => {
+                if !socket.rx.is_empty() {
+                    socket.abort(ifaces, None);
+                } else {
+                    socket.fin_queued = true;
+                    socket.output(ifaces, ifaces.now());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub(crate) fn abort(&mut self, ifaces: &mut Interfaces, handle: TcpHandle) {
+        if let Ok(socket) = self.socket(handle) {
+            socket.owned = false;
+            socket.abort(ifaces, None);
+        }
+    }
+
+    pub(crate) fn input(
+        &mut self,
+        ifaces: &mut Interfaces,
+        iface: usize,
+        ip: &Ipv4Header,
+        data: &[u8],
+    ) {
+        if ifaces.is_broadcast(iface, ip.dst) || ip.src.is_broadcast() || ip.src.is_multicast() {
+            return;
+        }
+        let Some((header, data)) = TcpHeader::parse(ip, data) else {
+            return;
+        };
+        let local = Endpoint::new(ip.dst, header.dst_port);
+        let remote = Endpoint::new(ip.src, header.src_port);
+        let now = ifaces.now();
+
+        if let Some(socket) = self.sockets.iter_mut().find(|socket| {
+            !matches!(socket.state, TcpState::Closed | TcpState::Listen)
+                && socket.local == local
+                && socket.remote == remote
+        }) {
+            socket.input(ifaces, ip, &header, data, now);
+            return;
+        }
+
+        let Some(listener) = self.sockets.iter().position(|socket| {
+            socket.state == TcpState::Listen
+                && socket.local.port == local.port
+                && (socket.local.addr.is_unspecified() || socket.local.addr == local.addr)
+        }) else {
+            send_reset(ifaces, ip, &header, data);
+            return;
+        };
+
+        if header.has(FLAG_RST) {
+            return;
+        }
+        if header.has(FLAG_ACK) {
+            send_reset(ifaces, ip, &header, data);
+            return;
+        }
+        if !header.has(FLAG_SYN) {
+            return;
+        }
+        // Past the backlog SYNs are dropped and the peer tries again later.
+        let pending = self
+            .sockets
+            .iter()
+            .filter(|socket| socket.parent == Some(listener) && !socket.owned && !socket.is_free())
+            .count();
+        if pending >= self.sockets[listener].backlog {
+            return;
+        }
+        let iss = self.next_iss(now);
+        let mss = Self::local_mss(ifaces, remote.addr);
+        let Ok(idx) = self.allocate() else {
+            return;
+        };
+        let socket = &mut self.sockets[idx];
+        socket.open(
+            local,
+            remote,
+            iss,
+            mss.min(header.mss.map_or(DEFAULT_MSS, usize::from)),
+        );
+        socket.state = TcpState::SynReceived;
+        socket.parent = Some(listener);
+        socket.rcv_nxt = header.seq.wrapping_add(1);
+        socket.snd_wnd = header.window as usize;
+        socket.snd_wl1 = header.seq;
+        socket.output(ifaces, now);
+    }
+
+    pub(crate) fn poll(&mut self, ifaces: &mut Interfaces, now: Duration) {
+        for socket in &mut self.sockets {
+            if socket.timer.is_some_and(|timer| timer <= now) {
+                socket.timeout(ifaces, now);
+            }
+        }
+    }
+
+    pub(crate) fn next_deadline(&self) -> Option<Duration> {
+        self.sockets.iter().filter_map(|socket| socket.timer).min()
+    }
+}
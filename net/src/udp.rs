@@ -0,0 +1,203 @@
+use crate::{
+    iface::Interfaces,
+    ipv4::{Ipv4Header, PROTO_UDP},
+    read_u16,
+    ring::Ring,
+    write_u16, Checksum, Endpoint, Ipv4Addr, NetError,
+};
+
+pub const UDP_HEADER_SIZE: usize = 8;
+
+const MAX_UDP_SOCKETS: usize = 8;
+const BUFFER_SIZE: usize = 8192;
+// Each queued datagram is preceded by its source address, port and length.
+const META_SIZE: usize = 8;
+
+pub(crate) const EPHEMERAL_PORTS: core::ops::RangeInclusive<u16> = 49152..=65535;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct UdpHandle(usize);
+
+struct UdpSocket {
+    open: bool,
+    local: Endpoint,
+    rx: Ring<BUFFER_SIZE>,
+}
+
+impl UdpSocket {
+    const CLOSED: UdpSocket = UdpSocket {
+        open: false,
+        local: Endpoint::new(Ipv4Addr::UNSPECIFIED, 0),
+        rx: Ring::new(),
+    };
+}
+
+pub struct Udp {
+    sockets: [UdpSocket; MAX_UDP_SOCKETS],
+    next_port: u16,
+}
+
+impl Udp {
+    pub(crate) const fn new() -> Self {
+        Self {
+            sockets: [UdpSocket::CLOSED; MAX_UDP_SOCKETS],
+            next_port: *EPHEMERAL_PORTS.start(),
+        }
+    }
+
+    fn socket(&mut self, handle: UdpHandle) -> Result<&mut UdpSocket, NetError> {
+        match self.sockets.get_mut(handle.0) {
+            Some(socket) if socket.open => Ok(socket),
+            _ => Err(NetError::NotConnected),
+        }
+    }
+
+    fn in_use(&self, local: Endpoint) -> bool {
+        self.sockets.iter().any(|socket| {
+            socket.open
+                && socket.local.port == local.port
+                && (socket.local.addr == local.addr
+                    || socket.local.addr.is_unspecified()
+                    || local.addr.is_unspecified())
+        })
+    }
+
+    // Port zero picks a free ephemeral port, an unspecified address takes
+    // datagrams sent to any local address.
+    pub(crate) fn bind(&mut self, mut local: Endpoint) -> Result<UdpHandle, NetError> {
+        let idx = self
+            .sockets
+            .iter()
+            .position(|socket| !socket.open)
+            .ok_or(NetError::Exhausted)?;
+        if local.port == 0 {
+            local.port = self.ephemeral_port(local.addr)?;
+        } else if self.in_use(local) {
+            return Err(NetError::AddrInUse);
+        }
+        let socket = &mut self.sockets[idx];
+        socket.open = true;
+        socket.local = local;
+        socket.rx.clear();
+        Ok(UdpHandle(idx))
+    }
+
+    fn ephemeral_port(&mut self, addr: Ipv4Addr) -> Result<u16, NetError> {
+        for _ in EPHEMERAL_PORTS {
+            let port = self.next_port;
+            self.next_port = match port {
+                65535 => *EPHEMERAL_PORTS.start(),
+                port => port + 1,
+            };
+            if !self.in_use(Endpoint::new(addr, port)) {
+                return Ok(port);
+            }
+        }
+        Err(NetError::AddrInUse)
+    }
+
+    pub(crate) fn close(&mut self, handle: UdpHandle) {
+        if let Ok(socket) = self.socket(handle) {
+            socket.open = false;
+        }
+    }
+
+    pub(crate) fn local(&mut self, handle: UdpHandle) -> Result<Endpoint, NetError> {
+        Ok(self.socket(handle)?.local)
+    }
+
+    pub(crate) fn send_to(
+        &mut self,
+        ifaces: &mut Interfaces,
+        handle: UdpHandle,
+        remote: Endpoint,
+        data: &[u8],
+    ) -> Result<(), NetError> {
+        let local = self.socket(handle)?.local;
+        let len = UDP_HEADER_SIZE + data.len();
+        if len > u16::MAX as usize {
+            return Err(NetError::TooLarge);
+        }
+        let route = ifaces.route(remote.addr)?;
+        let src = match local.addr.is_unspecified() {
+            true => route.src,
+            false => local.addr,
+        };
+
+        let mut header = [0; UDP_HEADER_SIZE];
+        write_u16(&mut header, 0, local.port);
+        write_u16(&mut header, 2, remote.port);
+        write_u16(&mut header, 4, len as u16);
+        let mut sum = Checksum::new();
+        sum.add_pseudo_header(src, remote.addr, PROTO_UDP, len);
+        sum.add(&header);
+        sum.add(data);
+        // Zero means no checksum was computed.
+        let sum = match sum.finish() {
+            0 => 0xffff,
+            sum => sum,
+        };
+        write_u16(&mut header, 6, sum);
+        ifaces.send_ip(&route, src, remote.addr, PROTO_UDP, &[&header, data])
+    }
+
+    // Datagrams longer than `buf` are cut short.
+    pub(crate) fn recv_from(
+        &mut self,
+        handle: UdpHandle,
+        buf: &mut [u8],
+    ) -> Result<(usize, Endpoint), NetError> {
+        let socket = self.socket(handle)?;
+        let mut meta = [0; META_SIZE];
+        if socket.rx.pop(&mut meta) == 0 {
+            return Err(NetError::WouldBlock);
+        }
+        let from = Endpoint::new(Ipv4Addr(meta[..4].try_into().unwrap()), read_u16(&meta, 4));
+        let len = read_u16(&meta, 6) as usize;
+        let copied = len.min(buf.len());
+        socket.rx.peek(0, &mut buf[..copied]);
+        socket.rx.consume(len);
+        Ok((copied, from))
+    }
+
+    // Returns whether a socket took the datagram, if not the sender is told
+    // the port is unreachable.
+    pub(crate) fn input(&mut self, header: &Ipv4Header, data: &[u8]) -> bool {
+        if data.len() < UDP_HEADER_SIZE {
+            return true;
+        }
+        let len = read_u16(data, 4) as usize;
+        if len < UDP_HEADER_SIZE || len > data.len() {
+            return true;
+        }
+        let data = &data[..len];
+        if read_u16(data, 6) != 0 {
+            let mut sum = Checksum::new();
+            sum.add_pseudo_header(header.src, header.dst, PROTO_UDP, len);
+            sum.add(data);
+            if sum.finish() != 0 {
+                return true;
+            }
+        }
+
+        let port = read_u16(data, 2);
+        let Some(socket) = self.sockets.iter_mut().find(|socket| {
+            socket.open
+                && socket.local.port == port
+                && (socket.local.addr.is_unspecified() || socket.local.addr == header.dst)
+        }) else {
+            return false;
+        };
+        let payload = &data[UDP_HEADER_SIZE..];
+        if socket.rx.free() < META_SIZE + payload.len() {
+            return true;
+        }
+        let mut meta = [0; META_SIZE];
+        meta[..4].copy_from_slice(&header.src.0);
+        write_u16(&mut meta, 4, read_u16(data, 0));
+        write_u16(&mut meta, 6, payload.len() as u16);
+        socket.rx.push(&meta);
+        socket.rx.push(payload);
+        true
+    }
+}
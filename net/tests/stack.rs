@@ -0,0 +1,617 @@
+use std::{cell::Cell, collections::VecDeque, sync::Mutex, time::Duration};
+
+use net::*;
+
+thread_local! {
+    static NOW: Cell<Duration> = const { Cell::new(Duration::ZERO) };
+}
+
+fn now() -> Duration {
+    NOW.with(Cell::get)
+}
+
+// A link whose frames stay queued until the test hands them on.
+struct Wire {
+    mac: MacAddress,
+    mtu: usize,
+    frames: Mutex<VecDeque<Vec<u8>>>,
+}
+
+impl Wire {
+    fn new(mac: MacAddress, mtu: usize) -> &'static Wire {
+        Box::leak(Box::new(Wire {
+            mac,
+            mtu,
+            frames: Mutex::new(VecDeque::new()),
+        }))
+    }
+
+    fn take(&self) -> Option<Vec<u8>> {
+        self.frames.lock().unwrap().pop_front()
+    }
+}
+
+impl Device for Wire {
+    fn mac(&self) -> MacAddress {
+        self.mac
+    }
+
+    fn transmit(&self, frame: &[u8]) -> Result<(), NetError> {
+        assert!(frame.len() <= ETH_HEADER_SIZE + self.mtu);
+        self.frames.lock().unwrap().push_back(frame.to_vec());
+        Ok(())
+    }
+
+    fn mtu(&self) -> usize {
+        self.mtu
+    }
+}
+
+struct Host {
+    stack: Box<Stack<'static>>,
+    lo: (Iface, &'static Wire),
+    eth: (Iface, &'static Wire),
+}
+
+impl Host {
+    fn new(id: u8, cidr: &str, mtu: usize) -> Host {
+        let mut stack = Box::new(Stack::new(now));
+        let lo_wire = Wire::new(MacAddress([0; 6]), ETH_MTU);
+        let lo = stack.add_interface(lo_wire).unwrap();
+        stack.set_address(lo, Some("127.0.0.1/8".parse().unwrap()));
+        let eth_wire = Wire::new(MacAddress([0x52, 0x54, 0, 0, 0, id]), mtu);
+        let eth = stack.add_interface(eth_wire).unwrap();
+        stack.set_address(eth, Some(cidr.parse().unwrap()));
+        Host {
+            stack,
+            lo: (lo, lo_wire),
+            eth: (eth, eth_wire),
+        }
+    }
+
+    fn addr(&self) -> Ipv4Addr {
+        self.stack.address(self.eth.0).unwrap().addr
+    }
+}
+
+// Delivers frames until every link is quiet. Ethernet frames go to all
+// other hosts as on a hub, unless `deliver` says to drop them.
+fn settle_with(hosts: &mut [&mut Host], mut deliver: impl FnMut(&[u8]) -> bool) {
+    loop {
+        let mut moved = false;
+        for idx in 0..hosts.len() {
+            while let Some(frame) = hosts[idx].lo.1.take() {
+                let lo = hosts[idx].lo.0;
+                hosts[idx].stack.input(lo, &frame);
+                moved = true;
+            }
+            while let Some(frame) = hosts[idx].eth.1.take() {
+                moved = true;
+                if !deliver(&frame) {
+                    continue;
+                }
+                for (other, host) in hosts.iter_mut().enumerate() {
+                    if other != idx {
+                        host.stack.input(host.eth.0, &frame);
+                    }
+                }
+            }
+        }
+        if !moved {
+            return;
+        }
+    }
+}
+
+fn settle(hosts: &mut [&mut Host]) {
+    settle_with(hosts, |_| true);
+}
+
+fn advance(hosts: &mut [&mut Host], by: Duration) {
+    NOW.with(|now| now.set(now.get() + by));
+    for host in hosts.iter_mut() {
+        host.stack.poll();
+    }
+    settle(hosts);
+}
+
+fn pair(mtu: usize) -> (Host, Host) {
+    (
+        Host::new(1, "10.0.0.1/24", mtu),
+        Host::new(2, "10.0.0.2/24", mtu),
+    )
+}
+
+// Returns the ICMP type and code of an IPv4 frame, if it carries ICMP.
+fn icmp_type(frame: &[u8]) -> Option<(u8, u8)> {
+    let ip = &frame[ETH_HEADER_SIZE..];
+    (frame[12..14] == [0x08, 0x00] && ip[9] == 1).then(|| {
+        let header_len = (ip[0] & 0xf) as usize * 4;
+        (ip[header_len], ip[header_len + 1])
+    })
+}
+
+fn is_tcp_data(frame: &[u8]) -> bool {
+    let ip = &frame[ETH_HEADER_SIZE..];
+    if frame[12..14] != [0x08, 0x00] || ip[9] != 6 {
+        return false;
+    }
+    let total = u16::from_be_bytes([ip[2], ip[3]]) as usize;
+    let tcp = &ip[(ip[0] & 0xf) as usize * 4..total];
+    tcp.len() > (tcp[12] >> 4) as usize * 4
+}
+
+fn pattern(len: usize) -> Vec<u8> {
+    (0..len).map(|idx| (idx * 7 % 251) as u8).collect()
+}
+
+#[test]
+fn parse_addresses() {
+    let cidr: Ipv4Cidr = "192.168.1.77/20".parse().unwrap();
+    assert_eq!(cidr.addr, Ipv4Addr::new(192, 168, 1, 77));
+    assert_eq!(cidr.netmask(), Ipv4Addr::new(255, 255, 240, 0));
+    assert_eq!(cidr.network(), Ipv4Addr::new(192, 168, 0, 0));
+    assert_eq!(cidr.broadcast(), Ipv4Addr::new(192, 168, 15, 255));
+    assert!(cidr.contains(Ipv4Addr::new(192, 168, 15, 1)));
+    assert!(!cidr.contains(Ipv4Addr::new(192, 168, 16, 1)));
+    assert_eq!(cidr.to_string(), "192.168.1.77/20");
+    assert!("10.0.0.256".parse::<Ipv4Addr>().is_err());
+    assert!("10.0.0.1/33".parse::<Ipv4Cidr>().is_err());
+    assert!("10.0.0".parse::<Ipv4Addr>().is_err());
+}
+
+#[test]
+fn checksum_odd_pieces() {
+    let data = pattern(101);
+    let mut sum = Checksum::new();
+    sum.add(&data[..3]);
+    sum.add(&data[3..50]);
+    sum.add(&data[50..]);
+    assert_eq!(sum.finish(), checksum(&data));
+}
+
+#[test]
+fn ping_loopback() {
+    let mut host = Host::new(1, "10.0.0.1/24", ETH_MTU);
+    let ping = host.stack.ping_open().unwrap();
+    host.stack
+        .ping_send(ping, Ipv4Addr::LOCALHOST, 1, 56)
+        .unwrap();
+    let addr = host.addr();
+    host.stack.ping_send(ping, addr, 2, 100).unwrap();
+    // Nothing local ever goes out on the wire.
+    assert!(host.eth.1.take().is_none());
+    settle(&mut [&mut host]);
+
+    let reply = host.stack.ping_recv(ping).unwrap();
+    assert_eq!(
+        (reply.from, reply.seq, reply.len),
+        (Ipv4Addr::LOCALHOST, 1, 56)
+    );
+    let reply = host.stack.ping_recv(ping).unwrap();
+    assert_eq!((reply.from, reply.seq, reply.len), (addr, 2, 100));
+    assert_eq!(host.stack.ping_recv(ping), Err(NetError::WouldBlock));
+}
+
+#[test]
+fn ping_resolves_neighbour() {
+    let (mut a, mut b) = pair(ETH_MTU);
+    let ping = a.stack.ping_open().unwrap();
+    a.stack.ping_send(ping, b.addr(), 7, 32).unwrap();
+    NOW.with(|now| now.set(now.get() + Duration::from_millis(3)));
+    settle(&mut [&mut a, &mut b]);
+    let reply = a.stack.ping_recv(ping).unwrap();
+    assert_eq!((reply.from, reply.seq, reply.len), (b.addr(), 7, 32));
+    assert_eq!(reply.rtt, Duration::from_millis(3));
+
+    // The neighbour is known now, so the request goes out straight away.
+    a.stack.ping_send(ping, b.addr(), 8, 32).unwrap();
+    let frame = a.eth.1.take().unwrap();
+    assert_eq!(icmp_type(&frame), Some((8, 0)));
+    assert_eq!(frame[..6], b.eth.1.mac().0);
+}
+
+#[test]
+fn unanswered_arp_gives_up() {
+    let (mut a, mut b) = pair(ETH_MTU);
+    let ping = a.stack.ping_open().unwrap();
+    a.stack
+        .ping_send(ping, Ipv4Addr::new(10, 0, 0, 9), 1, 8)
+        .unwrap();
+    let mut requests = 0;
+    let mut count = |frame: &[u8]| {
+        requests += (frame[12..14] == [0x08, 0x06]) as usize;
+        true
+    };
+    settle_with(&mut [&mut a, &mut b], &mut count);
+    for _ in 0..5 {
+        NOW.with(|now| now.set(now.get() + Duration::from_secs(1)));
+        a.stack.poll();
+        settle_with(&mut [&mut a, &mut b], &mut count);
+    }
+    assert_eq!(requests, 3);
+    assert_eq!(a.stack.poll(), None);
+    assert_eq!(a.stack.ping_recv(ping), Err(NetError::WouldBlock));
+}
+
+#[test]
+fn unreachable_without_route() {
+    let mut host = Host::new(1, "10.0.0.1/24", ETH_MTU);
+    let ping = host.stack.ping_open().unwrap();
+    let far = Ipv4Addr::new(192, 168, 0, 1);
+    assert_eq!(
+        host.stack.ping_send(ping, far, 1, 8),
+        Err(NetError::Unreachable)
+    );
+
+    host.stack
+        .set_gateway(host.eth.0, Some(Ipv4Addr::new(10, 0, 0, 254)));
+    host.stack.ping_send(ping, far, 1, 8).unwrap();
+    // The gateway gets resolved, not the destination.
+    let frame = host.eth.1.take().unwrap();
+    assert_eq!(frame[12..14], [0x08, 0x06]);
+    assert_eq!(
+        frame[ETH_HEADER_SIZE + 24..ETH_HEADER_SIZE + 28],
+        [10, 0, 0, 254]
+    );
+}
+
+#[test]
+fn udp_echo() {
+    let (mut a, mut b) = pair(ETH_MTU);
+    let server = b
+        .stack
+        .udp_bind(Endpoint::new(Ipv4Addr::UNSPECIFIED, 7))
+        .unwrap();
+    assert_eq!(
+        b.stack.udp_bind(Endpoint::new(b.addr(), 7)),
+        Err(NetError::AddrInUse)
+    );
+    let client = a
+        .stack
+        .udp_bind(Endpoint::new(Ipv4Addr::UNSPECIFIED, 0))
+        .unwrap();
+    let port = a.stack.udp_local(client).unwrap().port;
+    assert!(port >= 49152);
+
+    a.stack
+        .udp_send_to(client, Endpoint::new(b.addr(), 7), b"hello")
+        .unwrap();
+    settle(&mut [&mut a, &mut b]);
+    let mut buf = [0; 64];
+    let (len, from) = b.stack.udp_recv_from(server, &mut buf).unwrap();
+    assert_eq!(&buf[..len], b"hello");
+    assert_eq!(from, Endpoint::new(a.addr(), port));
+
+    b.stack.udp_send_to(server, from, &buf[..len]).unwrap();
+    settle(&mut [&mut a, &mut b]);
+    let (len, from) = a.stack.udp_recv_from(client, &mut buf[..3]).unwrap();
+    assert_eq!(&buf[..len], b"hel");
+    assert_eq!(from, Endpoint::new(b.addr(), 7));
+    assert_eq!(
+        a.stack.udp_recv_from(client, &mut buf),
+        Err(NetError::WouldBlock)
+    );
+}
+
+#[test]
+fn udp_port_unreachable() {
+    let (mut a, mut b) = pair(ETH_MTU);
+    let client = a
+        .stack
+        .udp_bind(Endpoint::new(Ipv4Addr::UNSPECIFIED, 0))
+        .unwrap();
+    a.stack
+        .udp_send_to(client, Endpoint::new(b.addr(), 9), b"anyone?")
+        .unwrap();
+    let mut errors = Vec::new();
+    settle_with(&mut [&mut a, &mut b], |frame| {
+        errors.extend(icmp_type(frame));
+        true
+    });
+    assert_eq!(errors, [(3, 3)]);
+
+    // Broadcasts never get errors back.
+    a.stack
+        .udp_send_to(
+            client,
+            Endpoint::new(Ipv4Addr::new(10, 0, 0, 255), 9),
+            b"all",
+        )
+        .unwrap();
+    settle_with(&mut [&mut a, &mut b], |frame| {
+        assert_eq!(icmp_type(frame), None);
+        true
+    });
+}
+
+#[test]
+fn fragmented_datagrams() {
+    let (mut a, mut b) = pair(576);
+    let server = b
+        .stack
+        .udp_bind(Endpoint::new(Ipv4Addr::UNSPECIFIED, 7))
+        .unwrap();
+    let client = a
+        .stack
+        .udp_bind(Endpoint::new(Ipv4Addr::UNSPECIFIED, 0))
+        .unwrap();
+    let data = pattern(4000);
+    let to = Endpoint::new(b.addr(), 7);
+    a.stack.udp_send_to(client, to, &data).unwrap();
+    settle(&mut [&mut a, &mut b]);
+    let mut buf = vec![0; 8192];
+    let (len, _) = b.stack.udp_recv_from(server, &mut buf).unwrap();
+    assert_eq!(buf[..len], data[..]);
+
+    // Out of order, with one fragment repeated.
+    a.stack.udp_send_to(client, to, &data[..3000]).unwrap();
+    let mut frames = Vec::new();
+    while let Some(frame) = a.eth.1.take() {
+        frames.push(frame);
+    }
+    assert_eq!(frames.len(), 6);
+    b.stack.input(b.eth.0, &frames[2]);
+    for frame in frames.iter().rev() {
+        b.stack.input(b.eth.0, frame);
+    }
+    let (len, _) = b.stack.udp_recv_from(server, &mut buf).unwrap();
+    assert_eq!(buf[..len], data[..3000]);
+    assert_eq!(
+        b.stack.udp_recv_from(server, &mut buf),
+        Err(NetError::WouldBlock)
+    );
+
+    // A missing fragment is given up on.
+    a.stack.udp_send_to(client, to, &data).unwrap();
+    let mut first = true;
+    settle_with(&mut [&mut a, &mut b], |_| !std::mem::take(&mut first));
+    advance(&mut [&mut a, &mut b], Duration::from_secs(31));
+    assert_eq!(
+        b.stack.udp_recv_from(server, &mut buf),
+        Err(NetError::WouldBlock)
+    );
+
+    let ping = a.stack.ping_open().unwrap();
+    a.stack.ping_send(ping, b.addr(), 1, 2000).unwrap();
+    settle(&mut [&mut a, &mut b]);
+    assert_eq!(a.stack.ping_recv(ping).unwrap().len, 2000);
+}
+
+fn connect(a: &mut Host, b: &mut Host, port: u16) -> (TcpHandle, TcpHandle, TcpHandle) {
+    let listener = b
+        .stack
+        .tcp_listen(Endpoint::new(Ipv4Addr::UNSPECIFIED, port), 4)
+        .unwrap();
+    assert_eq!(b.stack.tcp_accept(listener), Err(NetError::WouldBlock));
+    let client = a.stack.tcp_connect(Endpoint::new(b.addr(), port)).unwrap();
+    assert_eq!(a.stack.tcp_state(client), Ok(TcpState::SynSent));
+    settle(&mut [a, b]);
+    let server = b.stack.tcp_accept(listener).unwrap();
+    assert_eq!(a.stack.tcp_state(client), Ok(TcpState::Established));
+    assert_eq!(b.stack.tcp_state(server), Ok(TcpState::Established));
+    (listener, client, server)
+}
+
+// Moves `data` from `from` to `to`, dropping the data segments `drop`
+// picks, and returns what arrived.
+fn transfer(
+    a: &mut Host,
+    b: &mut Host,
+    from: TcpHandle,
+    to: TcpHandle,
+    data: &[u8],
+    mut drop: impl FnMut(usize) -> bool,
+) -> Vec<u8> {
+    let mut sent = 0;
+    let mut received = Vec::new();
+    let mut buf = [0; 1000];
+    let mut segments = 0;
+    for _ in 0..10000 {
+        if received.len() == data.len() {
+            break;
+        }
+        match a.stack.tcp_send(from, &data[sent..]) {
+            Ok(len) => sent += len,
+            Err(NetError::WouldBlock) => {}
+            Err(err) => panic!("send: {}", err),
+        }
+        settle_with(&mut [&mut *a, &mut *b], |frame| {
+            if !is_tcp_data(frame) {
+                return true;
+            }
+            segments += 1;
+            !drop(segments)
+        });
+        loop {
+            match b.stack.tcp_recv(to, &mut buf) {
+                Ok(len) => received.extend_from_slice(&buf[..len]),
+                Err(NetError::WouldBlock) => break,
+                Err(err) => panic!("recv: {}", err),
+            }
+        }
+        advance(&mut [&mut *a, &mut *b], Duration::from_millis(100));
+    }
+    received
+}
+
+#[test]
+fn tcp_echo_and_close() {
+    let (mut a, mut b) = pair(ETH_MTU);
+    let (listener, client, server) = connect(&mut a, &mut b, 7);
+    let (local, remote) = a.stack.tcp_endpoints(client).unwrap();
+    assert_eq!(b.stack.tcp_endpoints(server), Ok((remote, local)));
+
+    assert_eq!(a.stack.tcp_send(client, b"ping"), Ok(4));
+    settle(&mut [&mut a, &mut b]);
+    let mut buf = [0; 64];
+    let len = b.stack.tcp_recv(server, &mut buf).unwrap();
+    assert_eq!(&buf[..len], b"ping");
+    b.stack.tcp_send(server, &buf[..len]).unwrap();
+    settle(&mut [&mut a, &mut b]);
+    assert_eq!(a.stack.tcp_recv(client, &mut buf), Ok(4));
+    assert_eq!(&buf[..4], b"ping");
+
+    a.stack.tcp_close(client);
+    settle(&mut [&mut a, &mut b]);
+    assert_eq!(b.stack.tcp_state(server), Ok(TcpState::CloseWait));
+    assert_eq!(b.stack.tcp_recv(server, &mut buf), Ok(0));
+    // The half-closed side can still send.
+    b.stack.tcp_send(server, b"bye").unwrap();
+    b.stack.tcp_close(server);
+    settle(&mut [&mut a, &mut b]);
+
+    // The client lingers in TIME-WAIT before letting go of the socket.
+    assert_eq!(b.stack.tcp_state(server), Err(NetError::NotConnected));
+    assert_eq!(a.stack.poll(), Some(now() + Duration::from_secs(60)));
+    assert_eq!(b.stack.poll(), None);
+    advance(&mut [&mut a, &mut b], Duration::from_secs(61));
+    assert_eq!(a.stack.poll(), None);
+
+    b.stack.tcp_close(listener);
+    let client = a.stack.tcp_connect(Endpoint::new(b.addr(), 7)).unwrap();
+    settle(&mut [&mut a, &mut b]);
+    assert_eq!(a.stack.tcp_state(client), Ok(TcpState::Closed));
+    assert_eq!(a.stack.tcp_recv(client, &mut buf), Err(NetError::Refused));
+    assert_eq!(a.stack.tcp_send(client, b"x"), Err(NetError::Refused));
+}
+
+#[test]
+fn tcp_loopback() {
+    let mut host = Host::new(1, "10.0.0.1/24", ETH_MTU);
+    let listener = host
+        .stack
+        .tcp_listen(Endpoint::new(Ipv4Addr::LOCALHOST, 7), 1)
+        .unwrap();
+    let client = host
+        .stack
+        .tcp_connect(Endpoint::new(Ipv4Addr::LOCALHOST, 7))
+        .unwrap();
+    settle(&mut [&mut host]);
+    let server = host.stack.tcp_accept(listener).unwrap();
+    host.stack.tcp_send(client, b"over lo").unwrap();
+    settle(&mut [&mut host]);
+    let mut buf = [0; 16];
+    assert_eq!(host.stack.tcp_recv(server, &mut buf), Ok(7));
+    assert_eq!(&buf[..7], b"over lo");
+    // Going away with unread data resets the connection.
+    host.stack.tcp_send(server, b"unread").unwrap();
+    settle(&mut [&mut host]);
+    host.stack.tcp_close(client);
+    settle(&mut [&mut host]);
+    assert_eq!(host.stack.tcp_recv(server, &mut buf), Err(NetError::Reset));
+    assert!(host.eth.1.take().is_none());
+}
+
+#[test]
+fn tcp_bulk_transfer() {
+    let (mut a, mut b) = pair(ETH_MTU);
+    let (_, client, server) = connect(&mut a, &mut b, 5000);
+    let data = pattern(100_000);
+    let received = transfer(&mut a, &mut b, client, server, &data, |_| false);
+    assert_eq!(received, data);
+}
+
+#[test]
+fn tcp_retransmits_lost_segments() {
+    let (mut a, mut b) = pair(ETH_MTU);
+    let (_, client, server) = connect(&mut a, &mut b, 5000);
+    let data = pattern(50_000);
+    let received = transfer(&mut a, &mut b, client, server, &data, |seg| seg % 4 == 0);
+    assert_eq!(received, data);
+    assert_eq!(a.stack.tcp_state(client), Ok(TcpState::Established));
+}
+
+#[test]
+fn tcp_zero_window() {
+    let (mut a, mut b) = pair(ETH_MTU);
+    let (_, client, server) = connect(&mut a, &mut b, 5000);
+    let data = pattern(20_000);
+    let mut sent = 0;
+    while let Ok(len) = a.stack.tcp_send(client, &data[sent..]) {
+        sent += len;
+        settle(&mut [&mut a, &mut b]);
+    }
+    // Both buffers are full, the sender probes the closed window for longer
+    // than its retries would last on a dead link.
+    for _ in 0..20 {
+        advance(&mut [&mut a, &mut b], Duration::from_secs(30));
+    }
+    assert_eq!(a.stack.tcp_state(client), Ok(TcpState::Established));
+
+    let mut received = Vec::new();
+    let mut buf = [0; 1000];
+    for _ in 0..1000 {
+        if received.len() == data.len() {
+            break;
+        }
+        if let Ok(len) = a.stack.tcp_send(client, &data[sent..]) {
+            sent += len;
+        }
+        settle(&mut [&mut a, &mut b]);
+        while let Ok(len) = b.stack.tcp_recv(server, &mut buf) {
+            received.extend_from_slice(&buf[..len]);
+        }
+        settle(&mut [&mut a, &mut b]);
+    }
+    assert_eq!(received, data);
+}
+
+#[test]
+fn tcp_gives_up_on_dead_peer() {
+    let (mut a, mut b) = pair(ETH_MTU);
+    let (_, client, _) = connect(&mut a, &mut b, 5000);
+    a.stack.tcp_send(client, b"hello?").unwrap();
+    let mut deadline = Duration::ZERO;
+    while let Some(next) = a.stack.poll() {
+        deadline = next;
+        NOW.with(|now| now.set(next));
+        while a.eth.1.take().is_some() {}
+    }
+    assert!(deadline > Duration::from_secs(100));
+    assert_eq!(a.stack.tcp_state(client), Ok(TcpState::Closed));
+    assert_eq!(a.stack.tcp_send(client, b"x"), Err(NetError::TimedOut));
+
+    let client = a
+        .stack
+        .tcp_connect(Endpoint::new(Ipv4Addr::new(10, 0, 0, 3), 80))
+        .unwrap();
+    while let Some(next) = a.stack.poll() {
+        NOW.with(|now| now.set(next));
+        while a.eth.1.take().is_some() {}
+    }
+    assert_eq!(a.stack.tcp_state(client), Ok(TcpState::Closed));
+    let mut buf = [0; 8];
+    assert_eq!(a.stack.tcp_recv(client, &mut buf), Err(NetError::TimedOut));
+}
+
+#[test]
+fn tcp_listener_backlog() {
+    let (mut a, mut b) = pair(ETH_MTU);
+    let listener = b
+        .stack
+        .tcp_listen(Endpoint::new(Ipv4Addr::UNSPECIFIED, 80), 1)
+        .unwrap();
+    let first = a.stack.tcp_connect(Endpoint::new(b.addr(), 80)).unwrap();
+    let second = a.stack.tcp_connect(Endpoint::new(b.addr(), 80)).unwrap();
+    settle(&mut [&mut a, &mut b]);
+    assert_eq!(a.stack.tcp_state(first), Ok(TcpState::Established));
+    assert_eq!(a.stack.tcp_state(second), Ok(TcpState::SynSent));
+
+    // Accepting makes room and the retried SYN gets through.
+    b.stack.tcp_accept(listener).unwrap();
+    advance(&mut [&mut a, &mut b], Duration::from_secs(1));
+    assert_eq!(a.stack.tcp_state(second), Ok(TcpState::Established));
+    assert_eq!(
+        b.stack
+            .tcp_listen(Endpoint::new(Ipv4Addr::UNSPECIFIED, 80), 1),
+        Err(NetError::AddrInUse)
+    );
+
+    // Closing the listener resets connections nobody accepted.
+    b.stack.tcp_close(listener);
+    settle(&mut [&mut a, &mut b]);
+    let mut buf = [0; 8];
+    assert_eq!(a.stack.tcp_recv(second, &mut buf), Err(NetError::Reset));
+    assert_eq!(a.stack.tcp_state(first), Ok(TcpState::Established));
+}
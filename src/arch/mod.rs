@@ -1,9 +1,20 @@
 #[cfg(x86_64)]
 pub use x86_64::{
-    debug::print,
+    cpu_id, cpu_id_of, cpu_index,
     gdt::set_kernel_stack,
     halt,
-    idt::{disable_interrupts, enable_interrupts, switch_context},
+    idt::{
+        disable_interrupts, enable_interrupts, interrupts_enabled, switch_context,
+        wait_for_interrupt,
+    },
+    io::{Mmio, Pio, PortInOut},
+    lapic::wake_cpu,
+    memory::{alloc_dma, free_dma, map_framebuffer, map_mmio},
+    read_tsc,
+    tsc::tsc_mhz,
+    vector::{alloc_vector, register_irq, Vector, VectorError, VectorFn},
+    vga::{VgaText, VGA_BUFFER, VGA_COLS, VGA_ROWS},
+    MAX_CPUS,
 };
 
 #[cfg(x86_64)]
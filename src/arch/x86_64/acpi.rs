@@ -0,0 +1,157 @@
+use core::{fmt, slice};
+
+use multiboot2::{AcpiV1Tag, AcpiV2Tag, BootInfo};
+
+use crate::sync::OnceCell;
+
+use super::memory;
+
+pub const SDT_HEADER_SIZE: usize = 36;
+
+static ROOT: OnceCell<Root> = OnceCell::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcpiError {
+    NoRsdp,
+    BadChecksum([u8; 4]),
+    Truncated([u8; 4]),
+    Map(::memory::AllocError),
+}
+
+impl fmt::Display for AcpiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoRsdp => f.write_str("no rsdp in boot info"),
+            Self::BadChecksum(sig) => write!(f, "bad checksum in {}", Signature(sig)),
+            Self::Truncated(sig) => write!(f, "{} is truncated", Signature(sig)),
+            Self::Map(err) => write!(f, "mapping tables: {}", err),
+        }
+    }
+}
+
+impl From<::memory::AllocError> for AcpiError {
+    fn from(err: ::memory::AllocError) -> Self {
+        Self::Map(err)
+    }
+}
+
+struct Signature<'a>(&'a [u8; 4]);
+
+impl fmt::Display for Signature<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for &b in self.0 {
+            write!(f, "{}", b as char)?;
+        }
+        Ok(())
+    }
+}
+
+struct Root {
+    sdt: Sdt,
+    entry_size: usize,
+}
+
+pub fn init(boot_info: &BootInfo) -> Result<(), AcpiError> {
+    let (addr, entry_size) = match boot_info.find_tag::<AcpiV2Tag>() {
+        Some(tag) if tag.xsdt_addr() != 0 => (tag.xsdt_addr(), 8),
+        _ => match boot_info.find_tag::<AcpiV1Tag>() {
+            Some(tag) => (tag.rsdp().rsdt_addr as usize, 4),
+            None => return Err(AcpiError::NoRsdp),
+        },
+    };
+
+    let sdt = unsafe { Sdt::map(addr)? };
+    crate::info!(
+        "acpi: {} at {:#x} with {} tables",
+        Signature(&sdt.signature()),
+        addr,
+        sdt.data().len() / entry_size
+    );
+    let _ = ROOT.set(Root { sdt, entry_size });
+    Ok(())
+}
+
+pub fn find_table(signature: &[u8; 4]) -> Option<Sdt> {
+    let root = ROOT.get()?;
+    let data = root.sdt.data();
+    (0..data.len() / root.entry_size)
+        .filter_map(|idx| {
+            let off = SDT_HEADER_SIZE + idx * root.entry_size;
+            match root.entry_size {
+                8 => root.sdt.u64(off).map(|addr| addr as usize),
+                _ => root.sdt.u32(off).map(|addr| addr as usize),
+            }
+        })
+        .filter_map(|addr| match unsafe { Sdt::map(addr) } {
+            Ok(sdt) => Some(sdt),
+            Err(err) => {
+                crate::warn!("acpi: {}", err);
+                None
+            }
+        })
+        .find(|sdt| &sdt.signature() == signature)
+}
+
+#[derive(Clone, Copy)]
+pub struct Sdt {
+    bytes: &'static [u8],
+}
+
+impl Sdt {
+    unsafe fn map(addr: usize) -> Result<Self, AcpiError> {
+        memory::map_mmio(addr, SDT_HEADER_SIZE)?;
+        let header = slice::from_raw_parts(addr as *const u8, SDT_HEADER_SIZE);
+        let signature = [header[0], header[1], header[2], header[3]];
+        let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        if len < SDT_HEADER_SIZE {
+            return Err(AcpiError::Truncated(signature));
+        }
+
+        memory::map_mmio(addr, len)?;
+        let bytes = slice::from_raw_parts(addr as *const u8, len);
+        if bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) != 0 {
+            return Err(AcpiError::BadChecksum(signature));
+        }
+        Ok(Self { bytes })
+    }
+
+    #[inline]
+    pub fn signature(&self) -> [u8; 4] {
+        [self.bytes[0], self.bytes[1], self.bytes[2], self.bytes[3]]
+    }
+
+    #[inline]
+    pub fn revision(&self) -> u8 {
+        self.bytes[8]
+    }
+
+    #[inline]
+    pub fn bytes(&self) -> &'static [u8] {
+        self.bytes
+    }
+
+    #[inline]
+    pub fn data(&self) -> &'static [u8] {
+        &self.bytes[SDT_HEADER_SIZE..]
+    }
+
+    pub fn u8(&self, off: usize) -> Option<u8> {
+        self.bytes.get(off).copied()
+    }
+
+    pub fn u16(&self, off: usize) -> Option<u16> {
+        Some(u16::from_le_bytes(self.array(off)?))
+    }
+
+    pub fn u32(&self, off: usize) -> Option<u32> {
+        Some(u32::from_le_bytes(self.array(off)?))
+    }
+
+    pub fn u64(&self, off: usize) -> Option<u64> {
+        Some(u64::from_le_bytes(self.array(off)?))
+    }
+
+    fn array<const N: usize>(&self, off: usize) -> Option<[u8; N]> {
+        self.bytes.get(off..off + N)?.try_into().ok()
+    }
+}
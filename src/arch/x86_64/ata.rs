@@ -0,0 +1,613 @@
+use core::{
+    fmt,
+    sync::atomic::{AtomicU16, AtomicU8, Ordering},
+    time::Duration,
+};
+
+use ::memory::PAGE_SIZE;
+
+use crate::{
+    block::{self, BlockDevice, BlockError, SECTOR_SIZE},
+    pci::{self, Bar, Command, Device, Driver, Match, ProbeError},
+    sync::{Event, OnceCell, SleepMutex},
+    time,
+};
+
+use super::{
+    idt,
+    io::{Pio, PortInOut},
+    memory,
+};
+
+const REG_DATA: u16 = 0;
+const REG_ERROR: u16 = 1;
+const REG_COUNT: u16 = 2;
+const REG_LBA_LOW: u16 = 3;
+const REG_LBA_MID: u16 = 4;
+const REG_LBA_HIGH: u16 = 5;
+const REG_DRIVE: u16 = 6;
+const REG_STATUS: u16 = 7;
+const REG_COMMAND: u16 = 7;
+
+const STATUS_ERR: u8 = 1 << 0;
+const STATUS_DRQ: u8 = 1 << 3;
+const STATUS_DF: u8 = 1 << 5;
+const STATUS_BSY: u8 = 1 << 7;
+
+const CONTROL_NIEN: u8 = 1 << 1;
+const CONTROL_SRST: u8 = 1 << 2;
+
+const DRIVE_LBA: u8 = 0xe0;
+const DRIVE_SLAVE: u8 = 1 << 4;
+
+const CMD_READ_PIO: u8 = 0x20;
+const CMD_READ_PIO_EXT: u8 = 0x24;
+const CMD_READ_DMA_EXT: u8 = 0x25;
+const CMD_WRITE_PIO: u8 = 0x30;
+const CMD_WRITE_PIO_EXT: u8 = 0x34;
+const CMD_WRITE_DMA_EXT: u8 = 0x35;
+const CMD_READ_DMA: u8 = 0xc8;
+const CMD_WRITE_DMA: u8 = 0xca;
+const CMD_FLUSH: u8 = 0xe7;
+const CMD_FLUSH_EXT: u8 = 0xea;
+const CMD_IDENTIFY: u8 = 0xec;
+
+const BM_COMMAND: u16 = 0;
+const BM_STATUS: u16 = 2;
+const BM_PRDT: u16 = 4;
+const BM_START: u8 = 1 << 0;
+const BM_READ: u8 = 1 << 3;
+const BM_ERROR: u8 = 1 << 1;
+const BM_IRQ: u8 = 1 << 2;
+const PRD_END: u16 = 1 << 15;
+
+const IDENTIFY_MODEL: usize = 27;
+const IDENTIFY_LBA28: usize = 60;
+const IDENTIFY_FEATURES: usize = 83;
+const IDENTIFY_LBA48: usize = 100;
+const FEATURE_LBA48: u16 = 1 << 10;
+
+const LBA28_LIMIT: u64 = 1 << 28;
+// 128 sectors fill the 64 KiB bounce buffer, one PRD entry per page.
+const MAX_SECTORS: usize = 128;
+const DMA_PAGES: usize = MAX_SECTORS * SECTOR_SIZE / PAGE_SIZE;
+const DMA_LIMIT: usize = 1 << 32;
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+const NAMES: [&str; 4] = ["ata0", "ata1", "ata2", "ata3"];
+
+pub static CHANNELS: [Channel; 2] = [Channel::new(0x1f0, 0x3f6), Channel::new(0x170, 0x376)];
+static DISKS: [OnceCell<AtaDisk>; 4] = [const { OnceCell::new() }; 4];
+
+pub static PIIX_IDE: PiixIde = PiixIde;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AtaError {
+    NoDevice,
+    NotAta,
+    Timeout,
+    Device(u8),
+}
+
+impl fmt::Display for AtaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoDevice => f.write_str("no device"),
+            Self::NotAta => f.write_str("not an ata device"),
+            Self::Timeout => f.write_str("timed out"),
+            Self::Device(err) => write!(f, "device error {:#04x}", err),
+        }
+    }
+}
+
+impl From<AtaError> for BlockError {
+    fn from(err: AtaError) -> Self {
+        match err {
+            AtaError::Timeout => Self::Timeout,
+            AtaError::Device(err) => Self::Io(err),
+            AtaError::NoDevice | AtaError::NotAta => Self::Io(0),
+        }
+    }
+}
+
+// The PCI function is optional, without it the channels are driven through
+// the legacy ports with PIO only.
+pub fn init() {
+    pci::register(&PIIX_IDE);
+
+    for (idx, channel) in CHANNELS.iter().enumerate() {
+        if !channel.reset() {
+            continue;
+        }
+        for slave in [false, true] {
+            let disk_idx = idx * 2 + slave as usize;
+            match channel.identify(slave) {
+                Ok(info) => {
+                    let disk = AtaDisk {
+                        channel,
+                        slave,
+                        name: NAMES[disk_idx],
+                        lba48: info.lba48,
+                        sectors: info.sectors,
+                    };
+                    crate::info!(
+                        "{}: {} ({} sectors{})",
+                        disk.name,
+                        info.model(),
+                        disk.sectors,
+                        if channel.has_dma() { ", dma" } else { "" }
+                    );
+                    if let Ok(disk) = DISKS[disk_idx].set(disk) {
+                        block::register(disk);
+                    }
+                }
+                Err(AtaError::NoDevice) => {}
+                Err(err) => crate::info!("{}: {}", NAMES[disk_idx], err),
+            }
+        }
+    }
+}
+
+pub fn interrupt(idx: usize) {
+    if let Some(channel) = CHANNELS.get(idx) {
+        channel.interrupt();
+    }
+}
+
+pub struct PiixIde;
+
+impl Driver for PiixIde {
+    fn name(&self) -> &'static str {
+        "piix-ide"
+    }
+
+    fn matches(&self) -> &'static [Match] {
+        &[Match::Class(0x01, 0x01)]
+    }
+
+    // Native mode channels route their interrupt elsewhere and aren't
+    // supported, compatibility mode keeps IRQ 14 and 15.
+    fn probe(&self, device: &'static Device) -> Result<(), ProbeError> {
+        if device.prog_if() & 0x5 != 0 {
+            return Err(ProbeError::Unsupported);
+        }
+        let bm = match device.bar(4) {
+            Some(Bar::Io { port, .. }) => port,
+            _ => return Err(ProbeError::MissingBar(4)),
+        };
+        device.enable(Command::IO | Command::BUS_MASTER);
+
+        for (idx, channel) in CHANNELS.iter().enumerate() {
+            let prdt = memory::alloc_dma(1)?;
+            let buffer = memory::alloc_dma(DMA_PAGES)?;
+            // The PRDT base register and the PRD entries only hold 32-bit
+            // addresses, the channel stays on PIO if either lands above 4 GiB.
+            if prdt + PAGE_SIZE > DMA_LIMIT || buffer + DMA_PAGES * PAGE_SIZE > DMA_LIMIT {
+                memory::free_dma(prdt, 1);
+                memory::free_dma(buffer, DMA_PAGES);
+                crate::warn!("piix-ide: channel {} buffers above 4 GiB, using pio", idx);
+                continue;
+            }
+            let mut dma = channel.dma.lock();
+            *dma = Some(DmaBuffers { prdt, buffer });
+            channel.bm.store(bm + idx as u16 * 8, Ordering::Release);
+        }
+        Ok(())
+    }
+}
+
+struct DmaBuffers {
+    prdt: usize,
+    buffer: usize,
+}
+
+pub struct Channel {
+    base: u16,
+    ctrl: u16,
+    bm: AtomicU16,
+    irq: Event,
+    bm_status: AtomicU8,
+    dma: SleepMutex<Option<DmaBuffers>>,
+}
+
+struct Identify {
+    words: [u16; 256],
+    lba48: bool,
+    sectors: u64,
+}
+
+impl Identify {
+    fn model(&self) -> Model {
+        let mut model = [0u8; 40];
+        for (idx, word) in self.words[IDENTIFY_MODEL..IDENTIFY_MODEL + 20]
+            .iter()
+            .enumerate()
+        {
+            model[idx * 2..idx * 2 + 2].copy_from_slice(&word.to_be_bytes());
+        }
+        Model(model)
+    }
+}
+
+struct Model([u8; 40]);
+
+impl fmt::Display for Model {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let model = core::str::from_utf8(&self.0).unwrap_or("?");
+        f.write_str(model.trim())
+    }
+}
+
+impl Channel {
+    const fn new(base: u16, ctrl: u16) -> Self {
+        Self {
+            base,
+            ctrl,
+            bm: AtomicU16::new(0),
+            irq: Event::new(),
+            bm_status: AtomicU8::new(0),
+            dma: SleepMutex::new(None),
+        }
+    }
+
+    #[inline]
+    fn reg(&self, reg: u16) -> Pio<u8> {
+        Pio::new(self.base + reg)
+    }
+
+    #[inline]
+    fn data(&self) -> Pio<u16> {
+        Pio::new(self.base + REG_DATA)
+    }
+
+    #[inline]
+    fn control(&self) -> Pio<u8> {
+        Pio::new(self.ctrl)
+    }
+
+    #[inline]
+    fn bm_reg<T: PortInOut>(&self, reg: u16) -> Option<Pio<T>> {
+        match self.bm.load(Ordering::Acquire) {
+            0 => None,
+            bm => Some(Pio::new(bm + reg)),
+        }
+    }
+
+    #[inline]
+    fn has_dma(&self) -> bool {
+        self.bm.load(Ordering::Acquire) != 0
+    }
+
+    // Reading the alternate status four times gives the drive its 400ns.
+    fn delay(&self) -> u8 {
+        for _ in 0..4 {
+            self.control().read();
+        }
+        self.control().read()
+    }
+
+    // A floating bus reads back as all ones.
+    fn reset(&self) -> bool {
+        if self.reg(REG_STATUS).read() == 0xff {
+            return false;
+        }
+        self.control().write(CONTROL_SRST | CONTROL_NIEN);
+        self.delay();
+        self.control().write(0);
+        self.wait_idle().is_ok()
+    }
+
+    fn wait_idle(&self) -> Result<u8, AtaError> {
+        let deadline = time::monotonic() + TIMEOUT;
+        loop {
+            let status = self.control().read();
+            if status & STATUS_BSY == 0 {
+                return Ok(status);
+            }
+            if time::monotonic() >= deadline {
+                return Err(AtaError::Timeout);
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    fn wait_data(&self) -> Result<(), AtaError> {
+        let status = self.wait_idle()?;
+        let status = if status & (STATUS_DRQ | STATUS_ERR | STATUS_DF) == 0 {
+            self.delay()
+        } else {
+            status
+        };
+        self.check(status)?;
+        if status & STATUS_DRQ == 0 {
+            return Err(AtaError::Device(0));
+        }
+        Ok(())
+    }
+
+    fn check(&self, status: u8) -> Result<(), AtaError> {
+        if status & (STATUS_ERR | STATUS_DF) != 0 {
+            return Err(AtaError::Device(self.reg(REG_ERROR).read()));
+        }
+        Ok(())
+    }
+
+    fn select(&self, slave: bool, head: u8) {
+        let slave = if slave { DRIVE_SLAVE } else { 0 };
+        self.reg(REG_DRIVE).write(DRIVE_LBA | slave | (head & 0xf));
+        self.delay();
+    }
+
+    fn identify(&self, slave: bool) -> Result<Identify, AtaError> {
+        self.select(slave, 0);
+        for reg in [REG_COUNT, REG_LBA_LOW, REG_LBA_MID, REG_LBA_HIGH] {
+            self.reg(reg).write(0);
+        }
+        self.reg(REG_COMMAND).write(CMD_IDENTIFY);
+        if self.delay() == 0 {
+            return Err(AtaError::NoDevice);
+        }
+        self.wait_idle()?;
+        if self.reg(REG_LBA_MID).read() != 0 || self.reg(REG_LBA_HIGH).read() != 0 {
+            return Err(AtaError::NotAta);
+        }
+        self.wait_data()?;
+
+        let mut words = [0u16; 256];
+        for word in words.iter_mut() {
+            *word = self.data().read();
+        }
+        let lba48 = words[IDENTIFY_FEATURES] & FEATURE_LBA48 != 0;
+        let sectors = if lba48 {
+            (0..4).fold(0, |sectors, idx| {
+                sectors | ((words[IDENTIFY_LBA48 + idx] as u64) << (idx * 16))
+            })
+        } else {
+            words[IDENTIFY_LBA28] as u64 | ((words[IDENTIFY_LBA28 + 1] as u64) << 16)
+        };
+        Ok(Identify {
+            words,
+            lba48,
+            sectors,
+        })
+    }
+
+    fn setup(&self, slave: bool, lba: u64, count: usize, lba48: bool) {
+        if lba48 {
+            self.select(slave, 0);
+            self.reg(REG_COUNT).write((count >> 8) as u8);
+            self.reg(REG_LBA_LOW).write((lba >> 24) as u8);
+            self.reg(REG_LBA_MID).write((lba >> 32) as u8);
+            self.reg(REG_LBA_HIGH).write((lba >> 40) as u8);
+        } else {
+            self.select(slave, (lba >> 24) as u8);
+        }
+        self.reg(REG_COUNT).write(count as u8);
+        self.reg(REG_LBA_LOW).write(lba as u8);
+        self.reg(REG_LBA_MID).write((lba >> 8) as u8);
+        self.reg(REG_LBA_HIGH).write((lba >> 16) as u8);
+    }
+
+    fn read_pio(&self, disk: &AtaDisk, lba: u64, buf: &mut [u8]) -> Result<(), AtaError> {
+        let count = buf.len() / SECTOR_SIZE;
+        self.setup(disk.slave, lba, count, disk.lba48);
+        self.reg(REG_COMMAND).write(if disk.lba48 {
+            CMD_READ_PIO_EXT
+        } else {
+            CMD_READ_PIO
+        });
+        for sector in buf.chunks_mut(SECTOR_SIZE) {
+            self.wait_data()?;
+            for idx in (0..SECTOR_SIZE).step_by(2) {
+                let word = self.data().read();
+                sector[idx..idx + 2].copy_from_slice(&word.to_le_bytes());
+            }
+        }
+        Ok(())
+    }
+
+    fn write_pio(&self, disk: &AtaDisk, lba: u64, buf: &[u8]) -> Result<(), AtaError> {
+        let count = buf.len() / SECTOR_SIZE;
+        self.setup(disk.slave, lba, count, disk.lba48);
+        self.reg(REG_COMMAND).write(if disk.lba48 {
+            CMD_WRITE_PIO_EXT
+        } else {
+            CMD_WRITE_PIO
+        });
+        for sector in buf.chunks(SECTOR_SIZE) {
+            self.wait_data()?;
+            for idx in (0..SECTOR_SIZE).step_by(2) {
+                self.data()
+                    .write(u16::from_le_bytes([sector[idx], sector[idx + 1]]));
+            }
+        }
+        let status = self.wait_idle()?;
+        self.check(status)
+    }
+
+    fn transfer_dma(
+        &self,
+        disk: &AtaDisk,
+        dma: &DmaBuffers,
+        lba: u64,
+        count: usize,
+        write: bool,
+    ) -> Result<(), AtaError> {
+        let (Some(bm_cmd), Some(bm_status), Some(bm_prdt)) = (
+            self.bm_reg::<u8>(BM_COMMAND),
+            self.bm_reg::<u8>(BM_STATUS),
+            self.bm_reg::<u32>(BM_PRDT),
+        ) else {
+            return Err(AtaError::NoDevice);
+        };
+
+        let bytes = count * SECTOR_SIZE;
+        let entries = bytes.div_ceil(PAGE_SIZE);
+        let prdt = dma.prdt as *mut u32;
+        for idx in 0..entries {
+            let len = (bytes - idx * PAGE_SIZE).min(PAGE_SIZE) as u16;
+            let flags = if idx + 1 == entries { PRD_END } else { 0 };
+            unsafe {
+                prdt.add(idx * 2)
+                    .write_volatile((dma.buffer + idx * PAGE_SIZE) as u32);
+                prdt.add(idx * 2 + 1)
+                    .write_volatile(((flags as u32) << 16) | len as u32);
+            }
+        }
+
+        bm_prdt.write(dma.prdt as u32);
+        bm_cmd.write(if write { 0 } else { BM_READ });
+        bm_status.write(BM_IRQ | BM_ERROR);
+        self.irq.reset();
+        self.bm_status.store(0, Ordering::Relaxed);
+
+        self.setup(disk.slave, lba, count, disk.lba48);
+        self.reg(REG_COMMAND).write(match (disk.lba48, write) {
+            (true, true) => CMD_WRITE_DMA_EXT,
+            (true, false) => CMD_READ_DMA_EXT,
+            (false, true) => CMD_WRITE_DMA,
+            (false, false) => CMD_READ_DMA,
+        });
+        bm_cmd.write(bm_cmd.read() | BM_START);
+
+        let done = self.wait_irq(&bm_status);
+        bm_cmd.write(bm_cmd.read() & !BM_START);
+        done?;
+
+        let status = self.wait_idle()?;
+        self.check(status)?;
+        if self.bm_status.load(Ordering::Relaxed) & BM_ERROR != 0 {
+            return Err(AtaError::Device(0));
+        }
+        Ok(())
+    }
+
+    // Before interrupts are enabled the bus master status is polled instead.
+    fn wait_irq(&self, bm_status: &Pio<u8>) -> Result<(), AtaError> {
+        if idt::interrupts_enabled() {
+            if !self.irq.wait_timeout(TIMEOUT) {
+                return Err(AtaError::Timeout);
+            }
+            return Ok(());
+        }
+
+        let deadline = time::monotonic() + TIMEOUT;
+        loop {
+            let status = bm_status.read();
+            if status & BM_IRQ != 0 {
+                bm_status.write(status & (BM_IRQ | BM_ERROR));
+                self.bm_status.store(status, Ordering::Relaxed);
+                self.reg(REG_STATUS).read();
+                return Ok(());
+            }
+            if time::monotonic() >= deadline {
+                return Err(AtaError::Timeout);
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    fn interrupt(&self) {
+        if let Some(bm_status) = self.bm_reg::<u8>(BM_STATUS) {
+            let status = bm_status.read();
+            if status & BM_IRQ != 0 {
+                bm_status.write(status & (BM_IRQ | BM_ERROR));
+                self.bm_status.store(status, Ordering::Relaxed);
+            }
+        }
+        self.reg(REG_STATUS).read();
+        self.irq.set();
+    }
+
+    fn flush(&self, disk: &AtaDisk) -> Result<(), AtaError> {
+        self.select(disk.slave, 0);
+        self.reg(REG_COMMAND)
+            .write(if disk.lba48 { CMD_FLUSH_EXT } else { CMD_FLUSH });
+        let status = self.wait_idle()?;
+        self.check(status)
+    }
+}
+
+pub struct AtaDisk {
+    channel: &'static Channel,
+    slave: bool,
+    name: &'static str,
+    lba48: bool,
+    sectors: u64,
+}
+
+impl AtaDisk {
+    fn transfer(
+        &self,
+        lba: u64,
+        len: usize,
+        mut op: impl FnMut(u64, usize, usize) -> Result<(), AtaError>,
+    ) -> Result<(), BlockError> {
+        let count = block::check_range(self, lba, len)? as usize;
+        if !self.lba48 && lba + count as u64 > LBA28_LIMIT {
+            return Err(BlockError::OutOfRange);
+        }
+        let mut done = 0;
+        while done < count {
+            let chunk = (count - done).min(MAX_SECTORS);
+            op(lba + done as u64, done * SECTOR_SIZE, chunk)?;
+            done += chunk;
+        }
+        Ok(())
+    }
+}
+
+impl BlockDevice for AtaDisk {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn block_size(&self) -> usize {
+        SECTOR_SIZE
+    }
+
+    fn block_count(&self) -> u64 {
+        self.sectors
+    }
+
+    fn read(&self, lba: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+        let channel = self.channel;
+        let dma = channel.dma.lock();
+        self.transfer(lba, buf.len(), |lba, off, count| {
+            let buf = &mut buf[off..off + count * SECTOR_SIZE];
+            match dma.as_ref() {
+                Some(dma) => {
+                    channel.transfer_dma(self, dma, lba, count, false)?;
+                    let src =
+                        unsafe { core::slice::from_raw_parts(dma.buffer as *const u8, buf.len()) };
+                    buf.copy_from_slice(src);
+                    Ok(())
+                }
+                None => channel.read_pio(self, lba, buf),
+            }
+        })
+    }
+
+    fn write(&self, lba: u64, buf: &[u8]) -> Result<(), BlockError> {
+        let channel = self.channel;
+        let dma = channel.dma.lock();
+        self.transfer(lba, buf.len(), |lba, off, count| {
+            let buf = &buf[off..off + count * SECTOR_SIZE];
+            match dma.as_ref() {
+                Some(dma) => {
+                    let dst = unsafe {
+                        core::slice::from_raw_parts_mut(dma.buffer as *mut u8, buf.len())
+                    };
+                    dst.copy_from_slice(buf);
+                    channel.transfer_dma(self, dma, lba, count, true)
+                }
+                None => channel.write_pio(self, lba, buf),
+            }
+        })
+    }
+
+    fn flush(&self) -> Result<(), BlockError> {
+        let _dma = self.channel.dma.lock();
+        Ok(self.channel.flush(self)?)
+    }
+}
@@ -1,22 +1,3 @@
-.section .multiboot
-
-.set MBOOT2_MAGIC, 0xe85250d6
-.set MBOOT2_ARCH, 0
-.set MBOOT2_LENGTH, (mboot2_header_end - mboot2_header)
-.set MBOOT2_CHECKSUM, -(MBOOT2_MAGIC + MBOOT2_ARCH + MBOOT2_LENGTH)
-
-.align 8
-mboot2_header:
-.long MBOOT2_MAGIC
-.long MBOOT2_ARCH
-.long MBOOT2_LENGTH
-.long MBOOT2_CHECKSUM
-
-.word 0
-.word 0
-.long 8
-mboot2_header_end:
-
 .section .bss
 .align 16
 .skip 4 * 4096
@@ -1,35 +1,39 @@
-use core::fmt;
+use crate::log::Sink;
 
-use crate::sync::MutexGuard;
+use super::{
+    io::Pio,
+    serial::{SerialPort, COM1},
+};
 
-use super::serial::{SerialPort, COM1};
+pub static SERIAL_SINK: SerialSink = SerialSink;
 
-pub fn print(args: fmt::Arguments) {
-    use core::fmt::Write;
-    Writer::new().write_fmt(args).unwrap();
-}
-
-pub struct Writer<'a> {
-    serial: MutexGuard<'a, SerialPort>,
-}
+pub struct SerialSink;
 
-impl Writer<'_> {
-    fn new() -> Self {
-        Self {
-            serial: COM1.lock(),
+impl Sink for SerialSink {
+    fn write_str(&self, s: &str) {
+        let serial = COM1.lock();
+        for c in s.bytes() {
+            if c == b'\n' {
+                serial.write_byte(b'\r');
+            }
+            serial.write_byte(c);
         }
     }
-
-    pub fn write_byte(&mut self, c: u8) {
-        self.serial.write_byte(c);
-    }
 }
 
-impl fmt::Write for Writer<'_> {
-    fn write_str(&mut self, s: &str) -> fmt::Result {
+// The panicking CPU may be the one holding the COM1 lock, so this writes to
+// the port directly.
+pub static PANIC_SINK: PanicSink = PanicSink(SerialPort::new(Pio::new(0x3f8)));
+
+pub struct PanicSink(SerialPort);
+
+impl Sink for PanicSink {
+    fn write_str(&self, s: &str) {
         for c in s.bytes() {
-            self.write_byte(c);
+            if c == b'\n' {
+                self.0.write_byte(b'\r');
+            }
+            self.0.write_byte(c);
         }
-        Ok(())
     }
 }
@@ -1,10 +1,8 @@
-use core::{
-    arch::asm,
-    mem,
-    sync::atomic::{AtomicUsize, Ordering},
-};
+use core::{arch::asm, mem};
 
-use super::regs;
+use crate::sync::{IrqSafeMutex, OnceCell};
+
+use super::{cpu_index, regs};
 
 const MAX_GDTS: usize = 16;
 
@@ -18,25 +16,33 @@ const DPL3: u32 = 3 << 13;
 const PRESENT: u32 = 1 << 15;
 const LONG: u32 = 1 << 21;
 
-static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
-static mut GDTS: [(Gdt, Tss); 16] = [(Gdt::new(), Tss::new()); 16];
+static TASK_STATES: [OnceCell<IrqSafeMutex<Tss>>; MAX_GDTS] = [const { OnceCell::new() }; MAX_GDTS];
+static GDTS: [OnceCell<Gdt>; MAX_GDTS] = [const { OnceCell::new() }; MAX_GDTS];
 
 pub fn init() {
-    assert!(NEXT_ID.load(Ordering::Relaxed) < 16, "too many gdts");
+    let id = cpu_index();
+    assert!(id < MAX_GDTS, "too many gdts");
 
-    let (gdt, tss) = unsafe { &mut GDTS[NEXT_ID.fetch_add(1, Ordering::Acquire)] };
+    let tss = match TASK_STATES[id].set(IrqSafeMutex::new(Tss::new())) {
+        Ok(tss) => tss,
+        Err(err) => panic!("gdt for cpu {}: {}", id, err),
+    };
 
+    let mut gdt = Gdt::new();
     gdt.add_segment(PRESENT | DPL0 | CODE | LONG);
     gdt.add_segment(PRESENT | DPL3 | CODE | LONG);
     gdt.add_segment(PRESENT | DPL3 | DATA);
 
-    let tss_base = tss as *const _ as usize;
+    let tss_base = &*tss.lock() as *const Tss as usize;
     let tss_limit = mem::size_of::<Tss>() - 1;
     gdt.add_segment(PRESENT | TSS)
         .encode_tss_low(tss_base, tss_limit);
     gdt.add_segment(0).encode_tss_high(tss_base, tss_limit);
 
-    gdt.load();
+    match GDTS[id].set(gdt) {
+        Ok(gdt) => gdt.load(),
+        Err(err) => panic!("gdt for cpu {}: {}", id, err),
+    }
 
     unsafe {
         regs::load_cs(0x08);
@@ -48,13 +54,15 @@ pub fn init() {
 }
 
 pub fn set_kernel_stack(stack: usize) {
-    let id = 0;
-    let (_, tss) = unsafe { &mut GDTS[id] };
-    tss.set_kernel_stack(stack as u64);
+    let id = cpu_index();
+    match TASK_STATES.get(id).map(OnceCell::try_get) {
+        Some(Ok(tss)) => tss.lock().set_kernel_stack(stack as u64),
+        Some(Err(err)) => panic!("tss for cpu {}: {}", id, err),
+        None => panic!("no tss for cpu {}", id),
+    }
 }
 
 #[repr(C)]
-#[derive(Clone, Copy)]
 struct Gdt {
     entries: [Entry; 256],
     length: usize,
@@ -90,7 +98,6 @@ struct GdtPointer {
 }
 
 #[repr(packed)]
-#[derive(Clone, Copy)]
 struct Tss {
     _reserved0: u32,
     rsp0: u64,
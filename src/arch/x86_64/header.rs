@@ -0,0 +1,19 @@
+use multiboot2::header::{Architecture, FramebufferTag, Header, ModuleAlignTag};
+
+// The kernel is linked at a fixed address and enters through 32-bit `start`, so
+// relocation and EFI boot services handoff are not requested yet.
+#[repr(C)]
+struct Tags {
+    framebuffer: FramebufferTag,
+    module_align: ModuleAlignTag,
+}
+
+#[used]
+#[link_section = ".multiboot"]
+static HEADER: Header<Tags> = Header::new(
+    Architecture::I386,
+    Tags {
+        framebuffer: FramebufferTag::new(1024, 768, 32).optional(),
+        module_align: ModuleAlignTag::new(),
+    },
+);
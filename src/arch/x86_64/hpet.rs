@@ -0,0 +1,211 @@
+use core::{
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{
+    sync::OnceCell,
+    time::{clocksource, ClockEvent, ClockSource, Features},
+};
+
+use super::{acpi, io::Mmio, memory, regs};
+
+const TABLE_ADDRESS_SPACE: usize = 40;
+const TABLE_ADDRESS: usize = 44;
+const MMIO_SIZE: usize = 0x400;
+
+const CAP_COUNT_SIZE: u64 = 1 << 13;
+const CAP_LEGACY_ROUTE: u64 = 1 << 15;
+const CONF_ENABLE: u64 = 1 << 0;
+const CONF_LEGACY_ROUTE: u64 = 1 << 1;
+
+const TIMER_INT_ENABLE: u64 = 1 << 2;
+const TIMER_PERIODIC: u64 = 1 << 3;
+const TIMER_PERIODIC_CAP: u64 = 1 << 4;
+const TIMER_VAL_SET: u64 = 1 << 6;
+
+const FEMTOS_PER_SEC: u64 = 1_000_000_000_000_000;
+
+pub static HPET: OnceCell<Hpet> = OnceCell::new();
+static LEGACY: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HpetError {
+    NoTable,
+    NotMmio,
+    BadPeriod(u64),
+    Map(::memory::AllocError),
+}
+
+impl fmt::Display for HpetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoTable => f.write_str("no hpet table"),
+            Self::NotMmio => f.write_str("registers are not memory mapped"),
+            Self::BadPeriod(period) => write!(f, "bad counter period {} fs", period),
+            Self::Map(err) => write!(f, "mapping registers: {}", err),
+        }
+    }
+}
+
+impl From<::memory::AllocError> for HpetError {
+    fn from(err: ::memory::AllocError) -> Self {
+        Self::Map(err)
+    }
+}
+
+pub fn init() -> Result<(), HpetError> {
+    let table = acpi::find_table(b"HPET").ok_or(HpetError::NoTable)?;
+    if table.u8(TABLE_ADDRESS_SPACE) != Some(0) {
+        return Err(HpetError::NotMmio);
+    }
+    let addr = table.u64(TABLE_ADDRESS).ok_or(HpetError::NoTable)? as usize;
+    memory::map_mmio(addr, MMIO_SIZE)?;
+
+    let hpet = Hpet::new(Mmio::new(addr));
+    let period = hpet.caps.read() >> 32;
+    if period == 0 || period > 100_000_000 {
+        return Err(HpetError::BadPeriod(period));
+    }
+
+    hpet.conf
+        .write(hpet.conf.read() & !(CONF_ENABLE | CONF_LEGACY_ROUTE));
+    hpet.timer0_conf
+        .write(hpet.timer0_conf.read() & !(TIMER_INT_ENABLE | TIMER_PERIODIC));
+    hpet.counter.write(0);
+    hpet.conf.write(hpet.conf.read() | CONF_ENABLE);
+
+    crate::info!(
+        "hpet: {} Hz, {}-bit counter at {:#x}",
+        ClockSource::frequency(&hpet),
+        if hpet.is_wide() { 64 } else { 32 },
+        addr
+    );
+    let _ = HPET.set(hpet);
+    Ok(())
+}
+
+pub fn legacy_enabled() -> bool {
+    LEGACY.load(Ordering::Relaxed)
+}
+
+pub struct Hpet {
+    caps: Mmio<u64>,
+    conf: Mmio<u64>,
+    counter: Mmio<u64>,
+    timer0_conf: Mmio<u64>,
+    timer0_cmp: Mmio<u64>,
+}
+
+impl Hpet {
+    const fn new(base: Mmio<u64>) -> Self {
+        Self {
+            caps: base,
+            conf: base + 0x010,
+            counter: base + 0x0F0,
+            timer0_conf: base + 0x100,
+            timer0_cmp: base + 0x108,
+        }
+    }
+
+    #[inline]
+    pub fn is_wide(&self) -> bool {
+        self.caps.read() & CAP_COUNT_SIZE != 0
+    }
+
+    #[inline]
+    pub fn has_legacy_route(&self) -> bool {
+        self.caps.read() & CAP_LEGACY_ROUTE != 0
+    }
+
+    // Busy waits for `ms` milliseconds and returns the TSC ticks elapsed.
+    pub fn measure_tsc(&self, ms: u64) -> u64 {
+        let ticks = ClockSource::frequency(self) * ms / 1000;
+        let start = self.counter.read();
+        let tsc = unsafe { regs::read_tsc() };
+        while self.counter.read().wrapping_sub(start) < ticks {
+            core::hint::spin_loop();
+        }
+        let end = unsafe { regs::read_tsc() };
+        end - tsc
+    }
+
+    // Legacy replacement routes comparator 0 to IRQ0 but also takes IRQ8 away
+    // from the RTC, so it is only turned on once the comparator is in use.
+    fn route(&self) {
+        if !LEGACY.swap(true, Ordering::Relaxed) {
+            self.conf.write(self.conf.read() | CONF_LEGACY_ROUTE);
+        }
+    }
+
+    fn max_ticks(&self) -> u64 {
+        if self.is_wide() {
+            u64::MAX >> 1
+        } else {
+            u32::MAX as u64 >> 1
+        }
+    }
+}
+
+impl ClockSource for Hpet {
+    fn name(&self) -> &'static str {
+        "hpet"
+    }
+
+    fn rating(&self) -> u32 {
+        250
+    }
+
+    fn frequency(&self) -> u64 {
+        FEMTOS_PER_SEC / (self.caps.read() >> 32)
+    }
+
+    fn read(&self) -> u64 {
+        self.counter.read()
+    }
+}
+
+impl ClockEvent for Hpet {
+    fn name(&self) -> &'static str {
+        "hpet"
+    }
+
+    fn rating(&self) -> u32 {
+        150
+    }
+
+    fn features(&self) -> Features {
+        match self.timer0_conf.read() & TIMER_PERIODIC_CAP {
+            0 => Features::ONESHOT,
+            _ => Features::ONESHOT | Features::PERIODIC,
+        }
+    }
+
+    fn max_delta_ns(&self) -> u64 {
+        clocksource::ticks_to_ns(self.max_ticks(), ClockSource::frequency(self))
+    }
+
+    fn set_oneshot(&self, delta_ns: u64) {
+        self.route();
+        let ticks = clocksource::ns_to_ticks(delta_ns, ClockSource::frequency(self));
+        self.timer0_conf
+            .write((self.timer0_conf.read() & !TIMER_PERIODIC) | TIMER_INT_ENABLE);
+        self.timer0_cmp
+            .write(self.counter.read().wrapping_add(ticks.max(1)));
+    }
+
+    fn set_periodic(&self, period_ns: u64) {
+        self.route();
+        let ticks = clocksource::ns_to_ticks(period_ns, ClockSource::frequency(self)).max(1);
+        self.timer0_conf
+            .write(self.timer0_conf.read() | TIMER_INT_ENABLE | TIMER_PERIODIC | TIMER_VAL_SET);
+        self.timer0_cmp
+            .write(self.counter.read().wrapping_add(ticks));
+        self.timer0_cmp.write(ticks);
+    }
+
+    fn stop(&self) {
+        self.timer0_conf
+            .write(self.timer0_conf.read() & !(TIMER_INT_ENABLE | TIMER_PERIODIC));
+    }
+}
@@ -1,12 +1,14 @@
-use core::{
-    arch::asm,
-    hint,
-    sync::atomic::{AtomicUsize, Ordering},
-};
+use core::{arch::asm, hint};
 
 use crate::{
-    arch::x86_64::{pic::PIC1, regs},
+    arch::x86_64::{
+        ata, hpet, lapic,
+        pic::{PIC1, PIC2},
+        pit, ps2, regs, rtc,
+        vector::{self, FIRST_VECTOR, VECTOR_COUNT},
+    },
     println,
+    sync::Lazy,
 };
 
 const PRESENT: u8 = 1 << 7;
@@ -16,59 +18,65 @@ const DPL2: u8 = 2 << 5;
 const DPL3: u8 = 3 << 5;
 const INTERRUPT: u8 = 0xE;
 
-static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
-pub static mut IDT: Idt = Idt::new();
+static IDT: Lazy<Idt> = Lazy::new(new_idt);
 
 pub fn init() {
-    let idt = unsafe { &mut IDT };
-
-    if NEXT_ID.fetch_add(1, Ordering::Acquire) == 0 {
-        idt.set_handler_fn(0x00, division);
-        idt.set_handler_fn(0x01, debug);
-        idt.set_handler_fn(0x02, non_maskable);
-        idt.set_handler_fn(0x03, breakpoint);
-        idt.set_handler_fn(0x04, overflow);
-        idt.set_handler_fn(0x05, bound_range);
-        idt.set_handler_fn(0x06, invalid_opcode);
-        idt.set_handler_fn(0x07, device_not_available);
-        idt.set_handler_fn(0x08, double);
-        idt.set_handler_fn(0x0A, invalid_tss);
-        idt.set_handler_fn(0x0B, segment_not_present);
-        idt.set_handler_fn(0x0C, stack_segment);
-        idt.set_handler_fn(0x0D, general_protection);
-        idt.set_handler_fn(0x0E, page);
-        idt.set_handler_fn(0x10, x87_fp);
-        idt.set_handler_fn(0x11, alignment_check);
-        idt.set_handler_fn(0x12, machine_check);
-        idt.set_handler_fn(0x13, simd_fp);
-        idt.set_handler_fn(0x14, virtualization);
-        idt.set_handler_fn(0x15, control_protection);
-        idt.set_handler_fn(0x1C, hypervisor_injection);
-        idt.set_handler_fn(0x1D, vmm_communication);
-        idt.set_handler_fn(0x1E, security);
-
-        idt.set_handler_fn(0x20, pit);
-        idt.set_handler_fn(0x21, keyboard);
-        idt.set_handler_fn(0x22, cascade);
-        idt.set_handler_fn(0x23, com2);
-        idt.set_handler_fn(0x24, com1);
-        idt.set_handler_fn(0x25, lpt2);
-        idt.set_handler_fn(0x26, floppy_disk);
-        idt.set_handler_fn(0x27, lpt1);
-        idt.set_handler_fn(0x28, cmos);
-        idt.set_handler_fn(0x29, peripheral1);
-        idt.set_handler_fn(0x2A, peripheral2);
-        idt.set_handler_fn(0x2B, peripheral3);
-        idt.set_handler_fn(0x2C, mouse);
-        idt.set_handler_fn(0x2D, fpu);
-        idt.set_handler_fn(0x2E, primary_ata);
-        idt.set_handler_fn(0x2F, secondary_ata);
-
-        idt.set_handler_fn(0x7E, lapic);
-        idt.set_handler_fn(0x7F, invalidate_tlb);
+    IDT.load();
+}
+
+fn new_idt() -> Idt {
+    let mut idt = Idt::new();
+
+    idt.set_handler_fn(0x00, division);
+    idt.set_handler_fn(0x01, debug);
+    idt.set_handler_fn(0x02, non_maskable);
+    idt.set_handler_fn(0x03, breakpoint);
+    idt.set_handler_fn(0x04, overflow);
+    idt.set_handler_fn(0x05, bound_range);
+    idt.set_handler_fn(0x06, invalid_opcode);
+    idt.set_handler_fn(0x07, device_not_available);
+    idt.set_handler_fn(0x08, double);
+    idt.set_handler_fn(0x0A, invalid_tss);
+    idt.set_handler_fn(0x0B, segment_not_present);
+    idt.set_handler_fn(0x0C, stack_segment);
+    idt.set_handler_fn(0x0D, general_protection);
+    idt.set_handler_fn(0x0E, page);
+    idt.set_handler_fn(0x10, x87_fp);
+    idt.set_handler_fn(0x11, alignment_check);
+    idt.set_handler_fn(0x12, machine_check);
+    idt.set_handler_fn(0x13, simd_fp);
+    idt.set_handler_fn(0x14, virtualization);
+    idt.set_handler_fn(0x15, control_protection);
+    idt.set_handler_fn(0x1C, hypervisor_injection);
+    idt.set_handler_fn(0x1D, vmm_communication);
+    idt.set_handler_fn(0x1E, security);
+
+    idt.set_handler_fn(0x20, pit);
+    idt.set_handler_fn(0x21, keyboard);
+    idt.set_handler_fn(0x22, cascade);
+    idt.set_handler_fn(0x23, com2);
+    idt.set_handler_fn(0x24, com1);
+    idt.set_handler_fn(0x25, lpt2);
+    idt.set_handler_fn(0x26, floppy_disk);
+    idt.set_handler_fn(0x27, lpt1);
+    idt.set_handler_fn(0x28, cmos);
+    idt.set_handler_fn(0x29, peripheral1);
+    idt.set_handler_fn(0x2A, peripheral2);
+    idt.set_handler_fn(0x2B, peripheral3);
+    idt.set_handler_fn(0x2C, mouse);
+    idt.set_handler_fn(0x2D, fpu);
+    idt.set_handler_fn(0x2E, primary_ata);
+    idt.set_handler_fn(0x2F, secondary_ata);
+
+    for (idx, handler) in VECTORS.iter().enumerate() {
+        idt.set_handler_fn(FIRST_VECTOR as usize + idx, *handler);
     }
 
-    idt.load();
+    idt.set_handler_fn(0x7D, wake);
+    idt.set_handler_fn(0x7E, lapic);
+    idt.set_handler_fn(0x7F, invalidate_tlb);
+
+    idt
 }
 
 pub fn enable_interrupts() {
@@ -79,6 +87,14 @@ pub fn disable_interrupts() {
     unsafe { asm!("cli", options(nomem, nostack, att_syntax)) }
 }
 
+pub fn wait_for_interrupt() {
+    unsafe { asm!("sti", "hlt", "cli", options(nomem, nostack, att_syntax)) }
+}
+
+pub fn interrupts_enabled() -> bool {
+    unsafe { regs::read_rflags() & (1 << 9) != 0 }
+}
+
 #[repr(transparent)]
 pub struct Idt {
     entries: [Entry; 256],
@@ -444,12 +460,17 @@ exception_with_error!(security, |_err_code, _stack| {
 });
 
 interrupt!(pit, |_stack| {
-    // println!("pit interrupt");
+    if hpet::legacy_enabled() {
+        crate::time::clockevent::interrupt();
+    } else {
+        pit::PIT.interrupt();
+    }
     PIC1.lock().send_eoi();
 });
 
 interrupt!(keyboard, |_stack| {
-    println!("keyboard interrupt");
+    ps2::keyboard_interrupt();
+    PIC1.lock().send_eoi();
 });
 
 interrupt!(cascade, |_stack| {
@@ -465,7 +486,9 @@ interrupt!(com1, |_stack| {
 });
 
 interrupt!(lpt2, |_stack| {
-    println!("LPT2 interrupt");
+    vector::dispatch_irq(5);
+    PIC2.lock().send_eoi();
+    PIC1.lock().send_eoi();
 });
 
 interrupt!(floppy_disk, |_stack| {
@@ -477,23 +500,33 @@ interrupt!(lpt1, |_stack| {
 });
 
 interrupt!(cmos, |_stack| {
-    println!("CMOS interrupt");
+    rtc::interrupt();
+    PIC2.lock().send_eoi();
+    PIC1.lock().send_eoi();
 });
 
 interrupt!(peripheral1, |_stack| {
-    println!("peripheral interrupt");
+    vector::dispatch_irq(9);
+    PIC2.lock().send_eoi();
+    PIC1.lock().send_eoi();
 });
 
 interrupt!(peripheral2, |_stack| {
-    println!("peripheral interrupt");
+    vector::dispatch_irq(10);
+    PIC2.lock().send_eoi();
+    PIC1.lock().send_eoi();
 });
 
 interrupt!(peripheral3, |_stack| {
-    println!("peripheral interrupt");
+    vector::dispatch_irq(11);
+    PIC2.lock().send_eoi();
+    PIC1.lock().send_eoi();
 });
 
 interrupt!(mouse, |_stack| {
-    println!("mouse interrupt");
+    ps2::mouse_interrupt();
+    PIC2.lock().send_eoi();
+    PIC1.lock().send_eoi();
 });
 
 interrupt!(fpu, |_stack| {
@@ -501,15 +534,45 @@ interrupt!(fpu, |_stack| {
 });
 
 interrupt!(primary_ata, |_stack| {
-    println!("primary ATA interrupt");
+    ata::interrupt(0);
+    PIC2.lock().send_eoi();
+    PIC1.lock().send_eoi();
 });
 
 interrupt!(secondary_ata, |_stack| {
-    println!("secondary ATA interrupt");
+    ata::interrupt(1);
+    PIC2.lock().send_eoi();
+    PIC1.lock().send_eoi();
 });
 
 interrupt!(lapic, |_stack| {
-    // println!("lapic interrupt");
+    lapic::eoi();
+    crate::time::clockevent::interrupt();
+});
+
+macro_rules! vectors {
+    ($($idx:literal => $name:ident),* $(,)?) => {
+        $(interrupt!($name, |_stack| {
+            vector::dispatch($idx);
+        });)*
+
+        static VECTORS: [HandlerFn; VECTOR_COUNT] = [$($name),*];
+    };
+}
+
+vectors!(
+    0 => vector0, 1 => vector1, 2 => vector2, 3 => vector3,
+    4 => vector4, 5 => vector5, 6 => vector6, 7 => vector7,
+    8 => vector8, 9 => vector9, 10 => vector10, 11 => vector11,
+    12 => vector12, 13 => vector13, 14 => vector14, 15 => vector15,
+    16 => vector16, 17 => vector17, 18 => vector18, 19 => vector19,
+    20 => vector20, 21 => vector21, 22 => vector22, 23 => vector23,
+    24 => vector24, 25 => vector25, 26 => vector26, 27 => vector27,
+    28 => vector28, 29 => vector29, 30 => vector30, 31 => vector31,
+);
+
+interrupt!(wake, |_stack| {
+    lapic::eoi();
 });
 
 interrupt!(invalidate_tlb, |_stack| {
@@ -10,6 +10,26 @@ pub unsafe fn outb(port: u16, val: u8) {
     asm!("outb %al, %dx", in("al") val, in("dx") port, options(nomem, nostack, att_syntax));
 }
 
+pub unsafe fn inw(port: u16) -> u16 {
+    let ret: u16;
+    asm!("inw %dx, %ax", in("dx") port, out("ax") ret, options(nomem, nostack, att_syntax));
+    ret
+}
+
+pub unsafe fn outw(port: u16, val: u16) {
+    asm!("outw %ax, %dx", in("ax") val, in("dx") port, options(nomem, nostack, att_syntax));
+}
+
+pub unsafe fn inl(port: u16) -> u32 {
+    let ret: u32;
+    asm!("inl %dx, %eax", in("dx") port, out("eax") ret, options(nomem, nostack, att_syntax));
+    ret
+}
+
+pub unsafe fn outl(port: u16, val: u32) {
+    asm!("outl %eax, %dx", in("eax") val, in("dx") port, options(nomem, nostack, att_syntax));
+}
+
 pub trait PortInOut {
     unsafe fn port_in(port: u16) -> Self;
     unsafe fn port_out(port: u16, val: Self);
@@ -25,6 +45,26 @@ impl PortInOut for u8 {
     }
 }
 
+impl PortInOut for u16 {
+    unsafe fn port_in(port: u16) -> Self {
+        inw(port)
+    }
+
+    unsafe fn port_out(port: u16, val: Self) {
+        outw(port, val)
+    }
+}
+
+impl PortInOut for u32 {
+    unsafe fn port_in(port: u16) -> Self {
+        inl(port)
+    }
+
+    unsafe fn port_out(port: u16, val: Self) {
+        outl(port, val)
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct Pio<T: PortInOut> {
     port: u16,
@@ -1,13 +1,121 @@
-use core::sync::atomic::{AtomicU64, Ordering};
+use core::{arch::x86_64::__cpuid, fmt};
 
-use super::io::{self, Mmio};
-use super::regs;
+use crate::{
+    sync::OnceCell,
+    time::{clocksource, ClockEvent, Features, NANOS_PER_SEC},
+};
 
-pub static TSC_MHZ: AtomicU64 = AtomicU64::new(0);
+use super::{io::Mmio, memory, regs, tsc};
 
-pub fn init() {
-    if TSC_MHZ.load(Ordering::Relaxed) == 0 {
-        TSC_MHZ.store(unsafe { measure_tsc_mhz() }, Ordering::Release);
+const MSR_APIC_BASE: u64 = 0x1B;
+const MSR_TSC_DEADLINE: u64 = 0x6E0;
+const APIC_BASE_MASK: u64 = 0xffff_f000;
+const MMIO_SIZE: usize = 0x1000;
+
+const SVR_ENABLE: u32 = 0x100;
+const SPURIOUS_VECTOR: u32 = 0x27;
+pub const TIMER_VECTOR: u32 = 0x7E;
+pub const WAKE_VECTOR: u32 = 0x7D;
+
+const TIMER_ONESHOT: u32 = 0 << 17;
+const TIMER_PERIODIC: u32 = 1 << 17;
+const TIMER_TSC_DEADLINE: u32 = 2 << 17;
+const TIMER_MASKED: u32 = 1 << 16;
+const TIMER_DIVIDE_16: u32 = 0x3;
+
+const CALIBRATE_MS: u64 = 10;
+const MAX_DEADLINE_NS: u64 = 3600 * NANOS_PER_SEC;
+
+pub static LAPIC: OnceCell<LocalApic> = OnceCell::new();
+static TIMER: OnceCell<LapicTimer> = OnceCell::new();
+static DEADLINE: OnceCell<TscDeadline> = OnceCell::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LapicError {
+    Disabled,
+    Map(::memory::AllocError),
+}
+
+impl fmt::Display for LapicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Disabled => f.write_str("disabled by firmware"),
+            Self::Map(err) => write!(f, "mapping registers: {}", err),
+        }
+    }
+}
+
+impl From<::memory::AllocError> for LapicError {
+    fn from(err: ::memory::AllocError) -> Self {
+        Self::Map(err)
+    }
+}
+
+pub fn init() -> Result<(), LapicError> {
+    if LAPIC.is_initialized() {
+        return Ok(());
+    }
+
+    let msr = unsafe { regs::read_msr(MSR_APIC_BASE) };
+    if msr & (1 << 11) == 0 {
+        return Err(LapicError::Disabled);
+    }
+    let base = (msr & APIC_BASE_MASK) as usize;
+    memory::map_mmio(base, MMIO_SIZE)?;
+
+    let lapic = LocalApic::new(Mmio::new(base));
+    lapic.svr.write(SVR_ENABLE | SPURIOUS_VECTOR);
+    lapic.timer.write(TIMER_MASKED | TIMER_VECTOR);
+    let _ = LAPIC.set(lapic);
+    Ok(())
+}
+
+// Both timers stop in deep C-states unless the CPU reports an always running
+// APIC timer.
+fn always_running() -> bool {
+    unsafe { __cpuid(0).eax >= 6 && __cpuid(6).eax & (1 << 2) != 0 }
+}
+
+pub fn timer() -> Option<&'static LapicTimer> {
+    if let Some(timer) = TIMER.get() {
+        return Some(timer);
+    }
+    let lapic = LAPIC.get()?;
+    let freq = lapic.calibrate_timer();
+    crate::info!("lapic: timer at {} Hz", freq);
+    let timer = LapicTimer {
+        timer: lapic.timer,
+        ticr: lapic.ticr,
+        tdcr: lapic.tdcr,
+        freq,
+    };
+    match TIMER.set(timer) {
+        Ok(timer) => Some(timer),
+        Err(_) => TIMER.get(),
+    }
+}
+
+pub fn tsc_deadline() -> Option<&'static TscDeadline> {
+    if !tsc::has_deadline() || tsc::tsc_hz() == 0 {
+        return None;
+    }
+    let lapic = LAPIC.get()?;
+    match DEADLINE.set(TscDeadline { timer: lapic.timer }) {
+        Ok(deadline) => Some(deadline),
+        Err(_) => DEADLINE.get(),
+    }
+}
+
+// Brings a CPU out of `hlt` so that it rechecks whatever it is waiting on.
+pub fn wake_cpu(id: usize) {
+    if let Some(lapic) = LAPIC.get() {
+        lapic.send_ipi(id, WAKE_VECTOR as usize);
+    }
+}
+
+pub fn eoi() {
+    if let Some(lapic) = LAPIC.get() {
+        lapic.send_eoi();
     }
 }
 
@@ -38,19 +146,21 @@ impl LocalApic {
         }
     }
 
-    pub fn init(&self) {
-        self.svr.write(0x127);
-        self.timer.write(0x7E);
-        self.tdcr.write(0x3);
-        self.ticr.write(u32::MAX);
-        unsafe { tsc_delay(100000) }
-        self.timer.write(0x10000);
-
-        let freq = u32::MAX - self.tccr.read();
+    #[inline]
+    pub fn id(&self) -> u32 {
+        self.id.read() >> 24
+    }
 
-        self.timer.write(0x7E | 0x20000);
-        self.tdcr.write(0x3);
-        self.ticr.write(freq);
+    // Counts down from the maximum for a fixed number of TSC ticks.
+    fn calibrate_timer(&self) -> u64 {
+        self.timer
+            .write(TIMER_MASKED | TIMER_ONESHOT | TIMER_VECTOR);
+        self.tdcr.write(TIMER_DIVIDE_16);
+        self.ticr.write(u32::MAX);
+        unsafe { tsc_delay(CALIBRATE_MS * 1000) }
+        let elapsed = u32::MAX - self.tccr.read();
+        self.ticr.write(0);
+        elapsed as u64 * 1000 / CALIBRATE_MS
     }
 
     pub fn wake(&self, id: usize, addr: usize) {
@@ -72,8 +182,9 @@ impl LocalApic {
         }
     }
 
-    pub fn send_ipi(&self, num: usize) {
-        self.icr_lo.write(0xC0000 | (num as u32));
+    pub fn send_ipi(&self, id: usize, num: usize) {
+        self.icr_hi.write((id as u32) << 24);
+        self.icr_lo.write(0x4000 | (num as u32));
         while self.icr_lo.read() & (1 << 12) != 0 {}
     }
 
@@ -82,29 +193,98 @@ impl LocalApic {
     }
 }
 
-unsafe fn measure_tsc_mhz() -> u64 {
-    io::outb(0x61, (io::inb(0x61) & 0xDD) | 0x01);
+pub struct LapicTimer {
+    timer: Mmio<u32>,
+    ticr: Mmio<u32>,
+    tdcr: Mmio<u32>,
+    freq: u64,
+}
+
+impl ClockEvent for LapicTimer {
+    fn name(&self) -> &'static str {
+        "lapic"
+    }
+
+    fn rating(&self) -> u32 {
+        if always_running() {
+            300
+        } else {
+            100
+        }
+    }
 
-    let freq = 1193180 / 100;
-    io::outb(0x43, 0xB2);
-    io::outb(0x42, (freq & 0xFF) as u8);
-    io::outb(0x42, ((freq >> 8) & 0xFF) as u8);
+    fn features(&self) -> Features {
+        Features::ONESHOT | Features::PERIODIC
+    }
 
-    io::outb(0x61, io::inb(0x61) & 0xDE);
-    io::outb(0x61, io::inb(0x61) | 0x01);
+    fn max_delta_ns(&self) -> u64 {
+        clocksource::ticks_to_ns(u32::MAX as u64, self.freq)
+    }
 
-    let start = regs::read_tsc();
-    if (io::inb(0x61) & 0x20) != 0 {
-        while (io::inb(0x61) & 0x20) != 0 {}
-    } else {
-        while (io::inb(0x61) & 0x20) == 0 {}
+    fn set_oneshot(&self, delta_ns: u64) {
+        let ticks = clocksource::ns_to_ticks(delta_ns, self.freq).clamp(1, u32::MAX as u64);
+        self.timer.write(TIMER_ONESHOT | TIMER_VECTOR);
+        self.tdcr.write(TIMER_DIVIDE_16);
+        self.ticr.write(ticks as u32);
+    }
+
+    fn set_periodic(&self, period_ns: u64) {
+        let ticks = clocksource::ns_to_ticks(period_ns, self.freq).clamp(1, u32::MAX as u64);
+        self.timer.write(TIMER_PERIODIC | TIMER_VECTOR);
+        self.tdcr.write(TIMER_DIVIDE_16);
+        self.ticr.write(ticks as u32);
+    }
+
+    fn stop(&self) {
+        self.timer.write(TIMER_MASKED | TIMER_VECTOR);
+        self.ticr.write(0);
+    }
+}
+
+pub struct TscDeadline {
+    timer: Mmio<u32>,
+}
+
+impl ClockEvent for TscDeadline {
+    fn name(&self) -> &'static str {
+        "tsc-deadline"
+    }
+
+    fn rating(&self) -> u32 {
+        if always_running() {
+            350
+        } else {
+            120
+        }
     }
 
-    (regs::read_tsc() - start) / 10000
+    fn features(&self) -> Features {
+        Features::ONESHOT
+    }
+
+    fn max_delta_ns(&self) -> u64 {
+        MAX_DEADLINE_NS
+    }
+
+    // The LVT write has to be ordered before the MSR write.
+    fn set_oneshot(&self, delta_ns: u64) {
+        self.timer.write(TIMER_TSC_DEADLINE | TIMER_VECTOR);
+        let ticks = clocksource::ns_to_ticks(delta_ns, tsc::tsc_hz()).max(1);
+        unsafe {
+            core::arch::asm!("mfence", options(nostack, att_syntax));
+            regs::write_msr(MSR_TSC_DEADLINE, regs::read_tsc() + ticks);
+        }
+    }
+
+    fn set_periodic(&self, _period_ns: u64) {}
+
+    fn stop(&self) {
+        unsafe { regs::write_msr(MSR_TSC_DEADLINE, 0) }
+    }
 }
 
-unsafe fn tsc_delay(msecs: u64) {
+unsafe fn tsc_delay(usecs: u64) {
     let start = regs::read_tsc();
-    let freq = TSC_MHZ.load(Ordering::Relaxed);
-    while regs::read_tsc() < start + msecs * freq {}
+    let freq = tsc::tsc_mhz();
+    while regs::read_tsc() < start + usecs * freq {}
 }
@@ -1,23 +1,37 @@
+use core::ptr;
+
 use memory::{
     BitmapAlloc, BumpAlloc, Frame, FrameAlloc, FrameRange, MemoryArea, PageFlags, PageMapper,
     PAGE_MASK, PAGE_SIZE,
 };
 
-use multiboot2::{AreaType, BootInfo, MemoryMapTag, TagType};
+use multiboot2::{AreaType, BootInfo, MemoryMapTag, ModuleTag};
 
-use crate::sync::Mutex;
+use crate::{
+    cmdline::Size,
+    sync::{IrqSafeMutex, OnceCell},
+};
 
 const FIRST_FREE_FRAME: usize = 0x100000;
+const MAX_AREAS: usize = 512;
+
+crate::param!(static MEM: Option<Size> = "mem", None);
 
-static mut MMAP: [MemoryArea; 512] = [MemoryArea::new(0, 0); 512];
+static MMAP: OnceCell<([MemoryArea; MAX_AREAS], usize)> = OnceCell::new();
 
 pub fn init(boot_info: &BootInfo) {
     extern "C" {
         static kend: u8;
     }
 
-    let mmap_tag: &MemoryMapTag = boot_info.find_tag(TagType::Mmap).unwrap();
+    let mmap_tag: MemoryMapTag = boot_info.find_tag().unwrap();
+    let modules_end = boot_info
+        .find_tags::<ModuleTag>()
+        .map(|module| module.end_addr())
+        .max()
+        .unwrap_or(0);
 
+    let mut areas = [MemoryArea::new(0, 0); MAX_AREAS];
     let mut len = 0;
     for b_area in mmap_tag.areas() {
         if b_area.typ() != AreaType::Available {
@@ -28,22 +42,32 @@ pub fn init(boot_info: &BootInfo) {
             .start_addr()
             .max(FIRST_FREE_FRAME)
             .max(boot_info.end_addr())
+            .max(modules_end)
             .max(unsafe { &kend } as *const _ as usize);
         start += (PAGE_SIZE - (start & PAGE_MASK)) & PAGE_MASK;
 
         let mut end = b_area.end_addr();
         end += (PAGE_SIZE - (start & PAGE_MASK)) & PAGE_MASK;
+        if let Some(limit) = MEM.get() {
+            end = end.min(limit.bytes());
+        }
 
         let size = end.saturating_sub(start);
         if size == 0 {
             continue;
         }
 
-        unsafe { MMAP[len] = MemoryArea::new(start, size) }
+        if len == MAX_AREAS {
+            break;
+        }
+        areas[len] = MemoryArea::new(start, size);
         len += 1;
     }
 
-    let mmap = unsafe { &MMAP[0..len] };
+    let mmap = match MMAP.set((areas, len)) {
+        Ok((areas, len)) => &areas[..*len],
+        Err(err) => panic!("memory map: {}", err),
+    };
     let mut bump_alloc = BumpAlloc::new(mmap);
 
     {
@@ -65,10 +89,59 @@ pub fn init(boot_info: &BootInfo) {
     }
 
     let bitmap_alloc = BitmapAlloc::new(bump_alloc).unwrap();
-    *INNER_ALLOC.lock() = Some(bitmap_alloc);
+    if let Err(err) = INNER_ALLOC.set(IrqSafeMutex::new(bitmap_alloc)) {
+        panic!("frame allocator: {}", err);
+    }
+}
+
+// Physical memory is identity mapped, so DMA buffers are used through their
+// physical address.
+pub fn alloc_dma(count: usize) -> Result<usize, memory::AllocError> {
+    let mut alloc = FRAME_ALLOC;
+    let addr = alloc.alloc(count)?.first().addr();
+    unsafe { ptr::write_bytes(addr as *mut u8, 0, count * PAGE_SIZE) };
+    Ok(addr)
+}
+
+pub fn free_dma(addr: usize, count: usize) {
+    let mut alloc = FRAME_ALLOC;
+    alloc.free(FrameRange::from_addr(addr, count));
+}
+
+// Device registers have side effects on every access, so they are mapped
+// uncached.
+pub fn map_mmio(addr: usize, size: usize) -> Result<(), memory::AllocError> {
+    map(
+        addr,
+        size,
+        PageFlags::PRESENT | PageFlags::WRITE | PageFlags::NO_CACHE | PageFlags::WRITE_THROUGH,
+    )
+}
+
+// Framebuffers are plain memory to the device, write-through keeps reads
+// cached while every write still reaches the screen.
+pub fn map_framebuffer(addr: usize, size: usize) -> Result<(), memory::AllocError> {
+    map(
+        addr,
+        size,
+        PageFlags::PRESENT | PageFlags::WRITE | PageFlags::WRITE_THROUGH,
+    )
+}
+
+fn map(addr: usize, size: usize, flags: PageFlags) -> Result<(), memory::AllocError> {
+    let _guard = MAPPER_LOCK.lock();
+    let mut mapper = PageMapper::current(FRAME_ALLOC);
+    let mut page = addr & !PAGE_MASK;
+    while page < addr + size {
+        mapper.map_page(page, Frame::from_addr(page), flags)?;
+        page += PAGE_SIZE;
+    }
+    mapper.make_current();
+    Ok(())
 }
 
-static INNER_ALLOC: Mutex<Option<BitmapAlloc>> = Mutex::new(None);
+static MAPPER_LOCK: IrqSafeMutex<()> = IrqSafeMutex::new(());
+static INNER_ALLOC: OnceCell<IrqSafeMutex<BitmapAlloc>> = OnceCell::new();
 pub static FRAME_ALLOC: LockedAlloc = LockedAlloc;
 
 #[derive(Clone, Copy)]
@@ -76,18 +149,16 @@ pub struct LockedAlloc;
 
 impl FrameAlloc for LockedAlloc {
     fn alloc(&mut self, count: usize) -> Result<FrameRange, memory::AllocError> {
-        if let Some(ref mut alloc) = *INNER_ALLOC.lock() {
-            alloc.alloc(count)
-        } else {
-            panic!("no frame allocator");
+        match INNER_ALLOC.try_get() {
+            Ok(alloc) => alloc.lock().alloc(count),
+            Err(_) => Err(memory::AllocError::Uninitialized),
         }
     }
 
     fn free(&mut self, frames: FrameRange) {
-        if let Some(ref mut alloc) = *INNER_ALLOC.lock() {
-            alloc.free(frames)
-        } else {
-            panic!("no frame allocator");
+        match INNER_ALLOC.try_get() {
+            Ok(alloc) => alloc.lock().free(frames),
+            Err(err) => panic!("frame allocator: {}", err),
         }
     }
 }
@@ -1,31 +1,154 @@
-use core::arch::{asm, global_asm};
+use core::{
+    arch::{asm, global_asm, x86_64::__cpuid},
+    sync::atomic::{AtomicU8, AtomicUsize, Ordering},
+};
 
+use multiboot2::CmdlineTag;
+
+use crate::time::{clockevent, clocksource};
+
+pub mod acpi;
+pub mod ata;
 pub mod debug;
 pub mod gdt;
+mod header;
+pub mod hpet;
 pub mod idt;
 pub mod io;
 pub mod lapic;
 pub mod memory;
+pub mod pci;
 pub mod pic;
+pub mod pit;
+pub mod ps2;
 pub mod regs;
+pub mod rtc;
 pub mod serial;
+mod smep;
+pub mod tsc;
+pub mod vector;
+pub mod vga;
 
 global_asm!(include_str!("boot.s"), options(att_syntax));
 
+pub const MAX_CPUS: usize = 16;
+
+static CPU_COUNT: AtomicUsize = AtomicUsize::new(0);
+// Indexed by APIC id, holds the dense index plus one so that zero means the
+// CPU has not been brought up.
+static CPU_INDICES: [AtomicU8; 256] = [const { AtomicU8::new(0) }; 256];
+static CPU_IDS: [AtomicU8; MAX_CPUS] = [const { AtomicU8::new(0) }; MAX_CPUS];
+
 #[no_mangle]
 pub extern "C" fn kernel_entry(magic: u64, info: *const u8) -> ! {
-    let boot_info = multiboot2::init(magic, info).expect("unsupported bootloader");
-
+    register_cpu();
     serial::init();
+    crate::log::register_sink(&debug::SERIAL_SINK);
+    crate::log::register_panic_sink(&debug::PANIC_SINK);
+
+    let boot_info = match unsafe { multiboot2::init(magic, info) } {
+        Ok(boot_info) => boot_info,
+        Err(err) => panic!("boot info: {}", err),
+    };
+
+    if let Some(cmdline) = boot_info.find_tag::<CmdlineTag>() {
+        crate::cmdline::init(cmdline.get());
+    }
+    crate::log::init();
+
     gdt::init();
     pic::init();
     idt::init();
+    tsc::init();
+
+    memory::init(&boot_info);
+    smep::init();
+
+    if let Err(err) = acpi::init(&boot_info) {
+        crate::warn!("acpi: {}", err);
+    }
+    init_clocks();
+    match rtc::init() {
+        Ok(now) => {
+            crate::time::init(now);
+            crate::info!("wall clock {}", now);
+        }
+        Err(err) => crate::warn!("rtc: {}", err),
+    }
 
-    memory::init(boot_info);
+    crate::console::init(&boot_info);
+    crate::input::init();
+    ps2::init();
+    pci::init();
+    ata::init();
+    crate::virtio::init();
+    crate::net::init();
+    crate::vfs::init();
 
     crate::kernel_main();
 }
 
+fn init_clocks() {
+    pit::PIT.init();
+    if let Err(err) = hpet::init() {
+        crate::warn!("hpet: {}", err);
+    }
+    let hpet = hpet::HPET.get();
+    tsc::calibrate(hpet);
+
+    clocksource::register(&tsc::TSC);
+    // A 32-bit counter wraps within minutes and the readout is not extended.
+    if let Some(hpet) = hpet.filter(|hpet| hpet.is_wide()) {
+        clocksource::register(hpet);
+    }
+    clocksource::register(&pit::PIT);
+    clocksource::select();
+
+    match lapic::init() {
+        Ok(()) => {
+            if let Some(timer) = lapic::timer() {
+                clockevent::register(timer);
+            }
+            if let Some(deadline) = lapic::tsc_deadline() {
+                clockevent::register(deadline);
+            }
+        }
+        Err(err) => crate::warn!("lapic: {}", err),
+    }
+    if let Some(hpet) = hpet.filter(|hpet| hpet.has_legacy_route()) {
+        clockevent::register(hpet);
+    }
+    clockevent::select();
+}
+
+pub fn cpu_id() -> usize {
+    (unsafe { __cpuid(1) }.ebx >> 24) as usize
+}
+
+// Hands out indices in bring-up order for per-CPU tables, APIC ids can be
+// sparse.
+pub fn register_cpu() -> usize {
+    let idx = CPU_COUNT.fetch_add(1, Ordering::Relaxed);
+    assert!(idx < MAX_CPUS, "more than {} cpus", MAX_CPUS);
+    CPU_IDS[idx].store(cpu_id() as u8, Ordering::Relaxed);
+    CPU_INDICES[cpu_id()].store(idx as u8 + 1, Ordering::Relaxed);
+    idx
+}
+
+// The APIC id of the CPU brought up as `idx`.
+pub fn cpu_id_of(idx: usize) -> usize {
+    CPU_IDS[idx].load(Ordering::Relaxed) as usize
+}
+
+// Only the boot CPU runs before bring-up, and it gets index 0 anyway.
+pub fn cpu_index() -> usize {
+    (CPU_INDICES[cpu_id()].load(Ordering::Relaxed) as usize).saturating_sub(1)
+}
+
+pub fn read_tsc() -> u64 {
+    unsafe { regs::read_tsc() }
+}
+
 pub fn halt() -> ! {
     loop {
         unsafe { asm!("hlt", options(nomem, nostack, att_syntax)) }
@@ -0,0 +1,147 @@
+use crate::{
+    pci::{ConfigAccess, PciAddress},
+    sync::{IrqSafeMutex, OnceCell},
+};
+
+use super::{
+    acpi,
+    io::{Mmio, Pio},
+    memory,
+};
+
+const MCFG_ENTRIES: usize = 44;
+const MCFG_ENTRY_SIZE: usize = 16;
+const ECAM_BUS_SHIFT: usize = 20;
+
+const CONFIG_ENABLE: u32 = 1 << 31;
+
+static LEGACY: LegacyConfig = LegacyConfig::new();
+static ECAM: OnceCell<Ecam> = OnceCell::new();
+
+pub fn init() {
+    match init_ecam() {
+        Some(ecam) => crate::pci::init(ecam),
+        None => crate::pci::init(&LEGACY),
+    }
+}
+
+// Only the first segment is used, QEMU's q35 machine has a single one.
+fn init_ecam() -> Option<&'static Ecam> {
+    let mcfg = acpi::find_table(b"MCFG")?;
+    let entries = mcfg.bytes().len().saturating_sub(MCFG_ENTRIES) / MCFG_ENTRY_SIZE;
+    let ecam = (0..entries)
+        .map(|idx| MCFG_ENTRIES + idx * MCFG_ENTRY_SIZE)
+        .filter(|&off| mcfg.u16(off + 8) == Some(0))
+        .find_map(|off| {
+            Some(Ecam {
+                base: mcfg.u64(off)? as usize,
+                start_bus: mcfg.u8(off + 10)?,
+                end_bus: mcfg.u8(off + 11)?,
+            })
+        })?;
+
+    let buses = (ecam.end_bus - ecam.start_bus) as usize + 1;
+    if let Err(err) = memory::map_mmio(ecam.base, buses << ECAM_BUS_SHIFT) {
+        crate::warn!("pci: mapping ecam: {}", err);
+        return None;
+    }
+    crate::info!(
+        "pci: ecam at {:#x} for buses {}-{}",
+        ecam.base,
+        ecam.start_bus,
+        ecam.end_bus
+    );
+    ECAM.set(ecam).ok()
+}
+
+struct LegacyPorts {
+    addr: Pio<u32>,
+    data: Pio<u32>,
+}
+
+pub struct LegacyConfig {
+    ports: IrqSafeMutex<LegacyPorts>,
+}
+
+impl LegacyConfig {
+    const fn new() -> Self {
+        Self {
+            ports: IrqSafeMutex::new(LegacyPorts {
+                addr: Pio::new(0xcf8),
+                data: Pio::new(0xcfc),
+            }),
+        }
+    }
+
+    fn address(addr: PciAddress, offset: u16) -> u32 {
+        CONFIG_ENABLE
+            | ((addr.bus as u32) << 16)
+            | ((addr.device as u32) << 11)
+            | ((addr.function as u32) << 8)
+            | (offset as u32 & 0xfc)
+    }
+}
+
+impl ConfigAccess for LegacyConfig {
+    fn name(&self) -> &'static str {
+        "legacy ports"
+    }
+
+    fn size(&self) -> u16 {
+        0x100
+    }
+
+    fn read(&self, addr: PciAddress, offset: u16) -> u32 {
+        let ports = self.ports.lock();
+        ports.addr.write(Self::address(addr, offset));
+        ports.data.read()
+    }
+
+    fn write(&self, addr: PciAddress, offset: u16, val: u32) {
+        let ports = self.ports.lock();
+        ports.addr.write(Self::address(addr, offset));
+        ports.data.write(val);
+    }
+}
+
+pub struct Ecam {
+    base: usize,
+    start_bus: u8,
+    end_bus: u8,
+}
+
+impl Ecam {
+    fn register(&self, addr: PciAddress, offset: u16) -> Option<Mmio<u32>> {
+        if addr.bus < self.start_bus || addr.bus > self.end_bus {
+            return None;
+        }
+        let off = (((addr.bus - self.start_bus) as usize) << ECAM_BUS_SHIFT)
+            | ((addr.device as usize) << 15)
+            | ((addr.function as usize) << 12)
+            | (offset as usize & 0xffc);
+        Some(Mmio::new(self.base + off))
+    }
+}
+
+impl ConfigAccess for Ecam {
+    fn name(&self) -> &'static str {
+        "ecam"
+    }
+
+    fn size(&self) -> u16 {
+        0x1000
+    }
+
+    fn read(&self, addr: PciAddress, offset: u16) -> u32 {
+        match self.register(addr, offset) {
+            Some(reg) => reg.read(),
+            None => u32::MAX,
+        }
+    }
+
+    fn write(&self, addr: PciAddress, offset: u16, val: u32) {
+        if let Some(reg) = self.register(addr, offset) {
+            reg.write(val);
+        }
+    }
+}
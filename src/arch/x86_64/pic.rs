@@ -1,9 +1,9 @@
-use crate::sync::Mutex;
+use crate::sync::IrqSafeMutex;
 
 use super::io::Pio;
 
-pub static PIC1: Mutex<Pic> = Mutex::new(Pic::new(Pio::new(0x20), true, 0x20));
-pub static PIC2: Mutex<Pic> = Mutex::new(Pic::new(Pio::new(0xA0), false, 0x28));
+pub static PIC1: IrqSafeMutex<Pic> = IrqSafeMutex::new(Pic::new(Pio::new(0x20), true, 0x20));
+pub static PIC2: IrqSafeMutex<Pic> = IrqSafeMutex::new(Pic::new(Pio::new(0xA0), false, 0x28));
 
 pub fn init() {
     PIC1.lock().init();
@@ -0,0 +1,107 @@
+use crate::{sync::IrqSafeMutex, time::ClockSource};
+
+use super::{io::Pio, regs};
+
+pub const PIT_HZ: u64 = 1193182;
+
+const CMD_LATCH_CH0: u8 = 0x00;
+const CMD_CH0_RATE: u8 = 0x34;
+const CMD_CH2_ONESHOT: u8 = 0xb2;
+
+const GATE_CH2: u8 = 1 << 0;
+const SPEAKER: u8 = 1 << 1;
+const OUT_CH2: u8 = 1 << 5;
+
+const RELOAD: u64 = 1 << 16;
+
+pub static PIT: Pit = Pit::new();
+
+struct Counter {
+    last: u64,
+    base: u64,
+}
+
+pub struct Pit {
+    ch0: Pio<u8>,
+    ch2: Pio<u8>,
+    cmd: Pio<u8>,
+    ctrl: Pio<u8>,
+    counter: IrqSafeMutex<Counter>,
+}
+
+impl Pit {
+    const fn new() -> Self {
+        Self {
+            ch0: Pio::new(0x40),
+            ch2: Pio::new(0x42),
+            cmd: Pio::new(0x43),
+            ctrl: Pio::new(0x61),
+            counter: IrqSafeMutex::new(Counter { last: 0, base: 0 }),
+        }
+    }
+
+    // Channel 0 wraps every 55 ms and its interrupt keeps the extended count
+    // up to date between reads.
+    pub fn init(&self) {
+        self.cmd.write(CMD_CH0_RATE);
+        self.ch0.write(0);
+        self.ch0.write(0);
+    }
+
+    pub fn interrupt(&self) {
+        self.read();
+    }
+
+    // Runs channel 2 for `ms` milliseconds and returns the TSC ticks elapsed.
+    pub fn measure_tsc(&self, ms: u64) -> u64 {
+        let count = PIT_HZ * ms / 1000;
+        self.ctrl
+            .write((self.ctrl.read() & !(SPEAKER | GATE_CH2)) | GATE_CH2);
+        self.cmd.write(CMD_CH2_ONESHOT);
+        self.ch2.write(count as u8);
+        self.ch2.write((count >> 8) as u8);
+
+        self.ctrl.write(self.ctrl.read() & !GATE_CH2);
+        self.ctrl.write(self.ctrl.read() | GATE_CH2);
+        let start = unsafe { regs::read_tsc() };
+        while self.ctrl.read() & OUT_CH2 != 0 {
+            core::hint::spin_loop();
+        }
+        while self.ctrl.read() & OUT_CH2 == 0 {
+            core::hint::spin_loop();
+        }
+        let end = unsafe { regs::read_tsc() };
+        end - start
+    }
+}
+
+impl ClockSource for Pit {
+    fn name(&self) -> &'static str {
+        "pit"
+    }
+
+    fn rating(&self) -> u32 {
+        50
+    }
+
+    fn frequency(&self) -> u64 {
+        PIT_HZ
+    }
+
+    fn read(&self) -> u64 {
+        let mut counter = self.counter.lock();
+        self.cmd.write(CMD_LATCH_CH0);
+        let low = self.ch0.read() as u64;
+        let high = self.ch0.read() as u64;
+        let elapsed = (RELOAD - ((high << 8) | low)) % RELOAD;
+        if elapsed < counter.last {
+            counter.base += RELOAD;
+        }
+        counter.last = elapsed;
+        counter.base + elapsed
+    }
+}
+
+pub fn measure_tsc_hz(ms: u64) -> u64 {
+    PIT.measure_tsc(ms) * 1000 / ms
+}
@@ -0,0 +1,404 @@
+use core::fmt;
+
+use crate::{
+    input::{MouseKind, ScancodeSet, KEYBOARD, KEY_EVENTS, MOUSE, MOUSE_EVENTS},
+    sync::IrqSafeMutex,
+};
+
+use super::io::Pio;
+
+const STATUS_OUTPUT_FULL: u8 = 1 << 0;
+const STATUS_INPUT_FULL: u8 = 1 << 1;
+const STATUS_AUX_DATA: u8 = 1 << 5;
+
+const CMD_READ_CONFIG: u8 = 0x20;
+const CMD_WRITE_CONFIG: u8 = 0x60;
+const CMD_DISABLE_PORT2: u8 = 0xa7;
+const CMD_ENABLE_PORT2: u8 = 0xa8;
+const CMD_TEST_PORT2: u8 = 0xa9;
+const CMD_SELF_TEST: u8 = 0xaa;
+const CMD_TEST_PORT1: u8 = 0xab;
+const CMD_DISABLE_PORT1: u8 = 0xad;
+const CMD_ENABLE_PORT1: u8 = 0xae;
+const CMD_WRITE_PORT2: u8 = 0xd4;
+
+const CONFIG_PORT1_IRQ: u8 = 1 << 0;
+const CONFIG_PORT2_IRQ: u8 = 1 << 1;
+const CONFIG_PORT2_CLOCK_OFF: u8 = 1 << 5;
+const CONFIG_TRANSLATION: u8 = 1 << 6;
+
+const SELF_TEST_OK: u8 = 0x55;
+const PORT_TEST_OK: u8 = 0x00;
+
+const DEV_RESET: u8 = 0xff;
+const DEV_RESET_OK: u8 = 0xaa;
+const DEV_ENABLE_SCANNING: u8 = 0xf4;
+const DEV_ACK: u8 = 0xfa;
+const DEV_RESEND: u8 = 0xfe;
+
+const KBD_SET_LEDS: u8 = 0xed;
+const KBD_SCANCODE_SET: u8 = 0xf0;
+
+const MOUSE_GET_ID: u8 = 0xf2;
+const MOUSE_SAMPLE_RATE: u8 = 0xf3;
+const MOUSE_WHEEL_KNOCK: [u8; 3] = [200, 100, 80];
+const MOUSE_EXPLORER_KNOCK: [u8; 3] = [200, 200, 80];
+const MOUSE_DEFAULT_RATE: u8 = 100;
+
+const TIMEOUT: usize = 100_000;
+const RESET_TIMEOUT: usize = 1_000_000;
+const RETRIES: usize = 3;
+
+pub static PS2: IrqSafeMutex<Ps2Controller> = IrqSafeMutex::new(Ps2Controller::new());
+
+pub fn init() {
+    let mut ps2 = PS2.lock();
+    if let Err(err) = ps2.init() {
+        crate::warn!("ps/2 controller: {}", err);
+        return;
+    }
+
+    match ps2.init_keyboard() {
+        Ok(set) => {
+            KEYBOARD.lock().set_scancode_set(set);
+            crate::info!("ps/2 keyboard using scancode {:?}", set);
+        }
+        Err(err) => crate::warn!("ps/2 keyboard: {}", err),
+    }
+
+    match ps2.init_mouse() {
+        Ok(kind) => {
+            MOUSE.lock().set_kind(kind);
+            crate::info!("ps/2 mouse: {:?}", kind);
+        }
+        Err(Ps2Error::NoDevice) => {}
+        Err(err) => crate::warn!("ps/2 mouse: {}", err),
+    }
+}
+
+pub fn keyboard_interrupt() {
+    let mut ps2 = PS2.lock();
+    let Some(byte) = ps2.try_read(Port::First) else {
+        return;
+    };
+    if ps2.led_response(byte) {
+        return;
+    }
+
+    let mut keyboard = KEYBOARD.lock();
+    let leds = keyboard.leds();
+    let Some(event) = keyboard.feed(byte) else {
+        return;
+    };
+    if keyboard.leds() != leds {
+        ps2.queue_leds(keyboard.leds());
+    }
+    KEY_EVENTS.push(event);
+}
+
+pub fn mouse_interrupt() {
+    let Some(byte) = PS2.lock().try_read(Port::Second) else {
+        return;
+    };
+    if let Some(event) = MOUSE.lock().feed(byte) {
+        MOUSE_EVENTS.push(event);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ps2Error {
+    Timeout,
+    SelfTest(u8),
+    PortTest(Port, u8),
+    NoAck(u8),
+    NoDevice,
+}
+
+impl fmt::Display for Ps2Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Timeout => f.write_str("timed out"),
+            Self::SelfTest(resp) => write!(f, "self test failed ({:#x})", resp),
+            Self::PortTest(port, resp) => write!(f, "{:?} port test failed ({:#x})", port, resp),
+            Self::NoAck(resp) => write!(f, "device did not acknowledge ({:#x})", resp),
+            Self::NoDevice => f.write_str("no device"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Port {
+    First,
+    Second,
+}
+
+// Progress of a set LEDs command sent from the keyboard interrupt, whose ACKs
+// come back as keyboard interrupts of their own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LedUpdate {
+    Idle,
+    Command(u8),
+    Value(u8),
+}
+
+pub struct Ps2Controller {
+    data: Pio<u8>,
+    status_cmd: Pio<u8>,
+    config: u8,
+    port1: bool,
+    port2: bool,
+    leds: LedUpdate,
+    queued_leds: Option<u8>,
+}
+
+impl Ps2Controller {
+    pub const fn new() -> Self {
+        Self {
+            data: Pio::new(0x60),
+            status_cmd: Pio::new(0x64),
+            config: 0,
+            port1: false,
+            port2: false,
+            leds: LedUpdate::Idle,
+            queued_leds: None,
+        }
+    }
+
+    #[inline]
+    pub fn has_port(&self, port: Port) -> bool {
+        match port {
+            Port::First => self.port1,
+            Port::Second => self.port2,
+        }
+    }
+
+    pub fn init(&mut self) -> Result<(), Ps2Error> {
+        self.command(CMD_DISABLE_PORT1)?;
+        self.command(CMD_DISABLE_PORT2)?;
+        for _ in 0..TIMEOUT {
+            if self.status_cmd.read() & STATUS_OUTPUT_FULL == 0 {
+                break;
+            }
+            self.data.read();
+        }
+
+        let config = self.command_with_response(CMD_READ_CONFIG)?;
+        self.config = config & !(CONFIG_PORT1_IRQ | CONFIG_PORT2_IRQ | CONFIG_TRANSLATION);
+        self.write_config()?;
+
+        match self.command_with_response(CMD_SELF_TEST)? {
+            SELF_TEST_OK => {}
+            resp => return Err(Ps2Error::SelfTest(resp)),
+        }
+        self.write_config()?;
+
+        if config & CONFIG_PORT2_CLOCK_OFF != 0 {
+            self.command(CMD_ENABLE_PORT2)?;
+            self.port2 = self.command_with_response(CMD_READ_CONFIG)? & CONFIG_PORT2_CLOCK_OFF == 0;
+            self.command(CMD_DISABLE_PORT2)?;
+        }
+
+        match self.command_with_response(CMD_TEST_PORT1)? {
+            PORT_TEST_OK => self.port1 = true,
+            resp => crate::warn!("ps/2: {}", Ps2Error::PortTest(Port::First, resp)),
+        }
+        if self.port2 {
+            match self.command_with_response(CMD_TEST_PORT2)? {
+                PORT_TEST_OK => {}
+                resp => {
+                    self.port2 = false;
+                    crate::warn!("ps/2: {}", Ps2Error::PortTest(Port::Second, resp));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn init_keyboard(&mut self) -> Result<ScancodeSet, Ps2Error> {
+        if !self.port1 {
+            return Err(Ps2Error::NoDevice);
+        }
+        self.command(CMD_ENABLE_PORT1)?;
+        self.reset(Port::First)?;
+
+        let set = match self
+            .send(Port::First, KBD_SCANCODE_SET)
+            .and_then(|_| self.send(Port::First, 2))
+        {
+            Ok(()) => ScancodeSet::Set2,
+            Err(_) => {
+                self.config |= CONFIG_TRANSLATION;
+                ScancodeSet::Set1
+            }
+        };
+        self.send(Port::First, DEV_ENABLE_SCANNING)?;
+
+        self.config |= CONFIG_PORT1_IRQ;
+        self.write_config()?;
+        Ok(set)
+    }
+
+    fn init_mouse(&mut self) -> Result<MouseKind, Ps2Error> {
+        if !self.port2 {
+            return Err(Ps2Error::NoDevice);
+        }
+        self.command(CMD_ENABLE_PORT2)?;
+        self.reset(Port::Second)?;
+        self.read()?;
+
+        let mut kind = MouseKind::Standard;
+        if self.knock(&MOUSE_WHEEL_KNOCK)? == MouseKind::Wheel {
+            kind = self.knock(&MOUSE_EXPLORER_KNOCK)?;
+        }
+        self.send(Port::Second, MOUSE_SAMPLE_RATE)?;
+        self.send(Port::Second, MOUSE_DEFAULT_RATE)?;
+        self.send(Port::Second, DEV_ENABLE_SCANNING)?;
+
+        self.config |= CONFIG_PORT2_IRQ;
+        self.config &= !CONFIG_PORT2_CLOCK_OFF;
+        self.write_config()?;
+        Ok(kind)
+    }
+
+    // IntelliMouse extensions are unlocked by a magic sequence of sample rates.
+    fn knock(&self, rates: &[u8]) -> Result<MouseKind, Ps2Error> {
+        for &rate in rates {
+            self.send(Port::Second, MOUSE_SAMPLE_RATE)?;
+            self.send(Port::Second, rate)?;
+        }
+        self.send(Port::Second, MOUSE_GET_ID)?;
+        Ok(MouseKind::from_id(self.read()?).unwrap_or(MouseKind::Standard))
+    }
+
+    pub fn reset(&self, port: Port) -> Result<(), Ps2Error> {
+        self.send(port, DEV_RESET)?;
+        for _ in 0..RESET_TIMEOUT / TIMEOUT {
+            match self.read() {
+                Ok(DEV_RESET_OK) => return Ok(()),
+                Ok(resp) => return Err(Ps2Error::NoAck(resp)),
+                Err(Ps2Error::Timeout) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Err(Ps2Error::Timeout)
+    }
+
+    pub fn set_leds(&self, leds: u8) -> Result<(), Ps2Error> {
+        self.send(Port::First, KBD_SET_LEDS)?;
+        self.send(Port::First, leds)
+    }
+
+    // Starts a LED update without waiting for the keyboard, one already in
+    // flight is followed up once it is acknowledged.
+    fn queue_leds(&mut self, leds: u8) {
+        if self.leds != LedUpdate::Idle {
+            self.queued_leds = Some(leds);
+            return;
+        }
+        match self.write(KBD_SET_LEDS) {
+            Ok(()) => self.leds = LedUpdate::Command(leds),
+            Err(err) => crate::warn!("ps/2 keyboard leds: {}", err),
+        }
+    }
+
+    // Returns whether the byte answered a pending LED update.
+    fn led_response(&mut self, byte: u8) -> bool {
+        let (sent, next) = match self.leds {
+            LedUpdate::Idle => return false,
+            LedUpdate::Command(leds) => (KBD_SET_LEDS, LedUpdate::Value(leds)),
+            LedUpdate::Value(leds) => (leds, LedUpdate::Idle),
+        };
+        let result = match (byte, next) {
+            (DEV_ACK, LedUpdate::Value(leds)) => self.write(leds).map(|()| next),
+            (DEV_ACK, _) => Ok(LedUpdate::Idle),
+            (DEV_RESEND, _) => self.write(sent).map(|()| self.leds),
+            _ => return false,
+        };
+        self.leds = result.unwrap_or_else(|err| {
+            crate::warn!("ps/2 keyboard leds: {}", err);
+            LedUpdate::Idle
+        });
+        if self.leds == LedUpdate::Idle {
+            if let Some(leds) = self.queued_leds.take() {
+                self.queue_leds(leds);
+            }
+        }
+        true
+    }
+
+    pub fn send(&self, port: Port, byte: u8) -> Result<(), Ps2Error> {
+        for _ in 0..RETRIES {
+            if port == Port::Second {
+                self.command(CMD_WRITE_PORT2)?;
+            }
+            self.wait_input()?;
+            self.data.write(byte);
+            match self.read()? {
+                DEV_ACK => return Ok(()),
+                DEV_RESEND => continue,
+                resp => return Err(Ps2Error::NoAck(resp)),
+            }
+        }
+        Err(Ps2Error::NoAck(DEV_RESEND))
+    }
+
+    pub fn try_read(&self, port: Port) -> Option<u8> {
+        let status = self.status_cmd.read();
+        if status & STATUS_OUTPUT_FULL == 0 {
+            return None;
+        }
+        let from_port2 = status & STATUS_AUX_DATA != 0;
+        if from_port2 != (port == Port::Second) {
+            return None;
+        }
+        Some(self.data.read())
+    }
+
+    fn read(&self) -> Result<u8, Ps2Error> {
+        for _ in 0..TIMEOUT {
+            if self.status_cmd.read() & STATUS_OUTPUT_FULL != 0 {
+                return Ok(self.data.read());
+            }
+        }
+        Err(Ps2Error::Timeout)
+    }
+
+    fn write(&self, byte: u8) -> Result<(), Ps2Error> {
+        self.wait_input()?;
+        self.data.write(byte);
+        Ok(())
+    }
+
+    fn wait_input(&self) -> Result<(), Ps2Error> {
+        for _ in 0..TIMEOUT {
+            if self.status_cmd.read() & STATUS_INPUT_FULL == 0 {
+                return Ok(());
+            }
+        }
+        Err(Ps2Error::Timeout)
+    }
+
+    fn command(&self, cmd: u8) -> Result<(), Ps2Error> {
+        self.wait_input()?;
+        self.status_cmd.write(cmd);
+        Ok(())
+    }
+
+    fn command_with_response(&self, cmd: u8) -> Result<u8, Ps2Error> {
+        self.command(cmd)?;
+        self.read()
+    }
+
+    fn write_config(&self) -> Result<(), Ps2Error> {
+        self.command(CMD_WRITE_CONFIG)?;
+        self.wait_input()?;
+        self.data.write(self.config);
+        Ok(())
+    }
+}
+
+impl Default for Ps2Controller {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -37,6 +37,12 @@ pub unsafe fn gs_base() -> u64 {
     ret
 }
 
+pub unsafe fn read_rflags() -> u64 {
+    let val: u64;
+    asm!("pushfq", "popq {0}", out(reg) val, options(nomem, att_syntax));
+    val
+}
+
 pub unsafe fn read_cr2() -> u64 {
     let val: u64;
     asm!("mov %cr2, {0}", out(reg) val, options(nomem, nostack, att_syntax));
@@ -57,6 +63,16 @@ pub unsafe fn write_cr3(val: u64) {
     asm!("mov {0}, %cr3", in(reg) val, options(nomem, nostack, att_syntax));
 }
 
+pub unsafe fn read_cr4() -> u64 {
+    let val: u64;
+    asm!("mov %cr4, {0}", out(reg) val, options(nomem, nostack, att_syntax));
+    val
+}
+
+pub unsafe fn write_cr4(val: u64) {
+    asm!("mov {0}, %cr4", in(reg) val, options(nomem, nostack, att_syntax));
+}
+
 pub unsafe fn read_msr(reg: u64) -> u64 {
     let low: u64;
     let high: u64;
@@ -0,0 +1,270 @@
+use core::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::{
+    sync::{Event, IrqSafeMutex},
+    time::DateTime,
+};
+
+use super::{acpi, io::Pio};
+
+const REG_SECONDS: u8 = 0x00;
+const REG_SECONDS_ALARM: u8 = 0x01;
+const REG_MINUTES: u8 = 0x02;
+const REG_MINUTES_ALARM: u8 = 0x03;
+const REG_HOURS: u8 = 0x04;
+const REG_HOURS_ALARM: u8 = 0x05;
+const REG_DAY: u8 = 0x07;
+const REG_MONTH: u8 = 0x08;
+const REG_YEAR: u8 = 0x09;
+const REG_STATUS_A: u8 = 0x0a;
+const REG_STATUS_B: u8 = 0x0b;
+const REG_STATUS_C: u8 = 0x0c;
+
+const STATUS_A_UPDATING: u8 = 1 << 7;
+const STATUS_A_RATE: u8 = 0x0f;
+const STATUS_B_24_HOUR: u8 = 1 << 1;
+const STATUS_B_BINARY: u8 = 1 << 2;
+const STATUS_B_UPDATE_IRQ: u8 = 1 << 4;
+const STATUS_B_ALARM_IRQ: u8 = 1 << 5;
+const STATUS_B_PERIODIC_IRQ: u8 = 1 << 6;
+const STATUS_C_ALARM: u8 = 1 << 5;
+const STATUS_C_PERIODIC: u8 = 1 << 6;
+
+const HOUR_PM: u8 = 1 << 7;
+const ALARM_ANY: u8 = 0xc0;
+
+const FADT_CENTURY: usize = 108;
+const DEFAULT_CENTURY: u16 = 20;
+
+const MIN_RATE: u8 = 3;
+const MAX_RATE: u8 = 15;
+const BASE_FREQ: u32 = 32768;
+
+const MAX_READS: usize = 16;
+
+pub static RTC: IrqSafeMutex<Rtc> = IrqSafeMutex::new(Rtc::new());
+pub static ALARM: Event = Event::new();
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+pub fn init() -> Result<DateTime, RtcError> {
+    let century = acpi::find_table(b"FACP")
+        .and_then(|fadt| fadt.u8(FADT_CENTURY))
+        .unwrap_or(0);
+
+    let mut rtc = RTC.lock();
+    rtc.century = century;
+    rtc.update(REG_STATUS_B, |b| {
+        b & !(STATUS_B_PERIODIC_IRQ | STATUS_B_ALARM_IRQ | STATUS_B_UPDATE_IRQ)
+    });
+    rtc.read(REG_STATUS_C);
+    rtc.now()
+}
+
+pub fn now() -> Result<DateTime, RtcError> {
+    RTC.lock().now()
+}
+
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+pub fn interrupt() {
+    let status = RTC.lock().read(REG_STATUS_C);
+    if status & STATUS_C_PERIODIC != 0 {
+        TICKS.fetch_add(1, Ordering::Relaxed);
+    }
+    if status & STATUS_C_ALARM != 0 {
+        ALARM.set();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtcError {
+    Unstable,
+    Invalid(DateTime),
+    BadRate(u8),
+}
+
+impl fmt::Display for RtcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unstable => f.write_str("time kept changing while being read"),
+            Self::Invalid(time) => write!(f, "invalid time {}", time),
+            Self::BadRate(rate) => write!(f, "periodic rate {} out of range", rate),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Raw {
+    second: u8,
+    minute: u8,
+    hour: u8,
+    day: u8,
+    month: u8,
+    year: u8,
+    century: u8,
+}
+
+pub struct Rtc {
+    index: Pio<u8>,
+    data: Pio<u8>,
+    century: u8,
+}
+
+impl Rtc {
+    pub const fn new() -> Self {
+        Self {
+            index: Pio::new(0x70),
+            data: Pio::new(0x71),
+            century: 0,
+        }
+    }
+
+    pub fn now(&self) -> Result<DateTime, RtcError> {
+        let mut last = self.read_raw();
+        for _ in 0..MAX_READS {
+            let raw = self.read_raw();
+            if raw == last {
+                return self.decode(raw);
+            }
+            last = raw;
+        }
+        Err(RtcError::Unstable)
+    }
+
+    // Rates from 3 to 15 give 32768 >> (rate - 1) Hz, so 8 kHz down to 2 Hz.
+    pub fn set_periodic(&self, rate: u8) -> Result<u32, RtcError> {
+        if !(MIN_RATE..=MAX_RATE).contains(&rate) {
+            return Err(RtcError::BadRate(rate));
+        }
+        self.update(REG_STATUS_A, |a| (a & !STATUS_A_RATE) | rate);
+        self.update(REG_STATUS_B, |b| b | STATUS_B_PERIODIC_IRQ);
+        self.read(REG_STATUS_C);
+        Ok(BASE_FREQ >> (rate - 1))
+    }
+
+    pub fn disable_periodic(&self) {
+        self.update(REG_STATUS_B, |b| b & !STATUS_B_PERIODIC_IRQ);
+    }
+
+    // A `None` field matches any value, so `(None, None, Some(0))` fires every minute.
+    pub fn set_alarm(&self, hour: Option<u8>, minute: Option<u8>, second: Option<u8>) {
+        let status = self.read(REG_STATUS_B);
+        let encode = |val: Option<u8>| match val {
+            Some(val) => self.encode(val, status),
+            None => ALARM_ANY,
+        };
+        let hour_val = match hour {
+            Some(hour) if status & STATUS_B_24_HOUR == 0 => {
+                let pm = if hour >= 12 { HOUR_PM } else { 0 };
+                let hour = match hour % 12 {
+                    0 => 12,
+                    hour => hour,
+                };
+                self.encode(hour, status) | pm
+            }
+            hour => encode(hour),
+        };
+
+        ALARM.reset();
+        self.write(REG_HOURS_ALARM, hour_val);
+        self.write(REG_MINUTES_ALARM, encode(minute));
+        self.write(REG_SECONDS_ALARM, encode(second));
+        self.update(REG_STATUS_B, |b| b | STATUS_B_ALARM_IRQ);
+        self.read(REG_STATUS_C);
+    }
+
+    pub fn disable_alarm(&self) {
+        self.update(REG_STATUS_B, |b| b & !STATUS_B_ALARM_IRQ);
+    }
+
+    fn read_raw(&self) -> Raw {
+        while self.read(REG_STATUS_A) & STATUS_A_UPDATING != 0 {
+            core::hint::spin_loop();
+        }
+        Raw {
+            second: self.read(REG_SECONDS),
+            minute: self.read(REG_MINUTES),
+            hour: self.read(REG_HOURS),
+            day: self.read(REG_DAY),
+            month: self.read(REG_MONTH),
+            year: self.read(REG_YEAR),
+            century: match self.century {
+                0 => 0,
+                reg => self.read(reg),
+            },
+        }
+    }
+
+    fn decode(&self, raw: Raw) -> Result<DateTime, RtcError> {
+        let status = self.read(REG_STATUS_B);
+        let binary = status & STATUS_B_BINARY != 0;
+        let decode = |val: u8| if binary { val } else { from_bcd(val) };
+
+        let mut hour = decode(raw.hour & !HOUR_PM);
+        if status & STATUS_B_24_HOUR == 0 {
+            hour %= 12;
+            if raw.hour & HOUR_PM != 0 {
+                hour += 12;
+            }
+        }
+        let century = match self.century {
+            0 => DEFAULT_CENTURY,
+            _ => decode(raw.century) as u16,
+        };
+
+        let time = DateTime {
+            year: century * 100 + decode(raw.year) as u16,
+            month: decode(raw.month),
+            day: decode(raw.day),
+            hour,
+            minute: decode(raw.minute),
+            second: decode(raw.second),
+        };
+        if !time.is_valid() {
+            return Err(RtcError::Invalid(time));
+        }
+        Ok(time)
+    }
+
+    fn encode(&self, val: u8, status: u8) -> u8 {
+        if status & STATUS_B_BINARY != 0 {
+            val
+        } else {
+            to_bcd(val)
+        }
+    }
+
+    fn read(&self, reg: u8) -> u8 {
+        self.index.write(reg);
+        self.data.read()
+    }
+
+    fn write(&self, reg: u8, val: u8) {
+        self.index.write(reg);
+        self.data.write(val);
+    }
+
+    fn update(&self, reg: u8, f: impl FnOnce(u8) -> u8) {
+        let val = self.read(reg);
+        self.write(reg, f(val));
+    }
+}
+
+impl Default for Rtc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn from_bcd(val: u8) -> u8 {
+    (val >> 4) * 10 + (val & 0x0f)
+}
+
+fn to_bcd(val: u8) -> u8 {
+    ((val / 10) << 4) | (val % 10)
+}
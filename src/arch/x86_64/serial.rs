@@ -1,7 +1,7 @@
-use crate::{arch::x86_64::io::Pio, sync::Mutex};
+use crate::{arch::x86_64::io::Pio, sync::IrqSafeMutex};
 
-pub static COM1: Mutex<SerialPort> = Mutex::new(SerialPort::new(Pio::new(0x3f8)));
-pub static COM2: Mutex<SerialPort> = Mutex::new(SerialPort::new(Pio::new(0x2f8)));
+pub static COM1: IrqSafeMutex<SerialPort> = IrqSafeMutex::new(SerialPort::new(Pio::new(0x3f8)));
+pub static COM2: IrqSafeMutex<SerialPort> = IrqSafeMutex::new(SerialPort::new(Pio::new(0x2f8)));
 
 pub fn init() {
     COM1.lock().init();
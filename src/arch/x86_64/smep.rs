@@ -0,0 +1,19 @@
+use core::arch::x86_64::{__cpuid, __cpuid_count};
+
+use super::regs;
+
+const CR4_SMEP: u64 = 1 << 20;
+
+crate::param!(static NOSMEP: bool = "nosmep", false);
+
+pub fn init() {
+    if NOSMEP.get() {
+        crate::info!("smep disabled on the command line");
+        return;
+    }
+
+    let supported = unsafe { __cpuid(0).eax >= 7 && __cpuid_count(7, 0).ebx & (1 << 7) != 0 };
+    if supported {
+        unsafe { regs::write_cr4(regs::read_cr4() | CR4_SMEP) };
+    }
+}
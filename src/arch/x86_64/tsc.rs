@@ -0,0 +1,111 @@
+use core::{
+    arch::x86_64::__cpuid,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::time::ClockSource;
+
+use super::{hpet::Hpet, pit, regs};
+
+const CALIBRATE_MS: u64 = 10;
+const MAX_SKEW_PERMILLE: u64 = 10;
+
+static TSC_HZ: AtomicU64 = AtomicU64::new(0);
+
+pub static TSC: Tsc = Tsc;
+
+pub fn tsc_mhz() -> u64 {
+    TSC_HZ.load(Ordering::Relaxed) / 1_000_000
+}
+
+pub fn tsc_hz() -> u64 {
+    TSC_HZ.load(Ordering::Relaxed)
+}
+
+// A rough PIT measurement so that early log timestamps make sense.
+pub fn init() {
+    if TSC_HZ.load(Ordering::Relaxed) == 0 {
+        TSC_HZ.store(pit::measure_tsc_hz(CALIBRATE_MS), Ordering::Release);
+    }
+}
+
+// Prefers the HPET, then the frequency enumerated by CPUID and finally the PIT,
+// and warns when the others disagree with the chosen one.
+pub fn calibrate(hpet: Option<&Hpet>) -> u64 {
+    let hpet = hpet.map(|hpet| hpet.measure_tsc(CALIBRATE_MS) * 1000 / CALIBRATE_MS);
+    let cpuid = cpuid_hz();
+    let pit = pit::measure_tsc_hz(CALIBRATE_MS);
+
+    let (hz, from) = match (hpet, cpuid) {
+        (Some(hz), _) => (hz, "hpet"),
+        (None, Some(hz)) => (hz, "cpuid"),
+        (None, None) => (pit, "pit"),
+    };
+    for (other, name) in [(hpet, "hpet"), (cpuid, "cpuid"), (Some(pit), "pit")] {
+        let Some(other) = other else {
+            continue;
+        };
+        if hz.abs_diff(other) * 1000 > hz * MAX_SKEW_PERMILLE {
+            crate::warn!(
+                "tsc: {} says {} Hz but {} says {} Hz",
+                from,
+                hz,
+                name,
+                other
+            );
+        }
+    }
+
+    TSC_HZ.store(hz, Ordering::Release);
+    crate::info!(
+        "tsc: {} Hz from {}{}",
+        hz,
+        from,
+        if is_invariant() { ", invariant" } else { "" }
+    );
+    hz
+}
+
+pub fn is_invariant() -> bool {
+    unsafe { __cpuid(0x8000_0000).eax >= 0x8000_0007 && __cpuid(0x8000_0007).edx & (1 << 8) != 0 }
+}
+
+pub fn has_deadline() -> bool {
+    unsafe { __cpuid(1).ecx & (1 << 24) != 0 }
+}
+
+fn cpuid_hz() -> Option<u64> {
+    let max = unsafe { __cpuid(0).eax };
+    if max < 0x15 {
+        return None;
+    }
+    let leaf = unsafe { __cpuid(0x15) };
+    if leaf.eax == 0 || leaf.ebx == 0 || leaf.ecx == 0 {
+        return None;
+    }
+    Some(leaf.ecx as u64 * leaf.ebx as u64 / leaf.eax as u64)
+}
+
+pub struct Tsc;
+
+impl ClockSource for Tsc {
+    fn name(&self) -> &'static str {
+        "tsc"
+    }
+
+    fn rating(&self) -> u32 {
+        if is_invariant() {
+            300
+        } else {
+            150
+        }
+    }
+
+    fn frequency(&self) -> u64 {
+        tsc_hz()
+    }
+
+    fn read(&self) -> u64 {
+        unsafe { regs::read_tsc() }
+    }
+}
@@ -0,0 +1,103 @@
+use core::fmt;
+
+use crate::{pci::MsiMessage, sync::IrqSafeMutex};
+
+use super::lapic;
+
+pub const FIRST_VECTOR: u8 = 0x40;
+pub const VECTOR_COUNT: usize = 32;
+
+const IRQ_LINES: usize = 16;
+const PCI_IRQS: [u8; 4] = [5, 9, 10, 11];
+const HANDLERS_PER_IRQ: usize = 4;
+
+const MSI_ADDRESS: u64 = 0xfee0_0000;
+const MSI_DEST_SHIFT: u64 = 12;
+
+pub type VectorFn = fn(usize);
+type Handler = Option<(VectorFn, usize)>;
+
+static VECTORS: IrqSafeMutex<[Handler; VECTOR_COUNT]> = IrqSafeMutex::new([None; VECTOR_COUNT]);
+static IRQS: IrqSafeMutex<[[Handler; HANDLERS_PER_IRQ]; IRQ_LINES]> =
+    IrqSafeMutex::new([[None; HANDLERS_PER_IRQ]; IRQ_LINES]);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VectorError {
+    Exhausted,
+    BadIrq(u8),
+}
+
+impl fmt::Display for VectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exhausted => f.write_str("no free interrupt vectors"),
+            Self::BadIrq(irq) => write!(f, "irq {} is not available to pci devices", irq),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Vector(u8);
+
+impl Vector {
+    #[inline]
+    pub fn num(self) -> u8 {
+        self.0
+    }
+
+    // Fixed delivery, edge triggered, physical destination mode.
+    pub fn msi_message(self, apic_id: usize) -> MsiMessage {
+        MsiMessage {
+            address: MSI_ADDRESS | ((apic_id as u64 & 0xff) << MSI_DEST_SHIFT),
+            data: self.0 as u32,
+        }
+    }
+}
+
+pub fn alloc_vector(handler: VectorFn, data: usize) -> Result<Vector, VectorError> {
+    let mut vectors = VECTORS.lock();
+    let idx = vectors
+        .iter()
+        .position(Option::is_none)
+        .ok_or(VectorError::Exhausted)?;
+    vectors[idx] = Some((handler, data));
+    Ok(Vector(FIRST_VECTOR + idx as u8))
+}
+
+pub fn free_vector(vector: Vector) {
+    if let Some(slot) = VECTORS.lock().get_mut((vector.0 - FIRST_VECTOR) as usize) {
+        *slot = None;
+    }
+}
+
+// The handler runs with the table unlocked so that it may allocate or free
+// vectors itself.
+pub(super) fn dispatch(idx: usize) {
+    let entry = VECTORS.lock()[idx];
+    if let Some((handler, data)) = entry {
+        handler(data);
+    }
+    lapic::eoi();
+}
+
+// Legacy PCI interrupts are level triggered and shared, so every handler on
+// the line runs and checks its own device.
+pub fn register_irq(irq: u8, handler: VectorFn, data: usize) -> Result<(), VectorError> {
+    if !PCI_IRQS.contains(&irq) {
+        return Err(VectorError::BadIrq(irq));
+    }
+    let mut irqs = IRQS.lock();
+    let slot = irqs[irq as usize]
+        .iter_mut()
+        .find(|slot| slot.is_none())
+        .ok_or(VectorError::Exhausted)?;
+    *slot = Some((handler, data));
+    Ok(())
+}
+
+pub(super) fn dispatch_irq(irq: u8) {
+    let line = IRQS.lock()[irq as usize];
+    for (handler, data) in line.iter().flatten() {
+        handler(*data);
+    }
+}
@@ -0,0 +1,98 @@
+use core::ptr;
+
+use crate::console::Display;
+
+use super::io::Pio;
+
+pub const VGA_BUFFER: usize = 0xb8000;
+pub const VGA_COLS: usize = 80;
+pub const VGA_ROWS: usize = 25;
+
+const CRTC_CURSOR_START: u8 = 0x0a;
+const CRTC_CURSOR_END: u8 = 0x0b;
+const CRTC_CURSOR_HIGH: u8 = 0x0e;
+const CRTC_CURSOR_LOW: u8 = 0x0f;
+
+// VGA orders the color bits as IRGB while ANSI uses BGR.
+const COLORS: [u8; 16] = [0, 4, 2, 6, 1, 5, 3, 7, 8, 12, 10, 14, 9, 13, 11, 15];
+
+const UNKNOWN_CHAR: u8 = 0xfe;
+
+pub struct VgaText {
+    buffer: *mut u16,
+    cols: usize,
+    rows: usize,
+    crtc_index: Pio<u8>,
+    crtc_data: Pio<u8>,
+}
+
+unsafe impl Send for VgaText {}
+
+impl VgaText {
+    /// # Safety
+    /// `addr` must point to a mapped text buffer of `cols * rows` cells.
+    pub unsafe fn new(addr: usize, cols: usize, rows: usize) -> Self {
+        let vga = Self {
+            buffer: addr as *mut u16,
+            cols,
+            rows,
+            crtc_index: Pio::new(0x3d4),
+            crtc_data: Pio::new(0x3d5),
+        };
+        vga.write_crtc(CRTC_CURSOR_START, 14);
+        vga.write_crtc(CRTC_CURSOR_END, 15);
+        vga
+    }
+
+    fn write_crtc(&self, reg: u8, val: u8) {
+        self.crtc_index.write(reg);
+        self.crtc_data.write(val);
+    }
+
+    fn write_cell(&mut self, col: usize, row: usize, cell: u16) {
+        if col < self.cols && row < self.rows {
+            unsafe { ptr::write_volatile(self.buffer.add(row * self.cols + col), cell) };
+        }
+    }
+}
+
+impl Display for VgaText {
+    fn size(&self) -> (usize, usize) {
+        (self.cols, self.rows)
+    }
+
+    fn draw_char(&mut self, col: usize, row: usize, c: char, fg: u8, bg: u8) {
+        let byte = if c.is_ascii() && !c.is_ascii_control() {
+            c as u8
+        } else {
+            UNKNOWN_CHAR
+        };
+        self.write_cell(col, row, cell(byte, fg, bg));
+    }
+
+    fn fill(&mut self, col: usize, row: usize, cols: usize, rows: usize, bg: u8) {
+        for row in row..(row + rows).min(self.rows) {
+            for col in col..(col + cols).min(self.cols) {
+                self.write_cell(col, row, cell(b' ', bg, bg));
+            }
+        }
+    }
+
+    fn scroll_up(&mut self, bg: u8) {
+        let len = (self.rows - 1) * self.cols;
+        unsafe { ptr::copy(self.buffer.add(self.cols), self.buffer, len) };
+        self.fill(0, self.rows - 1, self.cols, 1, bg);
+    }
+
+    fn set_cursor(&mut self, col: usize, row: usize) {
+        let pos = (row * self.cols + col) as u16;
+        self.write_crtc(CRTC_CURSOR_HIGH, (pos >> 8) as u8);
+        self.write_crtc(CRTC_CURSOR_LOW, pos as u8);
+    }
+}
+
+// The high background bit selects blinking, so only the dim colors are usable.
+fn cell(byte: u8, fg: u8, bg: u8) -> u16 {
+    let attr = (COLORS[bg as usize & 0xf] & 0x7) << 4 | COLORS[fg as usize & 0xf];
+    (attr as u16) << 8 | byte as u16
+}
@@ -0,0 +1,331 @@
+use core::{ptr, slice};
+
+use ::memory::PAGE_SIZE;
+
+use crate::{arch, sync::SleepMutex};
+
+use super::{check_range, BlockDevice, BlockError, Geometry, Request, RequestQueue};
+
+pub const MAX_BLOCK_SIZE: usize = PAGE_SIZE;
+
+const CACHE_BLOCKS: usize = 128;
+// Merged write-back goes through a staging buffer of this many pages.
+const STAGING_PAGES: usize = 16;
+
+static CACHE: SleepMutex<Cache> = SleepMutex::new(Cache::new());
+
+#[derive(Clone, Copy)]
+struct Entry {
+    device: Option<&'static dyn BlockDevice>,
+    lba: u64,
+    dirty: bool,
+    last_used: u64,
+}
+
+impl Entry {
+    const EMPTY: Self = Self {
+        device: None,
+        lba: 0,
+        dirty: false,
+        last_used: 0,
+    };
+
+    #[inline]
+    fn belongs_to(&self, device: &dyn BlockDevice) -> bool {
+        self.device.is_some_and(|other| same(other, device))
+    }
+
+    #[inline]
+    fn holds(&self, device: &dyn BlockDevice, lba: u64) -> bool {
+        self.lba == lba && self.belongs_to(device)
+    }
+}
+
+#[inline]
+fn same(a: &dyn BlockDevice, b: &dyn BlockDevice) -> bool {
+    ptr::addr_eq(a as *const dyn BlockDevice, b as *const dyn BlockDevice)
+}
+
+// Blocks are kept one per page, evicted least recently used first and only
+// written to the device on eviction or sync.
+struct Cache {
+    data: usize,
+    staging: usize,
+    entries: [Entry; CACHE_BLOCKS],
+    tick: u64,
+    queue: RequestQueue<CACHE_BLOCKS>,
+}
+
+impl Cache {
+    const fn new() -> Self {
+        Self {
+            data: 0,
+            staging: 0,
+            entries: [Entry::EMPTY; CACHE_BLOCKS],
+            tick: 0,
+            queue: RequestQueue::new(),
+        }
+    }
+
+    fn ensure_memory(&mut self) -> Result<(), BlockError> {
+        if self.data == 0 {
+            let data = arch::alloc_dma(CACHE_BLOCKS)?;
+            match arch::alloc_dma(STAGING_PAGES) {
+                Ok(staging) => self.staging = staging,
+                Err(err) => {
+                    arch::free_dma(data, CACHE_BLOCKS);
+                    return Err(err.into());
+                }
+            }
+            self.data = data;
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::mut_from_ref)]
+    fn block(&self, slot: usize, size: usize) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut((self.data + slot * PAGE_SIZE) as *mut u8, size) }
+    }
+
+    fn lookup(&mut self, device: &dyn BlockDevice, lba: u64) -> Option<usize> {
+        let slot = self
+            .entries
+            .iter()
+            .position(|entry| entry.holds(device, lba))?;
+        self.tick += 1;
+        self.entries[slot].last_used = self.tick;
+        Some(slot)
+    }
+
+    fn insert(&mut self, device: &'static dyn BlockDevice, lba: u64) -> Result<usize, BlockError> {
+        let slot = match self.entries.iter().position(|entry| entry.device.is_none()) {
+            Some(slot) => slot,
+            None => {
+                let (slot, _) = self
+                    .entries
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, entry)| entry.last_used)
+                    .unwrap();
+                self.write_back(slot)?;
+                slot
+            }
+        };
+        self.tick += 1;
+        self.entries[slot] = Entry {
+            device: Some(device),
+            lba,
+            dirty: false,
+            last_used: self.tick,
+        };
+        Ok(slot)
+    }
+
+    fn write_back(&mut self, slot: usize) -> Result<(), BlockError> {
+        let entry = self.entries[slot];
+        if let (Some(device), true) = (entry.device, entry.dirty) {
+            device.write(entry.lba, self.block(slot, device.block_size()))?;
+            self.entries[slot].dirty = false;
+        }
+        Ok(())
+    }
+
+    // Consecutive misses are read from the device in one transfer straight
+    // into `buf` and copied into the cache afterwards.
+    fn read(
+        &mut self,
+        device: &'static dyn BlockDevice,
+        lba: u64,
+        buf: &mut [u8],
+    ) -> Result<(), BlockError> {
+        let count = check_range(device, lba, buf.len())? as usize;
+        let size = device.block_size();
+        let mut idx = 0;
+        while idx < count {
+            if let Some(slot) = self.lookup(device, lba + idx as u64) {
+                buf[idx * size..(idx + 1) * size].copy_from_slice(self.block(slot, size));
+                idx += 1;
+                continue;
+            }
+
+            let mut end = idx + 1;
+            while end < count
+                && end - idx < CACHE_BLOCKS
+                && !self
+                    .entries
+                    .iter()
+                    .any(|entry| entry.holds(device, lba + end as u64))
+            {
+                end += 1;
+            }
+            device.read(lba + idx as u64, &mut buf[idx * size..end * size])?;
+            for block in idx..end {
+                let slot = self.insert(device, lba + block as u64)?;
+                self.block(slot, size)
+                    .copy_from_slice(&buf[block * size..(block + 1) * size]);
+            }
+            idx = end;
+        }
+        Ok(())
+    }
+
+    fn write(
+        &mut self,
+        device: &'static dyn BlockDevice,
+        lba: u64,
+        buf: &[u8],
+    ) -> Result<(), BlockError> {
+        if device.is_read_only() {
+            return Err(BlockError::ReadOnly);
+        }
+        check_range(device, lba, buf.len())?;
+        let size = device.block_size();
+        for (idx, data) in buf.chunks(size).enumerate() {
+            let lba = lba + idx as u64;
+            let slot = match self.lookup(device, lba) {
+                Some(slot) => slot,
+                None => self.insert(device, lba)?,
+            };
+            self.block(slot, size).copy_from_slice(data);
+            self.entries[slot].dirty = true;
+        }
+        Ok(())
+    }
+
+    // Dirty blocks are sorted and neighbours merged into a single write.
+    fn sync(&mut self, device: &dyn BlockDevice) -> Result<(), BlockError> {
+        let size = device.block_size();
+        self.queue.clear();
+        for (slot, entry) in self.entries.iter().enumerate() {
+            if entry.dirty && entry.belongs_to(device) {
+                self.queue.push(Request {
+                    lba: entry.lba,
+                    slot,
+                });
+            }
+        }
+
+        let staging = self.staging as *mut u8;
+        let max = STAGING_PAGES * PAGE_SIZE / size;
+        for run in self.queue.runs(max) {
+            for (idx, request) in run.iter().enumerate() {
+                let src = self.block(request.slot, size);
+                unsafe { ptr::copy_nonoverlapping(src.as_ptr(), staging.add(idx * size), size) };
+            }
+            let data = unsafe { slice::from_raw_parts(staging, run.len() * size) };
+            device.write(run[0].lba, data)?;
+            for request in run {
+                self.entries[request.slot].dirty = false;
+            }
+        }
+        self.queue.clear();
+        Ok(())
+    }
+
+    fn invalidate(&mut self, device: &dyn BlockDevice, lba: u64, count: u64) {
+        for entry in self.entries.iter_mut() {
+            if entry.lba >= lba && entry.lba - lba < count && entry.belongs_to(device) {
+                *entry = Entry::EMPTY;
+            }
+        }
+    }
+}
+
+pub fn read(device: &'static dyn BlockDevice, lba: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+    if device.block_size() > MAX_BLOCK_SIZE {
+        return device.read(lba, buf);
+    }
+    let mut cache = CACHE.lock();
+    cache.ensure_memory()?;
+    cache.read(device, lba, buf)
+}
+
+pub fn write(device: &'static dyn BlockDevice, lba: u64, buf: &[u8]) -> Result<(), BlockError> {
+    if device.block_size() > MAX_BLOCK_SIZE {
+        return device.write(lba, buf);
+    }
+    let mut cache = CACHE.lock();
+    cache.ensure_memory()?;
+    cache.write(device, lba, buf)
+}
+
+pub fn sync(device: &dyn BlockDevice) -> Result<(), BlockError> {
+    CACHE.lock().sync(device)
+}
+
+pub fn sync_all() -> Result<(), BlockError> {
+    let mut cache = CACHE.lock();
+    while let Some(device) = cache
+        .entries
+        .iter()
+        .find(|entry| entry.dirty)
+        .and_then(|entry| entry.device)
+    {
+        cache.sync(device)?;
+    }
+    Ok(())
+}
+
+// Drops cached blocks without writing them back.
+pub fn invalidate(device: &dyn BlockDevice) {
+    CACHE.lock().invalidate(device, 0, u64::MAX);
+}
+
+// A block device whose reads and writes go through the cache. Flushing writes
+// back its dirty blocks before flushing the device itself.
+#[derive(Clone, Copy)]
+pub struct Cached {
+    device: &'static dyn BlockDevice,
+}
+
+impl Cached {
+    #[inline]
+    pub fn new(device: &'static dyn BlockDevice) -> Self {
+        Self { device }
+    }
+
+    #[inline]
+    pub fn device(&self) -> &'static dyn BlockDevice {
+        self.device
+    }
+}
+
+impl BlockDevice for Cached {
+    fn name(&self) -> &str {
+        self.device.name()
+    }
+
+    fn block_size(&self) -> usize {
+        self.device.block_size()
+    }
+
+    fn block_count(&self) -> u64 {
+        self.device.block_count()
+    }
+
+    fn read(&self, lba: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+        read(self.device, lba, buf)
+    }
+
+    fn write(&self, lba: u64, buf: &[u8]) -> Result<(), BlockError> {
+        write(self.device, lba, buf)
+    }
+
+    fn flush(&self) -> Result<(), BlockError> {
+        sync(self.device)?;
+        self.device.flush()
+    }
+
+    fn discard(&self, lba: u64, count: u64) -> Result<(), BlockError> {
+        CACHE.lock().invalidate(self.device, lba, count);
+        self.device.discard(lba, count)
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.device.is_read_only()
+    }
+
+    fn geometry(&self) -> Option<Geometry> {
+        self.device.geometry()
+    }
+}
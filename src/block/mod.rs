@@ -0,0 +1,142 @@
+use core::{
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::sync::OnceCell;
+
+pub use cache::*;
+pub use partition::*;
+pub use queue::*;
+
+pub mod cache;
+pub mod partition;
+pub mod queue;
+
+pub const SECTOR_SIZE: usize = 512;
+
+const MAX_DEVICES: usize = 32;
+
+static DEVICES: [OnceCell<&'static dyn BlockDevice>; MAX_DEVICES] =
+    [const { OnceCell::new() }; MAX_DEVICES];
+static NEXT_DEVICE: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockError {
+    OutOfRange,
+    Unaligned,
+    ReadOnly,
+    Unsupported,
+    Timeout,
+    NoMemory,
+    Io(u8),
+}
+
+impl fmt::Display for BlockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutOfRange => f.write_str("block out of range"),
+            Self::Unaligned => f.write_str("buffer is not a multiple of the block size"),
+            Self::ReadOnly => f.write_str("device is read only"),
+            Self::Unsupported => f.write_str("operation not supported by device"),
+            Self::Timeout => f.write_str("device timed out"),
+            Self::NoMemory => f.write_str("out of memory for transfer buffers"),
+            Self::Io(err) => write!(f, "device error {:#04x}", err),
+        }
+    }
+}
+
+impl From<::memory::AllocError> for BlockError {
+    fn from(_: ::memory::AllocError) -> Self {
+        Self::NoMemory
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Geometry {
+    pub cylinders: u16,
+    pub heads: u8,
+    pub sectors: u8,
+}
+
+impl fmt::Display for Geometry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}/{}", self.cylinders, self.heads, self.sectors)
+    }
+}
+
+// Buffers hold a whole number of blocks, block sizes are powers of two and
+// `lba` is in blocks.
+pub trait BlockDevice: Sync {
+    fn name(&self) -> &str;
+    fn block_size(&self) -> usize;
+    fn block_count(&self) -> u64;
+    fn read(&self, lba: u64, buf: &mut [u8]) -> Result<(), BlockError>;
+    fn write(&self, lba: u64, buf: &[u8]) -> Result<(), BlockError>;
+
+    fn flush(&self) -> Result<(), BlockError> {
+        Ok(())
+    }
+
+    // Tells the device `count` blocks are no longer in use.
+    fn discard(&self, _lba: u64, _count: u64) -> Result<(), BlockError> {
+        Err(BlockError::Unsupported)
+    }
+
+    fn geometry(&self) -> Option<Geometry> {
+        None
+    }
+
+    fn is_read_only(&self) -> bool {
+        false
+    }
+}
+
+// Checks a transfer against the device and returns the number of blocks.
+pub fn check_range(device: &dyn BlockDevice, lba: u64, len: usize) -> Result<u64, BlockError> {
+    let block_size = device.block_size();
+    if len & (block_size - 1) != 0 {
+        return Err(BlockError::Unaligned);
+    }
+    let count = (len / block_size) as u64;
+    match lba.checked_add(count) {
+        Some(end) if end <= device.block_count() => Ok(count),
+        _ => Err(BlockError::OutOfRange),
+    }
+}
+
+// Registers a whole disk along with the partitions found on it. Filesystems
+// are mounted on the partitions through the cache, never on the disk itself.
+pub fn register(device: &'static dyn BlockDevice) {
+    if add(device) {
+        partition::scan(device);
+    }
+}
+
+fn add(device: &'static dyn BlockDevice) -> bool {
+    let idx = NEXT_DEVICE.fetch_add(1, Ordering::Relaxed);
+    match DEVICES.get(idx) {
+        Some(slot) => {
+            let _ = slot.set(device);
+            crate::info!(
+                "block {}: {} blocks of {} bytes",
+                device.name(),
+                device.block_count(),
+                device.block_size()
+            );
+            true
+        }
+        None => {
+            crate::warn!("too many block devices, dropping {}", device.name());
+            false
+        }
+    }
+}
+
+pub fn devices() -> impl Iterator<Item = &'static dyn BlockDevice> {
+    DEVICES.iter().filter_map(OnceCell::get).copied()
+}
+
+pub fn find(name: &str) -> Option<&'static dyn BlockDevice> {
+    devices().find(|device| device.name() == name)
+}
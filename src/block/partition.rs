@@ -0,0 +1,416 @@
+use core::{
+    fmt::{self, Write},
+    slice, str,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use ::memory::PAGE_SIZE;
+
+use crate::{arch, sync::OnceCell};
+
+use super::{check_range, BlockDevice, BlockError, Geometry, SECTOR_SIZE};
+
+const MAX_PARTITIONS: usize = 32;
+const NAME_SIZE: usize = 16;
+
+const MBR_SIGNATURE: u16 = 0xaa55;
+const MBR_SIGNATURE_OFFSET: usize = 510;
+const MBR_ENTRIES: usize = 446;
+const MBR_ENTRY_SIZE: usize = 16;
+const MBR_TYPE: usize = 4;
+const MBR_START: usize = 8;
+const MBR_COUNT: usize = 12;
+const MBR_TYPE_EMPTY: u8 = 0x00;
+const MBR_TYPE_GPT: u8 = 0xee;
+const MBR_TYPES_EXTENDED: [u8; 3] = [0x05, 0x0f, 0x85];
+const MBR_FIRST_LOGICAL: usize = 5;
+const MAX_LOGICAL: usize = 64;
+
+const GPT_HEADER_LBA: u64 = 1;
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+const GPT_HEADER_SIZE: usize = 12;
+const GPT_HEADER_CRC: usize = 16;
+const GPT_FIRST_USABLE: usize = 40;
+const GPT_LAST_USABLE: usize = 48;
+const GPT_ENTRIES_LBA: usize = 72;
+const GPT_ENTRY_COUNT: usize = 80;
+const GPT_ENTRY_SIZE: usize = 84;
+const GPT_ENTRIES_CRC: usize = 88;
+const GPT_MIN_HEADER_SIZE: usize = 92;
+const GPT_MIN_ENTRY_SIZE: usize = 128;
+const GPT_ENTRY_TYPE: usize = 0;
+const GPT_ENTRY_FIRST: usize = 32;
+const GPT_ENTRY_LAST: usize = 40;
+
+static PARTITIONS: [OnceCell<Partition>; MAX_PARTITIONS] =
+    [const { OnceCell::new() }; MAX_PARTITIONS];
+static NEXT_PARTITION: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionError {
+    BadHeader,
+    BadChecksum,
+    OutOfRange,
+    Block(BlockError),
+}
+
+impl fmt::Display for PartitionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadHeader => f.write_str("bad gpt header"),
+            Self::BadChecksum => f.write_str("gpt checksum mismatch"),
+            Self::OutOfRange => f.write_str("partition past the end of the disk"),
+            Self::Block(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<BlockError> for PartitionError {
+    fn from(err: BlockError) -> Self {
+        Self::Block(err)
+    }
+}
+
+struct Name {
+    buf: [u8; NAME_SIZE],
+    len: usize,
+}
+
+impl Name {
+    fn as_str(&self) -> &str {
+        str::from_utf8(&self.buf[..self.len]).unwrap_or("?")
+    }
+}
+
+impl Write for Name {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        if end > NAME_SIZE {
+            return Err(fmt::Error);
+        }
+        self.buf[self.len..end].copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+// A range of blocks on a whole disk, named after it like "vda1" or "ata0p1".
+pub struct Partition {
+    parent: &'static dyn BlockDevice,
+    name: Name,
+    number: usize,
+    start: u64,
+    count: u64,
+}
+
+impl Partition {
+    #[inline]
+    pub fn parent(&self) -> &'static dyn BlockDevice {
+        self.parent
+    }
+
+    #[inline]
+    pub fn number(&self) -> usize {
+        self.number
+    }
+
+    #[inline]
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+}
+
+impl BlockDevice for Partition {
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn block_size(&self) -> usize {
+        self.parent.block_size()
+    }
+
+    fn block_count(&self) -> u64 {
+        self.count
+    }
+
+    fn read(&self, lba: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+        check_range(self, lba, buf.len())?;
+        self.parent.read(self.start + lba, buf)
+    }
+
+    fn write(&self, lba: u64, buf: &[u8]) -> Result<(), BlockError> {
+        check_range(self, lba, buf.len())?;
+        self.parent.write(self.start + lba, buf)
+    }
+
+    fn flush(&self) -> Result<(), BlockError> {
+        self.parent.flush()
+    }
+
+    fn discard(&self, lba: u64, count: u64) -> Result<(), BlockError> {
+        match lba.checked_add(count) {
+            Some(end) if end <= self.count => self.parent.discard(self.start + lba, count),
+            _ => Err(BlockError::OutOfRange),
+        }
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.parent.is_read_only()
+    }
+
+    fn geometry(&self) -> Option<Geometry> {
+        None
+    }
+}
+
+pub fn partitions() -> impl Iterator<Item = &'static Partition> {
+    PARTITIONS.iter().filter_map(OnceCell::get)
+}
+
+fn add(parent: &'static dyn BlockDevice, number: usize, start: u64, count: u64) {
+    let end = match start.checked_add(count) {
+        Some(end) if count > 0 && end <= parent.block_count() => end,
+        _ => {
+            crate::warn!(
+                "{}: partition {}: {}",
+                parent.name(),
+                number,
+                PartitionError::OutOfRange
+            );
+            return;
+        }
+    };
+
+    let mut name = Name {
+        buf: [0; NAME_SIZE],
+        len: 0,
+    };
+    let separator = if parent.name().ends_with(|c: char| c.is_ascii_digit()) {
+        "p"
+    } else {
+        ""
+    };
+    if write!(name, "{}{}{}", parent.name(), separator, number).is_err() {
+        crate::warn!("{}: partition {}: name too long", parent.name(), number);
+        return;
+    }
+
+    let idx = NEXT_PARTITION.fetch_add(1, Ordering::Relaxed);
+    let Some(slot) = PARTITIONS.get(idx) else {
+        crate::warn!("too many partitions, dropping {}", name.as_str());
+        return;
+    };
+    let partition = Partition {
+        parent,
+        name,
+        number,
+        start,
+        count,
+    };
+    if let Ok(partition) = slot.set(partition) {
+        crate::info!(
+            "{}: blocks {}..{} of {}",
+            partition.name(),
+            start,
+            end,
+            parent.name()
+        );
+        super::add(partition);
+    }
+}
+
+// Reads the partition table of a whole disk, a GPT if the MBR is protective
+// and the MBR otherwise, and registers every partition found.
+pub fn scan(device: &'static dyn BlockDevice) {
+    let size = device.block_size();
+    if size > PAGE_SIZE || device.block_count() == 0 {
+        return;
+    }
+    let page = match arch::alloc_dma(1) {
+        Ok(page) => page,
+        Err(err) => {
+            crate::warn!("{}: partition scan: {}", device.name(), err);
+            return;
+        }
+    };
+    let buf = unsafe { slice::from_raw_parts_mut(page as *mut u8, size) };
+    if let Err(err) = scan_mbr(device, buf) {
+        crate::warn!("{}: partition table: {}", device.name(), err);
+    }
+    arch::free_dma(page, 1);
+}
+
+#[inline]
+fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+#[inline]
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+#[inline]
+fn read_u64(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
+
+#[derive(Clone, Copy)]
+struct MbrEntry {
+    kind: u8,
+    start: u64,
+    count: u64,
+}
+
+fn mbr_entry(buf: &[u8], idx: usize) -> MbrEntry {
+    let entry = &buf[MBR_ENTRIES + idx * MBR_ENTRY_SIZE..];
+    MbrEntry {
+        kind: entry[MBR_TYPE],
+        start: read_u32(entry, MBR_START) as u64,
+        count: read_u32(entry, MBR_COUNT) as u64,
+    }
+}
+
+fn scan_mbr(device: &'static dyn BlockDevice, buf: &mut [u8]) -> Result<(), PartitionError> {
+    device.read(0, buf)?;
+    if buf.len() < SECTOR_SIZE || read_u16(buf, MBR_SIGNATURE_OFFSET) != MBR_SIGNATURE {
+        return Ok(());
+    }
+
+    let entries = [0, 1, 2, 3].map(|idx| mbr_entry(buf, idx));
+    if entries.iter().any(|entry| entry.kind == MBR_TYPE_GPT) {
+        return scan_gpt(device, buf);
+    }
+
+    let mut extended = None;
+    for (idx, entry) in entries.iter().enumerate() {
+        if entry.kind == MBR_TYPE_EMPTY {
+            continue;
+        }
+        if MBR_TYPES_EXTENDED.contains(&entry.kind) {
+            extended.get_or_insert(entry.start);
+            continue;
+        }
+        add(device, idx + 1, entry.start, entry.count);
+    }
+
+    if let Some(first) = extended {
+        scan_logical(device, buf, first)?;
+    }
+    Ok(())
+}
+
+// Logical partitions are a chain of boot records, each describing one
+// partition relative to itself and the next record relative to the first.
+fn scan_logical(
+    device: &'static dyn BlockDevice,
+    buf: &mut [u8],
+    first: u64,
+) -> Result<(), PartitionError> {
+    let mut ebr = first;
+    for number in MBR_FIRST_LOGICAL..MBR_FIRST_LOGICAL + MAX_LOGICAL {
+        device.read(ebr, buf)?;
+        if read_u16(buf, MBR_SIGNATURE_OFFSET) != MBR_SIGNATURE {
+            break;
+        }
+        let logical = mbr_entry(buf, 0);
+        let next = mbr_entry(buf, 1);
+        if logical.kind != MBR_TYPE_EMPTY {
+            add(device, number, ebr + logical.start, logical.count);
+        }
+        if next.kind == MBR_TYPE_EMPTY || next.start == 0 {
+            break;
+        }
+        ebr = first + next.start;
+    }
+    Ok(())
+}
+
+fn scan_gpt(device: &'static dyn BlockDevice, buf: &mut [u8]) -> Result<(), PartitionError> {
+    let block_size = buf.len();
+    device.read(GPT_HEADER_LBA, buf)?;
+    if &buf[..GPT_SIGNATURE.len()] != GPT_SIGNATURE {
+        return Err(PartitionError::BadHeader);
+    }
+    let header_size = read_u32(buf, GPT_HEADER_SIZE) as usize;
+    if !(GPT_MIN_HEADER_SIZE..=block_size).contains(&header_size) {
+        return Err(PartitionError::BadHeader);
+    }
+    let header_crc = read_u32(buf, GPT_HEADER_CRC);
+    buf[GPT_HEADER_CRC..GPT_HEADER_CRC + 4].fill(0);
+    if crc32(!0, &buf[..header_size]) != !header_crc {
+        return Err(PartitionError::BadChecksum);
+    }
+
+    let first_usable = read_u64(buf, GPT_FIRST_USABLE);
+    let last_usable = read_u64(buf, GPT_LAST_USABLE);
+    let entries_lba = read_u64(buf, GPT_ENTRIES_LBA);
+    let entry_count = read_u32(buf, GPT_ENTRY_COUNT) as usize;
+    let entry_size = read_u32(buf, GPT_ENTRY_SIZE) as usize;
+    let entries_crc = read_u32(buf, GPT_ENTRIES_CRC);
+    if entry_size < GPT_MIN_ENTRY_SIZE
+        || !entry_size.is_power_of_two()
+        || entry_size > block_size
+        || last_usable < first_usable
+    {
+        return Err(PartitionError::BadHeader);
+    }
+
+    // The checksum covers the whole array, so it is checked before anything
+    // is registered.
+    let per_block = block_size / entry_size;
+    let blocks = entry_count.div_ceil(per_block);
+    let mut crc = !0;
+    for block in 0..blocks {
+        device.read(entries_lba + block as u64, buf)?;
+        let count = (entry_count - block * per_block).min(per_block);
+        crc = crc32(crc, &buf[..count * entry_size]);
+    }
+    if crc != !entries_crc {
+        return Err(PartitionError::BadChecksum);
+    }
+
+    for block in 0..blocks {
+        device.read(entries_lba + block as u64, buf)?;
+        let count = (entry_count - block * per_block).min(per_block);
+        for idx in 0..count {
+            let entry = &buf[idx * entry_size..(idx + 1) * entry_size];
+            if entry[GPT_ENTRY_TYPE..GPT_ENTRY_TYPE + 16]
+                .iter()
+                .all(|&b| b == 0)
+            {
+                continue;
+            }
+            let first = read_u64(entry, GPT_ENTRY_FIRST);
+            let last = read_u64(entry, GPT_ENTRY_LAST);
+            let number = block * per_block + idx + 1;
+            if first < first_usable || last > last_usable || last < first {
+                crate::warn!(
+                    "{}: partition {}: {}",
+                    device.name(),
+                    number,
+                    PartitionError::OutOfRange
+                );
+                continue;
+            }
+            add(device, number, first, last - first + 1);
+        }
+    }
+    Ok(())
+}
+
+// CRC-32 as used by GPT, without the initial and final inversion so that it
+// can be computed over several buffers.
+fn crc32(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
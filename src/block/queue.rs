@@ -0,0 +1,86 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Request {
+    pub lba: u64,
+    pub slot: usize,
+}
+
+// Pending requests are kept sorted by block so that neighbouring ones can be
+// issued to the device as a single transfer.
+pub struct RequestQueue<const N: usize> {
+    requests: [Request; N],
+    len: usize,
+}
+
+impl<const N: usize> RequestQueue<N> {
+    pub const fn new() -> Self {
+        Self {
+            requests: [Request { lba: 0, slot: 0 }; N],
+            len: 0,
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    // A request for a block that is already queued replaces it. Returns false
+    // if the queue is full.
+    pub fn push(&mut self, request: Request) -> bool {
+        let requests = &mut self.requests[..self.len];
+        match requests.binary_search_by_key(&request.lba, |r| r.lba) {
+            Ok(idx) => {
+                requests[idx] = request;
+                true
+            }
+            Err(_) if self.len == N => false,
+            Err(idx) => {
+                self.requests.copy_within(idx..self.len, idx + 1);
+                self.requests[idx] = request;
+                self.len += 1;
+                true
+            }
+        }
+    }
+
+    // Runs of consecutive blocks, at most `max` requests each.
+    pub fn runs(&self, max: usize) -> Runs<'_> {
+        Runs {
+            requests: &self.requests[..self.len],
+            max: max.max(1),
+        }
+    }
+}
+
+pub struct Runs<'a> {
+    requests: &'a [Request],
+    max: usize,
+}
+
+impl<'a> Iterator for Runs<'a> {
+    type Item = &'a [Request];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let first = self.requests.first()?;
+        let mut len = 1;
+        while len < self.requests.len()
+            && len < self.max
+            && self.requests[len].lba == first.lba + len as u64
+        {
+            len += 1;
+        }
+        let (run, rest) = self.requests.split_at(len);
+        self.requests = rest;
+        Some(run)
+    }
+}
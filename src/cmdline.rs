@@ -0,0 +1,121 @@
+use core::slice;
+
+pub use ::cmdline::{parse, ParamError, Size};
+
+use crate::sync::{InitError, OnceCell};
+
+#[macro_export]
+macro_rules! param {
+    ($vis:vis static $name:ident: $ty:ty = $key:literal, $default:expr) => {
+        $vis static $name: $crate::cmdline::Param<$ty> = $crate::cmdline::Param::new($key, $default);
+
+        const _: () = {
+            #[used]
+            #[link_section = ".cmdline_params"]
+            static PARAM: &dyn $crate::cmdline::Setup = &$name;
+        };
+    };
+}
+
+pub trait Setup: Sync {
+    fn name(&self) -> &'static str;
+    fn setup(&self, value: Option<&'static str>) -> Result<(), ParamError>;
+}
+
+pub trait ParamValue: Sized + Copy {
+    fn parse(value: Option<&'static str>) -> Result<Self, ParamError>;
+}
+
+pub struct Param<T> {
+    name: &'static str,
+    default: T,
+    value: OnceCell<T>,
+}
+
+impl<T: ParamValue> Param<T> {
+    pub const fn new(name: &'static str, default: T) -> Self {
+        Self {
+            name,
+            default,
+            value: OnceCell::new(),
+        }
+    }
+
+    pub fn get(&self) -> T {
+        self.value.get().copied().unwrap_or(self.default)
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.value.is_initialized()
+    }
+}
+
+impl<T: ParamValue + Send + Sync> Setup for Param<T> {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn setup(&self, value: Option<&'static str>) -> Result<(), ParamError> {
+        match self.value.set(T::parse(value)?) {
+            Ok(_) => Ok(()),
+            Err(InitError::AlreadyInitialized) => Err(ParamError::Duplicate),
+            Err(InitError::Uninitialized) => unreachable!(),
+        }
+    }
+}
+
+impl ParamValue for bool {
+    fn parse(value: Option<&'static str>) -> Result<Self, ParamError> {
+        ::cmdline::parse_bool(value)
+    }
+}
+
+impl ParamValue for usize {
+    fn parse(value: Option<&'static str>) -> Result<Self, ParamError> {
+        ::cmdline::parse_usize(value)
+    }
+}
+
+impl ParamValue for &'static str {
+    fn parse(value: Option<&'static str>) -> Result<Self, ParamError> {
+        value.ok_or(ParamError::MissingValue)
+    }
+}
+
+impl<T: ParamValue> ParamValue for Option<T> {
+    fn parse(value: Option<&'static str>) -> Result<Self, ParamError> {
+        T::parse(value).map(Some)
+    }
+}
+
+impl ParamValue for Size {
+    fn parse(value: Option<&'static str>) -> Result<Self, ParamError> {
+        Size::parse(value)
+    }
+}
+
+pub fn init(cmdline: &'static str) {
+    for arg in parse(cmdline) {
+        match params().iter().find(|param| param.name() == arg.key) {
+            Some(param) => {
+                if let Err(err) = param.setup(arg.value) {
+                    crate::warn!("parameter `{}`: {}", arg.key, err);
+                }
+            }
+            None => crate::warn!("unknown parameter `{}`", arg.key),
+        }
+    }
+}
+
+fn params() -> &'static [&'static dyn Setup] {
+    extern "C" {
+        static cmdline_params_start: u8;
+        static cmdline_params_end: u8;
+    }
+
+    unsafe {
+        let start = &cmdline_params_start as *const u8 as *const &'static dyn Setup;
+        let end = &cmdline_params_end as *const u8 as *const &'static dyn Setup;
+        slice::from_raw_parts(start, end.offset_from(start) as usize)
+    }
+}
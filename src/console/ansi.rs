@@ -0,0 +1,134 @@
+const MAX_PARAMS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Print(char),
+    Execute(char),
+    Csi(Csi),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Csi {
+    params: [u16; MAX_PARAMS],
+    len: usize,
+    // Parameters past `MAX_PARAMS` are dropped instead of running into the
+    // last one.
+    overflow: bool,
+    private: bool,
+    action: char,
+}
+
+impl Csi {
+    const fn new() -> Self {
+        Self {
+            params: [0; MAX_PARAMS],
+            len: 0,
+            overflow: false,
+            private: false,
+            action: '\0',
+        }
+    }
+
+    #[inline]
+    pub fn action(&self) -> char {
+        self.action
+    }
+
+    #[inline]
+    pub fn is_private(&self) -> bool {
+        self.private
+    }
+
+    #[inline]
+    pub fn params(&self) -> &[u16] {
+        &self.params[..self.len]
+    }
+
+    pub fn param(&self, idx: usize, default: u16) -> u16 {
+        match self.params().get(idx) {
+            Some(&0) | None => default,
+            Some(&val) => val,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    Csi,
+}
+
+pub struct Parser {
+    state: State,
+    csi: Csi,
+}
+
+impl Parser {
+    pub const fn new() -> Self {
+        Self {
+            state: State::Ground,
+            csi: Csi::new(),
+        }
+    }
+
+    pub fn advance(&mut self, c: char) -> Option<Action> {
+        match (self.state, c) {
+            (_, '\x1b') => {
+                self.state = State::Escape;
+                None
+            }
+            (State::Ground, c) if c.is_control() => Some(Action::Execute(c)),
+            (State::Ground, c) => Some(Action::Print(c)),
+            (State::Escape, '[') => {
+                self.csi = Csi::new();
+                self.state = State::Csi;
+                None
+            }
+            (State::Escape, _) => {
+                self.state = State::Ground;
+                None
+            }
+            (State::Csi, '0'..='9') if self.csi.overflow => None,
+            (State::Csi, '0'..='9') => {
+                if self.csi.len == 0 {
+                    self.csi.len = 1;
+                }
+                if let Some(param) = self.csi.params.get_mut(self.csi.len - 1) {
+                    *param = param
+                        .saturating_mul(10)
+                        .saturating_add(c as u16 - '0' as u16);
+                }
+                None
+            }
+            (State::Csi, ';') => {
+                if self.csi.len == 0 {
+                    self.csi.len = 1;
+                }
+                if self.csi.len == MAX_PARAMS {
+                    self.csi.overflow = true;
+                } else {
+                    self.csi.len += 1;
+                }
+                None
+            }
+            (State::Csi, '?') => {
+                self.csi.private = true;
+                None
+            }
+            (State::Csi, '\x40'..='\x7e') => {
+                self.csi.action = c;
+                self.state = State::Ground;
+                Some(Action::Csi(self.csi))
+            }
+            (State::Csi, c) if c.is_control() => Some(Action::Execute(c)),
+            (State::Csi, _) => None,
+        }
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
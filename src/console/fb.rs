@@ -0,0 +1,183 @@
+use core::{fmt, ptr};
+
+use multiboot2::{ColorField, FramebufferTag, FramebufferType};
+
+use super::{psf::Font, term::Display};
+
+const PALETTE: [Rgb; 16] = [
+    Rgb(0x00, 0x00, 0x00),
+    Rgb(0xaa, 0x00, 0x00),
+    Rgb(0x00, 0xaa, 0x00),
+    Rgb(0xaa, 0x55, 0x00),
+    Rgb(0x00, 0x00, 0xaa),
+    Rgb(0xaa, 0x00, 0xaa),
+    Rgb(0x00, 0xaa, 0xaa),
+    Rgb(0xaa, 0xaa, 0xaa),
+    Rgb(0x55, 0x55, 0x55),
+    Rgb(0xff, 0x55, 0x55),
+    Rgb(0x55, 0xff, 0x55),
+    Rgb(0xff, 0xff, 0x55),
+    Rgb(0x55, 0x55, 0xff),
+    Rgb(0xff, 0x55, 0xff),
+    Rgb(0x55, 0xff, 0xff),
+    Rgb(0xff, 0xff, 0xff),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FbError {
+    UnsupportedType,
+    UnsupportedDepth(usize),
+}
+
+impl fmt::Display for FbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedType => f.write_str("framebuffer is not direct rgb"),
+            Self::UnsupportedDepth(bpp) => write!(f, "unsupported depth of {} bpp", bpp),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+pub struct Framebuffer {
+    base: *mut u8,
+    pitch: usize,
+    width: usize,
+    height: usize,
+    bytes_pp: usize,
+    red: ColorField,
+    green: ColorField,
+    blue: ColorField,
+}
+
+unsafe impl Send for Framebuffer {}
+
+impl Framebuffer {
+    pub unsafe fn new(tag: &FramebufferTag) -> Result<Self, FbError> {
+        let Some(FramebufferType::Rgb { red, green, blue }) = tag.buffer_type() else {
+            return Err(FbError::UnsupportedType);
+        };
+        if tag.bpp() != 24 && tag.bpp() != 32 {
+            return Err(FbError::UnsupportedDepth(tag.bpp()));
+        }
+
+        Ok(Self {
+            base: tag.addr() as *mut u8,
+            pitch: tag.pitch(),
+            width: tag.width(),
+            height: tag.height(),
+            bytes_pp: tag.bpp() / 8,
+            red,
+            green,
+            blue,
+        })
+    }
+
+    #[inline]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn color(&self, rgb: Rgb) -> u32 {
+        let channel = |val: u8, field: ColorField| {
+            ((val as u32) >> 8u32.saturating_sub(field.size as u32)) << field.position
+        };
+        channel(rgb.0, self.red) | channel(rgb.1, self.green) | channel(rgb.2, self.blue)
+    }
+
+    pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: u32) {
+        let width = width.min(self.width.saturating_sub(x));
+        let height = height.min(self.height.saturating_sub(y));
+        for row in y..y + height {
+            for col in x..x + width {
+                unsafe { self.write_pixel(col, row, color) };
+            }
+        }
+    }
+
+    pub fn scroll_up(&mut self, lines: usize, color: u32) {
+        let lines = lines.min(self.height);
+        let len = (self.height - lines) * self.pitch;
+        unsafe { ptr::copy(self.base.add(lines * self.pitch), self.base, len) };
+        self.fill_rect(0, self.height - lines, self.width, lines, color);
+    }
+
+    fn draw_glyph(&mut self, x: usize, y: usize, font: &Font, c: char, fg: u32, bg: u32) {
+        let glyph = font.glyph(c);
+        let bytes_per_row = font.bytes_per_row();
+        if x + font.width() > self.width || y + font.height() > self.height {
+            return;
+        }
+
+        // PSF2 glyphs may be padded past `height` rows.
+        for (row, bits) in glyph
+            .chunks_exact(bytes_per_row)
+            .take(font.height())
+            .enumerate()
+        {
+            for col in 0..font.width() {
+                let set = bits[col / 8] & (0x80 >> (col % 8)) != 0;
+                unsafe { self.write_pixel(x + col, y + row, if set { fg } else { bg }) };
+            }
+        }
+    }
+
+    unsafe fn write_pixel(&mut self, x: usize, y: usize, color: u32) {
+        let pixel = self.base.add(y * self.pitch + x * self.bytes_pp);
+        match self.bytes_pp {
+            4 => ptr::write_volatile(pixel as *mut u32, color),
+            _ => {
+                let bytes = color.to_le_bytes();
+                for (i, &byte) in bytes.iter().take(self.bytes_pp).enumerate() {
+                    ptr::write_volatile(pixel.add(i), byte);
+                }
+            }
+        }
+    }
+}
+
+pub struct FbDisplay {
+    fb: Framebuffer,
+    font: Font<'static>,
+}
+
+impl FbDisplay {
+    pub fn new(fb: Framebuffer, font: Font<'static>) -> Self {
+        Self { fb, font }
+    }
+}
+
+impl Display for FbDisplay {
+    fn size(&self) -> (usize, usize) {
+        (
+            self.fb.width() / self.font.width(),
+            self.fb.height() / self.font.height(),
+        )
+    }
+
+    fn draw_char(&mut self, col: usize, row: usize, c: char, fg: u8, bg: u8) {
+        let (x, y) = (col * self.font.width(), row * self.font.height());
+        let fg = self.fb.color(PALETTE[fg as usize]);
+        let bg = self.fb.color(PALETTE[bg as usize]);
+        self.fb.draw_glyph(x, y, &self.font, c, fg, bg);
+    }
+
+    fn fill(&mut self, col: usize, row: usize, cols: usize, rows: usize, bg: u8) {
+        let (width, height) = (self.font.width(), self.font.height());
+        let bg = self.fb.color(PALETTE[bg as usize]);
+        self.fb
+            .fill_rect(col * width, row * height, cols * width, rows * height, bg);
+    }
+
+    fn scroll_up(&mut self, bg: u8) {
+        let bg = self.fb.color(PALETTE[bg as usize]);
+        self.fb.scroll_up(self.font.height(), bg);
+    }
+}
@@ -0,0 +1,137 @@
+use core::{
+    slice,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use multiboot2::{BootInfo, FramebufferTag, FramebufferType, ModuleTag};
+
+use crate::{
+    arch::{self, VgaText, VGA_BUFFER, VGA_COLS, VGA_ROWS},
+    log::{self, Sink},
+    sync::{IrqSafeMutex, OnceCell},
+};
+
+pub use fb::*;
+pub use psf::*;
+pub use term::*;
+
+pub mod ansi;
+pub mod fb;
+pub mod psf;
+pub mod term;
+
+const FONT_MODULE: &str = "font";
+const MAX_CONSOLES: usize = 4;
+
+static CONSOLES: [OnceCell<&'static dyn Console>; MAX_CONSOLES] =
+    [const { OnceCell::new() }; MAX_CONSOLES];
+static NEXT_CONSOLE: AtomicUsize = AtomicUsize::new(0);
+
+static FB_CONSOLE: OnceCell<IrqSafeMutex<Terminal<FbDisplay>>> = OnceCell::new();
+static VGA_CONSOLE: OnceCell<IrqSafeMutex<Terminal<VgaText>>> = OnceCell::new();
+
+pub static CONSOLE_SINK: ConsoleSink = ConsoleSink;
+
+pub trait Console: Sync {
+    fn write_str(&self, s: &str);
+}
+
+impl<D: Display> Console for IrqSafeMutex<Terminal<D>> {
+    fn write_str(&self, s: &str) {
+        self.lock().write_str(s);
+    }
+}
+
+pub struct ConsoleSink;
+
+impl Sink for ConsoleSink {
+    fn write_str(&self, s: &str) {
+        for console in CONSOLES.iter().filter_map(OnceCell::get) {
+            console.write_str(s);
+        }
+    }
+}
+
+pub fn register(console: &'static dyn Console) {
+    let idx = NEXT_CONSOLE.fetch_add(1, Ordering::Relaxed);
+    match CONSOLES.get(idx) {
+        Some(slot) => {
+            let _ = slot.set(console);
+        }
+        None => crate::warn!("too many consoles"),
+    }
+}
+
+pub fn init(boot_info: &BootInfo<'static>) {
+    log::register_sink(&CONSOLE_SINK);
+
+    match boot_info.find_tag::<FramebufferTag>() {
+        Some(tag) if matches!(tag.buffer_type(), Some(FramebufferType::Text)) => {
+            init_vga(tag.addr(), tag.width(), tag.height())
+        }
+        Some(tag) => init_fb(boot_info, &tag),
+        None => init_vga(VGA_BUFFER, VGA_COLS, VGA_ROWS),
+    }
+}
+
+fn init_vga(addr: usize, cols: usize, rows: usize) {
+    if let Err(err) = arch::map_framebuffer(addr, cols * rows * 2) {
+        crate::warn!("vga console: {}", err);
+        return;
+    }
+
+    let console = Terminal::new(unsafe { VgaText::new(addr, cols, rows) });
+    if let Ok(console) = VGA_CONSOLE.set(IrqSafeMutex::new(console)) {
+        register(console);
+        crate::info!("vga console {}x{} at {:#x}", cols, rows, addr);
+    }
+}
+
+fn init_fb(boot_info: &BootInfo<'static>, tag: &FramebufferTag) {
+    let fb = match unsafe { Framebuffer::new(tag) } {
+        Ok(fb) => fb,
+        Err(err) => {
+            crate::info!("framebuffer console: {}", err);
+            return;
+        }
+    };
+
+    let Some(module) = boot_info
+        .find_tags::<ModuleTag>()
+        .find(|module| module.cmdline() == FONT_MODULE)
+    else {
+        crate::warn!("framebuffer console: no `{}` module", FONT_MODULE);
+        return;
+    };
+    let bytes = unsafe { slice::from_raw_parts(module.start_addr() as *const u8, module.size()) };
+    let font = match Font::parse(bytes) {
+        Ok(font) => font,
+        Err(err) => {
+            crate::warn!("framebuffer console: {}", err);
+            return;
+        }
+    };
+    if font.width() > fb.width() || font.height() > fb.height() {
+        crate::warn!("framebuffer console: font larger than the screen");
+        return;
+    }
+
+    if let Err(err) = arch::map_framebuffer(tag.addr(), tag.size()) {
+        crate::warn!("framebuffer console: {}", err);
+        return;
+    }
+
+    let console = Terminal::new(FbDisplay::new(fb, font));
+    let (cols, rows) = console.size();
+    if let Ok(console) = FB_CONSOLE.set(IrqSafeMutex::new(console)) {
+        register(console);
+        crate::info!(
+            "framebuffer console {}x{} at {:#x}, {}x{} cells",
+            tag.width(),
+            tag.height(),
+            tag.addr(),
+            cols,
+            rows
+        );
+    }
+}
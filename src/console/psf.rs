@@ -0,0 +1,175 @@
+use core::fmt;
+
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF1_MODE512: u8 = 0x01;
+const PSF1_MODEHASTAB: u8 = 0x02;
+const PSF1_SEPARATOR: u16 = 0xffff;
+const PSF1_STARTSEQ: u16 = 0xfffe;
+
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xb5, 0x4a, 0x86];
+const PSF2_HAS_UNICODE_TABLE: u32 = 0x01;
+const PSF2_SEPARATOR: u8 = 0xff;
+const PSF2_STARTSEQ: u8 = 0xfe;
+
+const MAP_SIZE: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontError {
+    BadMagic,
+    Truncated,
+    BadHeader,
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadMagic => f.write_str("not a psf font"),
+            Self::Truncated => f.write_str("font data truncated"),
+            Self::BadHeader => f.write_str("invalid font header"),
+        }
+    }
+}
+
+pub struct Font<'a> {
+    glyphs: &'a [u8],
+    count: usize,
+    width: usize,
+    height: usize,
+    glyph_size: usize,
+    map: [u16; MAP_SIZE],
+}
+
+impl<'a> Font<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, FontError> {
+        if bytes.starts_with(&PSF1_MAGIC) {
+            Self::parse_psf1(bytes)
+        } else if bytes.starts_with(&PSF2_MAGIC) {
+            Self::parse_psf2(bytes)
+        } else {
+            Err(FontError::BadMagic)
+        }
+    }
+
+    fn parse_psf1(bytes: &'a [u8]) -> Result<Self, FontError> {
+        let mode = *bytes.get(2).ok_or(FontError::Truncated)?;
+        let height = *bytes.get(3).ok_or(FontError::Truncated)? as usize;
+        if height == 0 {
+            return Err(FontError::BadHeader);
+        }
+
+        let count = if mode & PSF1_MODE512 != 0 { 512 } else { 256 };
+        let end = 4 + count * height;
+        let glyphs = bytes.get(4..end).ok_or(FontError::Truncated)?;
+
+        let mut font = Self::new(glyphs, count, 8, height, height);
+        if mode & PSF1_MODEHASTAB != 0 {
+            let mut entries = (end..bytes.len().saturating_sub(1))
+                .step_by(2)
+                .map(|i| u16::from_le_bytes([bytes[i], bytes[i + 1]]));
+            for glyph in 0..count {
+                for code in entries.by_ref() {
+                    match code {
+                        PSF1_SEPARATOR => break,
+                        PSF1_STARTSEQ => {
+                            entries.by_ref().find(|&code| code == PSF1_SEPARATOR);
+                            break;
+                        }
+                        code => font.map_char(code as u32, glyph),
+                    }
+                }
+            }
+        }
+        Ok(font)
+    }
+
+    fn parse_psf2(bytes: &'a [u8]) -> Result<Self, FontError> {
+        let field = |idx: usize| -> Result<usize, FontError> {
+            bytes
+                .get(idx * 4..idx * 4 + 4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+                .ok_or(FontError::Truncated)
+        };
+
+        let header_size = field(2)?;
+        let flags = field(3)? as u32;
+        let count = field(4)?;
+        let glyph_size = field(5)?;
+        let height = field(6)?;
+        let width = field(7)?;
+        if count == 0 || width == 0 || height == 0 || glyph_size < width.div_ceil(8) * height {
+            return Err(FontError::BadHeader);
+        }
+
+        let end = count
+            .checked_mul(glyph_size)
+            .and_then(|len| len.checked_add(header_size))
+            .ok_or(FontError::BadHeader)?;
+        let glyphs = bytes.get(header_size..end).ok_or(FontError::Truncated)?;
+
+        let mut font = Self::new(glyphs, count, width, height, glyph_size);
+        if flags & PSF2_HAS_UNICODE_TABLE != 0 {
+            let mut table = bytes[end..].split(|&b| b == PSF2_SEPARATOR);
+            for glyph in 0..count {
+                let Some(entry) = table.next() else {
+                    break;
+                };
+                let singles = entry.split(|&b| b == PSF2_STARTSEQ).next().unwrap_or(&[]);
+                if let Ok(chars) = core::str::from_utf8(singles) {
+                    for c in chars.chars() {
+                        font.map_char(c as u32, glyph);
+                    }
+                }
+            }
+        }
+        Ok(font)
+    }
+
+    fn new(glyphs: &'a [u8], count: usize, width: usize, height: usize, glyph_size: usize) -> Self {
+        let mut map = [0; MAP_SIZE];
+        for (code, glyph) in map.iter_mut().enumerate() {
+            *glyph = code.min(count - 1) as u16;
+        }
+        Self {
+            glyphs,
+            count,
+            width,
+            height,
+            glyph_size,
+            map,
+        }
+    }
+
+    fn map_char(&mut self, code: u32, glyph: usize) {
+        if let Some(entry) = self.map.get_mut(code as usize) {
+            *entry = glyph as u16;
+        }
+    }
+
+    #[inline]
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    #[inline]
+    pub fn bytes_per_row(&self) -> usize {
+        self.width.div_ceil(8)
+    }
+
+    pub fn glyph(&self, c: char) -> &'a [u8] {
+        let idx = match self.map.get(c as usize) {
+            Some(&idx) => idx as usize,
+            None => self.map[b'?' as usize] as usize,
+        };
+        &self.glyphs[idx * self.glyph_size..(idx + 1) * self.glyph_size]
+    }
+}
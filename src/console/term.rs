@@ -0,0 +1,175 @@
+use super::ansi::{Action, Csi, Parser};
+
+const TAB_WIDTH: usize = 8;
+
+pub const DEFAULT_FG: u8 = 7;
+pub const DEFAULT_BG: u8 = 0;
+
+// Colors are indices into the 16-color ANSI palette.
+pub trait Display: Send {
+    fn size(&self) -> (usize, usize);
+    fn draw_char(&mut self, col: usize, row: usize, c: char, fg: u8, bg: u8);
+    fn fill(&mut self, col: usize, row: usize, cols: usize, rows: usize, bg: u8);
+    fn scroll_up(&mut self, bg: u8);
+
+    fn set_cursor(&mut self, _col: usize, _row: usize) {}
+}
+
+pub struct Terminal<D: Display> {
+    display: D,
+    cols: usize,
+    rows: usize,
+    col: usize,
+    row: usize,
+    fg: u8,
+    bg: u8,
+    bold: bool,
+    parser: Parser,
+}
+
+impl<D: Display> Terminal<D> {
+    pub fn new(display: D) -> Self {
+        let (cols, rows) = display.size();
+        let mut term = Self {
+            display,
+            cols,
+            rows,
+            col: 0,
+            row: 0,
+            fg: DEFAULT_FG,
+            bg: DEFAULT_BG,
+            bold: false,
+            parser: Parser::new(),
+        };
+        term.clear();
+        term
+    }
+
+    #[inline]
+    pub fn size(&self) -> (usize, usize) {
+        (self.cols, self.rows)
+    }
+
+    pub fn write_str(&mut self, s: &str) {
+        for c in s.chars() {
+            match self.parser.advance(c) {
+                Some(Action::Print(c)) => self.put_char(c),
+                Some(Action::Execute(c)) => self.execute(c),
+                Some(Action::Csi(csi)) => self.csi(&csi),
+                None => {}
+            }
+        }
+        self.display
+            .set_cursor(self.col.min(self.cols - 1), self.row);
+    }
+
+    pub fn clear(&mut self) {
+        self.display.fill(0, 0, self.cols, self.rows, self.bg);
+        self.col = 0;
+        self.row = 0;
+        self.display.set_cursor(0, 0);
+    }
+
+    fn put_char(&mut self, c: char) {
+        if self.col >= self.cols {
+            self.newline();
+        }
+        let fg = if self.bold && self.fg < 8 {
+            self.fg + 8
+        } else {
+            self.fg
+        };
+        self.display.draw_char(self.col, self.row, c, fg, self.bg);
+        self.col += 1;
+    }
+
+    fn execute(&mut self, c: char) {
+        match c {
+            '\n' => self.newline(),
+            '\r' => self.col = 0,
+            '\t' => self.col = ((self.col / TAB_WIDTH + 1) * TAB_WIDTH).min(self.cols - 1),
+            '\x08' => self.col = self.col.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    fn newline(&mut self) {
+        self.col = 0;
+        if self.row + 1 < self.rows {
+            self.row += 1;
+        } else {
+            self.display.scroll_up(self.bg);
+        }
+    }
+
+    fn csi(&mut self, csi: &Csi) {
+        let n = csi.param(0, 1) as usize;
+        match csi.action() {
+            'A' => self.row = self.row.saturating_sub(n),
+            'B' => self.row = (self.row + n).min(self.rows - 1),
+            'C' => self.col = (self.col + n).min(self.cols - 1),
+            'D' => self.col = self.col.saturating_sub(n),
+            'G' => self.col = (n - 1).min(self.cols - 1),
+            'H' | 'f' => {
+                self.row = (n - 1).min(self.rows - 1);
+                self.col = (csi.param(1, 1) as usize - 1).min(self.cols - 1);
+            }
+            'J' => self.erase_display(csi.param(0, 0)),
+            'K' => self.erase_line(csi.param(0, 0)),
+            'm' => self.sgr(csi),
+            _ => {}
+        }
+    }
+
+    fn erase_display(&mut self, mode: u16) {
+        match mode {
+            0 => {
+                self.erase_line(0);
+                let row = self.row + 1;
+                self.display
+                    .fill(0, row, self.cols, self.rows - row, self.bg);
+            }
+            1 => {
+                self.erase_line(1);
+                self.display.fill(0, 0, self.cols, self.row, self.bg);
+            }
+            _ => self.display.fill(0, 0, self.cols, self.rows, self.bg),
+        }
+    }
+
+    fn erase_line(&mut self, mode: u16) {
+        let col = self.col.min(self.cols);
+        let (start, end) = match mode {
+            0 => (col, self.cols),
+            1 => (0, (col + 1).min(self.cols)),
+            _ => (0, self.cols),
+        };
+        self.display.fill(start, self.row, end - start, 1, self.bg);
+    }
+
+    fn sgr(&mut self, csi: &Csi) {
+        if csi.params().is_empty() {
+            self.reset_attrs();
+        }
+        for &param in csi.params() {
+            match param {
+                0 => self.reset_attrs(),
+                1 => self.bold = true,
+                22 => self.bold = false,
+                30..=37 => self.fg = (param - 30) as u8,
+                39 => self.fg = DEFAULT_FG,
+                40..=47 => self.bg = (param - 40) as u8,
+                49 => self.bg = DEFAULT_BG,
+                90..=97 => self.fg = (param - 90) as u8 + 8,
+                100..=107 => self.bg = (param - 100) as u8 + 8,
+                _ => {}
+            }
+        }
+    }
+
+    fn reset_attrs(&mut self) {
+        self.fg = DEFAULT_FG;
+        self.bg = DEFAULT_BG;
+        self.bold = false;
+    }
+}
@@ -0,0 +1,323 @@
+use core::ops::{BitAnd, BitOr};
+
+use super::{Decoder, EventQueue, Keymap, ScancodeSet, US};
+
+const KEY_QUEUE_SIZE: usize = 64;
+
+const LED_SCROLL_LOCK: u8 = 1 << 0;
+const LED_NUM_LOCK: u8 = 1 << 1;
+const LED_CAPS_LOCK: u8 = 1 << 2;
+
+pub static KEY_EVENTS: EventQueue<KeyEvent, KEY_QUEUE_SIZE> = EventQueue::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum KeyCode {
+    Escape,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    PrintScreen,
+    ScrollLock,
+    Pause,
+    Backquote,
+    Digit1,
+    Digit2,
+    Digit3,
+    Digit4,
+    Digit5,
+    Digit6,
+    Digit7,
+    Digit8,
+    Digit9,
+    Digit0,
+    Minus,
+    Equal,
+    Backspace,
+    Tab,
+    Q,
+    W,
+    E,
+    R,
+    T,
+    Y,
+    U,
+    I,
+    O,
+    P,
+    LeftBracket,
+    RightBracket,
+    Backslash,
+    CapsLock,
+    A,
+    S,
+    D,
+    F,
+    G,
+    H,
+    J,
+    K,
+    L,
+    Semicolon,
+    Quote,
+    Enter,
+    LeftShift,
+    NonUsBackslash,
+    Z,
+    X,
+    C,
+    V,
+    B,
+    N,
+    M,
+    Comma,
+    Period,
+    Slash,
+    RightShift,
+    LeftCtrl,
+    LeftMeta,
+    LeftAlt,
+    Space,
+    RightAlt,
+    RightMeta,
+    Menu,
+    RightCtrl,
+    Insert,
+    Delete,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Up,
+    Down,
+    Left,
+    Right,
+    NumLock,
+    KpDivide,
+    KpMultiply,
+    KpMinus,
+    KpPlus,
+    KpEnter,
+    KpPeriod,
+    Kp0,
+    Kp1,
+    Kp2,
+    Kp3,
+    Kp4,
+    Kp5,
+    Kp6,
+    Kp7,
+    Kp8,
+    Kp9,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct Modifiers(u16);
+
+impl Modifiers {
+    pub const NONE: Modifiers = Self::new(0);
+    pub const LEFT_SHIFT: Modifiers = Self::new(1 << 0);
+    pub const RIGHT_SHIFT: Modifiers = Self::new(1 << 1);
+    pub const LEFT_CTRL: Modifiers = Self::new(1 << 2);
+    pub const RIGHT_CTRL: Modifiers = Self::new(1 << 3);
+    pub const LEFT_ALT: Modifiers = Self::new(1 << 4);
+    pub const ALT_GR: Modifiers = Self::new(1 << 5);
+    pub const LEFT_META: Modifiers = Self::new(1 << 6);
+    pub const RIGHT_META: Modifiers = Self::new(1 << 7);
+    pub const CAPS_LOCK: Modifiers = Self::new(1 << 8);
+    pub const NUM_LOCK: Modifiers = Self::new(1 << 9);
+    pub const SCROLL_LOCK: Modifiers = Self::new(1 << 10);
+
+    pub const SHIFT: Modifiers = Self::new(Self::LEFT_SHIFT.0 | Self::RIGHT_SHIFT.0);
+    pub const CTRL: Modifiers = Self::new(Self::LEFT_CTRL.0 | Self::RIGHT_CTRL.0);
+    pub const META: Modifiers = Self::new(Self::LEFT_META.0 | Self::RIGHT_META.0);
+
+    #[inline]
+    const fn new(val: u16) -> Self {
+        Self(val)
+    }
+
+    #[inline]
+    pub const fn raw(self) -> u16 {
+        self.0
+    }
+
+    fn set(&mut self, other: Self, on: bool) {
+        if on {
+            self.0 |= other.0;
+        } else {
+            self.0 &= !other.0;
+        }
+    }
+
+    fn toggle(&mut self, other: Self) {
+        self.0 ^= other.0;
+    }
+}
+
+impl BitAnd for Modifiers {
+    type Output = bool;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        (self.0 & rhs.0) != 0
+    }
+}
+
+impl BitOr for Modifiers {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub code: KeyCode,
+    pub pressed: bool,
+    pub modifiers: Modifiers,
+    pub ch: Option<char>,
+}
+
+pub struct Keyboard {
+    decoder: Decoder,
+    keymap: &'static Keymap,
+    modifiers: Modifiers,
+    held: [u64; 2],
+}
+
+impl Keyboard {
+    pub const fn new() -> Self {
+        Self {
+            decoder: Decoder::new(ScancodeSet::Set2),
+            keymap: &US,
+            modifiers: Modifiers::NONE,
+            held: [0; 2],
+        }
+    }
+
+    pub fn set_scancode_set(&mut self, set: ScancodeSet) {
+        self.decoder = Decoder::new(set);
+    }
+
+    pub fn set_keymap(&mut self, keymap: &'static Keymap) {
+        self.keymap = keymap;
+    }
+
+    #[inline]
+    pub fn keymap(&self) -> &'static Keymap {
+        self.keymap
+    }
+
+    #[inline]
+    pub fn modifiers(&self) -> Modifiers {
+        self.modifiers
+    }
+
+    pub fn is_held(&self, code: KeyCode) -> bool {
+        let code = code as usize;
+        self.held[code / 64] & (1 << (code % 64)) != 0
+    }
+
+    pub fn leds(&self) -> u8 {
+        let mut leds = 0;
+        if self.modifiers & Modifiers::SCROLL_LOCK {
+            leds |= LED_SCROLL_LOCK;
+        }
+        if self.modifiers & Modifiers::NUM_LOCK {
+            leds |= LED_NUM_LOCK;
+        }
+        if self.modifiers & Modifiers::CAPS_LOCK {
+            leds |= LED_CAPS_LOCK;
+        }
+        leds
+    }
+
+    pub fn feed(&mut self, byte: u8) -> Option<KeyEvent> {
+        let (code, pressed) = self.decoder.advance(byte)?;
+        let repeat = pressed && self.is_held(code);
+        self.set_held(code, pressed);
+
+        match code {
+            KeyCode::LeftShift => self.modifiers.set(Modifiers::LEFT_SHIFT, pressed),
+            KeyCode::RightShift => self.modifiers.set(Modifiers::RIGHT_SHIFT, pressed),
+            KeyCode::LeftCtrl => self.modifiers.set(Modifiers::LEFT_CTRL, pressed),
+            KeyCode::RightCtrl => self.modifiers.set(Modifiers::RIGHT_CTRL, pressed),
+            KeyCode::LeftAlt => self.modifiers.set(Modifiers::LEFT_ALT, pressed),
+            KeyCode::RightAlt => self.modifiers.set(Modifiers::ALT_GR, pressed),
+            KeyCode::LeftMeta => self.modifiers.set(Modifiers::LEFT_META, pressed),
+            KeyCode::RightMeta => self.modifiers.set(Modifiers::RIGHT_META, pressed),
+            KeyCode::CapsLock if pressed && !repeat => self.modifiers.toggle(Modifiers::CAPS_LOCK),
+            KeyCode::NumLock if pressed && !repeat => self.modifiers.toggle(Modifiers::NUM_LOCK),
+            KeyCode::ScrollLock if pressed && !repeat => {
+                self.modifiers.toggle(Modifiers::SCROLL_LOCK)
+            }
+            _ => {}
+        }
+
+        Some(KeyEvent {
+            code,
+            pressed,
+            modifiers: self.modifiers,
+            ch: if pressed { self.translate(code) } else { None },
+        })
+    }
+
+    fn set_held(&mut self, code: KeyCode, held: bool) {
+        let code = code as usize;
+        if held {
+            self.held[code / 64] |= 1 << (code % 64);
+        } else {
+            self.held[code / 64] &= !(1 << (code % 64));
+        }
+    }
+
+    fn translate(&self, code: KeyCode) -> Option<char> {
+        let num_lock = self.modifiers & Modifiers::NUM_LOCK;
+        let c = match code {
+            KeyCode::Escape => '\x1b',
+            KeyCode::Backspace => '\x08',
+            KeyCode::Tab => '\t',
+            KeyCode::Enter | KeyCode::KpEnter => '\n',
+            KeyCode::Space => ' ',
+            KeyCode::KpDivide => '/',
+            KeyCode::KpMultiply => '*',
+            KeyCode::KpMinus => '-',
+            KeyCode::KpPlus => '+',
+            KeyCode::KpPeriod if num_lock => '.',
+            KeyCode::Kp0 if num_lock => '0',
+            KeyCode::Kp1 if num_lock => '1',
+            KeyCode::Kp2 if num_lock => '2',
+            KeyCode::Kp3 if num_lock => '3',
+            KeyCode::Kp4 if num_lock => '4',
+            KeyCode::Kp5 if num_lock => '5',
+            KeyCode::Kp6 if num_lock => '6',
+            KeyCode::Kp7 if num_lock => '7',
+            KeyCode::Kp8 if num_lock => '8',
+            KeyCode::Kp9 if num_lock => '9',
+            _ => self.keymap.translate(code, self.modifiers)?,
+        };
+
+        if self.modifiers & Modifiers::CTRL && c.is_ascii_alphabetic() {
+            Some((c.to_ascii_lowercase() as u8 & 0x1f) as char)
+        } else {
+            Some(c)
+        }
+    }
+}
+
+impl Default for Keyboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
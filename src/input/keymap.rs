@@ -0,0 +1,149 @@
+use super::{KeyCode, Modifiers};
+
+const NONE: char = '\0';
+
+pub static KEYMAPS: [&Keymap; 2] = [&US, &DE];
+
+pub struct Keymap {
+    name: &'static str,
+    keys: &'static [(KeyCode, [char; 3])],
+}
+
+impl Keymap {
+    pub const fn new(name: &'static str, keys: &'static [(KeyCode, [char; 3])]) -> Self {
+        Self { name, keys }
+    }
+
+    pub fn find(name: &str) -> Option<&'static Keymap> {
+        KEYMAPS.iter().copied().find(|keymap| keymap.name == name)
+    }
+
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn translate(&self, code: KeyCode, modifiers: Modifiers) -> Option<char> {
+        let &(_, [normal, shifted, alt_gr]) = self.keys.iter().find(|(key, _)| *key == code)?;
+
+        let c = if modifiers & Modifiers::ALT_GR {
+            alt_gr
+        } else {
+            let caps = modifiers & Modifiers::CAPS_LOCK && normal.is_alphabetic();
+            if (modifiers & Modifiers::SHIFT) != caps {
+                shifted
+            } else {
+                normal
+            }
+        };
+        (c != NONE).then_some(c)
+    }
+}
+
+pub static US: Keymap = Keymap::new(
+    "us",
+    &[
+        (KeyCode::Backquote, ['`', '~', NONE]),
+        (KeyCode::Digit1, ['1', '!', NONE]),
+        (KeyCode::Digit2, ['2', '@', NONE]),
+        (KeyCode::Digit3, ['3', '#', NONE]),
+        (KeyCode::Digit4, ['4', '$', NONE]),
+        (KeyCode::Digit5, ['5', '%', NONE]),
+        (KeyCode::Digit6, ['6', '^', NONE]),
+        (KeyCode::Digit7, ['7', '&', NONE]),
+        (KeyCode::Digit8, ['8', '*', NONE]),
+        (KeyCode::Digit9, ['9', '(', NONE]),
+        (KeyCode::Digit0, ['0', ')', NONE]),
+        (KeyCode::Minus, ['-', '_', NONE]),
+        (KeyCode::Equal, ['=', '+', NONE]),
+        (KeyCode::Q, ['q', 'Q', NONE]),
+        (KeyCode::W, ['w', 'W', NONE]),
+        (KeyCode::E, ['e', 'E', NONE]),
+        (KeyCode::R, ['r', 'R', NONE]),
+        (KeyCode::T, ['t', 'T', NONE]),
+        (KeyCode::Y, ['y', 'Y', NONE]),
+        (KeyCode::U, ['u', 'U', NONE]),
+        (KeyCode::I, ['i', 'I', NONE]),
+        (KeyCode::O, ['o', 'O', NONE]),
+        (KeyCode::P, ['p', 'P', NONE]),
+        (KeyCode::LeftBracket, ['[', '{', NONE]),
+        (KeyCode::RightBracket, [']', '}', NONE]),
+        (KeyCode::Backslash, ['\\', '|', NONE]),
+        (KeyCode::A, ['a', 'A', NONE]),
+        (KeyCode::S, ['s', 'S', NONE]),
+        (KeyCode::D, ['d', 'D', NONE]),
+        (KeyCode::F, ['f', 'F', NONE]),
+        (KeyCode::G, ['g', 'G', NONE]),
+        (KeyCode::H, ['h', 'H', NONE]),
+        (KeyCode::J, ['j', 'J', NONE]),
+        (KeyCode::K, ['k', 'K', NONE]),
+        (KeyCode::L, ['l', 'L', NONE]),
+        (KeyCode::Semicolon, [';', ':', NONE]),
+        (KeyCode::Quote, ['\'', '"', NONE]),
+        (KeyCode::NonUsBackslash, ['\\', '|', NONE]),
+        (KeyCode::Z, ['z', 'Z', NONE]),
+        (KeyCode::X, ['x', 'X', NONE]),
+        (KeyCode::C, ['c', 'C', NONE]),
+        (KeyCode::V, ['v', 'V', NONE]),
+        (KeyCode::B, ['b', 'B', NONE]),
+        (KeyCode::N, ['n', 'N', NONE]),
+        (KeyCode::M, ['m', 'M', NONE]),
+        (KeyCode::Comma, [',', '<', NONE]),
+        (KeyCode::Period, ['.', '>', NONE]),
+        (KeyCode::Slash, ['/', '?', NONE]),
+    ],
+);
+
+pub static DE: Keymap = Keymap::new(
+    "de",
+    &[
+        (KeyCode::Backquote, ['^', '°', NONE]),
+        (KeyCode::Digit1, ['1', '!', NONE]),
+        (KeyCode::Digit2, ['2', '"', '²']),
+        (KeyCode::Digit3, ['3', '§', '³']),
+        (KeyCode::Digit4, ['4', '$', NONE]),
+        (KeyCode::Digit5, ['5', '%', NONE]),
+        (KeyCode::Digit6, ['6', '&', NONE]),
+        (KeyCode::Digit7, ['7', '/', '{']),
+        (KeyCode::Digit8, ['8', '(', '[']),
+        (KeyCode::Digit9, ['9', ')', ']']),
+        (KeyCode::Digit0, ['0', '=', '}']),
+        (KeyCode::Minus, ['ß', '?', '\\']),
+        (KeyCode::Equal, ['´', '`', NONE]),
+        (KeyCode::Q, ['q', 'Q', '@']),
+        (KeyCode::W, ['w', 'W', NONE]),
+        (KeyCode::E, ['e', 'E', '€']),
+        (KeyCode::R, ['r', 'R', NONE]),
+        (KeyCode::T, ['t', 'T', NONE]),
+        (KeyCode::Y, ['z', 'Z', NONE]),
+        (KeyCode::U, ['u', 'U', NONE]),
+        (KeyCode::I, ['i', 'I', NONE]),
+        (KeyCode::O, ['o', 'O', NONE]),
+        (KeyCode::P, ['p', 'P', NONE]),
+        (KeyCode::LeftBracket, ['ü', 'Ü', NONE]),
+        (KeyCode::RightBracket, ['+', '*', '~']),
+        (KeyCode::Backslash, ['#', '\'', NONE]),
+        (KeyCode::A, ['a', 'A', NONE]),
+        (KeyCode::S, ['s', 'S', NONE]),
+        (KeyCode::D, ['d', 'D', NONE]),
+        (KeyCode::F, ['f', 'F', NONE]),
+        (KeyCode::G, ['g', 'G', NONE]),
+        (KeyCode::H, ['h', 'H', NONE]),
+        (KeyCode::J, ['j', 'J', NONE]),
+        (KeyCode::K, ['k', 'K', NONE]),
+        (KeyCode::L, ['l', 'L', NONE]),
+        (KeyCode::Semicolon, ['ö', 'Ö', NONE]),
+        (KeyCode::Quote, ['ä', 'Ä', NONE]),
+        (KeyCode::NonUsBackslash, ['<', '>', '|']),
+        (KeyCode::Z, ['y', 'Y', NONE]),
+        (KeyCode::X, ['x', 'X', NONE]),
+        (KeyCode::C, ['c', 'C', NONE]),
+        (KeyCode::V, ['v', 'V', NONE]),
+        (KeyCode::B, ['b', 'B', NONE]),
+        (KeyCode::N, ['n', 'N', NONE]),
+        (KeyCode::M, ['m', 'M', 'µ']),
+        (KeyCode::Comma, [',', ';', NONE]),
+        (KeyCode::Period, ['.', ':', NONE]),
+        (KeyCode::Slash, ['-', '_', NONE]),
+    ],
+);
@@ -0,0 +1,106 @@
+use crate::sync::{IrqSafeMutex, Semaphore};
+
+pub use keyboard::*;
+pub use keymap::*;
+pub use mouse::*;
+pub use scancode::*;
+
+pub mod keyboard;
+pub mod keymap;
+pub mod mouse;
+pub mod scancode;
+
+crate::param!(static KEYMAP: &'static str = "keymap", "us");
+
+pub static KEYBOARD: IrqSafeMutex<Keyboard> = IrqSafeMutex::new(Keyboard::new());
+pub static MOUSE: IrqSafeMutex<Mouse> = IrqSafeMutex::new(Mouse::new());
+
+pub fn init() {
+    match Keymap::find(KEYMAP.get()) {
+        Some(keymap) => KEYBOARD.lock().set_keymap(keymap),
+        None => crate::warn!("unknown keymap `{}`, using us", KEYMAP.get()),
+    }
+}
+
+pub struct EventQueue<T: Copy, const N: usize> {
+    ring: IrqSafeMutex<Ring<T, N>>,
+    ready: Semaphore,
+}
+
+impl<T: Copy, const N: usize> EventQueue<T, N> {
+    pub const fn new() -> Self {
+        Self {
+            ring: IrqSafeMutex::new(Ring::new()),
+            ready: Semaphore::new(0),
+        }
+    }
+
+    pub fn push(&self, event: T) -> bool {
+        if !self.ring.lock().push(event) {
+            return false;
+        }
+        self.ready.up();
+        true
+    }
+
+    pub fn pop(&self) -> T {
+        self.ready.down();
+        self.ring.lock().pop().expect("event queue out of sync")
+    }
+
+    pub fn try_pop(&self) -> Option<T> {
+        if !self.ready.try_down() {
+            return None;
+        }
+        self.ring.lock().pop()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ready.count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T: Copy, const N: usize> Default for EventQueue<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Ring<T: Copy, const N: usize> {
+    entries: [Option<T>; N],
+    head: usize,
+    len: usize,
+}
+
+impl<T: Copy, const N: usize> Ring<T, N> {
+    const fn new() -> Self {
+        Self {
+            entries: [None; N],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, event: T) -> bool {
+        if self.len == N {
+            return false;
+        }
+        self.entries[(self.head + self.len) % N] = Some(event);
+        self.len += 1;
+        true
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        let event = self.entries[self.head].take();
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        event
+    }
+}
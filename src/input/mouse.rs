@@ -0,0 +1,167 @@
+use core::ops::{BitAnd, BitOr};
+
+use super::EventQueue;
+
+const MOUSE_QUEUE_SIZE: usize = 128;
+
+const PACKET_ALWAYS_SET: u8 = 1 << 3;
+const PACKET_X_SIGN: u8 = 1 << 4;
+const PACKET_Y_SIGN: u8 = 1 << 5;
+const PACKET_X_OVERFLOW: u8 = 1 << 6;
+const PACKET_Y_OVERFLOW: u8 = 1 << 7;
+const PACKET_BUTTONS: u8 = 0x07;
+const PACKET_EXTRA_BUTTONS: u8 = 0x30;
+
+pub static MOUSE_EVENTS: EventQueue<MouseEvent, MOUSE_QUEUE_SIZE> = EventQueue::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseKind {
+    Standard,
+    Wheel,
+    Explorer,
+}
+
+impl MouseKind {
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0x00 => Some(Self::Standard),
+            0x03 => Some(Self::Wheel),
+            0x04 => Some(Self::Explorer),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn packet_size(self) -> usize {
+        match self {
+            Self::Standard => 3,
+            Self::Wheel | Self::Explorer => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct MouseButtons(u8);
+
+impl MouseButtons {
+    pub const NONE: MouseButtons = Self::new(0);
+    pub const LEFT: MouseButtons = Self::new(1 << 0);
+    pub const RIGHT: MouseButtons = Self::new(1 << 1);
+    pub const MIDDLE: MouseButtons = Self::new(1 << 2);
+    pub const BACK: MouseButtons = Self::new(1 << 3);
+    pub const FORWARD: MouseButtons = Self::new(1 << 4);
+
+    #[inline]
+    const fn new(val: u8) -> Self {
+        Self(val)
+    }
+
+    #[inline]
+    pub const fn raw(self) -> u8 {
+        self.0
+    }
+}
+
+impl BitAnd for MouseButtons {
+    type Output = bool;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        (self.0 & rhs.0) != 0
+    }
+}
+
+impl BitOr for MouseButtons {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+// `dy` grows downwards, matching screen coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MouseEvent {
+    pub dx: i16,
+    pub dy: i16,
+    pub wheel: i8,
+    pub buttons: MouseButtons,
+}
+
+pub struct Mouse {
+    kind: MouseKind,
+    packet: [u8; 4],
+    len: usize,
+    dropped: usize,
+}
+
+impl Mouse {
+    pub const fn new() -> Self {
+        Self {
+            kind: MouseKind::Standard,
+            packet: [0; 4],
+            len: 0,
+            dropped: 0,
+        }
+    }
+
+    pub fn set_kind(&mut self, kind: MouseKind) {
+        self.kind = kind;
+        self.len = 0;
+    }
+
+    #[inline]
+    pub fn kind(&self) -> MouseKind {
+        self.kind
+    }
+
+    #[inline]
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    pub fn feed(&mut self, byte: u8) -> Option<MouseEvent> {
+        if self.len == 0 && byte & PACKET_ALWAYS_SET == 0 {
+            self.dropped += 1;
+            return None;
+        }
+
+        self.packet[self.len] = byte;
+        self.len += 1;
+        if self.len < self.kind.packet_size() {
+            return None;
+        }
+        self.len = 0;
+
+        let flags = self.packet[0];
+        if flags & (PACKET_X_OVERFLOW | PACKET_Y_OVERFLOW) != 0 {
+            self.dropped += 1;
+            return None;
+        }
+
+        let dx = self.packet[1] as i16 - (((flags & PACKET_X_SIGN) as i16) << 4);
+        let dy = self.packet[2] as i16 - (((flags & PACKET_Y_SIGN) as i16) << 3);
+        let mut buttons = flags & PACKET_BUTTONS;
+        let wheel = match self.kind {
+            MouseKind::Standard => 0,
+            MouseKind::Wheel => self.packet[3] as i8,
+            MouseKind::Explorer => {
+                buttons |= (self.packet[3] & PACKET_EXTRA_BUTTONS) >> 1;
+                ((self.packet[3] << 4) as i8) >> 4
+            }
+        };
+
+        Some(MouseEvent {
+            dx,
+            dy: -dy,
+            wheel,
+            buttons: MouseButtons::new(buttons),
+        })
+    }
+}
+
+impl Default for Mouse {
+    fn default() -> Self {
+        Self::new()
+    }
+}
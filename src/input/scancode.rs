@@ -0,0 +1,328 @@
+use super::KeyCode;
+
+const EXTENDED: u8 = 0xe0;
+const PAUSE: u8 = 0xe1;
+const RELEASE: u8 = 0xf0;
+const ACK: u8 = 0xfa;
+const RESEND: u8 = 0xfe;
+
+const SET1_BREAK: u8 = 0x80;
+const SET1_PAUSE_LEN: u8 = 5;
+const SET2_PAUSE_LEN: u8 = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScancodeSet {
+    Set1,
+    Set2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Start,
+    Extended,
+    Release,
+    ExtendedRelease,
+    Pause(u8),
+}
+
+pub struct Decoder {
+    set: ScancodeSet,
+    state: State,
+}
+
+impl Decoder {
+    pub const fn new(set: ScancodeSet) -> Self {
+        Self {
+            set,
+            state: State::Start,
+        }
+    }
+
+    #[inline]
+    pub fn set(&self) -> ScancodeSet {
+        self.set
+    }
+
+    pub fn advance(&mut self, byte: u8) -> Option<(KeyCode, bool)> {
+        match (self.state, byte) {
+            (State::Pause(1), _) => {
+                self.state = State::Start;
+                Some((KeyCode::Pause, true))
+            }
+            (State::Pause(left), _) => {
+                self.state = State::Pause(left - 1);
+                None
+            }
+            (State::Start, ACK | RESEND) => None,
+            (State::Start, EXTENDED) => {
+                self.state = State::Extended;
+                None
+            }
+            (State::Start, PAUSE) => {
+                self.state = State::Pause(match self.set {
+                    ScancodeSet::Set1 => SET1_PAUSE_LEN,
+                    ScancodeSet::Set2 => SET2_PAUSE_LEN,
+                });
+                None
+            }
+            (State::Start, RELEASE) if self.set == ScancodeSet::Set2 => {
+                self.state = State::Release;
+                None
+            }
+            (State::Extended, RELEASE) if self.set == ScancodeSet::Set2 => {
+                self.state = State::ExtendedRelease;
+                None
+            }
+            (state, byte) => {
+                self.state = State::Start;
+                let extended = matches!(state, State::Extended | State::ExtendedRelease);
+                match self.set {
+                    ScancodeSet::Set1 => {
+                        let code = set1_key(byte & !SET1_BREAK, extended)?;
+                        Some((code, byte & SET1_BREAK == 0))
+                    }
+                    ScancodeSet::Set2 => {
+                        let code = set2_key(byte, extended)?;
+                        Some((code, matches!(state, State::Start | State::Extended)))
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn set1_key(code: u8, extended: bool) -> Option<KeyCode> {
+    use KeyCode::*;
+
+    if extended {
+        return Some(match code {
+            0x1c => KpEnter,
+            0x1d => RightCtrl,
+            0x35 => KpDivide,
+            0x37 => PrintScreen,
+            0x38 => RightAlt,
+            0x47 => Home,
+            0x48 => Up,
+            0x49 => PageUp,
+            0x4b => Left,
+            0x4d => Right,
+            0x4f => End,
+            0x50 => Down,
+            0x51 => PageDown,
+            0x52 => Insert,
+            0x53 => Delete,
+            0x5b => LeftMeta,
+            0x5c => RightMeta,
+            0x5d => Menu,
+            _ => return None,
+        });
+    }
+
+    Some(match code {
+        0x01 => Escape,
+        0x02 => Digit1,
+        0x03 => Digit2,
+        0x04 => Digit3,
+        0x05 => Digit4,
+        0x06 => Digit5,
+        0x07 => Digit6,
+        0x08 => Digit7,
+        0x09 => Digit8,
+        0x0a => Digit9,
+        0x0b => Digit0,
+        0x0c => Minus,
+        0x0d => Equal,
+        0x0e => Backspace,
+        0x0f => Tab,
+        0x10 => Q,
+        0x11 => W,
+        0x12 => E,
+        0x13 => R,
+        0x14 => T,
+        0x15 => Y,
+        0x16 => U,
+        0x17 => I,
+        0x18 => O,
+        0x19 => P,
+        0x1a => LeftBracket,
+        0x1b => RightBracket,
+        0x1c => Enter,
+        0x1d => LeftCtrl,
+        0x1e => A,
+        0x1f => S,
+        0x20 => D,
+        0x21 => F,
+        0x22 => G,
+        0x23 => H,
+        0x24 => J,
+        0x25 => K,
+        0x26 => L,
+        0x27 => Semicolon,
+        0x28 => Quote,
+        0x29 => Backquote,
+        0x2a => LeftShift,
+        0x2b => Backslash,
+        0x2c => Z,
+        0x2d => X,
+        0x2e => C,
+        0x2f => V,
+        0x30 => B,
+        0x31 => N,
+        0x32 => M,
+        0x33 => Comma,
+        0x34 => Period,
+        0x35 => Slash,
+        0x36 => RightShift,
+        0x37 => KpMultiply,
+        0x38 => LeftAlt,
+        0x39 => Space,
+        0x3a => CapsLock,
+        0x3b => F1,
+        0x3c => F2,
+        0x3d => F3,
+        0x3e => F4,
+        0x3f => F5,
+        0x40 => F6,
+        0x41 => F7,
+        0x42 => F8,
+        0x43 => F9,
+        0x44 => F10,
+        0x45 => NumLock,
+        0x46 => ScrollLock,
+        0x47 => Kp7,
+        0x48 => Kp8,
+        0x49 => Kp9,
+        0x4a => KpMinus,
+        0x4b => Kp4,
+        0x4c => Kp5,
+        0x4d => Kp6,
+        0x4e => KpPlus,
+        0x4f => Kp1,
+        0x50 => Kp2,
+        0x51 => Kp3,
+        0x52 => Kp0,
+        0x53 => KpPeriod,
+        0x56 => NonUsBackslash,
+        0x57 => F11,
+        0x58 => F12,
+        _ => return None,
+    })
+}
+
+fn set2_key(code: u8, extended: bool) -> Option<KeyCode> {
+    use KeyCode::*;
+
+    if extended {
+        return Some(match code {
+            0x11 => RightAlt,
+            0x14 => RightCtrl,
+            0x1f => LeftMeta,
+            0x27 => RightMeta,
+            0x2f => Menu,
+            0x4a => KpDivide,
+            0x5a => KpEnter,
+            0x69 => End,
+            0x6b => Left,
+            0x6c => Home,
+            0x70 => Insert,
+            0x71 => Delete,
+            0x72 => Down,
+            0x74 => Right,
+            0x75 => Up,
+            0x7a => PageDown,
+            0x7c => PrintScreen,
+            0x7d => PageUp,
+            _ => return None,
+        });
+    }
+
+    Some(match code {
+        0x01 => F9,
+        0x03 => F5,
+        0x04 => F3,
+        0x05 => F1,
+        0x06 => F2,
+        0x07 => F12,
+        0x09 => F10,
+        0x0a => F8,
+        0x0b => F6,
+        0x0c => F4,
+        0x0d => Tab,
+        0x0e => Backquote,
+        0x11 => LeftAlt,
+        0x12 => LeftShift,
+        0x14 => LeftCtrl,
+        0x15 => Q,
+        0x16 => Digit1,
+        0x1a => Z,
+        0x1b => S,
+        0x1c => A,
+        0x1d => W,
+        0x1e => Digit2,
+        0x21 => C,
+        0x22 => X,
+        0x23 => D,
+        0x24 => E,
+        0x25 => Digit4,
+        0x26 => Digit3,
+        0x29 => Space,
+        0x2a => V,
+        0x2b => F,
+        0x2c => T,
+        0x2d => R,
+        0x2e => Digit5,
+        0x31 => N,
+        0x32 => B,
+        0x33 => H,
+        0x34 => G,
+        0x35 => Y,
+        0x36 => Digit6,
+        0x3a => M,
+        0x3b => J,
+        0x3c => U,
+        0x3d => Digit7,
+        0x3e => Digit8,
+        0x41 => Comma,
+        0x42 => K,
+        0x43 => I,
+        0x44 => O,
+        0x45 => Digit0,
+        0x46 => Digit9,
+        0x49 => Period,
+        0x4a => Slash,
+        0x4b => L,
+        0x4c => Semicolon,
+        0x4d => P,
+        0x4e => Minus,
+        0x52 => Quote,
+        0x54 => LeftBracket,
+        0x55 => Equal,
+        0x58 => CapsLock,
+        0x59 => RightShift,
+        0x5a => Enter,
+        0x5b => RightBracket,
+        0x5d => Backslash,
+        0x61 => NonUsBackslash,
+        0x66 => Backspace,
+        0x69 => Kp1,
+        0x6b => Kp4,
+        0x6c => Kp7,
+        0x70 => Kp0,
+        0x71 => KpPeriod,
+        0x72 => Kp2,
+        0x73 => Kp5,
+        0x74 => Kp6,
+        0x75 => Kp8,
+        0x76 => Escape,
+        0x77 => NumLock,
+        0x78 => F11,
+        0x79 => KpPlus,
+        0x7a => Kp3,
+        0x7b => KpMinus,
+        0x7c => KpMultiply,
+        0x7d => Kp9,
+        0x7e => ScrollLock,
+        0x83 => F7,
+        _ => return None,
+    })
+}
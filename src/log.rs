@@ -0,0 +1,460 @@
+use core::{
+    cell::UnsafeCell,
+    fmt::{self, Write},
+    ptr,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use crate::{
+    arch::{self, MAX_CPUS},
+    sync::{InitError, OnceCell},
+};
+
+const MAX_SINKS: usize = 4;
+const RING_SIZE: usize = 64;
+const MSG_SIZE: usize = 192;
+
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::Info;
+
+static RINGS: [Ring; MAX_CPUS] = [const { Ring::new() }; MAX_CPUS];
+static SINKS: Sinks = Sinks::new();
+static PANIC_SINK: OnceCell<&'static dyn Sink> = OnceCell::new();
+static DRAINING: AtomicBool = AtomicBool::new(false);
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+crate::param!(static FILTER: &'static str = "log", "");
+
+static FILTER_SPEC: OnceCell<&'static str> = OnceCell::new();
+
+#[macro_export]
+macro_rules! log {
+    ($level:expr, $($arg:tt)+) => (
+        $crate::log::log($level, module_path!(), format_args!($($arg)+))
+    );
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)+) => ($crate::log!($crate::log::Level::Error, $($arg)+));
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)+) => ($crate::log!($crate::log::Level::Warn, $($arg)+));
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)+) => ($crate::log!($crate::log::Level::Info, $($arg)+));
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)+) => ($crate::log!($crate::log::Level::Debug, $($arg)+));
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)+) => ($crate::log!($crate::log::Level::Trace, $($arg)+));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Level {
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
+
+impl Level {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Error => "ERROR",
+            Self::Warn => "WARN",
+            Self::Info => "INFO",
+            Self::Debug => "DEBUG",
+            Self::Trace => "TRACE",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum LevelFilter {
+    Off = 0,
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
+
+impl LevelFilter {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "off" => Some(Self::Off),
+            "error" => Some(Self::Error),
+            "warn" => Some(Self::Warn),
+            "info" => Some(Self::Info),
+            "debug" => Some(Self::Debug),
+            "trace" => Some(Self::Trace),
+            _ => None,
+        }
+    }
+
+    pub fn enabled(self, level: Level) -> bool {
+        level as u8 <= self as u8
+    }
+}
+
+pub trait Sink: Sync {
+    fn write_str(&self, s: &str);
+}
+
+pub fn init() {
+    if FILTER.is_set() {
+        let _ = set_filter(FILTER.get());
+    }
+}
+
+pub fn set_filter(spec: &'static str) -> Result<(), InitError> {
+    FILTER_SPEC.set(spec).map(|_| ())
+}
+
+pub fn register_sink(sink: &'static dyn Sink) {
+    while DRAINING
+        .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        core::hint::spin_loop();
+    }
+    unsafe { SINKS.push(sink) };
+    DRAINING.store(false, Ordering::Release);
+
+    flush();
+}
+
+// Used instead of the registered sinks once the kernel panics, so it must not
+// take any locks.
+pub fn register_panic_sink(sink: &'static dyn Sink) {
+    let _ = PANIC_SINK.set(sink);
+}
+
+pub fn enabled(level: Level, module: &str) -> bool {
+    let spec = filter_spec();
+    let module = module.strip_prefix("kernel::").unwrap_or(module);
+
+    let mut filter = DEFAULT_LEVEL;
+    let mut best = None;
+    for directive in spec.split(',').filter(|d| !d.is_empty()) {
+        match directive.split_once('=') {
+            Some((name, level)) => {
+                let Some(level) = LevelFilter::parse(level) else {
+                    continue;
+                };
+                let matches = module
+                    .strip_prefix(name)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"));
+                if matches && !best.is_some_and(|len| name.len() < len) {
+                    best = Some(name.len());
+                    filter = level;
+                }
+            }
+            None => {
+                if let (None, Some(level)) = (best, LevelFilter::parse(directive)) {
+                    filter = level;
+                }
+            }
+        }
+    }
+
+    filter.enabled(level)
+}
+
+pub fn log(level: Level, module: &'static str, args: fmt::Arguments) {
+    if enabled(level, module) {
+        write_record(Some(level), module, args);
+        flush();
+    }
+}
+
+pub fn print(args: fmt::Arguments) {
+    write_record(None, "", args);
+    flush();
+}
+
+pub fn flush() {
+    loop {
+        if DRAINING
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return;
+        }
+        unsafe { drain(&SINKS) };
+        DRAINING.store(false, Ordering::Release);
+
+        if !RINGS.iter().any(Ring::has_ready) {
+            return;
+        }
+    }
+}
+
+// Logs the panic without flushing and then dumps everything queued to the
+// panic sink. Draining stays claimed so that other CPUs stop writing to the
+// regular sinks.
+pub fn dump(args: fmt::Arguments) {
+    write_record(Some(Level::Error), "kernel", args);
+    DRAINING.swap(true, Ordering::Acquire);
+    let sink = PANIC_SINK.get().copied().unwrap_or(&SINKS);
+    unsafe { drain(sink) };
+}
+
+fn filter_spec() -> &'static str {
+    FILTER_SPEC.get().copied().unwrap_or("")
+}
+
+fn write_record(level: Option<Level>, module: &'static str, args: fmt::Arguments) {
+    let cpu = arch::cpu_index();
+    let ring = &RINGS[cpu];
+    let tsc = arch::read_tsc();
+    ring.push(|record| {
+        record.tsc = tsc;
+        record.cpu = cpu;
+        record.level = level;
+        record.module = module;
+        record.len = 0;
+        let _ = record.write_fmt(args);
+    });
+}
+
+unsafe fn drain(sink: &dyn Sink) {
+    let mut record = Record::new();
+    loop {
+        let dropped = DROPPED.swap(0, Ordering::Relaxed);
+        if dropped != 0 {
+            sink.write_fmt(format_args!("[log: {} records dropped]\n", dropped));
+        }
+
+        let mut oldest: Option<(&Ring, u64)> = None;
+        for ring in &RINGS {
+            if let Some(tsc) = ring.peek_tsc() {
+                if !oldest.is_some_and(|(_, t)| tsc >= t) {
+                    oldest = Some((ring, tsc));
+                }
+            }
+        }
+
+        let Some((ring, _)) = oldest else {
+            break;
+        };
+        if ring.pop(&mut record) {
+            record.emit(sink);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Record {
+    tsc: u64,
+    cpu: usize,
+    level: Option<Level>,
+    module: &'static str,
+    len: usize,
+    msg: [u8; MSG_SIZE],
+}
+
+impl Record {
+    const fn new() -> Self {
+        Self {
+            tsc: 0,
+            cpu: 0,
+            level: None,
+            module: "",
+            len: 0,
+            msg: [0; MSG_SIZE],
+        }
+    }
+
+    fn msg(&self) -> &str {
+        let len = self.len.min(MSG_SIZE);
+        core::str::from_utf8(&self.msg[..len]).unwrap_or("<invalid utf-8>")
+    }
+
+    fn emit(&self, sink: &dyn Sink) {
+        let Some(level) = self.level else {
+            sink.write_str(self.msg());
+            return;
+        };
+
+        if let Some(micros) = self.tsc.checked_div(arch::tsc_mhz()) {
+            sink.write_fmt(format_args!(
+                "[{:>5}.{:06}] ",
+                micros / 1_000_000,
+                micros % 1_000_000
+            ));
+        } else {
+            sink.write_fmt(format_args!("[{:>12}] ", self.tsc));
+        }
+
+        let module = self.module.strip_prefix("kernel::").unwrap_or(self.module);
+        sink.write_fmt(format_args!(
+            "cpu{} {:<5} {}: {}",
+            self.cpu,
+            level.as_str(),
+            module,
+            self.msg()
+        ));
+        if self.len > MSG_SIZE {
+            sink.write_str("...");
+        }
+        sink.write_str("\n");
+    }
+}
+
+impl Write for Record {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let start = self.len.min(MSG_SIZE);
+        let mut count = s.len().min(MSG_SIZE - start);
+        while !s.is_char_boundary(count) {
+            count -= 1;
+        }
+        self.msg[start..start + count].copy_from_slice(&s.as_bytes()[..count]);
+        self.len += s.len();
+        Ok(())
+    }
+}
+
+struct Slot {
+    seq: AtomicUsize,
+    record: UnsafeCell<Record>,
+}
+
+impl Slot {
+    const fn new() -> Self {
+        Self {
+            seq: AtomicUsize::new(0),
+            record: UnsafeCell::new(Record::new()),
+        }
+    }
+}
+
+// Each CPU only writes to its own ring, so the only concurrent writers are
+// nested interrupt handlers. A slot's sequence number is odd while it is being
+// written and becomes `2 * (index + 1)` once committed.
+struct Ring {
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    slots: [Slot; RING_SIZE],
+}
+
+impl Ring {
+    const fn new() -> Self {
+        Self {
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            slots: [const { Slot::new() }; RING_SIZE],
+        }
+    }
+
+    fn push(&self, f: impl FnOnce(&mut Record)) {
+        let idx = self.head.fetch_add(1, Ordering::AcqRel);
+        let slot = &self.slots[idx % RING_SIZE];
+        slot.seq.store(2 * idx + 1, Ordering::Release);
+        f(unsafe { &mut *slot.record.get() });
+        slot.seq.store(2 * (idx + 1), Ordering::Release);
+    }
+
+    fn has_ready(&self) -> bool {
+        let tail = self.tail.load(Ordering::Acquire);
+        self.slots[tail % RING_SIZE].seq.load(Ordering::Acquire) == 2 * (tail + 1)
+    }
+
+    fn skip_lost(&self) -> usize {
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Relaxed);
+        if head - tail > RING_SIZE {
+            DROPPED.fetch_add(head - RING_SIZE - tail, Ordering::Relaxed);
+            self.tail.store(head - RING_SIZE, Ordering::Relaxed);
+            head - RING_SIZE
+        } else {
+            tail
+        }
+    }
+
+    fn peek_tsc(&self) -> Option<u64> {
+        let tail = self.skip_lost();
+        let slot = &self.slots[tail % RING_SIZE];
+        if slot.seq.load(Ordering::Acquire) != 2 * (tail + 1) {
+            return None;
+        }
+        Some(unsafe { ptr::read_volatile(&(*slot.record.get()).tsc) })
+    }
+
+    fn pop(&self, out: &mut Record) -> bool {
+        let tail = self.skip_lost();
+        let slot = &self.slots[tail % RING_SIZE];
+        let seq = 2 * (tail + 1);
+        if slot.seq.load(Ordering::Acquire) != seq {
+            return false;
+        }
+        *out = unsafe { ptr::read_volatile(slot.record.get()) };
+        let valid = slot.seq.load(Ordering::Acquire) == seq;
+        self.tail.store(tail + 1, Ordering::Release);
+        if !valid {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+        }
+        valid
+    }
+}
+
+unsafe impl Sync for Ring {}
+
+struct Sinks {
+    len: UnsafeCell<usize>,
+    sinks: UnsafeCell<[Option<&'static dyn Sink>; MAX_SINKS]>,
+}
+
+impl Sinks {
+    const fn new() -> Self {
+        Self {
+            len: UnsafeCell::new(0),
+            sinks: UnsafeCell::new([None; MAX_SINKS]),
+        }
+    }
+
+    unsafe fn push(&self, sink: &'static dyn Sink) {
+        let len = &mut *self.len.get();
+        assert!(*len < MAX_SINKS, "too many log sinks");
+        (*self.sinks.get())[*len] = Some(sink);
+        *len += 1;
+    }
+}
+
+impl Sink for Sinks {
+    fn write_str(&self, s: &str) {
+        let sinks = unsafe { &*self.sinks.get() };
+        for sink in sinks.iter().flatten() {
+            sink.write_str(s);
+        }
+    }
+}
+
+unsafe impl Sync for Sinks {}
+
+impl dyn Sink + '_ {
+    fn write_fmt(&self, args: fmt::Arguments) {
+        let _ = Write::write_fmt(&mut SinkWriter(self), args);
+    }
+}
+
+struct SinkWriter<'a>(&'a dyn Sink);
+
+impl Write for SinkWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write_str(s);
+        Ok(())
+    }
+}
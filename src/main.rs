@@ -7,22 +7,36 @@
 use core::panic::PanicInfo;
 
 mod arch;
+mod block;
+mod cmdline;
+mod console;
+mod input;
+mod log;
+mod net;
+mod pci;
+mod sched;
 mod sync;
+mod time;
+mod vfs;
+mod virtio;
 
 pub fn kernel_main() -> ! {
     arch::enable_interrupts();
-    arch::halt();
+    loop {
+        net::poll();
+        time::idle();
+    }
 }
 
 #[panic_handler]
 fn _panic(info: &PanicInfo) -> ! {
-    println!("{}", info);
+    log::dump(format_args!("{}", info));
     loop {}
 }
 
 #[macro_export]
 macro_rules! print {
-    ($($arg:tt)*) => ($crate::arch::print(format_args!($($arg)*)));
+    ($($arg:tt)*) => ($crate::log::print(format_args!($($arg)*)));
 }
 
 #[macro_export]
@@ -0,0 +1,438 @@
+use core::{
+    ptr, slice,
+    sync::atomic::{fence, AtomicBool, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use ::memory::PAGE_SIZE;
+
+use crate::{
+    arch::{self, Mmio},
+    pci::{self, Bar, Command, Device, Driver, Match, ProbeError},
+    sync::{IrqSafeMutex, OnceCell},
+    time,
+};
+
+use super::{MacAddress, NetDevice, TxError, MAX_FRAME_SIZE};
+
+const VENDOR_INTEL: u16 = 0x8086;
+const DEVICE_82540EM: u16 = 0x100e;
+const DEVICE_82545EM: u16 = 0x100f;
+
+const REG_CTRL: usize = 0x0000;
+const REG_STATUS: usize = 0x0008;
+const REG_EERD: usize = 0x0014;
+const REG_ICR: usize = 0x00c0;
+const REG_IMS: usize = 0x00d0;
+const REG_IMC: usize = 0x00d8;
+const REG_RCTL: usize = 0x0100;
+const REG_TCTL: usize = 0x0400;
+const REG_TIPG: usize = 0x0410;
+const REG_RDBAL: usize = 0x2800;
+const REG_RDBAH: usize = 0x2804;
+const REG_RDLEN: usize = 0x2808;
+const REG_RDH: usize = 0x2810;
+const REG_RDT: usize = 0x2818;
+const REG_RDTR: usize = 0x2820;
+const REG_TDBAL: usize = 0x3800;
+const REG_TDBAH: usize = 0x3804;
+const REG_TDLEN: usize = 0x3808;
+const REG_TDH: usize = 0x3810;
+const REG_TDT: usize = 0x3818;
+const REG_MTA: usize = 0x5200;
+const REG_RAL: usize = 0x5400;
+const REG_RAH: usize = 0x5404;
+const MTA_ENTRIES: usize = 128;
+
+const CTRL_ASDE: u32 = 1 << 5;
+const CTRL_SLU: u32 = 1 << 6;
+const CTRL_LRST: u32 = 1 << 3;
+const CTRL_ILOS: u32 = 1 << 7;
+const CTRL_RST: u32 = 1 << 26;
+const CTRL_VME: u32 = 1 << 30;
+const CTRL_PHY_RST: u32 = 1 << 31;
+
+const STATUS_LU: u32 = 1 << 1;
+
+const EERD_START: u32 = 1 << 0;
+const EERD_DONE: u32 = 1 << 4;
+const EERD_ADDR_SHIFT: u32 = 8;
+const EERD_DATA_SHIFT: u32 = 16;
+
+const INT_LSC: u32 = 1 << 2;
+const INT_RXDMT0: u32 = 1 << 4;
+const INT_RXO: u32 = 1 << 6;
+const INT_RXT0: u32 = 1 << 7;
+const INT_RX: u32 = INT_RXDMT0 | INT_RXO | INT_RXT0;
+
+const RCTL_EN: u32 = 1 << 1;
+const RCTL_BAM: u32 = 1 << 15;
+const RCTL_SECRC: u32 = 1 << 26;
+
+const TCTL_EN: u32 = 1 << 1;
+const TCTL_PSP: u32 = 1 << 3;
+const TCTL_CT: u32 = 0x0f << 4;
+const TCTL_COLD: u32 = 0x40 << 12;
+// The recommended gaps for copper links.
+const TIPG_COPPER: u32 = 10 | (8 << 10) | (6 << 20);
+
+const RAH_AV: u32 = 1 << 31;
+
+const DESC_DD: u8 = 1 << 0;
+const DESC_EOP: u8 = 1 << 1;
+const CMD_EOP: u8 = 1 << 0;
+const CMD_IFCS: u8 = 1 << 1;
+const CMD_RS: u8 = 1 << 3;
+
+// Rings of 16 byte descriptors share the first page, every descriptor owns
+// a 2 KiB buffer, the receive buffer size the device defaults to.
+const RING_LEN: usize = 32;
+const DESC_SIZE: usize = 16;
+const BUFFER_SIZE: usize = 2048;
+const RX_RING: usize = 0;
+const TX_RING: usize = RING_LEN * DESC_SIZE;
+const RING_PAGES: usize = 1;
+const BUFFER_PAGES: usize = RING_LEN * BUFFER_SIZE / PAGE_SIZE;
+const DMA_PAGES: usize = RING_PAGES + 2 * BUFFER_PAGES;
+
+const MAX_NICS: usize = 4;
+const RESET_TIMEOUT: Duration = Duration::from_millis(100);
+const EEPROM_TIMEOUT: Duration = Duration::from_millis(10);
+
+pub static E1000_DRIVER: E1000Driver = E1000Driver;
+
+static NICS: [OnceCell<E1000>; MAX_NICS] = [const { OnceCell::new() }; MAX_NICS];
+static NEXT_NIC: AtomicUsize = AtomicUsize::new(0);
+
+pub fn init() {
+    pci::register(&E1000_DRIVER);
+}
+
+fn interrupt(idx: usize) {
+    if let Some(nic) = NICS[idx].get() {
+        nic.interrupt();
+    }
+}
+
+pub struct E1000Driver;
+
+impl Driver for E1000Driver {
+    fn name(&self) -> &'static str {
+        "e1000"
+    }
+
+    fn matches(&self) -> &'static [Match] {
+        &[
+            Match::Device(VENDOR_INTEL, DEVICE_82540EM),
+            Match::Device(VENDOR_INTEL, DEVICE_82545EM),
+        ]
+    }
+
+    fn probe(&self, device: &'static Device) -> Result<(), ProbeError> {
+        let idx = NEXT_NIC.fetch_add(1, Ordering::Relaxed);
+        if idx >= MAX_NICS {
+            return Err(ProbeError::Device("too many e1000 devices"));
+        }
+        let name = super::eth_name().ok_or(ProbeError::Device("too many ethernet devices"))?;
+        let (addr, size) = match device.bar(0) {
+            Some(Bar::Memory { addr, size, .. }) => (addr as usize, size as usize),
+            _ => return Err(ProbeError::MissingBar(0)),
+        };
+        arch::map_mmio(addr, size)?;
+        device.enable(Command::MEMORY | Command::BUS_MASTER);
+
+        // The line is shared and the handler ignores the slot until it is
+        // filled, device interrupts stay masked until then.
+        arch::register_irq(device.interrupt_line(), interrupt, idx)
+            .map_err(|_| ProbeError::Device("no usable interrupt line"))?;
+        let nic = E1000::new(name, addr)?;
+        if let Ok(nic) = NICS[idx].set(nic) {
+            nic.reg(REG_IMS).write(INT_RX | INT_LSC);
+            super::register(nic);
+        }
+        Ok(())
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct RxDesc {
+    addr: u64,
+    len: u16,
+    checksum: u16,
+    status: u8,
+    errors: u8,
+    special: u16,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct TxDesc {
+    addr: u64,
+    len: u16,
+    cso: u8,
+    cmd: u8,
+    status: u8,
+    css: u8,
+    special: u16,
+}
+
+struct Rings {
+    rx_next: usize,
+    tx_next: usize,
+}
+
+pub struct E1000 {
+    name: &'static str,
+    regs: usize,
+    mac: MacAddress,
+    link: AtomicBool,
+    dma: usize,
+    rings: IrqSafeMutex<Rings>,
+}
+
+impl E1000 {
+    fn new(name: &'static str, regs: usize) -> Result<Self, ProbeError> {
+        let mut nic = Self {
+            name,
+            regs,
+            mac: MacAddress::ZERO,
+            link: AtomicBool::new(false),
+            dma: 0,
+            rings: IrqSafeMutex::new(Rings {
+                rx_next: 0,
+                tx_next: 0,
+            }),
+        };
+        nic.reset()?;
+        nic.mac = nic.read_mac();
+        nic.dma = arch::alloc_dma(DMA_PAGES)?;
+        nic.setup();
+        Ok(nic)
+    }
+
+    #[inline]
+    fn reg(&self, reg: usize) -> Mmio<u32> {
+        Mmio::new(self.regs + reg)
+    }
+
+    #[inline]
+    fn rx_buffer(&self, idx: usize) -> usize {
+        self.dma + RING_PAGES * PAGE_SIZE + idx * BUFFER_SIZE
+    }
+
+    #[inline]
+    fn tx_buffer(&self, idx: usize) -> usize {
+        self.dma + (RING_PAGES + BUFFER_PAGES) * PAGE_SIZE + idx * BUFFER_SIZE
+    }
+
+    #[inline]
+    fn rx_desc(&self, idx: usize) -> *mut RxDesc {
+        (self.dma + RX_RING + idx * DESC_SIZE) as *mut RxDesc
+    }
+
+    #[inline]
+    fn tx_desc(&self, idx: usize) -> *mut TxDesc {
+        (self.dma + TX_RING + idx * DESC_SIZE) as *mut TxDesc
+    }
+
+    fn reset(&self) -> Result<(), ProbeError> {
+        self.reg(REG_IMC).write(u32::MAX);
+        self.reg(REG_CTRL)
+            .write(self.reg(REG_CTRL).read() | CTRL_RST);
+        let deadline = time::monotonic() + RESET_TIMEOUT;
+        while self.reg(REG_CTRL).read() & CTRL_RST != 0 {
+            if time::monotonic() >= deadline {
+                return Err(ProbeError::Device("reset timed out"));
+            }
+            core::hint::spin_loop();
+        }
+        self.reg(REG_IMC).write(u32::MAX);
+        self.reg(REG_ICR).read();
+
+        let ctrl = self.reg(REG_CTRL).read() & !(CTRL_LRST | CTRL_ILOS | CTRL_VME | CTRL_PHY_RST);
+        self.reg(REG_CTRL).write(ctrl | CTRL_SLU | CTRL_ASDE);
+        Ok(())
+    }
+
+    fn read_eeprom(&self, word: u8) -> Option<u16> {
+        self.reg(REG_EERD)
+            .write(EERD_START | (word as u32) << EERD_ADDR_SHIFT);
+        let deadline = time::monotonic() + EEPROM_TIMEOUT;
+        loop {
+            let eerd = self.reg(REG_EERD).read();
+            if eerd & EERD_DONE != 0 {
+                return Some((eerd >> EERD_DATA_SHIFT) as u16);
+            }
+            if time::monotonic() >= deadline {
+                return None;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    // The address the firmware loaded into the first receive address
+    // register is used if the EEPROM doesn't answer.
+    fn read_mac(&self) -> MacAddress {
+        let mut mac = [0; 6];
+        for word in 0..3 {
+            match self.read_eeprom(word) {
+                Some(value) => mac[word as usize * 2..][..2].copy_from_slice(&value.to_le_bytes()),
+                None => {
+                    let low = self.reg(REG_RAL).read().to_le_bytes();
+                    let high = self.reg(REG_RAH).read().to_le_bytes();
+                    mac[..4].copy_from_slice(&low);
+                    mac[4..].copy_from_slice(&high[..2]);
+                    break;
+                }
+            }
+        }
+        MacAddress(mac)
+    }
+
+    fn setup(&self) {
+        let [a, b, c, d, e, f] = self.mac.0;
+        self.reg(REG_RAL).write(u32::from_le_bytes([a, b, c, d]));
+        self.reg(REG_RAH)
+            .write(u32::from_le_bytes([e, f, 0, 0]) | RAH_AV);
+        for idx in 0..MTA_ENTRIES {
+            self.reg(REG_MTA + idx * 4).write(0);
+        }
+
+        for idx in 0..RING_LEN {
+            let desc = RxDesc {
+                addr: self.rx_buffer(idx) as u64,
+                len: 0,
+                checksum: 0,
+                status: 0,
+                errors: 0,
+                special: 0,
+            };
+            unsafe { ptr::write_volatile(self.rx_desc(idx), desc) };
+            // Free transmit descriptors look like completed ones.
+            let desc = TxDesc {
+                addr: self.tx_buffer(idx) as u64,
+                len: 0,
+                cso: 0,
+                cmd: 0,
+                status: DESC_DD,
+                css: 0,
+                special: 0,
+            };
+            unsafe { ptr::write_volatile(self.tx_desc(idx), desc) };
+        }
+
+        let rx_ring = (self.dma + RX_RING) as u64;
+        self.reg(REG_RDBAL).write(rx_ring as u32);
+        self.reg(REG_RDBAH).write((rx_ring >> 32) as u32);
+        self.reg(REG_RDLEN).write((RING_LEN * DESC_SIZE) as u32);
+        self.reg(REG_RDH).write(0);
+        // One descriptor stays with the driver so a full ring is told apart
+        // from an empty one.
+        self.reg(REG_RDT).write(RING_LEN as u32 - 1);
+        self.reg(REG_RDTR).write(0);
+        self.reg(REG_RCTL).write(RCTL_EN | RCTL_BAM | RCTL_SECRC);
+
+        let tx_ring = (self.dma + TX_RING) as u64;
+        self.reg(REG_TDBAL).write(tx_ring as u32);
+        self.reg(REG_TDBAH).write((tx_ring >> 32) as u32);
+        self.reg(REG_TDLEN).write((RING_LEN * DESC_SIZE) as u32);
+        self.reg(REG_TDH).write(0);
+        self.reg(REG_TDT).write(0);
+        self.reg(REG_TIPG).write(TIPG_COPPER);
+        self.reg(REG_TCTL)
+            .write(TCTL_EN | TCTL_PSP | TCTL_CT | TCTL_COLD);
+
+        self.link.store(
+            self.reg(REG_STATUS).read() & STATUS_LU != 0,
+            Ordering::Relaxed,
+        );
+    }
+
+    // Reading the cause register acknowledges the interrupt.
+    fn interrupt(&self) {
+        let cause = self.reg(REG_ICR).read();
+        if cause & INT_LSC != 0 {
+            let up = self.reg(REG_STATUS).read() & STATUS_LU != 0;
+            if self.link.swap(up, Ordering::Relaxed) != up {
+                crate::info!("{}: link {}", self.name, if up { "up" } else { "down" });
+            }
+        }
+        if cause & INT_RX != 0 {
+            super::notify();
+        }
+    }
+}
+
+impl NetDevice for E1000 {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn mac(&self) -> MacAddress {
+        self.mac
+    }
+
+    fn link_up(&self) -> bool {
+        self.link.load(Ordering::Relaxed)
+    }
+
+    fn transmit(&self, frame: &[u8]) -> Result<(), TxError> {
+        if frame.len() > MAX_FRAME_SIZE {
+            return Err(TxError::TooLarge);
+        }
+        if !self.link_up() {
+            return Err(TxError::LinkDown);
+        }
+        let mut rings = self.rings.lock();
+        let idx = rings.tx_next;
+        let next = (idx + 1) % RING_LEN;
+        // One descriptor is always left unused, otherwise a full ring would
+        // have TDT catch up with TDH and look empty to the hardware.
+        if unsafe { ptr::read_volatile(self.tx_desc(next)) }.status & DESC_DD == 0 {
+            return Err(TxError::Busy);
+        }
+        let desc = self.tx_desc(idx);
+        let mut current = unsafe { ptr::read_volatile(desc) };
+        let buffer =
+            unsafe { slice::from_raw_parts_mut(self.tx_buffer(idx) as *mut u8, frame.len()) };
+        buffer.copy_from_slice(frame);
+        current.len = frame.len() as u16;
+        current.cmd = CMD_EOP | CMD_IFCS | CMD_RS;
+        current.status = 0;
+        unsafe { ptr::write_volatile(desc, current) };
+
+        rings.tx_next = next;
+        fence(Ordering::SeqCst);
+        self.reg(REG_TDT).write(next as u32);
+        Ok(())
+    }
+
+    fn receive(&self, buf: &mut [u8]) -> Option<usize> {
+        let mut rings = self.rings.lock();
+        loop {
+            let idx = rings.rx_next;
+            let desc = self.rx_desc(idx);
+            let mut current = unsafe { ptr::read_volatile(desc) };
+            if current.status & DESC_DD == 0 {
+                return None;
+            }
+
+            // A 2 KiB buffer holds any frame, one spanning buffers is as
+            // broken as one with errors.
+            let len = current.len as usize;
+            let good = current.status & DESC_EOP != 0 && current.errors == 0 && len <= buf.len();
+            if good {
+                let src = unsafe { slice::from_raw_parts(self.rx_buffer(idx) as *const u8, len) };
+                buf[..len].copy_from_slice(src);
+            }
+            current.status = 0;
+            unsafe { ptr::write_volatile(desc, current) };
+            rings.rx_next = (idx + 1) % RING_LEN;
+            self.reg(REG_RDT).write(idx as u32);
+            if good {
+                return Some(len);
+            }
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use core::time::Duration;
+
+use ::net::{Endpoint, Ipv4Addr, NetError, PingHandle, Stack, TcpHandle, UdpHandle};
+
+use crate::{sync::IrqSafeMutex, time};
+
+use super::STACK;
+
+const ECHO_PORT: u16 = 7;
+const MAX_CONNECTIONS: usize = 4;
+const BUFFER_SIZE: usize = 512;
+// The largest UDP payload that fits an Ethernet frame unfragmented.
+const MAX_DATAGRAM: usize = 1472;
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+const PING_SIZE: usize = 56;
+
+crate::param!(static PING: Option<Ipv4Addr> = "ping", None);
+
+static SERVICES: IrqSafeMutex<Services> = IrqSafeMutex::new(Services::new());
+
+// Data read from a connection that the send buffer had no room for yet.
+#[derive(Clone, Copy)]
+struct Connection {
+    handle: TcpHandle,
+    buf: [u8; BUFFER_SIZE],
+    start: usize,
+    end: usize,
+}
+
+struct Pinger {
+    handle: PingHandle,
+    dst: Ipv4Addr,
+    seq: u16,
+    next: Duration,
+}
+
+struct Services {
+    udp: Option<UdpHandle>,
+    tcp: Option<TcpHandle>,
+    connections: [Option<Connection>; MAX_CONNECTIONS],
+    ping: Option<Pinger>,
+}
+
+impl Services {
+    const fn new() -> Self {
+        Self {
+            udp: None,
+            tcp: None,
+            connections: [None; MAX_CONNECTIONS],
+            ping: None,
+        }
+    }
+}
+
+// Echo on UDP and TCP port 7, RFC 862, plus pinging whatever `ping` names.
+pub fn init() {
+    let mut stack = STACK.lock();
+    let mut services = SERVICES.lock();
+    let any = Endpoint::new(Ipv4Addr::UNSPECIFIED, ECHO_PORT);
+    match stack.udp_bind(any) {
+        Ok(handle) => services.udp = Some(handle),
+        Err(err) => crate::warn!("echo: udp port {}: {}", ECHO_PORT, err),
+    }
+    match stack.tcp_listen(any, MAX_CONNECTIONS) {
+        Ok(handle) => services.tcp = Some(handle),
+        Err(err) => crate::warn!("echo: tcp port {}: {}", ECHO_PORT, err),
+    }
+    if let Some(dst) = PING.get() {
+        match stack.ping_open() {
+            Ok(handle) => {
+                services.ping = Some(Pinger {
+                    handle,
+                    dst,
+                    seq: 0,
+                    next: time::monotonic(),
+                });
+                // Gets the first poll going without waiting for traffic.
+                super::notify();
+            }
+            Err(err) => crate::warn!("ping: {}", err),
+        }
+    }
+}
+
+// Serves whatever has arrived and returns when the next ping is due.
+pub fn poll(stack: &mut Stack) -> Option<Duration> {
+    let mut services = SERVICES.lock();
+    if let Some(handle) = services.udp {
+        serve_udp(stack, handle);
+    }
+    if let Some(listener) = services.tcp {
+        while let Ok(handle) = stack.tcp_accept(listener) {
+            accept(stack, &mut services.connections, handle);
+        }
+    }
+    for slot in &mut services.connections {
+        let Some(connection) = slot else {
+            continue;
+        };
+        if !serve_tcp(stack, connection) {
+            stack.tcp_close(connection.handle);
+            *slot = None;
+        }
+    }
+    services.ping.as_mut().map(|pinger| ping(stack, pinger))
+}
+
+fn serve_udp(stack: &mut Stack, handle: UdpHandle) {
+    let mut buf = [0; MAX_DATAGRAM];
+    while let Ok((len, from)) = stack.udp_recv_from(handle, &mut buf) {
+        if let Err(err) = stack.udp_send_to(handle, from, &buf[..len]) {
+            crate::debug!("echo: udp {}: {}", from, err);
+        }
+    }
+}
+
+fn accept(stack: &mut Stack, connections: &mut [Option<Connection>], handle: TcpHandle) {
+    let Some(slot) = connections.iter_mut().find(|slot| slot.is_none()) else {
+        stack.tcp_abort(handle);
+        return;
+    };
+    if let Ok((_, remote)) = stack.tcp_endpoints(handle) {
+        crate::debug!("echo: connection from {}", remote);
+    }
+    *slot = Some(Connection {
+        handle,
+        buf: [0; BUFFER_SIZE],
+        start: 0,
+        end: 0,
+    });
+}
+
+// Returns false once the connection is done with.
+fn serve_tcp(stack: &mut Stack, connection: &mut Connection) -> bool {
+    loop {
+        if connection.start == connection.end {
+            match stack.tcp_recv(connection.handle, &mut connection.buf) {
+                Ok(0) => return false,
+                Ok(len) => (connection.start, connection.end) = (0, len),
+                Err(NetError::WouldBlock) => return true,
+                Err(_) => return false,
+            }
+        }
+        let data = &connection.buf[connection.start..connection.end];
+        match stack.tcp_send(connection.handle, data) {
+            Ok(len) => connection.start += len,
+            Err(NetError::WouldBlock) => return true,
+            Err(_) => return false,
+        }
+    }
+}
+
+fn ping(stack: &mut Stack, pinger: &mut Pinger) -> Duration {
+    while let Ok(reply) = stack.ping_recv(pinger.handle) {
+        crate::info!(
+            "ping: {} bytes from {}: seq={} ttl={} time={}us",
+            reply.len,
+            reply.from,
+            reply.seq,
+            reply.ttl,
+            reply.rtt.as_micros()
+        );
+    }
+    let now = time::monotonic();
+    if pinger.next <= now {
+        if let Err(err) = stack.ping_send(pinger.handle, pinger.dst, pinger.seq, PING_SIZE) {
+            crate::warn!("ping: {}: {}", pinger.dst, err);
+        }
+        pinger.seq = pinger.seq.wrapping_add(1);
+        pinger.next = now + PING_INTERVAL;
+    }
+    pinger.next
+}
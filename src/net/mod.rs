@@ -0,0 +1,239 @@
+use core::{
+    fmt,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use ::net::{Iface, Ipv4Addr, Ipv4Cidr, NetError, Stack};
+
+use crate::{
+    cmdline::{ParamError, ParamValue},
+    sync::{IrqSafeMutex, OnceCell},
+    time::{self, timer},
+};
+
+pub use ::net::{MacAddress, ETH_MTU, MAX_FRAME_SIZE, MIN_FRAME_SIZE};
+pub use loopback::*;
+
+pub mod e1000;
+pub mod echo;
+pub mod loopback;
+pub mod rtl8139;
+
+const MAX_DEVICES: usize = 8;
+const ETH_NAMES: [&str; 4] = ["eth0", "eth1", "eth2", "eth3"];
+
+// Matches QEMU's user networking, which hands out 10.0.2.15 by DHCP.
+crate::param!(static IP: Ipv4Cidr = "ip", Ipv4Cidr::new(Ipv4Addr::new(10, 0, 2, 15), 24));
+crate::param!(static GATEWAY: Option<Ipv4Addr> = "gateway", Some(Ipv4Addr::new(10, 0, 2, 2)));
+
+pub static STACK: IrqSafeMutex<Stack<'static>> = IrqSafeMutex::new(Stack::new(time::monotonic));
+
+static DEVICES: [OnceCell<Link>; MAX_DEVICES] = [const { OnceCell::new() }; MAX_DEVICES];
+static NEXT_DEVICE: AtomicUsize = AtomicUsize::new(0);
+static NEXT_ETH: AtomicUsize = AtomicUsize::new(0);
+static PENDING: AtomicBool = AtomicBool::new(false);
+// The deadline the stack's wake-up timer is set for.
+static WAKEUP: IrqSafeMutex<Option<(Duration, timer::TimerId)>> = IrqSafeMutex::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxError {
+    TooLarge,
+    Busy,
+    LinkDown,
+}
+
+impl fmt::Display for TxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLarge => f.write_str("frame too large"),
+            Self::Busy => f.write_str("transmit ring full"),
+            Self::LinkDown => f.write_str("link is down"),
+        }
+    }
+}
+
+impl From<TxError> for NetError {
+    fn from(err: TxError) -> Self {
+        match err {
+            TxError::TooLarge => Self::TooLarge,
+            TxError::Busy => Self::WouldBlock,
+            TxError::LinkDown => Self::Unreachable,
+        }
+    }
+}
+
+impl ParamValue for Ipv4Addr {
+    fn parse(value: Option<&'static str>) -> Result<Self, ParamError> {
+        let value = value.ok_or(ParamError::MissingValue)?;
+        value.parse().map_err(|_| ParamError::InvalidValue)
+    }
+}
+
+impl ParamValue for Ipv4Cidr {
+    fn parse(value: Option<&'static str>) -> Result<Self, ParamError> {
+        let value = value.ok_or(ParamError::MissingValue)?;
+        value.parse().map_err(|_| ParamError::InvalidValue)
+    }
+}
+
+// Frames are whole Ethernet frames without the frame check sequence, short
+// ones are padded by the device. `receive` never blocks, devices call
+// `notify` once frames are waiting.
+pub trait NetDevice: Sync {
+    fn name(&self) -> &str;
+    fn mac(&self) -> MacAddress;
+    fn link_up(&self) -> bool;
+    fn transmit(&self, frame: &[u8]) -> Result<(), TxError>;
+    // Copies the next frame into `buf`, frames that don't fit are dropped.
+    fn receive(&self, buf: &mut [u8]) -> Option<usize>;
+
+    fn mtu(&self) -> usize {
+        ETH_MTU
+    }
+}
+
+// A registered device along with the stack's interface on top of it.
+struct Link {
+    device: &'static dyn NetDevice,
+    iface: OnceCell<Iface>,
+}
+
+impl ::net::Device for Link {
+    fn mac(&self) -> MacAddress {
+        self.device.mac()
+    }
+
+    fn transmit(&self, frame: &[u8]) -> Result<(), NetError> {
+        Ok(self.device.transmit(frame)?)
+    }
+
+    fn mtu(&self) -> usize {
+        self.device.mtu()
+    }
+}
+
+pub fn init() {
+    e1000::init();
+    rtl8139::init();
+    register(&LOOPBACK);
+    echo::init();
+}
+
+// Loopback gets 127.0.0.1, the first Ethernet device whatever `ip` and
+// `gateway` say, others stay unconfigured.
+pub fn register(device: &'static dyn NetDevice) {
+    let idx = NEXT_DEVICE.fetch_add(1, Ordering::Relaxed);
+    let Some(slot) = DEVICES.get(idx) else {
+        crate::warn!("too many network devices, dropping {}", device.name());
+        return;
+    };
+    let _ = slot.set(Link {
+        device,
+        iface: OnceCell::new(),
+    });
+    crate::info!(
+        "net {}: mac {}, link {}",
+        device.name(),
+        device.mac(),
+        if device.link_up() { "up" } else { "down" }
+    );
+
+    let link = slot.get().unwrap();
+    let mut stack = STACK.lock();
+    let iface = match stack.add_interface(link) {
+        Ok(iface) => iface,
+        Err(err) => {
+            crate::warn!("net {}: {}", device.name(), err);
+            return;
+        }
+    };
+    let _ = link.iface.set(iface);
+    let (cidr, gateway) = match device.name() {
+        "lo" => (Ipv4Cidr::new(Ipv4Addr::LOCALHOST, 8), None),
+        "eth0" => (IP.get(), GATEWAY.get()),
+        _ => return,
+    };
+    stack.set_address(iface, Some(cidr));
+    stack.set_gateway(iface, gateway);
+    match gateway {
+        Some(gateway) => crate::info!(
+            "net {}: address {}, gateway {}",
+            device.name(),
+            cidr,
+            gateway
+        ),
+        None => crate::info!("net {}: address {}", device.name(), cidr),
+    }
+}
+
+pub fn devices() -> impl Iterator<Item = &'static dyn NetDevice> {
+    DEVICES
+        .iter()
+        .filter_map(OnceCell::get)
+        .map(|link| link.device)
+}
+
+pub fn find(name: &str) -> Option<&'static dyn NetDevice> {
+    devices().find(|device| device.name() == name)
+}
+
+// Ethernet devices share one numbering whatever their driver.
+pub fn eth_name() -> Option<&'static str> {
+    ETH_NAMES
+        .get(NEXT_ETH.fetch_add(1, Ordering::Relaxed))
+        .copied()
+}
+
+// Called from interrupt handlers when frames arrive.
+pub fn notify() {
+    PENDING.store(true, Ordering::Release);
+}
+
+// Feeds received frames to the stack and runs its timers, from the idle
+// loop after `notify` or once the stack's next deadline has passed.
+pub fn poll() {
+    if !PENDING.swap(false, Ordering::Acquire) {
+        return;
+    }
+    let mut frame = [0; MAX_FRAME_SIZE];
+    let mut stack = STACK.lock();
+    for link in DEVICES.iter().filter_map(OnceCell::get) {
+        let Some(&iface) = link.iface.get() else {
+            continue;
+        };
+        while let Some(len) = link.device.receive(&mut frame) {
+            stack.input(iface, &frame[..len]);
+        }
+    }
+    let services = echo::poll(&mut stack);
+    let deadline = match (stack.poll(), services) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    };
+    drop(stack);
+    if let Some(deadline) = deadline {
+        arm(deadline);
+    }
+}
+
+// Only ever moves the wake-up earlier, a timer left armed for an earlier
+// deadline than needed just costs an idle loop pass.
+fn arm(deadline: Duration) {
+    let mut wakeup = WAKEUP.lock();
+    if wakeup.is_some_and(|(armed, _)| armed <= deadline) {
+        return;
+    }
+    if let Some((_, id)) = wakeup.take() {
+        timer::cancel(id);
+    }
+    match timer::add(deadline, wake, 0) {
+        Ok(id) => *wakeup = Some((deadline, id)),
+        Err(err) => crate::warn!("net: {}", err),
+    }
+}
+
+fn wake(_: usize) {
+    WAKEUP.lock().take();
+    notify();
+}
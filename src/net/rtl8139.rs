@@ -0,0 +1,337 @@
+use core::{
+    ptr, slice,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use ::memory::PAGE_SIZE;
+
+use crate::{
+    arch::{self, Pio, PortInOut},
+    pci::{self, Bar, Command, Device, Driver, Match, ProbeError},
+    sync::{IrqSafeMutex, OnceCell},
+    time,
+};
+
+use super::{MacAddress, NetDevice, TxError, MAX_FRAME_SIZE, MIN_FRAME_SIZE};
+
+const VENDOR_REALTEK: u16 = 0x10ec;
+const DEVICE_RTL8139: u16 = 0x8139;
+
+const REG_IDR: u16 = 0x00;
+const REG_MAR: u16 = 0x08;
+const REG_TSD: u16 = 0x10;
+const REG_TSAD: u16 = 0x20;
+const REG_RBSTART: u16 = 0x30;
+const REG_CR: u16 = 0x37;
+const REG_CAPR: u16 = 0x38;
+const REG_IMR: u16 = 0x3c;
+const REG_ISR: u16 = 0x3e;
+const REG_TCR: u16 = 0x40;
+const REG_RCR: u16 = 0x44;
+const REG_CONFIG1: u16 = 0x52;
+const REG_BMSR: u16 = 0x64;
+
+const CR_BUFE: u8 = 1 << 0;
+const CR_TE: u8 = 1 << 2;
+const CR_RE: u8 = 1 << 3;
+const CR_RST: u8 = 1 << 4;
+
+const INT_ROK: u16 = 1 << 0;
+const INT_RER: u16 = 1 << 1;
+const INT_TOK: u16 = 1 << 2;
+const INT_TER: u16 = 1 << 3;
+const INT_RXOVW: u16 = 1 << 4;
+const INT_LINK: u16 = 1 << 5;
+const INT_FOVW: u16 = 1 << 6;
+const INT_RX: u16 = INT_ROK | INT_RER | INT_RXOVW | INT_FOVW;
+
+const RCR_APM: u32 = 1 << 1;
+const RCR_AM: u32 = 1 << 2;
+const RCR_AB: u32 = 1 << 3;
+const RCR_WRAP: u32 = 1 << 7;
+const RCR_MXDMA_UNLIMITED: u32 = 0x7 << 8;
+const TCR_MXDMA_2048: u32 = 0x7 << 8;
+
+const TSD_OWN: u32 = 1 << 13;
+const TSD_SIZE: u32 = 0x1fff;
+
+const RX_STATUS_ROK: u16 = 1 << 0;
+const BMSR_LINK: u16 = 1 << 2;
+
+// An 8 KiB ring with room past its end for a frame the device writes
+// without wrapping.
+const RX_RING_SIZE: usize = 8192;
+const RX_HEADER_SIZE: usize = 4;
+const RX_PAGES: usize = (RX_RING_SIZE + 16 + MAX_FRAME_SIZE + 4).div_ceil(PAGE_SIZE);
+// The device reads CAPR as trailing the read pointer by 16 bytes.
+const CAPR_OFFSET: u16 = 16;
+const CRC_SIZE: usize = 4;
+
+const TX_SLOTS: usize = 4;
+const TX_BUFFER_SIZE: usize = 2048;
+const TX_PAGES: usize = TX_SLOTS * TX_BUFFER_SIZE / PAGE_SIZE;
+
+const MAX_NICS: usize = 4;
+const RESET_TIMEOUT: Duration = Duration::from_millis(100);
+
+pub static RTL8139_DRIVER: Rtl8139Driver = Rtl8139Driver;
+
+static NICS: [OnceCell<Rtl8139>; MAX_NICS] = [const { OnceCell::new() }; MAX_NICS];
+static NEXT_NIC: AtomicUsize = AtomicUsize::new(0);
+
+pub fn init() {
+    pci::register(&RTL8139_DRIVER);
+}
+
+fn interrupt(idx: usize) {
+    if let Some(nic) = NICS[idx].get() {
+        nic.interrupt();
+    }
+}
+
+pub struct Rtl8139Driver;
+
+impl Driver for Rtl8139Driver {
+    fn name(&self) -> &'static str {
+        "rtl8139"
+    }
+
+    fn matches(&self) -> &'static [Match] {
+        &[Match::Device(VENDOR_REALTEK, DEVICE_RTL8139)]
+    }
+
+    fn probe(&self, device: &'static Device) -> Result<(), ProbeError> {
+        let idx = NEXT_NIC.fetch_add(1, Ordering::Relaxed);
+        if idx >= MAX_NICS {
+            return Err(ProbeError::Device("too many rtl8139 devices"));
+        }
+        let name = super::eth_name().ok_or(ProbeError::Device("too many ethernet devices"))?;
+        let port = match device.bar(0) {
+            Some(Bar::Io { port, .. }) => port,
+            _ => return Err(ProbeError::MissingBar(0)),
+        };
+        device.enable(Command::IO | Command::BUS_MASTER);
+
+        // The line is shared and the handler ignores the slot until it is
+        // filled, device interrupts stay masked until then.
+        arch::register_irq(device.interrupt_line(), interrupt, idx)
+            .map_err(|_| ProbeError::Device("no usable interrupt line"))?;
+        let nic = Rtl8139::new(name, port)?;
+        if let Ok(nic) = NICS[idx].set(nic) {
+            nic.reg::<u16>(REG_IMR)
+                .write(INT_RX | INT_TOK | INT_TER | INT_LINK);
+            super::register(nic);
+        }
+        Ok(())
+    }
+}
+
+struct State {
+    // Offset of the next frame in the receive ring.
+    rx_offset: usize,
+    tx_next: usize,
+    tx_used: [bool; TX_SLOTS],
+}
+
+pub struct Rtl8139 {
+    name: &'static str,
+    port: u16,
+    mac: MacAddress,
+    link: AtomicBool,
+    rx_ring: usize,
+    tx_buffers: usize,
+    state: IrqSafeMutex<State>,
+}
+
+impl Rtl8139 {
+    fn new(name: &'static str, port: u16) -> Result<Self, ProbeError> {
+        let mut nic = Self {
+            name,
+            port,
+            mac: MacAddress::ZERO,
+            link: AtomicBool::new(false),
+            rx_ring: 0,
+            tx_buffers: 0,
+            state: IrqSafeMutex::new(State {
+                rx_offset: 0,
+                tx_next: 0,
+                tx_used: [false; TX_SLOTS],
+            }),
+        };
+        nic.reset()?;
+        let mut mac = [0; 6];
+        for (idx, byte) in mac.iter_mut().enumerate() {
+            *byte = nic.reg::<u8>(REG_IDR + idx as u16).read();
+        }
+        nic.mac = MacAddress(mac);
+
+        // Buffer addresses are 32 bits wide.
+        nic.rx_ring = arch::alloc_dma(RX_PAGES + TX_PAGES)?;
+        if nic.rx_ring + (RX_PAGES + TX_PAGES) * PAGE_SIZE > u32::MAX as usize {
+            arch::free_dma(nic.rx_ring, RX_PAGES + TX_PAGES);
+            return Err(ProbeError::Device("buffers above 4 GiB"));
+        }
+        nic.tx_buffers = nic.rx_ring + RX_PAGES * PAGE_SIZE;
+        nic.setup();
+        Ok(nic)
+    }
+
+    #[inline]
+    fn reg<T: PortInOut>(&self, reg: u16) -> Pio<T> {
+        Pio::new(self.port + reg)
+    }
+
+    #[inline]
+    fn tx_buffer(&self, slot: usize) -> usize {
+        self.tx_buffers + slot * TX_BUFFER_SIZE
+    }
+
+    fn reset(&self) -> Result<(), ProbeError> {
+        // Wakes the chip out of its low power state.
+        self.reg::<u8>(REG_CONFIG1).write(0);
+        self.reg::<u8>(REG_CR).write(CR_RST);
+        let deadline = time::monotonic() + RESET_TIMEOUT;
+        while self.reg::<u8>(REG_CR).read() & CR_RST != 0 {
+            if time::monotonic() >= deadline {
+                return Err(ProbeError::Device("reset timed out"));
+            }
+            core::hint::spin_loop();
+        }
+        Ok(())
+    }
+
+    fn setup(&self) {
+        self.reg::<u16>(REG_IMR).write(0);
+        self.reg::<u32>(REG_RBSTART).write(self.rx_ring as u32);
+        for slot in 0..TX_SLOTS {
+            self.reg::<u32>(REG_TSAD + slot as u16 * 4)
+                .write(self.tx_buffer(slot) as u32);
+        }
+        for reg in [REG_MAR, REG_MAR + 4] {
+            self.reg::<u32>(reg).write(0);
+        }
+        self.reg::<u8>(REG_CR).write(CR_RE | CR_TE);
+        // Frames at the end of the ring run on into the slack past it
+        // instead of wrapping, so they are always read in one piece.
+        self.reg::<u32>(REG_RCR)
+            .write(RCR_APM | RCR_AM | RCR_AB | RCR_WRAP | RCR_MXDMA_UNLIMITED);
+        self.reg::<u32>(REG_TCR).write(TCR_MXDMA_2048);
+        self.reg::<u16>(REG_CAPR)
+            .write(0u16.wrapping_sub(CAPR_OFFSET));
+        self.reg::<u16>(REG_ISR).write(u16::MAX);
+        self.link.store(self.read_link(), Ordering::Relaxed);
+    }
+
+    #[inline]
+    fn read_link(&self) -> bool {
+        self.reg::<u16>(REG_BMSR).read() & BMSR_LINK != 0
+    }
+
+    // Status bits are cleared by writing them back.
+    fn interrupt(&self) {
+        let status = self.reg::<u16>(REG_ISR).read();
+        if status == 0 {
+            return;
+        }
+        self.reg::<u16>(REG_ISR).write(status);
+        if status & INT_LINK != 0 {
+            let up = self.read_link();
+            if self.link.swap(up, Ordering::Relaxed) != up {
+                crate::info!("{}: link {}", self.name, if up { "up" } else { "down" });
+            }
+        }
+        if status & INT_RX != 0 {
+            super::notify();
+        }
+    }
+}
+
+impl NetDevice for Rtl8139 {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn mac(&self) -> MacAddress {
+        self.mac
+    }
+
+    fn link_up(&self) -> bool {
+        self.link.load(Ordering::Relaxed)
+    }
+
+    // Four buffers are used in turn, the device sets the own bit of a slot
+    // once it has copied the frame out.
+    fn transmit(&self, frame: &[u8]) -> Result<(), TxError> {
+        if frame.len() > MAX_FRAME_SIZE {
+            return Err(TxError::TooLarge);
+        }
+        if !self.link_up() {
+            return Err(TxError::LinkDown);
+        }
+        let mut state = self.state.lock();
+        let slot = state.tx_next;
+        let tsd = self.reg::<u32>(REG_TSD + slot as u16 * 4);
+        if state.tx_used[slot] && tsd.read() & TSD_OWN == 0 {
+            return Err(TxError::Busy);
+        }
+
+        // The device doesn't pad short frames itself.
+        let len = frame.len().max(MIN_FRAME_SIZE);
+        let buffer = unsafe { slice::from_raw_parts_mut(self.tx_buffer(slot) as *mut u8, len) };
+        buffer[..frame.len()].copy_from_slice(frame);
+        buffer[frame.len()..].fill(0);
+        tsd.write(len as u32 & TSD_SIZE);
+
+        state.tx_used[slot] = true;
+        state.tx_next = (slot + 1) % TX_SLOTS;
+        Ok(())
+    }
+
+    fn receive(&self, buf: &mut [u8]) -> Option<usize> {
+        let mut state = self.state.lock();
+        loop {
+            if self.reg::<u8>(REG_CR).read() & CR_BUFE != 0 {
+                return None;
+            }
+            let offset = state.rx_offset;
+            let header = self.rx_ring + offset;
+            let status = unsafe { ptr::read_volatile(header as *const u16) };
+            let size = unsafe { ptr::read_volatile((header + 2) as *const u16) } as usize;
+
+            // A frame with a bad header means the ring can't be trusted, so
+            // receiving restarts from the beginning.
+            if status & RX_STATUS_ROK == 0
+                || !(CRC_SIZE..=MAX_FRAME_SIZE + CRC_SIZE).contains(&size)
+            {
+                crate::warn!(
+                    "{}: bad receive header {:#06x}, resetting ring",
+                    self.name,
+                    status
+                );
+                self.reg::<u8>(REG_CR).write(CR_TE);
+                self.reg::<u8>(REG_CR).write(CR_RE | CR_TE);
+                self.reg::<u32>(REG_RBSTART).write(self.rx_ring as u32);
+                self.reg::<u16>(REG_CAPR)
+                    .write(0u16.wrapping_sub(CAPR_OFFSET));
+                state.rx_offset = 0;
+                return None;
+            }
+
+            let len = size - CRC_SIZE;
+            let fits = len <= buf.len();
+            if fits {
+                let src =
+                    unsafe { slice::from_raw_parts((header + RX_HEADER_SIZE) as *const u8, len) };
+                buf[..len].copy_from_slice(src);
+            }
+            let next = (offset + RX_HEADER_SIZE + size + 3) & !3;
+            state.rx_offset = next % RX_RING_SIZE;
+            self.reg::<u16>(REG_CAPR)
+                .write((state.rx_offset as u16).wrapping_sub(CAPR_OFFSET));
+            if fits {
+                return Some(len);
+            }
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use super::{Command, Device, REG_BAR0};
+
+const BAR_IO: u32 = 1 << 0;
+const BAR_TYPE_MASK: u32 = 0x6;
+const BAR_TYPE_64: u32 = 0x4;
+const BAR_PREFETCHABLE: u32 = 1 << 3;
+const BAR_MEM_MASK: u32 = !0xf;
+const BAR_IO_MASK: u32 = !0x3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bar {
+    Memory {
+        addr: u64,
+        size: u64,
+        prefetchable: bool,
+        wide: bool,
+    },
+    Io {
+        port: u16,
+        size: u16,
+    },
+}
+
+impl Bar {
+    #[inline]
+    pub fn addr(&self) -> u64 {
+        match *self {
+            Self::Memory { addr, .. } => addr,
+            Self::Io { port, .. } => port as u64,
+        }
+    }
+
+    #[inline]
+    pub fn size(&self) -> u64 {
+        match *self {
+            Self::Memory { size, .. } => size,
+            Self::Io { size, .. } => size as u64,
+        }
+    }
+
+    #[inline]
+    pub fn is_io(&self) -> bool {
+        matches!(self, Self::Io { .. })
+    }
+}
+
+// Returns the BAR and the number of slots it occupies. Decoding is turned off
+// while the size is probed so that the all-ones address is never claimed.
+pub(super) fn read(device: &Device, idx: usize) -> Option<(Bar, usize)> {
+    if idx >= device.bar_count() {
+        return None;
+    }
+    let reg = REG_BAR0 + idx as u16 * 4;
+    let low = device.read_u32(reg);
+    let wide = low & BAR_IO == 0 && low & BAR_TYPE_MASK == BAR_TYPE_64;
+    if wide && idx + 1 >= device.bar_count() {
+        return None;
+    }
+
+    let command = device.command();
+    device.set_command(command.without(Command::IO | Command::MEMORY));
+    let low_mask = probe(device, reg, low);
+    let high = if wide { device.read_u32(reg + 4) } else { 0 };
+    let high_mask = if wide {
+        probe(device, reg + 4, high)
+    } else {
+        u32::MAX
+    };
+    device.set_command(command);
+
+    if low & BAR_IO != 0 {
+        let mask = (low_mask & BAR_IO_MASK) as u16;
+        if mask == 0 {
+            return None;
+        }
+        let bar = Bar::Io {
+            port: (low & BAR_IO_MASK) as u16,
+            size: (!mask).wrapping_add(1),
+        };
+        return Some((bar, 1));
+    }
+
+    if low_mask & BAR_MEM_MASK == 0 && (!wide || high_mask == 0) {
+        return None;
+    }
+    let mask = ((high_mask as u64) << 32) | (low_mask & BAR_MEM_MASK) as u64;
+    let bar = Bar::Memory {
+        addr: ((high as u64) << 32) | (low & BAR_MEM_MASK) as u64,
+        size: (!mask).wrapping_add(1),
+        prefetchable: low & BAR_PREFETCHABLE != 0,
+        wide,
+    };
+    Some((bar, if wide { 2 } else { 1 }))
+}
+
+fn probe(device: &Device, reg: u16, original: u32) -> u32 {
+    device.write_u32(reg, u32::MAX);
+    let mask = device.read_u32(reg);
+    device.write_u32(reg, original);
+    mask
+}
@@ -0,0 +1,98 @@
+use super::Device;
+
+pub const CAP_POWER: u8 = 0x01;
+pub const CAP_MSI: u8 = 0x05;
+pub const CAP_VENDOR: u8 = 0x09;
+pub const CAP_EXPRESS: u8 = 0x10;
+pub const CAP_MSIX: u8 = 0x11;
+
+const EXTENDED_START: u16 = 0x100;
+// A list can't hold more entries than fit in the configuration space, so
+// anything longer is a loop.
+const MAX_CAPABILITIES: usize = 48;
+const MAX_EXTENDED: usize = 960;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capability {
+    pub id: u8,
+    pub offset: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtendedCapability {
+    pub id: u16,
+    pub version: u8,
+    pub offset: u16,
+}
+
+pub struct Capabilities<'a> {
+    device: &'a Device,
+    next: u16,
+    left: usize,
+}
+
+impl<'a> Capabilities<'a> {
+    pub(super) fn new(device: &'a Device, start: u16) -> Self {
+        Self {
+            device,
+            next: start & !0x3,
+            left: MAX_CAPABILITIES,
+        }
+    }
+}
+
+impl Iterator for Capabilities<'_> {
+    type Item = Capability;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next < 0x40 || self.left == 0 {
+            return None;
+        }
+        self.left -= 1;
+        let offset = self.next;
+        let header = self.device.read_u16(offset);
+        self.next = (header >> 8) & !0x3;
+        Some(Capability {
+            id: header as u8,
+            offset,
+        })
+    }
+}
+
+pub struct ExtendedCapabilities<'a> {
+    device: &'a Device,
+    next: u16,
+    left: usize,
+}
+
+impl<'a> ExtendedCapabilities<'a> {
+    pub(super) fn new(device: &'a Device) -> Self {
+        Self {
+            device,
+            next: EXTENDED_START,
+            left: MAX_EXTENDED,
+        }
+    }
+}
+
+impl Iterator for ExtendedCapabilities<'_> {
+    type Item = ExtendedCapability;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next < EXTENDED_START || self.left == 0 {
+            return None;
+        }
+        self.left -= 1;
+        let offset = self.next;
+        let header = self.device.read_u32(offset);
+        if header == 0 || header == u32::MAX {
+            return None;
+        }
+        self.next = (header >> 20) as u16 & !0x3;
+        Some(ExtendedCapability {
+            id: header as u16,
+            version: (header >> 16) as u8 & 0xf,
+            offset,
+        })
+    }
+}
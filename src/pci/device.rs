@@ -0,0 +1,268 @@
+use core::ops::{BitAnd, BitOr};
+
+use crate::sync::OnceCell;
+
+use super::{
+    access, bar, Bar, Capabilities, Capability, Driver, ExtendedCapabilities, PciAddress, NO_DEVICE,
+};
+
+pub const HEADER_MULTIFUNCTION: u8 = 1 << 7;
+pub const HEADER_TYPE_MASK: u8 = 0x7f;
+pub const HEADER_GENERAL: u8 = 0x00;
+pub const HEADER_BRIDGE: u8 = 0x01;
+
+pub const REG_ID: u16 = 0x00;
+pub const REG_COMMAND: u16 = 0x04;
+pub const REG_CLASS: u16 = 0x08;
+pub const REG_HEADER: u16 = 0x0c;
+pub const REG_BAR0: u16 = 0x10;
+pub const REG_BUSES: u16 = 0x18;
+pub const REG_SUBSYSTEM: u16 = 0x2c;
+pub const REG_CAPABILITIES: u16 = 0x34;
+pub const REG_INTERRUPT: u16 = 0x3c;
+
+const STATUS_CAPABILITIES: u16 = 1 << 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct Command(u16);
+
+impl Command {
+    pub const IO: Command = Self::new(1 << 0);
+    pub const MEMORY: Command = Self::new(1 << 1);
+    pub const BUS_MASTER: Command = Self::new(1 << 2);
+    pub const INTX_DISABLE: Command = Self::new(1 << 10);
+
+    #[inline]
+    pub const fn new(val: u16) -> Self {
+        Self(val)
+    }
+
+    #[inline]
+    pub const fn raw(self) -> u16 {
+        self.0
+    }
+
+    #[inline]
+    pub const fn without(self, other: Command) -> Self {
+        Self(self.0 & !other.0)
+    }
+}
+
+impl BitAnd for Command {
+    type Output = bool;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        (self.0 & rhs.0) != 0
+    }
+}
+
+impl BitOr for Command {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+pub struct Device {
+    addr: PciAddress,
+    vendor: u16,
+    device: u16,
+    class: u8,
+    subclass: u8,
+    prog_if: u8,
+    revision: u8,
+    header: u8,
+    driver: OnceCell<&'static dyn Driver>,
+}
+
+impl Device {
+    pub(super) fn read_header(addr: PciAddress) -> Option<u8> {
+        let access = access()?;
+        if access.read(addr, REG_ID) as u16 == NO_DEVICE {
+            return None;
+        }
+        Some((access.read(addr, REG_HEADER) >> 16) as u8)
+    }
+
+    pub(super) fn probe(addr: PciAddress) -> Option<Self> {
+        let access = access()?;
+        let id = access.read(addr, REG_ID);
+        if id as u16 == NO_DEVICE {
+            return None;
+        }
+        let class = access.read(addr, REG_CLASS);
+        Some(Self {
+            addr,
+            vendor: id as u16,
+            device: (id >> 16) as u16,
+            class: (class >> 24) as u8,
+            subclass: (class >> 16) as u8,
+            prog_if: (class >> 8) as u8,
+            revision: class as u8,
+            header: (access.read(addr, REG_HEADER) >> 16) as u8,
+            driver: OnceCell::new(),
+        })
+    }
+
+    #[inline]
+    pub fn address(&self) -> PciAddress {
+        self.addr
+    }
+
+    #[inline]
+    pub fn vendor(&self) -> u16 {
+        self.vendor
+    }
+
+    #[inline]
+    pub fn device(&self) -> u16 {
+        self.device
+    }
+
+    #[inline]
+    pub fn class(&self) -> u8 {
+        self.class
+    }
+
+    #[inline]
+    pub fn subclass(&self) -> u8 {
+        self.subclass
+    }
+
+    #[inline]
+    pub fn prog_if(&self) -> u8 {
+        self.prog_if
+    }
+
+    #[inline]
+    pub fn revision(&self) -> u8 {
+        self.revision
+    }
+
+    #[inline]
+    pub fn header_type(&self) -> u8 {
+        self.header & HEADER_TYPE_MASK
+    }
+
+    #[inline]
+    pub fn driver(&self) -> Option<&'static dyn Driver> {
+        self.driver.get().copied()
+    }
+
+    pub(super) fn set_driver(&self, driver: &'static dyn Driver) -> bool {
+        self.driver.set(driver).is_ok()
+    }
+
+    pub fn secondary_bus(&self) -> Option<u8> {
+        match self.header_type() {
+            HEADER_BRIDGE => Some((self.read_u32(REG_BUSES) >> 8) as u8),
+            _ => None,
+        }
+    }
+
+    pub fn read_u32(&self, offset: u16) -> u32 {
+        match access() {
+            Some(access) if offset < access.size() => access.read(self.addr, offset & !0x3),
+            _ => u32::MAX,
+        }
+    }
+
+    pub fn read_u16(&self, offset: u16) -> u16 {
+        (self.read_u32(offset) >> ((offset & 0x2) * 8)) as u16
+    }
+
+    pub fn read_u8(&self, offset: u16) -> u8 {
+        (self.read_u32(offset) >> ((offset & 0x3) * 8)) as u8
+    }
+
+    pub fn write_u32(&self, offset: u16, val: u32) {
+        if let Some(access) = access().filter(|access| offset < access.size()) {
+            access.write(self.addr, offset & !0x3, val);
+        }
+    }
+
+    // Read-modify-write of the containing dword, so avoid this on registers
+    // with write-one-to-clear neighbours such as the status register.
+    pub fn write_u16(&self, offset: u16, val: u16) {
+        let shift = (offset & 0x2) * 8;
+        let dword = self.read_u32(offset) & !(0xffff << shift);
+        self.write_u32(offset, dword | ((val as u32) << shift));
+    }
+
+    #[inline]
+    pub fn command(&self) -> Command {
+        Command::new(self.read_u16(REG_COMMAND))
+    }
+
+    // The upper half is the status register, whose bits are cleared by
+    // writing ones, so it is written back as zero.
+    pub fn set_command(&self, command: Command) {
+        self.write_u32(REG_COMMAND, command.raw() as u32);
+    }
+
+    pub fn enable(&self, command: Command) {
+        self.set_command(self.command() | command);
+    }
+
+    #[inline]
+    pub fn status(&self) -> u16 {
+        self.read_u16(REG_COMMAND + 2)
+    }
+
+    #[inline]
+    pub fn interrupt_line(&self) -> u8 {
+        self.read_u8(REG_INTERRUPT)
+    }
+
+    #[inline]
+    pub fn interrupt_pin(&self) -> u8 {
+        self.read_u8(REG_INTERRUPT + 1)
+    }
+
+    pub fn bar_count(&self) -> usize {
+        match self.header_type() {
+            HEADER_GENERAL => 6,
+            HEADER_BRIDGE => 2,
+            _ => 0,
+        }
+    }
+
+    pub fn bar(&self, idx: usize) -> Option<Bar> {
+        bar::read(self, idx).map(|(bar, _)| bar)
+    }
+
+    pub fn bars(&self) -> impl Iterator<Item = (usize, Bar)> + '_ {
+        let mut idx = 0;
+        core::iter::from_fn(move || {
+            while idx < self.bar_count() {
+                let current = idx;
+                match bar::read(self, current) {
+                    Some((bar, slots)) => {
+                        idx += slots;
+                        return Some((current, bar));
+                    }
+                    None => idx += 1,
+                }
+            }
+            None
+        })
+    }
+
+    pub fn capabilities(&self) -> Capabilities<'_> {
+        let start = match self.status() & STATUS_CAPABILITIES {
+            0 => 0,
+            _ => self.read_u8(REG_CAPABILITIES) as u16,
+        };
+        Capabilities::new(self, start)
+    }
+
+    pub fn find_capability(&self, id: u8) -> Option<Capability> {
+        self.capabilities().find(|cap| cap.id == id)
+    }
+
+    pub fn extended_capabilities(&self) -> ExtendedCapabilities<'_> {
+        ExtendedCapabilities::new(self)
+    }
+}
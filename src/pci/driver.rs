@@ -0,0 +1,113 @@
+use core::{
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::sync::OnceCell;
+
+use super::{devices, Device};
+
+const MAX_DRIVERS: usize = 16;
+
+static DRIVERS: [OnceCell<&'static dyn Driver>; MAX_DRIVERS] =
+    [const { OnceCell::new() }; MAX_DRIVERS];
+static NEXT_DRIVER: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Match {
+    Device(u16, u16),
+    Vendor(u16),
+    Class(u8, u8),
+    ProgIf(u8, u8, u8),
+}
+
+impl Match {
+    pub fn matches(&self, device: &Device) -> bool {
+        match *self {
+            Self::Device(vendor, id) => device.vendor() == vendor && device.device() == id,
+            Self::Vendor(vendor) => device.vendor() == vendor,
+            Self::Class(class, subclass) => {
+                device.class() == class && device.subclass() == subclass
+            }
+            Self::ProgIf(class, subclass, prog_if) => {
+                device.class() == class
+                    && device.subclass() == subclass
+                    && device.prog_if() == prog_if
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeError {
+    Unsupported,
+    MissingBar(usize),
+    Map(::memory::AllocError),
+    Device(&'static str),
+}
+
+impl fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported => f.write_str("unsupported device"),
+            Self::MissingBar(idx) => write!(f, "missing bar {}", idx),
+            Self::Map(err) => write!(f, "mapping registers: {}", err),
+            Self::Device(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl From<::memory::AllocError> for ProbeError {
+    fn from(err: ::memory::AllocError) -> Self {
+        Self::Map(err)
+    }
+}
+
+pub trait Driver: Sync {
+    fn name(&self) -> &'static str;
+    fn matches(&self) -> &'static [Match];
+    fn probe(&self, device: &'static Device) -> Result<(), ProbeError>;
+}
+
+// Devices found later are bound as they are enumerated, so drivers can be
+// registered before or after the bus scan.
+pub fn register(driver: &'static dyn Driver) {
+    let idx = NEXT_DRIVER.fetch_add(1, Ordering::Relaxed);
+    match DRIVERS.get(idx) {
+        Some(slot) => {
+            let _ = slot.set(driver);
+        }
+        None => {
+            crate::warn!("too many pci drivers, dropping {}", driver.name());
+            return;
+        }
+    }
+
+    for device in devices().filter(|device| device.driver().is_none()) {
+        try_bind(device, driver);
+    }
+}
+
+pub(super) fn bind(device: &'static Device) {
+    for driver in DRIVERS.iter().filter_map(OnceCell::get) {
+        if device.driver().is_some() {
+            return;
+        }
+        try_bind(device, *driver);
+    }
+}
+
+fn try_bind(device: &'static Device, driver: &'static dyn Driver) {
+    if !driver.matches().iter().any(|m| m.matches(device)) {
+        return;
+    }
+    match driver.probe(device) {
+        Ok(()) => {
+            if device.set_driver(driver) {
+                crate::info!("pci {}: bound to {}", device.address(), driver.name());
+            }
+        }
+        Err(ProbeError::Unsupported) => {}
+        Err(err) => crate::warn!("pci {}: {}: {}", device.address(), driver.name(), err),
+    }
+}
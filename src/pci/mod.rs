@@ -0,0 +1,155 @@
+use core::{
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::sync::OnceCell;
+
+pub use bar::*;
+pub use capability::*;
+pub use device::*;
+pub use driver::*;
+pub use msi::*;
+
+pub mod bar;
+pub mod capability;
+pub mod device;
+pub mod driver;
+pub mod msi;
+
+const MAX_DEVICES: usize = 64;
+const MAX_BUSES: usize = 256;
+const DEVICES_PER_BUS: u8 = 32;
+const FUNCTIONS_PER_DEVICE: u8 = 8;
+
+const NO_DEVICE: u16 = 0xffff;
+
+static ACCESS: OnceCell<&'static dyn ConfigAccess> = OnceCell::new();
+static DEVICES: [OnceCell<Device>; MAX_DEVICES] = [const { OnceCell::new() }; MAX_DEVICES];
+static NEXT_DEVICE: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PciAddress {
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8,
+}
+
+impl PciAddress {
+    #[inline]
+    pub const fn new(bus: u8, device: u8, function: u8) -> Self {
+        Self {
+            bus,
+            device,
+            function,
+        }
+    }
+}
+
+impl fmt::Display for PciAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02x}:{:02x}.{}", self.bus, self.device, self.function)
+    }
+}
+
+// Reads and writes are dword sized and aligned. Only ECAM reaches the
+// extended configuration space past 256 bytes.
+pub trait ConfigAccess: Sync {
+    fn name(&self) -> &'static str;
+    fn size(&self) -> u16;
+    fn read(&self, addr: PciAddress, offset: u16) -> u32;
+    fn write(&self, addr: PciAddress, offset: u16, val: u32);
+}
+
+pub fn init(access: &'static dyn ConfigAccess) {
+    if ACCESS.set(access).is_err() {
+        return;
+    }
+
+    let mut visited = [false; MAX_BUSES];
+    let root = PciAddress::new(0, 0, 0);
+    if Device::read_header(root).is_some_and(|header| header & HEADER_MULTIFUNCTION != 0) {
+        for function in 0..FUNCTIONS_PER_DEVICE {
+            if vendor(PciAddress::new(0, 0, function)) != NO_DEVICE {
+                scan_bus(function, &mut visited);
+            }
+        }
+    } else {
+        scan_bus(0, &mut visited);
+    }
+
+    crate::info!(
+        "pci: {} devices through {}",
+        devices().count(),
+        access.name()
+    );
+}
+
+pub(crate) fn access() -> Option<&'static dyn ConfigAccess> {
+    ACCESS.get().copied()
+}
+
+pub fn devices() -> impl Iterator<Item = &'static Device> {
+    DEVICES.iter().filter_map(OnceCell::get)
+}
+
+pub fn find(addr: PciAddress) -> Option<&'static Device> {
+    devices().find(|device| device.address() == addr)
+}
+
+fn vendor(addr: PciAddress) -> u16 {
+    match access() {
+        Some(access) => access.read(addr, 0) as u16,
+        None => NO_DEVICE,
+    }
+}
+
+fn scan_bus(bus: u8, visited: &mut [bool; MAX_BUSES]) {
+    if visited[bus as usize] {
+        return;
+    }
+    visited[bus as usize] = true;
+
+    for device in 0..DEVICES_PER_BUS {
+        let Some(header) = Device::read_header(PciAddress::new(bus, device, 0)) else {
+            continue;
+        };
+        let functions = match header & HEADER_MULTIFUNCTION {
+            0 => 1,
+            _ => FUNCTIONS_PER_DEVICE,
+        };
+        for function in 0..functions {
+            scan_function(PciAddress::new(bus, device, function), visited);
+        }
+    }
+}
+
+fn scan_function(addr: PciAddress, visited: &mut [bool; MAX_BUSES]) {
+    let Some(device) = Device::probe(addr) else {
+        return;
+    };
+    crate::info!(
+        "pci {}: {:04x}:{:04x} class {:02x}:{:02x}:{:02x}",
+        addr,
+        device.vendor(),
+        device.device(),
+        device.class(),
+        device.subclass(),
+        device.prog_if()
+    );
+
+    let secondary = device.secondary_bus();
+    let idx = NEXT_DEVICE.fetch_add(1, Ordering::Relaxed);
+    match DEVICES.get(idx) {
+        Some(slot) => {
+            if let Ok(device) = slot.set(device) {
+                driver::bind(device);
+            }
+        }
+        None => crate::warn!("pci {}: too many devices", addr),
+    }
+
+    if let Some(bus) = secondary {
+        scan_bus(bus, visited);
+    }
+}
@@ -0,0 +1,265 @@
+use core::fmt;
+
+use crate::arch::{self, Mmio, Vector, VectorError, VectorFn};
+
+use super::{Bar, Command, Device, CAP_MSI, CAP_MSIX};
+
+const MSI_CONTROL: u16 = 0x2;
+const MSI_ADDRESS_LOW: u16 = 0x4;
+const MSI_ADDRESS_HIGH: u16 = 0x8;
+const MSI_ENABLE: u16 = 1 << 0;
+const MSI_MULTIPLE_ENABLE: u16 = 0x7 << 4;
+const MSI_64BIT: u16 = 1 << 7;
+const MSI_PER_VECTOR_MASK: u16 = 1 << 8;
+
+const MSIX_CONTROL: u16 = 0x2;
+const MSIX_TABLE: u16 = 0x4;
+const MSIX_ENABLE: u16 = 1 << 15;
+const MSIX_FUNCTION_MASK: u16 = 1 << 14;
+const MSIX_TABLE_SIZE: u16 = 0x7ff;
+const MSIX_BIR: u32 = 0x7;
+const MSIX_ENTRY_SIZE: usize = 16;
+const MSIX_ENTRY_ADDRESS_LOW: usize = 0x0;
+const MSIX_ENTRY_ADDRESS_HIGH: usize = 0x4;
+const MSIX_ENTRY_DATA: usize = 0x8;
+const MSIX_ENTRY_CONTROL: usize = 0xc;
+const MSIX_ENTRY_MASKED: u32 = 1 << 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MsiMessage {
+    pub address: u64,
+    pub data: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MsiError {
+    NoCapability,
+    NoMasking,
+    BadTable(u8),
+    OutOfRange(u16),
+    Vector(VectorError),
+    Map(::memory::AllocError),
+}
+
+impl fmt::Display for MsiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoCapability => f.write_str("capability not present"),
+            Self::NoMasking => f.write_str("per-vector masking not supported"),
+            Self::BadTable(bar) => write!(f, "table in unusable bar {}", bar),
+            Self::OutOfRange(idx) => write!(f, "entry {} out of range", idx),
+            Self::Vector(err) => write!(f, "{}", err),
+            Self::Map(err) => write!(f, "mapping table: {}", err),
+        }
+    }
+}
+
+impl From<VectorError> for MsiError {
+    fn from(err: VectorError) -> Self {
+        Self::Vector(err)
+    }
+}
+
+impl From<::memory::AllocError> for MsiError {
+    fn from(err: ::memory::AllocError) -> Self {
+        Self::Map(err)
+    }
+}
+
+// Only a single message is used, multiple message mode needs a contiguous
+// aligned block of vectors.
+pub struct Msi<'a> {
+    device: &'a Device,
+    offset: u16,
+}
+
+impl<'a> Msi<'a> {
+    pub fn new(device: &'a Device) -> Result<Self, MsiError> {
+        let cap = device
+            .find_capability(CAP_MSI)
+            .ok_or(MsiError::NoCapability)?;
+        Ok(Self {
+            device,
+            offset: cap.offset,
+        })
+    }
+
+    #[inline]
+    fn control(&self) -> u16 {
+        self.device.read_u16(self.offset + MSI_CONTROL)
+    }
+
+    #[inline]
+    fn set_control(&self, control: u16) {
+        self.device.write_u16(self.offset + MSI_CONTROL, control);
+    }
+
+    #[inline]
+    pub fn is_64bit(&self) -> bool {
+        self.control() & MSI_64BIT != 0
+    }
+
+    #[inline]
+    pub fn has_masking(&self) -> bool {
+        self.control() & MSI_PER_VECTOR_MASK != 0
+    }
+
+    fn data_offset(&self) -> u16 {
+        if self.is_64bit() {
+            self.offset + 0xc
+        } else {
+            self.offset + 0x8
+        }
+    }
+
+    fn mask_offset(&self) -> u16 {
+        self.data_offset() + 0x4
+    }
+
+    pub fn set_message(&self, message: MsiMessage) {
+        self.device
+            .write_u32(self.offset + MSI_ADDRESS_LOW, message.address as u32);
+        if self.is_64bit() {
+            self.device.write_u32(
+                self.offset + MSI_ADDRESS_HIGH,
+                (message.address >> 32) as u32,
+            );
+        }
+        self.device
+            .write_u16(self.data_offset(), message.data as u16);
+        self.set_control(self.control() & !MSI_MULTIPLE_ENABLE);
+    }
+
+    pub fn route(&self, handler: VectorFn, data: usize, cpu: usize) -> Result<Vector, MsiError> {
+        let vector = arch::alloc_vector(handler, data)?;
+        self.set_message(vector.msi_message(cpu));
+        Ok(vector)
+    }
+
+    pub fn set_masked(&self, masked: bool) -> Result<(), MsiError> {
+        if !self.has_masking() {
+            return Err(MsiError::NoMasking);
+        }
+        let bits = self.device.read_u32(self.mask_offset());
+        let bits = if masked { bits | 1 } else { bits & !1 };
+        self.device.write_u32(self.mask_offset(), bits);
+        Ok(())
+    }
+
+    // Legacy INTx is turned off once messages are in use.
+    pub fn enable(&self) {
+        self.set_control(self.control() | MSI_ENABLE);
+        self.device.enable(Command::INTX_DISABLE);
+    }
+
+    pub fn disable(&self) {
+        self.set_control(self.control() & !MSI_ENABLE);
+    }
+}
+
+pub struct MsiX<'a> {
+    device: &'a Device,
+    offset: u16,
+    table: usize,
+    size: u16,
+}
+
+impl<'a> MsiX<'a> {
+    pub fn new(device: &'a Device) -> Result<Self, MsiError> {
+        let cap = device
+            .find_capability(CAP_MSIX)
+            .ok_or(MsiError::NoCapability)?;
+        let size = (device.read_u16(cap.offset + MSIX_CONTROL) & MSIX_TABLE_SIZE) + 1;
+        let table = device.read_u32(cap.offset + MSIX_TABLE);
+        let bir = (table & MSIX_BIR) as u8;
+        let addr = match device.bar(bir as usize) {
+            Some(Bar::Memory { addr, .. }) => addr as usize + (table & !MSIX_BIR) as usize,
+            _ => return Err(MsiError::BadTable(bir)),
+        };
+        arch::map_mmio(addr, size as usize * MSIX_ENTRY_SIZE)?;
+        device.enable(Command::MEMORY);
+
+        let msix = Self {
+            device,
+            offset: cap.offset,
+            table: addr,
+            size,
+        };
+        for idx in 0..size {
+            msix.entry(idx, MSIX_ENTRY_CONTROL).write(MSIX_ENTRY_MASKED);
+        }
+        Ok(msix)
+    }
+
+    #[inline]
+    pub fn size(&self) -> u16 {
+        self.size
+    }
+
+    #[inline]
+    fn control(&self) -> u16 {
+        self.device.read_u16(self.offset + MSIX_CONTROL)
+    }
+
+    #[inline]
+    fn set_control(&self, control: u16) {
+        self.device.write_u16(self.offset + MSIX_CONTROL, control);
+    }
+
+    fn entry(&self, idx: u16, reg: usize) -> Mmio<u32> {
+        Mmio::new(self.table + idx as usize * MSIX_ENTRY_SIZE + reg)
+    }
+
+    fn check(&self, idx: u16) -> Result<(), MsiError> {
+        if idx < self.size {
+            Ok(())
+        } else {
+            Err(MsiError::OutOfRange(idx))
+        }
+    }
+
+    pub fn set_message(&self, idx: u16, message: MsiMessage) -> Result<(), MsiError> {
+        self.check(idx)?;
+        self.entry(idx, MSIX_ENTRY_ADDRESS_LOW)
+            .write(message.address as u32);
+        self.entry(idx, MSIX_ENTRY_ADDRESS_HIGH)
+            .write((message.address >> 32) as u32);
+        self.entry(idx, MSIX_ENTRY_DATA).write(message.data);
+        Ok(())
+    }
+
+    // Entries start out masked and are unmasked once routed.
+    pub fn route(
+        &self,
+        idx: u16,
+        handler: VectorFn,
+        data: usize,
+        cpu: usize,
+    ) -> Result<Vector, MsiError> {
+        self.check(idx)?;
+        let vector = arch::alloc_vector(handler, data)?;
+        self.set_message(idx, vector.msi_message(cpu))?;
+        self.set_masked(idx, false)?;
+        Ok(vector)
+    }
+
+    pub fn set_masked(&self, idx: u16, masked: bool) -> Result<(), MsiError> {
+        self.check(idx)?;
+        let ctrl = self.entry(idx, MSIX_ENTRY_CONTROL);
+        if masked {
+            ctrl.write(ctrl.read() | MSIX_ENTRY_MASKED);
+        } else {
+            ctrl.write(ctrl.read() & !MSIX_ENTRY_MASKED);
+        }
+        Ok(())
+    }
+
+    pub fn enable(&self) {
+        self.set_control((self.control() | MSIX_ENABLE) & !MSIX_FUNCTION_MASK);
+        self.device.enable(Command::INTX_DISABLE);
+    }
+
+    pub fn disable(&self) {
+        self.set_control(self.control() & !MSIX_ENABLE);
+    }
+}
@@ -0,0 +1,56 @@
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use crate::{arch, sync::IrqGuard};
+
+const MAX_TASKS: usize = 16;
+
+const RUNNING: u8 = 0;
+const BLOCKED: u8 = 1;
+const RUNNABLE: u8 = 2;
+
+static PRIORITIES: [AtomicU8; MAX_TASKS] = [const { AtomicU8::new(0) }; MAX_TASKS];
+static STATES: [AtomicU8; MAX_TASKS] = [const { AtomicU8::new(RUNNING) }; MAX_TASKS];
+
+// There are no threads yet: every CPU runs exactly one task, identified by the
+// CPU index, and blocking idles the CPU until the wake flag is set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TaskId(usize);
+
+impl TaskId {
+    pub const fn idx(self) -> usize {
+        self.0
+    }
+}
+
+pub fn current() -> TaskId {
+    let idx = arch::cpu_index();
+    assert!(idx < MAX_TASKS, "more than {} tasks", MAX_TASKS);
+    TaskId(idx)
+}
+
+pub fn priority(task: TaskId) -> u8 {
+    PRIORITIES[task.0].load(Ordering::Relaxed)
+}
+
+pub fn set_priority(task: TaskId, priority: u8) {
+    PRIORITIES[task.0].store(priority, Ordering::Relaxed);
+}
+
+// The state is swapped on both sides, so either `unblock` sees the task
+// blocked and sends the IPI, or the task sees its wake flag before halting.
+pub fn block(woken: &AtomicBool) {
+    let _irq = IrqGuard::new();
+    let state = &STATES[current().0];
+    state.swap(BLOCKED, Ordering::SeqCst);
+    while !woken.load(Ordering::Acquire) {
+        arch::wait_for_interrupt();
+    }
+    state.store(RUNNING, Ordering::Relaxed);
+}
+
+pub fn unblock(task: TaskId) {
+    let state = STATES[task.0].swap(RUNNABLE, Ordering::SeqCst);
+    if state == BLOCKED && task != current() {
+        arch::wake_cpu(arch::cpu_id_of(task.0));
+    }
+}
@@ -0,0 +1,47 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::{SleepMutexGuard, WaitQueue};
+
+pub struct Condvar {
+    seq: AtomicUsize,
+    queue: WaitQueue,
+}
+
+impl Condvar {
+    pub const fn new() -> Self {
+        Self {
+            seq: AtomicUsize::new(0),
+            queue: WaitQueue::new(),
+        }
+    }
+
+    pub fn wait<'a, T>(&self, guard: SleepMutexGuard<'a, T>) -> SleepMutexGuard<'a, T> {
+        let mutex = guard.mutex();
+        let seq = self.seq.load(Ordering::Acquire);
+        drop(guard);
+        self.queue
+            .wait_until(|| self.seq.load(Ordering::Acquire) != seq);
+        mutex.lock()
+    }
+
+    pub fn wait_while<'a, T>(
+        &self,
+        mut guard: SleepMutexGuard<'a, T>,
+        mut cond: impl FnMut(&mut T) -> bool,
+    ) -> SleepMutexGuard<'a, T> {
+        while cond(&mut guard) {
+            guard = self.wait(guard);
+        }
+        guard
+    }
+
+    pub fn notify_one(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        self.queue.wake_one();
+    }
+
+    pub fn notify_all(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        self.queue.wake_all();
+    }
+}
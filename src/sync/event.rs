@@ -0,0 +1,111 @@
+use core::{
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use crate::time::{self, timer};
+
+use super::WaitQueue;
+
+const COMPLETE_ALL: usize = usize::MAX / 2;
+
+pub struct Event {
+    set: AtomicBool,
+    queue: WaitQueue,
+}
+
+impl Event {
+    pub const fn new() -> Self {
+        Self {
+            set: AtomicBool::new(false),
+            queue: WaitQueue::new(),
+        }
+    }
+
+    pub fn set(&self) {
+        self.set.store(true, Ordering::Release);
+        self.queue.wake_all();
+    }
+
+    pub fn reset(&self) {
+        self.set.store(false, Ordering::Release);
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.set.load(Ordering::Acquire)
+    }
+
+    pub fn wait(&self) {
+        self.queue.wait_until(|| self.is_set());
+    }
+
+    // The timer wakes the queue so the deadline is rechecked. Returns whether
+    // the event was set.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let deadline = time::monotonic() + timeout;
+        let expired = || time::monotonic() >= deadline;
+        match timer::add(deadline, wake_event, self as *const Event as usize) {
+            Ok(id) => {
+                self.queue.wait_until(|| self.is_set() || expired());
+                timer::cancel(id);
+            }
+            Err(_) => {
+                while !self.is_set() && !expired() {
+                    core::hint::spin_loop();
+                }
+            }
+        }
+        self.is_set()
+    }
+}
+
+fn wake_event(data: usize) {
+    let event = unsafe { &*(data as *const Event) };
+    event.queue.wake_all();
+}
+
+pub struct Completion {
+    done: AtomicUsize,
+    queue: WaitQueue,
+}
+
+impl Completion {
+    pub const fn new() -> Self {
+        Self {
+            done: AtomicUsize::new(0),
+            queue: WaitQueue::new(),
+        }
+    }
+
+    pub fn complete(&self) {
+        let _ = self
+            .done
+            .fetch_update(Ordering::Release, Ordering::Relaxed, |done| {
+                Some(if done >= COMPLETE_ALL { done } else { done + 1 })
+            });
+        self.queue.wake_one();
+    }
+
+    pub fn complete_all(&self) {
+        self.done.store(COMPLETE_ALL, Ordering::Release);
+        self.queue.wake_all();
+    }
+
+    pub fn reinit(&self) {
+        self.done.store(0, Ordering::Release);
+    }
+
+    pub fn wait(&self) {
+        self.queue.wait_until(|| self.try_wait());
+    }
+
+    pub fn try_wait(&self) -> bool {
+        self.done
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |done| match done {
+                0 => None,
+                COMPLETE_ALL.. => Some(done),
+                _ => Some(done - 1),
+            })
+            .is_ok()
+    }
+}
@@ -0,0 +1,100 @@
+use core::{
+    fmt::{Debug, Display},
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+};
+
+use crate::arch;
+
+use super::{Mutex, MutexGuard};
+
+pub struct IrqGuard {
+    enabled: bool,
+}
+
+impl IrqGuard {
+    pub fn new() -> Self {
+        let enabled = arch::interrupts_enabled();
+        arch::disable_interrupts();
+        Self { enabled }
+    }
+}
+
+impl Drop for IrqGuard {
+    fn drop(&mut self) {
+        if self.enabled {
+            arch::enable_interrupts();
+        }
+    }
+}
+
+pub struct IrqSafeMutex<T> {
+    inner: Mutex<T>,
+}
+
+impl<T> IrqSafeMutex<T> {
+    pub const fn new(inner: T) -> Self {
+        Self {
+            inner: Mutex::new(inner),
+        }
+    }
+
+    pub fn lock(&self) -> IrqSafeMutexGuard<'_, T> {
+        let irq = IrqGuard::new();
+        IrqSafeMutexGuard {
+            guard: ManuallyDrop::new(self.inner.lock()),
+            _irq: irq,
+        }
+    }
+
+    pub fn try_lock(&self) -> Option<IrqSafeMutexGuard<'_, T>> {
+        let irq = IrqGuard::new();
+        let guard = self.inner.try_lock()?;
+        Some(IrqSafeMutexGuard {
+            guard: ManuallyDrop::new(guard),
+            _irq: irq,
+        })
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.inner.is_locked()
+    }
+}
+
+pub struct IrqSafeMutexGuard<'a, T> {
+    guard: ManuallyDrop<MutexGuard<'a, T>>,
+    _irq: IrqGuard,
+}
+
+impl<T> Deref for IrqSafeMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for IrqSafeMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T: Debug> Debug for IrqSafeMutexGuard<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: Display> Display for IrqSafeMutexGuard<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        Display::fmt(&**self, f)
+    }
+}
+
+impl<T> Drop for IrqSafeMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        // Release the lock before `_irq` restores the interrupt flag.
+        unsafe { ManuallyDrop::drop(&mut self.guard) }
+    }
+}
@@ -0,0 +1,19 @@
+pub use event::*;
+pub use irq::*;
+pub use mutex::*;
+pub use once::*;
+pub use semaphore::*;
+pub use sleep_mutex::*;
+pub use wait::*;
+
+pub mod condvar;
+pub mod event;
+pub mod irq;
+pub mod mutex;
+pub mod once;
+pub mod owner;
+pub mod rwlock;
+pub mod semaphore;
+pub mod sleep_mutex;
+pub mod ticket;
+pub mod wait;
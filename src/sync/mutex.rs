@@ -6,8 +6,11 @@ use core::{
     sync::atomic::{AtomicBool, Ordering},
 };
 
+use super::owner::LockOwner;
+
 pub struct Mutex<T> {
     locked: AtomicBool,
+    owner: LockOwner,
     inner: UnsafeCell<T>,
 }
 
@@ -15,22 +18,41 @@ impl<T> Mutex<T> {
     pub const fn new(inner: T) -> Self {
         Self {
             locked: AtomicBool::new(false),
+            owner: LockOwner::new(),
             inner: UnsafeCell::new(inner),
         }
     }
 
     pub fn lock(&self) -> MutexGuard<'_, T> {
+        self.owner.check_recursion();
+        let mut spins = 0;
         loop {
-            if self
-                .locked
-                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
-                .is_ok()
-            {
-                break MutexGuard { mutex: self };
+            if let Some(guard) = self.try_lock() {
+                break guard;
+            }
+            while self.locked.load(Ordering::Relaxed) {
+                self.owner.check_held(&mut spins);
+                hint::spin_loop();
             }
-            hint::spin_loop();
         }
     }
+
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        if self
+            .locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+        {
+            self.owner.acquire();
+            Some(MutexGuard { mutex: self })
+        } else {
+            None
+        }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
 }
 
 unsafe impl<T: Send> Send for Mutex<T> {}
@@ -68,6 +90,7 @@ impl<T: Display> Display for MutexGuard<'_, T> {
 
 impl<T> Drop for MutexGuard<'_, T> {
     fn drop(&mut self) {
+        self.mutex.owner.release();
         self.mutex.locked.store(false, Ordering::Release);
     }
 }
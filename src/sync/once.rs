@@ -0,0 +1,166 @@
+use core::{
+    cell::{Cell, UnsafeCell},
+    fmt::{self, Display},
+    hint,
+    mem::MaybeUninit,
+    ops::Deref,
+    sync::atomic::{AtomicU8, AtomicUsize, Ordering},
+};
+
+use crate::arch;
+
+const INCOMPLETE: u8 = 0;
+const RUNNING: u8 = 1;
+const COMPLETE: u8 = 2;
+
+const NO_CPU: usize = usize::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitError {
+    Uninitialized,
+    AlreadyInitialized,
+}
+
+impl Display for InitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Uninitialized => f.write_str("not initialized"),
+            Self::AlreadyInitialized => f.write_str("already initialized"),
+        }
+    }
+}
+
+pub struct Once<T> {
+    state: AtomicU8,
+    cpu: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+impl<T> Once<T> {
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(INCOMPLETE),
+            cpu: AtomicUsize::new(NO_CPU),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    pub fn call_once(&self, f: impl FnOnce() -> T) -> &T {
+        match self
+            .state
+            .compare_exchange(INCOMPLETE, RUNNING, Ordering::Acquire, Ordering::Acquire)
+        {
+            Ok(_) => {
+                self.cpu.store(arch::cpu_id(), Ordering::Relaxed);
+                unsafe { (*self.value.get()).write(f()) };
+                self.cpu.store(NO_CPU, Ordering::Relaxed);
+                self.state.store(COMPLETE, Ordering::Release);
+            }
+            Err(RUNNING) => {
+                // An interrupt handler on the initializing CPU would spin forever.
+                if self.cpu.load(Ordering::Relaxed) == arch::cpu_id() {
+                    panic!("recursive initialization");
+                }
+                while self.state.load(Ordering::Acquire) == RUNNING {
+                    hint::spin_loop();
+                }
+            }
+            Err(_) => {}
+        }
+        unsafe { self.get_unchecked() }
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        self.try_get().ok()
+    }
+
+    pub fn try_get(&self) -> Result<&T, InitError> {
+        if self.is_completed() {
+            Ok(unsafe { self.get_unchecked() })
+        } else {
+            Err(InitError::Uninitialized)
+        }
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.state.load(Ordering::Acquire) == COMPLETE
+    }
+
+    unsafe fn get_unchecked(&self) -> &T {
+        (*self.value.get()).assume_init_ref()
+    }
+}
+
+unsafe impl<T: Send> Send for Once<T> {}
+unsafe impl<T: Send + Sync> Sync for Once<T> {}
+
+impl<T> Drop for Once<T> {
+    fn drop(&mut self) {
+        if *self.state.get_mut() == COMPLETE {
+            unsafe { self.value.get_mut().assume_init_drop() }
+        }
+    }
+}
+
+pub struct OnceCell<T> {
+    once: Once<T>,
+}
+
+impl<T> OnceCell<T> {
+    pub const fn new() -> Self {
+        Self { once: Once::new() }
+    }
+
+    pub fn set(&self, value: T) -> Result<&T, InitError> {
+        let once = &self.once;
+        once.state
+            .compare_exchange(INCOMPLETE, RUNNING, Ordering::Acquire, Ordering::Relaxed)
+            .map_err(|_| InitError::AlreadyInitialized)?;
+        unsafe { (*once.value.get()).write(value) };
+        once.state.store(COMPLETE, Ordering::Release);
+        Ok(unsafe { once.get_unchecked() })
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        self.once.get()
+    }
+
+    pub fn try_get(&self) -> Result<&T, InitError> {
+        self.once.try_get()
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.once.is_completed()
+    }
+}
+
+pub struct Lazy<T, F = fn() -> T> {
+    once: Once<T>,
+    init: Cell<Option<F>>,
+}
+
+impl<T, F: FnOnce() -> T> Lazy<T, F> {
+    pub const fn new(init: F) -> Self {
+        Self {
+            once: Once::new(),
+            init: Cell::new(Some(init)),
+        }
+    }
+
+    pub fn force(this: &Self) -> &T {
+        this.once.call_once(|| match this.init.take() {
+            Some(init) => init(),
+            None => panic!("lazy initializer panicked"),
+        })
+    }
+}
+
+impl<T, F: FnOnce() -> T> Deref for Lazy<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        Lazy::force(self)
+    }
+}
+
+unsafe impl<T: Send + Sync, F: Send> Sync for Lazy<T, F> {}
@@ -0,0 +1,91 @@
+#[cfg(debug_assertions)]
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+#[cfg(debug_assertions)]
+use crate::arch;
+
+#[cfg(debug_assertions)]
+const NO_OWNER: usize = usize::MAX;
+#[cfg(debug_assertions)]
+const CHECK_INTERVAL: usize = 1 << 16;
+#[cfg(debug_assertions)]
+const HELD_TOO_LONG_MS: u64 = 100;
+
+#[cfg(debug_assertions)]
+pub struct LockOwner {
+    cpu: AtomicUsize,
+    since: AtomicU64,
+    reported: AtomicBool,
+}
+
+#[cfg(not(debug_assertions))]
+pub struct LockOwner;
+
+#[cfg(debug_assertions)]
+impl LockOwner {
+    pub const fn new() -> Self {
+        Self {
+            cpu: AtomicUsize::new(NO_OWNER),
+            since: AtomicU64::new(0),
+            reported: AtomicBool::new(false),
+        }
+    }
+
+    pub fn check_recursion(&self) {
+        let cpu = arch::cpu_id();
+        if self.cpu.load(Ordering::Relaxed) == cpu {
+            panic!("recursive lock acquisition on cpu {}", cpu);
+        }
+    }
+
+    pub fn check_held(&self, spins: &mut usize) {
+        *spins += 1;
+        if *spins & (CHECK_INTERVAL - 1) != 0 {
+            return;
+        }
+
+        let since = self.since.load(Ordering::Relaxed);
+        let limit = match arch::tsc_mhz() {
+            0 => 1 << 32,
+            mhz => mhz * 1000 * HELD_TOO_LONG_MS,
+        };
+        if arch::read_tsc().saturating_sub(since) > limit
+            && !self.reported.swap(true, Ordering::Relaxed)
+        {
+            crate::warn!(
+                "lock held by cpu {} for more than {} ms",
+                self.cpu.load(Ordering::Relaxed),
+                HELD_TOO_LONG_MS
+            );
+        }
+    }
+
+    pub fn acquire(&self) {
+        self.since.store(arch::read_tsc(), Ordering::Relaxed);
+        self.reported.store(false, Ordering::Relaxed);
+        self.cpu.store(arch::cpu_id(), Ordering::Relaxed);
+    }
+
+    pub fn release(&self) {
+        self.cpu.store(NO_OWNER, Ordering::Relaxed);
+    }
+}
+
+#[cfg(not(debug_assertions))]
+impl LockOwner {
+    pub const fn new() -> Self {
+        Self
+    }
+
+    #[inline]
+    pub fn check_recursion(&self) {}
+
+    #[inline]
+    pub fn check_held(&self, _spins: &mut usize) {}
+
+    #[inline]
+    pub fn acquire(&self) {}
+
+    #[inline]
+    pub fn release(&self) {}
+}
@@ -0,0 +1,157 @@
+use core::{
+    cell::UnsafeCell,
+    fmt::{Debug, Display},
+    hint,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use super::owner::LockOwner;
+
+const WRITER: usize = 1 << 0;
+const WRITER_PENDING: usize = 1 << 1;
+const READER: usize = 1 << 2;
+
+pub struct RwLock<T> {
+    state: AtomicUsize,
+    owner: LockOwner,
+    inner: UnsafeCell<T>,
+}
+
+impl<T> RwLock<T> {
+    pub const fn new(inner: T) -> Self {
+        Self {
+            state: AtomicUsize::new(0),
+            owner: LockOwner::new(),
+            inner: UnsafeCell::new(inner),
+        }
+    }
+
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.owner.check_recursion();
+        let mut spins = 0;
+        loop {
+            if let Some(guard) = self.try_read() {
+                break guard;
+            }
+            self.owner.check_held(&mut spins);
+            hint::spin_loop();
+        }
+    }
+
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        let state = self.state.load(Ordering::Relaxed);
+        if state & (WRITER | WRITER_PENDING) != 0 {
+            return None;
+        }
+        self.state
+            .compare_exchange(state, state + READER, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| RwLockReadGuard { lock: self })
+    }
+
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.owner.check_recursion();
+        let mut spins = 0;
+        loop {
+            if let Some(guard) = self.try_write() {
+                break guard;
+            }
+            self.state.fetch_or(WRITER_PENDING, Ordering::Relaxed);
+            self.owner.check_held(&mut spins);
+            hint::spin_loop();
+        }
+    }
+
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        let state = self.state.load(Ordering::Relaxed);
+        if state & !WRITER_PENDING != 0 {
+            return None;
+        }
+        self.state
+            .compare_exchange(state, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| {
+                self.owner.acquire();
+                RwLockWriteGuard { lock: self }
+            })
+    }
+
+    pub fn readers(&self) -> usize {
+        self.state.load(Ordering::Relaxed) / READER
+    }
+
+    pub fn is_write_locked(&self) -> bool {
+        self.state.load(Ordering::Relaxed) & WRITER != 0
+    }
+}
+
+unsafe impl<T: Send> Send for RwLock<T> {}
+unsafe impl<T: Send + Sync> Sync for RwLock<T> {}
+
+pub struct RwLockReadGuard<'a, T> {
+    lock: &'a RwLock<T>,
+}
+
+impl<T> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.inner.get() }
+    }
+}
+
+impl<T: Debug> Debug for RwLockReadGuard<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: Display> Display for RwLockReadGuard<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        Display::fmt(&**self, f)
+    }
+}
+
+impl<T> Drop for RwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.state.fetch_sub(READER, Ordering::Release);
+    }
+}
+
+pub struct RwLockWriteGuard<'a, T> {
+    lock: &'a RwLock<T>,
+}
+
+impl<T> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.lock.inner.get() }
+    }
+}
+
+impl<T> DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.inner.get() }
+    }
+}
+
+impl<T: Debug> Debug for RwLockWriteGuard<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: Display> Display for RwLockWriteGuard<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        Display::fmt(&**self, f)
+    }
+}
+
+impl<T> Drop for RwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.owner.release();
+        self.lock.state.fetch_and(!WRITER, Ordering::Release);
+    }
+}
@@ -0,0 +1,38 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::WaitQueue;
+
+pub struct Semaphore {
+    count: AtomicUsize,
+    queue: WaitQueue,
+}
+
+impl Semaphore {
+    pub const fn new(count: usize) -> Self {
+        Self {
+            count: AtomicUsize::new(count),
+            queue: WaitQueue::new(),
+        }
+    }
+
+    pub fn down(&self) {
+        self.queue.wait_until(|| self.try_down());
+    }
+
+    pub fn try_down(&self) -> bool {
+        self.count
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |count| {
+                count.checked_sub(1)
+            })
+            .is_ok()
+    }
+
+    pub fn up(&self) {
+        self.count.fetch_add(1, Ordering::Release);
+        self.queue.wake_one();
+    }
+
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+}
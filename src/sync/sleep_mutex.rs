@@ -0,0 +1,115 @@
+use core::{
+    cell::UnsafeCell,
+    fmt::{Debug, Display},
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::sched::{self, TaskId};
+
+use super::WaitQueue;
+
+const NO_OWNER: usize = usize::MAX;
+
+pub struct SleepMutex<T: ?Sized> {
+    owner: AtomicUsize,
+    queue: WaitQueue,
+    inner: UnsafeCell<T>,
+}
+
+impl<T> SleepMutex<T> {
+    pub const fn new(inner: T) -> Self {
+        Self {
+            owner: AtomicUsize::new(NO_OWNER),
+            queue: WaitQueue::new(),
+            inner: UnsafeCell::new(inner),
+        }
+    }
+}
+
+impl<T: ?Sized> SleepMutex<T> {
+    pub fn lock(&self) -> SleepMutexGuard<'_, T> {
+        let task = sched::current();
+        assert!(
+            self.owner.load(Ordering::Relaxed) != task.idx(),
+            "recursive sleep mutex acquisition"
+        );
+        self.queue.wait_until(|| {
+            // The unlocking task may have handed the mutex straight to us.
+            self.owner.load(Ordering::Acquire) == task.idx() || self.acquire(task)
+        });
+        SleepMutexGuard { mutex: self }
+    }
+
+    pub fn try_lock(&self) -> Option<SleepMutexGuard<'_, T>> {
+        if self.acquire(sched::current()) {
+            Some(SleepMutexGuard { mutex: self })
+        } else {
+            None
+        }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.owner.load(Ordering::Relaxed) != NO_OWNER
+    }
+
+    fn acquire(&self, task: TaskId) -> bool {
+        self.owner
+            .compare_exchange(NO_OWNER, task.idx(), Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    fn unlock(&self) {
+        // Hand the mutex to the highest priority waiter so that a lower
+        // priority task can't barge in between the wakeup and the retry.
+        self.queue.wake_one_with(|next| {
+            let next = next.map_or(NO_OWNER, TaskId::idx);
+            self.owner.store(next, Ordering::Release);
+        });
+    }
+}
+
+unsafe impl<T: ?Sized + Send> Send for SleepMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for SleepMutex<T> {}
+
+pub struct SleepMutexGuard<'a, T: ?Sized> {
+    mutex: &'a SleepMutex<T>,
+}
+
+impl<'a, T: ?Sized> SleepMutexGuard<'a, T> {
+    pub fn mutex(&self) -> &'a SleepMutex<T> {
+        self.mutex
+    }
+}
+
+impl<T: ?Sized> Deref for SleepMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.mutex.inner.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for SleepMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.inner.get() }
+    }
+}
+
+impl<T: ?Sized + Debug> Debug for SleepMutexGuard<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + Display> Display for SleepMutexGuard<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        Display::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized> Drop for SleepMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.unlock();
+    }
+}
@@ -0,0 +1,97 @@
+use core::{
+    cell::UnsafeCell,
+    fmt::{Debug, Display},
+    hint,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use super::owner::LockOwner;
+
+pub struct TicketMutex<T> {
+    next: AtomicUsize,
+    serving: AtomicUsize,
+    owner: LockOwner,
+    inner: UnsafeCell<T>,
+}
+
+impl<T> TicketMutex<T> {
+    pub const fn new(inner: T) -> Self {
+        Self {
+            next: AtomicUsize::new(0),
+            serving: AtomicUsize::new(0),
+            owner: LockOwner::new(),
+            inner: UnsafeCell::new(inner),
+        }
+    }
+
+    pub fn lock(&self) -> TicketMutexGuard<'_, T> {
+        self.owner.check_recursion();
+        let ticket = self.next.fetch_add(1, Ordering::Relaxed);
+        let mut spins = 0;
+        while self.serving.load(Ordering::Acquire) != ticket {
+            self.owner.check_held(&mut spins);
+            hint::spin_loop();
+        }
+        self.owner.acquire();
+        TicketMutexGuard { mutex: self }
+    }
+
+    pub fn try_lock(&self) -> Option<TicketMutexGuard<'_, T>> {
+        let ticket = self.serving.load(Ordering::Acquire);
+        if self
+            .next
+            .compare_exchange(ticket, ticket + 1, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+        {
+            self.owner.acquire();
+            Some(TicketMutexGuard { mutex: self })
+        } else {
+            None
+        }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.next.load(Ordering::Relaxed) != self.serving.load(Ordering::Relaxed)
+    }
+}
+
+unsafe impl<T: Send> Send for TicketMutex<T> {}
+unsafe impl<T: Send> Sync for TicketMutex<T> {}
+
+pub struct TicketMutexGuard<'a, T> {
+    mutex: &'a TicketMutex<T>,
+}
+
+impl<T> Deref for TicketMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.mutex.inner.get() }
+    }
+}
+
+impl<T> DerefMut for TicketMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.inner.get() }
+    }
+}
+
+impl<T: Debug> Debug for TicketMutexGuard<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: Display> Display for TicketMutexGuard<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        Display::fmt(&**self, f)
+    }
+}
+
+impl<T> Drop for TicketMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.owner.release();
+        self.mutex.serving.fetch_add(1, Ordering::Release);
+    }
+}
@@ -0,0 +1,133 @@
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::sched::{self, TaskId};
+
+use super::IrqSafeMutex;
+
+const MAX_WAITERS: usize = 32;
+
+pub struct Waiter {
+    task: TaskId,
+    priority: u8,
+    woken: AtomicBool,
+}
+
+impl Waiter {
+    fn new() -> Self {
+        let task = sched::current();
+        Self {
+            task,
+            priority: sched::priority(task),
+            woken: AtomicBool::new(false),
+        }
+    }
+
+    fn wake(&self) -> TaskId {
+        let task = self.task;
+        self.woken.store(true, Ordering::Release);
+        task
+    }
+}
+
+struct WaiterList {
+    entries: [*const Waiter; MAX_WAITERS],
+    len: usize,
+}
+
+impl WaiterList {
+    const fn new() -> Self {
+        Self {
+            entries: [core::ptr::null(); MAX_WAITERS],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, waiter: &Waiter) {
+        assert!(self.len < MAX_WAITERS, "too many waiters");
+        self.entries[self.len] = waiter;
+        self.len += 1;
+    }
+
+    fn remove(&mut self, idx: usize) -> &Waiter {
+        let waiter = self.entries[idx];
+        self.entries.copy_within(idx + 1..self.len, idx);
+        self.len -= 1;
+        unsafe { &*waiter }
+    }
+
+    fn highest_priority(&self) -> Option<usize> {
+        let mut best: Option<(usize, u8)> = None;
+        for (idx, &waiter) in self.entries[..self.len].iter().enumerate() {
+            let priority = unsafe { (*waiter).priority };
+            if !best.is_some_and(|(_, p)| p >= priority) {
+                best = Some((idx, priority));
+            }
+        }
+        best.map(|(idx, _)| idx)
+    }
+}
+
+unsafe impl Send for WaiterList {}
+
+pub struct WaitQueue {
+    waiters: IrqSafeMutex<WaiterList>,
+}
+
+impl WaitQueue {
+    pub const fn new() -> Self {
+        Self {
+            waiters: IrqSafeMutex::new(WaiterList::new()),
+        }
+    }
+
+    pub fn wait_until(&self, mut cond: impl FnMut() -> bool) {
+        loop {
+            let waiter = Waiter::new();
+            {
+                let mut waiters = self.waiters.lock();
+                if cond() {
+                    return;
+                }
+                waiters.push(&waiter);
+            }
+            sched::block(&waiter.woken);
+        }
+    }
+
+    pub fn wake_one(&self) -> bool {
+        self.wake_one_with(|_| {})
+    }
+
+    pub fn wake_one_with(&self, f: impl FnOnce(Option<TaskId>)) -> bool {
+        let mut waiters = self.waiters.lock();
+        match waiters.highest_priority() {
+            Some(idx) => {
+                let waiter = waiters.remove(idx);
+                f(Some(waiter.task));
+                sched::unblock(waiter.wake());
+                true
+            }
+            None => {
+                f(None);
+                false
+            }
+        }
+    }
+
+    pub fn wake_all(&self) -> usize {
+        let mut waiters = self.waiters.lock();
+        let count = waiters.len;
+        while waiters.len != 0 {
+            sched::unblock(waiters.remove(0).wake());
+        }
+        count
+    }
+
+    pub fn len(&self) -> usize {
+        self.waiters.lock().len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
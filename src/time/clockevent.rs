@@ -0,0 +1,124 @@
+use core::{
+    ops::{BitAnd, BitOr},
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+
+use crate::{
+    arch::{self, MAX_CPUS},
+    sync::OnceCell,
+};
+
+use super::{clocksource, timer, NANOS_PER_SEC};
+
+const MAX_DEVICES: usize = 4;
+const TICK_HZ: u64 = 1000;
+const MIN_DELTA_NS: u64 = 1000;
+
+static DEVICES: [[OnceCell<&'static dyn ClockEvent>; MAX_DEVICES]; MAX_CPUS] =
+    [const { [const { OnceCell::new() }; MAX_DEVICES] }; MAX_CPUS];
+static NEXT_DEVICE: [AtomicUsize; MAX_CPUS] = [const { AtomicUsize::new(0) }; MAX_CPUS];
+static CURRENT: [OnceCell<&'static dyn ClockEvent>; MAX_CPUS] =
+    [const { OnceCell::new() }; MAX_CPUS];
+static EVENTS: [AtomicU64; MAX_CPUS] = [const { AtomicU64::new(0) }; MAX_CPUS];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct Features(u8);
+
+impl Features {
+    pub const ONESHOT: Features = Self::new(1 << 0);
+    pub const PERIODIC: Features = Self::new(1 << 1);
+
+    #[inline]
+    const fn new(val: u8) -> Self {
+        Self(val)
+    }
+}
+
+impl BitAnd for Features {
+    type Output = bool;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        (self.0 & rhs.0) != 0
+    }
+}
+
+impl BitOr for Features {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+// A per-CPU interrupt source. Devices call `interrupt` from their handler.
+pub trait ClockEvent: Sync {
+    fn name(&self) -> &'static str;
+    fn rating(&self) -> u32;
+    fn features(&self) -> Features;
+    fn max_delta_ns(&self) -> u64;
+    fn set_oneshot(&self, delta_ns: u64);
+    fn set_periodic(&self, period_ns: u64);
+    fn stop(&self);
+}
+
+pub fn register(device: &'static dyn ClockEvent) {
+    let cpu = arch::cpu_index();
+    let idx = NEXT_DEVICE[cpu].fetch_add(1, Ordering::Relaxed);
+    match DEVICES[cpu].get(idx) {
+        Some(slot) => {
+            let _ = slot.set(device);
+        }
+        None => crate::warn!("too many clock event devices, dropping {}", device.name()),
+    }
+}
+
+pub fn select() -> Option<&'static dyn ClockEvent> {
+    let cpu = arch::cpu_index();
+    let device = *DEVICES[cpu]
+        .iter()
+        .filter_map(OnceCell::get)
+        .max_by_key(|device| device.rating())?;
+
+    match CURRENT[cpu].set(device) {
+        Ok(&device) => {
+            crate::info!("cpu{} clock events from {}", cpu, device.name());
+            if !(device.features() & Features::ONESHOT) {
+                device.set_periodic(NANOS_PER_SEC / TICK_HZ);
+            }
+            Some(device)
+        }
+        Err(_) => current(),
+    }
+}
+
+pub fn current() -> Option<&'static dyn ClockEvent> {
+    CURRENT[arch::cpu_index()].get().copied()
+}
+
+pub fn events() -> u64 {
+    EVENTS[arch::cpu_index()].load(Ordering::Relaxed)
+}
+
+// Periodic-only devices keep ticking and just let the timer code poll.
+pub fn program(deadline: Option<u64>) {
+    let Some(device) = current() else {
+        return;
+    };
+    if !(device.features() & Features::ONESHOT) {
+        return;
+    }
+
+    match deadline {
+        Some(deadline) => {
+            let delta = deadline.saturating_sub(clocksource::read_ns());
+            device.set_oneshot(delta.clamp(MIN_DELTA_NS, device.max_delta_ns()));
+        }
+        None => device.stop(),
+    }
+}
+
+pub fn interrupt() {
+    EVENTS[arch::cpu_index()].fetch_add(1, Ordering::Relaxed);
+    timer::expire();
+}
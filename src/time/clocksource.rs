@@ -0,0 +1,87 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{arch, sync::OnceCell};
+
+use super::NANOS_PER_SEC;
+
+const MAX_SOURCES: usize = 4;
+
+static SOURCES: [OnceCell<&'static dyn ClockSource>; MAX_SOURCES] =
+    [const { OnceCell::new() }; MAX_SOURCES];
+static NEXT_SOURCE: AtomicUsize = AtomicUsize::new(0);
+static CURRENT: OnceCell<Current> = OnceCell::new();
+
+// A free-running counter. Sources with a higher rating are more precise and
+// cheaper to read.
+pub trait ClockSource: Sync {
+    fn name(&self) -> &'static str;
+    fn rating(&self) -> u32;
+    fn frequency(&self) -> u64;
+    fn read(&self) -> u64;
+}
+
+struct Current {
+    source: &'static dyn ClockSource,
+    base: u64,
+    base_ns: u64,
+}
+
+pub fn register(source: &'static dyn ClockSource) {
+    let idx = NEXT_SOURCE.fetch_add(1, Ordering::Relaxed);
+    match SOURCES.get(idx) {
+        Some(slot) => {
+            let _ = slot.set(source);
+        }
+        None => crate::warn!("too many clock sources, dropping {}", source.name()),
+    }
+}
+
+pub fn select() -> Option<&'static dyn ClockSource> {
+    let source = SOURCES
+        .iter()
+        .filter_map(OnceCell::get)
+        .filter(|source| source.frequency() != 0)
+        .max_by_key(|source| source.rating())?;
+
+    let selected = Current {
+        source: *source,
+        base: source.read(),
+        base_ns: read_ns(),
+    };
+    match CURRENT.set(selected) {
+        Ok(selected) => {
+            crate::info!(
+                "clocksource {} at {} Hz",
+                selected.source.name(),
+                selected.source.frequency()
+            );
+            Some(selected.source)
+        }
+        Err(_) => current(),
+    }
+}
+
+pub fn current() -> Option<&'static dyn ClockSource> {
+    CURRENT.get().map(|current| current.source)
+}
+
+pub fn read_ns() -> u64 {
+    match CURRENT.get() {
+        Some(current) => {
+            let ticks = current.source.read().wrapping_sub(current.base);
+            current.base_ns + ticks_to_ns(ticks, current.source.frequency())
+        }
+        None => ticks_to_ns(arch::read_tsc(), arch::tsc_mhz() * 1_000_000),
+    }
+}
+
+pub fn ticks_to_ns(ticks: u64, freq: u64) -> u64 {
+    match freq {
+        0 => 0,
+        freq => (ticks as u128 * NANOS_PER_SEC as u128 / freq as u128) as u64,
+    }
+}
+
+pub fn ns_to_ticks(ns: u64, freq: u64) -> u64 {
+    (ns as u128 * freq as u128 / NANOS_PER_SEC as u128) as u64
+}
@@ -0,0 +1,112 @@
+use core::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::arch;
+
+pub use clockevent::{ClockEvent, Features};
+pub use clocksource::ClockSource;
+
+pub mod clockevent;
+pub mod clocksource;
+pub mod timer;
+
+pub const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+const SECS_PER_DAY: u64 = 86400;
+const DAYS_TO_UNIX_EPOCH: i64 = 719468;
+
+static WALL_OFFSET_NS: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl DateTime {
+    pub fn from_unix(secs: u64) -> Self {
+        let days = (secs / SECS_PER_DAY) as i64 + DAYS_TO_UNIX_EPOCH;
+        let rem = secs % SECS_PER_DAY;
+
+        let era = days.div_euclid(146097);
+        let doe = days.rem_euclid(146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + (month <= 2) as i64;
+
+        Self {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            hour: (rem / 3600) as u8,
+            minute: (rem / 60 % 60) as u8,
+            second: (rem % 60) as u8,
+        }
+    }
+
+    pub fn unix(&self) -> u64 {
+        let (month, day) = (self.month as i64, self.day as i64);
+        let year = self.year as i64 - (month <= 2) as i64;
+        let era = year.div_euclid(400);
+        let yoe = year.rem_euclid(400);
+        let mp = if month > 2 { month - 3 } else { month + 9 };
+        let doy = (153 * mp + 2) / 5 + day - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        let days = era * 146097 + doe - DAYS_TO_UNIX_EPOCH;
+
+        let secs = self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64;
+        (days * SECS_PER_DAY as i64 + secs).max(0) as u64
+    }
+
+    pub fn is_valid(&self) -> bool {
+        (1..=12).contains(&self.month)
+            && (1..=31).contains(&self.day)
+            && self.hour < 24
+            && self.minute < 60
+            && self.second < 60
+    }
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+pub fn init(now: DateTime) {
+    let offset = (now.unix() * NANOS_PER_SEC).saturating_sub(clocksource::read_ns());
+    WALL_OFFSET_NS.store(offset, Ordering::Release);
+}
+
+pub fn monotonic() -> Duration {
+    Duration::from_nanos(clocksource::read_ns())
+}
+
+pub fn now() -> Duration {
+    Duration::from_nanos(WALL_OFFSET_NS.load(Ordering::Acquire)) + monotonic()
+}
+
+pub fn now_datetime() -> DateTime {
+    DateTime::from_unix(now().as_secs())
+}
+
+// Timers reprogram the clock event device as they come and go, so an idle CPU
+// sleeps until the next deadline without taking periodic ticks.
+pub fn idle() {
+    timer::expire();
+    arch::wait_for_interrupt();
+}
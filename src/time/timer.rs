@@ -0,0 +1,214 @@
+use core::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::{
+    arch::{self, MAX_CPUS},
+    sync::{IrqGuard, IrqSafeMutex},
+};
+
+use super::{clockevent, clocksource};
+
+const MAX_TIMERS: usize = 64;
+
+static TIMERS: IrqSafeMutex<TimerHeap> = IrqSafeMutex::new(TimerHeap::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+// The id of the callback each CPU is running, zero when none is.
+static RUNNING: [AtomicU64; MAX_CPUS] = [const { AtomicU64::new(0) }; MAX_CPUS];
+
+pub type TimerFn = fn(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimerId(u64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerError {
+    Full,
+}
+
+impl fmt::Display for TimerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Full => f.write_str("too many pending timers"),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Timer {
+    deadline: u64,
+    id: u64,
+    callback: TimerFn,
+    data: usize,
+}
+
+impl Timer {
+    const EMPTY: Timer = Timer {
+        deadline: 0,
+        id: 0,
+        callback: |_| {},
+        data: 0,
+    };
+
+    fn before(&self, other: &Timer) -> bool {
+        (self.deadline, self.id) < (other.deadline, other.id)
+    }
+}
+
+struct TimerHeap {
+    timers: [Timer; MAX_TIMERS],
+    len: usize,
+}
+
+impl TimerHeap {
+    const fn new() -> Self {
+        Self {
+            timers: [Timer::EMPTY; MAX_TIMERS],
+            len: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<&Timer> {
+        self.timers[..self.len].first()
+    }
+
+    fn push(&mut self, timer: Timer) -> Result<(), TimerError> {
+        if self.len == MAX_TIMERS {
+            return Err(TimerError::Full);
+        }
+        self.timers[self.len] = timer;
+        self.len += 1;
+        self.sift_up(self.len - 1);
+        Ok(())
+    }
+
+    fn remove(&mut self, idx: usize) -> Timer {
+        let timer = self.timers[idx];
+        self.len -= 1;
+        if idx < self.len {
+            self.timers[idx] = self.timers[self.len];
+            self.sift_down(idx);
+            self.sift_up(idx);
+        }
+        timer
+    }
+
+    fn sift_up(&mut self, mut idx: usize) {
+        while idx > 0 {
+            let parent = (idx - 1) / 2;
+            if !self.timers[idx].before(&self.timers[parent]) {
+                break;
+            }
+            self.timers.swap(idx, parent);
+            idx = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut idx: usize) {
+        loop {
+            let mut first = idx;
+            for child in [2 * idx + 1, 2 * idx + 2] {
+                if child < self.len && self.timers[child].before(&self.timers[first]) {
+                    first = child;
+                }
+            }
+            if first == idx {
+                break;
+            }
+            self.timers.swap(idx, first);
+            idx = first;
+        }
+    }
+}
+
+// Deadlines are measured on the monotonic clock.
+pub fn add(deadline: Duration, callback: TimerFn, data: usize) -> Result<TimerId, TimerError> {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let deadline = deadline.as_nanos() as u64;
+
+    let mut timers = TIMERS.lock();
+    timers.push(Timer {
+        deadline,
+        id,
+        callback,
+        data,
+    })?;
+    if timers.peek().is_some_and(|first| first.id == id) {
+        clockevent::program(Some(deadline));
+    }
+    Ok(TimerId(id))
+}
+
+pub fn after(delay: Duration, callback: TimerFn, data: usize) -> Result<TimerId, TimerError> {
+    add(super::monotonic() + delay, callback, data)
+}
+
+// A callback that already started on another CPU is waited for, so that its
+// data may be freed once this returns.
+pub fn cancel(id: TimerId) -> bool {
+    let mut timers = TIMERS.lock();
+    let Some(idx) = timers.timers[..timers.len]
+        .iter()
+        .position(|timer| timer.id == id.0)
+    else {
+        drop(timers);
+        let cpu = arch::cpu_index();
+        while RUNNING
+            .iter()
+            .enumerate()
+            .any(|(idx, running)| idx != cpu && running.load(Ordering::Acquire) == id.0)
+        {
+            core::hint::spin_loop();
+        }
+        return false;
+    };
+    timers.remove(idx);
+    if idx == 0 {
+        clockevent::program(timers.peek().map(|timer| timer.deadline));
+    }
+    true
+}
+
+pub fn next_deadline() -> Option<Duration> {
+    TIMERS
+        .lock()
+        .peek()
+        .map(|timer| Duration::from_nanos(timer.deadline))
+}
+
+// Callbacks run with the heap unlocked so that they can re-arm themselves.
+pub fn expire() {
+    loop {
+        let mut timers = TIMERS.lock();
+        let now = clocksource::read_ns();
+        match timers.peek() {
+            Some(timer) if timer.deadline <= now => {
+                let timer = timers.remove(0);
+                let running = &RUNNING[arch::cpu_index()];
+                running.store(timer.id, Ordering::Relaxed);
+                drop(timers);
+                (timer.callback)(timer.data);
+                running.store(0, Ordering::Release);
+            }
+            next => {
+                clockevent::program(next.map(|timer| timer.deadline));
+                return;
+            }
+        }
+    }
+}
+
+pub fn sleep(duration: Duration) {
+    let deadline = super::monotonic() + duration;
+    let armed = add(deadline, |_| {}, 0).is_ok();
+    let _irq = IrqGuard::new();
+    while super::monotonic() < deadline {
+        if armed {
+            arch::wait_for_interrupt();
+        } else {
+            core::hint::spin_loop();
+        }
+    }
+}
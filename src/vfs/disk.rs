@@ -0,0 +1,127 @@
+use core::slice;
+
+use fs::{Disk, FsError};
+
+use crate::{
+    arch,
+    block::{BlockDevice, BlockError, Cached, MAX_BLOCK_SIZE},
+};
+
+impl From<BlockError> for FsError {
+    fn from(err: BlockError) -> Self {
+        match err {
+            BlockError::ReadOnly => Self::ReadOnly,
+            _ => Self::Io,
+        }
+    }
+}
+
+// Byte addressed access to a block device through the block cache. Partial
+// blocks are read, and written back whole, through a scratch block.
+pub struct BlockDisk {
+    device: Cached,
+    scratch: usize,
+}
+
+impl BlockDisk {
+    pub fn new(device: &'static dyn BlockDevice) -> Result<Self, FsError> {
+        if device.block_size() > MAX_BLOCK_SIZE {
+            return Err(FsError::Unsupported);
+        }
+        let scratch = arch::alloc_dma(1).map_err(|_| FsError::Io)?;
+        Ok(Self {
+            device: Cached::new(device),
+            scratch,
+        })
+    }
+
+    #[inline]
+    pub fn device(&self) -> &'static dyn BlockDevice {
+        self.device.device()
+    }
+
+    fn scratch(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.scratch as *mut u8, self.device.block_size()) }
+    }
+
+    fn check(&self, offset: u64, len: usize) -> Result<(), FsError> {
+        match offset.checked_add(len as u64) {
+            Some(end) if end <= self.size() => Ok(()),
+            _ => Err(FsError::Io),
+        }
+    }
+}
+
+impl Disk for BlockDisk {
+    fn size(&self) -> u64 {
+        self.device.block_count() * self.device.block_size() as u64
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<(), FsError> {
+        self.check(offset, buf.len())?;
+        let block_size = self.device.block_size();
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = offset + done as u64;
+            let lba = pos / block_size as u64;
+            let within = (pos % block_size as u64) as usize;
+            let rest = buf.len() - done;
+            if within == 0 && rest >= block_size {
+                let len = rest & !(block_size - 1);
+                self.device.read(lba, &mut buf[done..done + len])?;
+                done += len;
+            } else {
+                let len = (block_size - within).min(rest);
+                let device = self.device;
+                let scratch = self.scratch();
+                device.read(lba, scratch)?;
+                buf[done..done + len].copy_from_slice(&scratch[within..within + len]);
+                done += len;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> Result<(), FsError> {
+        self.check(offset, buf.len())?;
+        if self.is_read_only() {
+            return Err(FsError::ReadOnly);
+        }
+        let block_size = self.device.block_size();
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = offset + done as u64;
+            let lba = pos / block_size as u64;
+            let within = (pos % block_size as u64) as usize;
+            let rest = buf.len() - done;
+            if within == 0 && rest >= block_size {
+                let len = rest & !(block_size - 1);
+                self.device.write(lba, &buf[done..done + len])?;
+                done += len;
+            } else {
+                let len = (block_size - within).min(rest);
+                let device = self.device;
+                let scratch = self.scratch();
+                device.read(lba, scratch)?;
+                scratch[within..within + len].copy_from_slice(&buf[done..done + len]);
+                device.write(lba, scratch)?;
+                done += len;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), FsError> {
+        Ok(self.device.flush()?)
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.device.is_read_only()
+    }
+}
+
+impl Drop for BlockDisk {
+    fn drop(&mut self) {
+        arch::free_dma(self.scratch, 1);
+    }
+}
@@ -0,0 +1,287 @@
+use core::{
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use fs::{DirEntry, Ext2, Fat, FileSystem, FileType, FsError, Ino, Metadata, Name};
+
+use crate::{
+    block::{self, BlockDevice},
+    sync::{IrqSafeMutex, OnceCell, SleepMutex},
+    time,
+};
+
+pub use disk::*;
+
+pub mod disk;
+
+const MAX_MOUNTS: usize = 8;
+const MOUNT_DIR: &str = "/mnt/";
+
+crate::param!(static ROOT: Option<&'static str> = "root", None);
+
+pub type Fs = SleepMutex<dyn FileSystem + Send>;
+
+static FATS: [OnceCell<SleepMutex<Fat<BlockDisk>>>; MAX_MOUNTS] =
+    [const { OnceCell::new() }; MAX_MOUNTS];
+static NEXT_FAT: AtomicUsize = AtomicUsize::new(0);
+static EXT2S: [OnceCell<SleepMutex<Ext2<BlockDisk>>>; MAX_MOUNTS] =
+    [const { OnceCell::new() }; MAX_MOUNTS];
+static NEXT_EXT2: AtomicUsize = AtomicUsize::new(0);
+
+static MOUNTS: IrqSafeMutex<[Option<Mount>; MAX_MOUNTS]> = IrqSafeMutex::new([None; MAX_MOUNTS]);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MountError {
+    InvalidPath,
+    Busy,
+    TooMany,
+    Fs(FsError),
+}
+
+impl fmt::Display for MountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidPath => f.write_str("mount point is not an absolute path"),
+            Self::Busy => f.write_str("mount point already in use"),
+            Self::TooMany => f.write_str("too many mounts"),
+            Self::Fs(err) => fmt::Display::fmt(err, f),
+        }
+    }
+}
+
+impl From<FsError> for MountError {
+    fn from(err: FsError) -> Self {
+        Self::Fs(err)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Mount {
+    path: Name,
+    source: &'static str,
+    fs: &'static Fs,
+}
+
+impl Mount {
+    #[inline]
+    pub fn path(&self) -> &str {
+        self.path.as_str()
+    }
+
+    #[inline]
+    pub fn source(&self) -> &'static str {
+        self.source
+    }
+
+    #[inline]
+    pub fn fs(&self) -> &'static Fs {
+        self.fs
+    }
+}
+
+// A file or directory on a mounted filesystem.
+#[derive(Clone, Copy)]
+pub struct Node {
+    fs: &'static Fs,
+    ino: Ino,
+}
+
+impl Node {
+    #[inline]
+    pub fn ino(&self) -> Ino {
+        self.ino
+    }
+
+    pub fn metadata(&self) -> Result<Metadata, FsError> {
+        self.fs.lock().metadata(self.ino)
+    }
+
+    pub fn lookup(&self, name: &str) -> Result<Node, FsError> {
+        let ino = self.fs.lock().lookup(self.ino, name)?;
+        Ok(Node { fs: self.fs, ino })
+    }
+
+    pub fn read(&self, offset: u64, buf: &mut [u8]) -> Result<usize, FsError> {
+        self.fs.lock().read(self.ino, offset, buf)
+    }
+
+    pub fn write(&self, offset: u64, buf: &[u8]) -> Result<usize, FsError> {
+        self.fs.lock().write(self.ino, offset, buf)
+    }
+
+    pub fn truncate(&self, size: u64) -> Result<(), FsError> {
+        self.fs.lock().truncate(self.ino, size)
+    }
+
+    pub fn read_dir(&self, pos: u64) -> Result<Option<(DirEntry, u64)>, FsError> {
+        self.fs.lock().read_dir(self.ino, pos)
+    }
+
+    pub fn create(&self, name: &str, kind: FileType) -> Result<Node, FsError> {
+        let ino = self.fs.lock().create(self.ino, name, kind)?;
+        Ok(Node { fs: self.fs, ino })
+    }
+
+    pub fn remove(&self, name: &str) -> Result<(), FsError> {
+        self.fs.lock().remove(self.ino, name)
+    }
+
+    pub fn symlink(&self, name: &str, target: &str) -> Result<Node, FsError> {
+        let ino = self.fs.lock().symlink(self.ino, name, target)?;
+        Ok(Node { fs: self.fs, ino })
+    }
+
+    pub fn read_link(&self, buf: &mut [u8]) -> Result<usize, FsError> {
+        self.fs.lock().read_link(self.ino, buf)
+    }
+}
+
+fn normalize(path: &str) -> Option<&str> {
+    if !path.starts_with('/') {
+        return None;
+    }
+    match path.trim_end_matches('/') {
+        "" => Some("/"),
+        path => Some(path),
+    }
+}
+
+// The part of `path` below the mount point, if it is under it.
+fn strip_mount<'a>(mount: &str, path: &'a str) -> Option<&'a str> {
+    if mount == "/" {
+        return Some(path);
+    }
+    let rest = path.strip_prefix(mount)?;
+    (rest.is_empty() || rest.starts_with('/')).then_some(rest)
+}
+
+pub fn mount(path: &str, source: &'static str, fs: &'static Fs) -> Result<(), MountError> {
+    let path = normalize(path).ok_or(MountError::InvalidPath)?;
+    let mut name = Name::new();
+    name.push_str(path)?;
+
+    let mut mounts = MOUNTS.lock();
+    if mounts.iter().flatten().any(|mount| mount.path() == path) {
+        return Err(MountError::Busy);
+    }
+    let slot = mounts
+        .iter_mut()
+        .find(|slot| slot.is_none())
+        .ok_or(MountError::TooMany)?;
+    *slot = Some(Mount {
+        path: name,
+        source,
+        fs,
+    });
+    Ok(())
+}
+
+pub fn mounts() -> impl Iterator<Item = Mount> {
+    let mounts = *MOUNTS.lock();
+    mounts.into_iter().flatten()
+}
+
+// Paths are absolute and resolved within the filesystem mounted deepest
+// along them, so ".." never leaves a mount. The same goes for symbolic
+// links, absolute targets start at the root of the mount.
+pub fn open(path: &str) -> Result<Node, FsError> {
+    let path = normalize(path).ok_or(FsError::InvalidName)?;
+    let (mount, rest) = {
+        let mounts = MOUNTS.lock();
+        mounts
+            .iter()
+            .flatten()
+            .filter_map(|mount| Some((*mount, strip_mount(mount.path(), path)?)))
+            .max_by_key(|(mount, _)| mount.path().len())
+            .ok_or(FsError::NotFound)?
+    };
+
+    let mut fs = mount.fs.lock();
+    let root = fs.root();
+    let ino = fs::resolve(&mut *fs, root, rest)?;
+    Ok(Node { fs: mount.fs, ino })
+}
+
+pub fn create(path: &str, kind: FileType) -> Result<Node, FsError> {
+    let (parent, name) = fs::split(path);
+    let parent = if parent.is_empty() { "/" } else { parent };
+    open(parent)?.create(name, kind)
+}
+
+pub fn symlink(path: &str, target: &str) -> Result<Node, FsError> {
+    let (parent, name) = fs::split(path);
+    let parent = if parent.is_empty() { "/" } else { parent };
+    open(parent)?.symlink(name, target)
+}
+
+pub fn remove(path: &str) -> Result<(), FsError> {
+    let (parent, name) = fs::split(path);
+    let parent = if parent.is_empty() { "/" } else { parent };
+    open(parent)?.remove(name)
+}
+
+// Syncs every mount and reports the first failure.
+pub fn sync_all() -> Result<(), FsError> {
+    let mut result = Ok(());
+    for mount in mounts() {
+        if let Err(err) = mount.fs.lock().sync() {
+            crate::warn!("{}: sync: {}", mount.source, err);
+            result = result.and(Err(err));
+        }
+    }
+    result
+}
+
+fn clock() -> u64 {
+    time::now().as_secs()
+}
+
+fn probe(device: &'static dyn BlockDevice) -> Option<&'static Fs> {
+    if let Ok(ext2) = Ext2::mount(BlockDisk::new(device).ok()?) {
+        let slot = EXT2S.get(NEXT_EXT2.fetch_add(1, Ordering::Relaxed))?;
+        let ext2 = slot.set(SleepMutex::new(ext2.with_clock(clock))).ok()?;
+        return Some(ext2);
+    }
+    let fat = Fat::mount(BlockDisk::new(device).ok()?).ok()?;
+    let slot = FATS.get(NEXT_FAT.fetch_add(1, Ordering::Relaxed))?;
+    let fat = slot.set(SleepMutex::new(fat.with_clock(clock))).ok()?;
+    Some(fat)
+}
+
+// Mounts the partition named by `root=` at "/" and every other partition
+// with a known filesystem under "/mnt".
+pub fn init() {
+    let root = ROOT.get();
+    let mut root_mounted = false;
+    for partition in block::partitions() {
+        let device: &'static dyn BlockDevice = partition;
+        let Some(fs) = probe(device) else {
+            continue;
+        };
+
+        let is_root = root == Some(device.name());
+        let mut path = Name::new();
+        let pushed = if is_root {
+            path.push_str("/")
+        } else {
+            path.push_str(MOUNT_DIR)
+                .and_then(|()| path.push_str(device.name()))
+        };
+        let result = pushed
+            .map_err(MountError::from)
+            .and_then(|()| mount(path.as_str(), device.name(), fs));
+        let name = fs.lock().name();
+        match result {
+            Ok(()) => {
+                root_mounted |= is_root;
+                crate::info!("{}: mounted {} at {}", device.name(), name, path);
+            }
+            Err(err) => crate::warn!("{}: mount {}: {}", device.name(), path, err),
+        }
+    }
+
+    if let Some(root) = root.filter(|_| !root_mounted) {
+        crate::warn!("root device {} not found", root);
+    }
+}
@@ -0,0 +1,464 @@
+use core::{
+    slice,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use ::memory::PAGE_SIZE;
+
+use crate::{
+    arch,
+    block::{self, BlockDevice, BlockError, Geometry, SECTOR_SIZE},
+    pci::{self, Device, Driver, Match, ProbeError},
+    sync::{Event, IrqSafeMutex, OnceCell, Semaphore},
+    time,
+};
+
+use super::{
+    create_queue, device_type, fail, negotiate, start, Buffer, DeviceType, PciTransport, Transport,
+    VirtQueue, VirtioError, VENDOR,
+};
+
+const F_SIZE_MAX: u64 = 1 << 1;
+const F_GEOMETRY: u64 = 1 << 4;
+const F_RO: u64 = 1 << 5;
+const F_BLK_SIZE: u64 = 1 << 6;
+const F_FLUSH: u64 = 1 << 9;
+const F_DISCARD: u64 = 1 << 13;
+const SUPPORTED: u64 = F_SIZE_MAX | F_GEOMETRY | F_RO | F_BLK_SIZE | F_FLUSH | F_DISCARD;
+
+const CONFIG_CAPACITY: u16 = 0;
+const CONFIG_SIZE_MAX: u16 = 8;
+const CONFIG_CYLINDERS: u16 = 16;
+const CONFIG_HEADS: u16 = 18;
+const CONFIG_SECTORS: u16 = 19;
+const CONFIG_BLK_SIZE: u16 = 20;
+const CONFIG_MAX_DISCARD_SECTORS: u16 = 36;
+
+const REQ_IN: u32 = 0;
+const REQ_OUT: u32 = 1;
+const REQ_FLUSH: u32 = 4;
+const REQ_DISCARD: u32 = 11;
+
+const STATUS_OK: u8 = 0;
+const STATUS_UNSUPPORTED: u8 = 2;
+const STATUS_PENDING: u8 = 0xff;
+
+const HEADER_SIZE: usize = 16;
+const DISCARD_SIZE: usize = 16;
+const SLOT_DISCARD: usize = 16;
+const SLOT_STATUS: usize = 32;
+const SLOT_STRIDE: usize = 64;
+
+// Each in-flight request owns a slot with its header and a bounce buffer.
+const SLOTS: usize = 8;
+const SLOT_PAGES: usize = 8;
+const SLOT_BYTES: usize = SLOT_PAGES * PAGE_SIZE;
+
+const MAX_DISKS: usize = 4;
+const NAMES: [&str; MAX_DISKS] = ["vda", "vdb", "vdc", "vdd"];
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+pub static VIRTIO_BLK: VirtioBlkDriver = VirtioBlkDriver;
+
+static DISKS: [OnceCell<VirtioBlk>; MAX_DISKS] = [const { OnceCell::new() }; MAX_DISKS];
+static NEXT_DISK: AtomicUsize = AtomicUsize::new(0);
+
+pub fn init() {
+    pci::register(&VIRTIO_BLK);
+}
+
+fn interrupt(idx: usize) {
+    if let Some(disk) = DISKS[idx].get() {
+        disk.transport.ack_interrupt();
+        disk.complete();
+    }
+}
+
+pub struct VirtioBlkDriver;
+
+impl Driver for VirtioBlkDriver {
+    fn name(&self) -> &'static str {
+        "virtio-blk"
+    }
+
+    fn matches(&self) -> &'static [Match] {
+        &[Match::Vendor(VENDOR)]
+    }
+
+    fn probe(&self, device: &'static Device) -> Result<(), ProbeError> {
+        if device_type(device) != Some(DeviceType::Block) {
+            return Err(ProbeError::Unsupported);
+        }
+        let idx = NEXT_DISK.fetch_add(1, Ordering::Relaxed);
+        if idx >= MAX_DISKS {
+            return Err(ProbeError::Device("too many virtio disks"));
+        }
+
+        let disk = VirtioBlk::new(idx, PciTransport::new(device)?)?;
+        crate::info!(
+            "{}: {} sectors{}{}",
+            disk.name,
+            disk.capacity,
+            if disk.read_only { ", read only" } else { "" },
+            if disk.transport.is_legacy() {
+                ", legacy"
+            } else {
+                ""
+            }
+        );
+        if let Some(geometry) = disk.geometry {
+            crate::info!("{}: geometry {}", disk.name, geometry);
+        }
+        if let Ok(disk) = DISKS[idx].set(disk) {
+            block::register(disk);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Slot {
+    Free,
+    Reserved,
+    Pending(u16),
+    Done,
+}
+
+pub struct VirtioBlk {
+    name: &'static str,
+    transport: PciTransport,
+    queue: VirtQueue,
+    features: u64,
+    capacity: u64,
+    block_size: usize,
+    max_bytes: usize,
+    max_discard: u32,
+    read_only: bool,
+    geometry: Option<Geometry>,
+    headers: usize,
+    buffers: usize,
+    slots: IrqSafeMutex<[Slot; SLOTS]>,
+    free: Semaphore,
+    done: [Event; SLOTS],
+}
+
+impl VirtioBlk {
+    fn new(idx: usize, transport: PciTransport) -> Result<Self, VirtioError> {
+        match Self::setup(idx, &transport) {
+            Ok((features, queue, headers, buffers)) => {
+                let disk = Self::from_config(idx, transport, features, queue, headers, buffers);
+                start(&disk.transport);
+                Ok(disk)
+            }
+            Err(err) => {
+                fail(&transport);
+                Err(err)
+            }
+        }
+    }
+
+    fn setup(
+        idx: usize,
+        transport: &PciTransport,
+    ) -> Result<(u64, VirtQueue, usize, usize), VirtioError> {
+        let features = negotiate(transport, SUPPORTED)?;
+        transport.enable_interrupts(interrupt, idx)?;
+        let queue = create_queue(transport, 0)?;
+        let headers = arch::alloc_dma(1)?;
+        let buffers = match arch::alloc_dma(SLOTS * SLOT_PAGES) {
+            Ok(buffers) => buffers,
+            Err(err) => {
+                arch::free_dma(headers, 1);
+                return Err(err.into());
+            }
+        };
+        Ok((features, queue, headers, buffers))
+    }
+
+    fn from_config(
+        idx: usize,
+        transport: PciTransport,
+        features: u64,
+        queue: VirtQueue,
+        headers: usize,
+        buffers: usize,
+    ) -> Self {
+        let block_size = if features & F_BLK_SIZE != 0 {
+            transport.read_config_u32(CONFIG_BLK_SIZE) as usize
+        } else {
+            SECTOR_SIZE
+        };
+        // Anything odd, or larger than a bounce buffer slot, is ignored in
+        // favour of plain sectors.
+        let block_size =
+            if block_size.is_power_of_two() && (SECTOR_SIZE..=SLOT_BYTES).contains(&block_size) {
+                block_size
+            } else {
+                SECTOR_SIZE
+            };
+        let max_bytes = if features & F_SIZE_MAX != 0 {
+            match transport.read_config_u32(CONFIG_SIZE_MAX) as usize & !(block_size - 1) {
+                0 => SLOT_BYTES,
+                size => size.min(SLOT_BYTES),
+            }
+        } else {
+            SLOT_BYTES
+        };
+        let max_discard = if features & F_DISCARD != 0 {
+            transport.read_config_u32(CONFIG_MAX_DISCARD_SECTORS)
+        } else {
+            0
+        };
+        let geometry = if features & F_GEOMETRY != 0 {
+            Some(Geometry {
+                cylinders: transport.read_config_u16(CONFIG_CYLINDERS),
+                heads: transport.read_config_u8(CONFIG_HEADS),
+                sectors: transport.read_config_u8(CONFIG_SECTORS),
+            })
+        } else {
+            None
+        };
+
+        Self {
+            name: NAMES[idx],
+            capacity: transport.read_config_u64(CONFIG_CAPACITY),
+            transport,
+            queue,
+            features,
+            block_size,
+            max_bytes: max_bytes.max(block_size),
+            max_discard,
+            read_only: features & F_RO != 0,
+            geometry,
+            headers,
+            buffers,
+            slots: IrqSafeMutex::new([Slot::Free; SLOTS]),
+            free: Semaphore::new(SLOTS),
+            done: [const { Event::new() }; SLOTS],
+        }
+    }
+
+    #[inline]
+    fn header(&self, slot: usize) -> usize {
+        self.headers + slot * SLOT_STRIDE
+    }
+
+    #[inline]
+    fn buffer(&self, slot: usize) -> usize {
+        self.buffers + slot * SLOT_BYTES
+    }
+
+    #[inline]
+    fn sectors_per_block(&self) -> u64 {
+        (self.block_size / SECTOR_SIZE) as u64
+    }
+
+    fn acquire(&self) -> usize {
+        self.free.down();
+        let mut slots = self.slots.lock();
+        let idx = slots
+            .iter()
+            .position(|slot| *slot == Slot::Free)
+            .expect("virtio-blk slot accounting");
+        slots[idx] = Slot::Reserved;
+        idx
+    }
+
+    fn release(&self, slot: usize) {
+        self.slots.lock()[slot] = Slot::Free;
+        self.free.up();
+    }
+
+    fn complete(&self) {
+        while let Some((head, _)) = self.queue.pop_used() {
+            let mut slots = self.slots.lock();
+            if let Some(idx) = slots.iter().position(|slot| *slot == Slot::Pending(head)) {
+                slots[idx] = Slot::Done;
+                self.done[idx].set();
+            }
+        }
+    }
+
+    // Before interrupts are enabled the used ring is polled instead.
+    fn wait(&self, slot: usize) -> bool {
+        if arch::interrupts_enabled() {
+            return self.done[slot].wait_timeout(TIMEOUT);
+        }
+        let deadline = time::monotonic() + TIMEOUT;
+        while !self.done[slot].is_set() {
+            if time::monotonic() >= deadline {
+                return false;
+            }
+            self.complete();
+            core::hint::spin_loop();
+        }
+        true
+    }
+
+    // Runs one request in `slot`, `data` points into the slot's bounce buffer
+    // or its discard segment.
+    fn request(
+        &self,
+        slot: usize,
+        kind: u32,
+        sector: u64,
+        data: Option<Buffer>,
+    ) -> Result<(), BlockError> {
+        let header = self.header(slot);
+        unsafe {
+            (header as *mut u32).write_volatile(kind);
+            ((header + 4) as *mut u32).write_volatile(0);
+            ((header + 8) as *mut u64).write_volatile(sector);
+            ((header + SLOT_STATUS) as *mut u8).write_volatile(STATUS_PENDING);
+        }
+
+        let head = Buffer::read_only(header, HEADER_SIZE as u32);
+        let status = Buffer::write_only(header + SLOT_STATUS, 1);
+        self.done[slot].reset();
+        let added = {
+            let mut slots = self.slots.lock();
+            let added = match data {
+                Some(data) => self.queue.add(&[head, data, status]),
+                None => self.queue.add(&[head, status]),
+            };
+            if let Ok(head) = added {
+                slots[slot] = Slot::Pending(head);
+            }
+            added
+        };
+        if added.is_err() {
+            self.release(slot);
+            return Err(BlockError::Io(0));
+        }
+        self.queue.kick(&self.transport);
+
+        // The device still owns the buffers of a request that timed out, so
+        // its slot is never reused.
+        if !self.wait(slot) {
+            return Err(BlockError::Timeout);
+        }
+        let result = match unsafe { ((header + SLOT_STATUS) as *const u8).read_volatile() } {
+            STATUS_OK => Ok(()),
+            STATUS_UNSUPPORTED => Err(BlockError::Unsupported),
+            err => Err(BlockError::Io(err)),
+        };
+        self.release(slot);
+        result
+    }
+
+    fn transfer(
+        &self,
+        lba: u64,
+        len: usize,
+        mut op: impl FnMut(usize, u64, usize, usize) -> Result<(), BlockError>,
+    ) -> Result<(), BlockError> {
+        block::check_range(self, lba, len)?;
+        let mut done = 0;
+        while done < len {
+            let chunk = (len - done).min(self.max_bytes);
+            let sector = (lba + (done / self.block_size) as u64) * self.sectors_per_block();
+            let slot = self.acquire();
+            op(slot, sector, done, chunk)?;
+            done += chunk;
+        }
+        Ok(())
+    }
+}
+
+impl BlockDevice for VirtioBlk {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    fn block_count(&self) -> u64 {
+        self.capacity / self.sectors_per_block()
+    }
+
+    fn read(&self, lba: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+        self.transfer(lba, buf.len(), |slot, sector, off, len| {
+            let bounce = self.buffer(slot);
+            self.request(
+                slot,
+                REQ_IN,
+                sector,
+                Some(Buffer::write_only(bounce, len as u32)),
+            )?;
+            let src = unsafe { slice::from_raw_parts(bounce as *const u8, len) };
+            buf[off..off + len].copy_from_slice(src);
+            Ok(())
+        })
+    }
+
+    fn write(&self, lba: u64, buf: &[u8]) -> Result<(), BlockError> {
+        if self.read_only {
+            return Err(BlockError::ReadOnly);
+        }
+        self.transfer(lba, buf.len(), |slot, sector, off, len| {
+            let bounce = self.buffer(slot);
+            let dst = unsafe { slice::from_raw_parts_mut(bounce as *mut u8, len) };
+            dst.copy_from_slice(&buf[off..off + len]);
+            self.request(
+                slot,
+                REQ_OUT,
+                sector,
+                Some(Buffer::read_only(bounce, len as u32)),
+            )
+        })
+    }
+
+    // Without the flush feature the device writes through.
+    fn flush(&self) -> Result<(), BlockError> {
+        if self.features & F_FLUSH == 0 {
+            return Ok(());
+        }
+        let slot = self.acquire();
+        self.request(slot, REQ_FLUSH, 0, None)
+    }
+
+    fn discard(&self, lba: u64, count: u64) -> Result<(), BlockError> {
+        if self.max_discard == 0 {
+            return Err(BlockError::Unsupported);
+        }
+        if self.read_only {
+            return Err(BlockError::ReadOnly);
+        }
+        match lba.checked_add(count) {
+            Some(end) if end <= self.block_count() => {}
+            _ => return Err(BlockError::OutOfRange),
+        }
+
+        let mut sector = lba * self.sectors_per_block();
+        let end = (lba + count) * self.sectors_per_block();
+        while sector < end {
+            let sectors = (end - sector).min(self.max_discard as u64);
+            let slot = self.acquire();
+            let segment = self.header(slot) + SLOT_DISCARD;
+            unsafe {
+                (segment as *mut u64).write_volatile(sector);
+                ((segment + 8) as *mut u32).write_volatile(sectors as u32);
+                ((segment + 12) as *mut u32).write_volatile(0);
+            }
+            self.request(
+                slot,
+                REQ_DISCARD,
+                0,
+                Some(Buffer::read_only(segment, DISCARD_SIZE as u32)),
+            )?;
+            sector += sectors;
+        }
+        Ok(())
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn geometry(&self) -> Option<Geometry> {
+        self.geometry
+    }
+}
@@ -0,0 +1,240 @@
+use core::fmt;
+
+use crate::{
+    arch::{VectorError, VectorFn},
+    pci::{Device, MsiError, ProbeError, REG_SUBSYSTEM},
+};
+
+pub use pci::*;
+pub use queue::*;
+
+pub mod blk;
+pub mod pci;
+pub mod queue;
+
+pub const VENDOR: u16 = 0x1af4;
+
+const TRANSITIONAL_FIRST: u16 = 0x1000;
+const TRANSITIONAL_LAST: u16 = 0x103f;
+const MODERN_FIRST: u16 = 0x1040;
+const MODERN_LAST: u16 = 0x107f;
+
+pub const STATUS_ACKNOWLEDGE: u8 = 1 << 0;
+pub const STATUS_DRIVER: u8 = 1 << 1;
+pub const STATUS_DRIVER_OK: u8 = 1 << 2;
+pub const STATUS_FEATURES_OK: u8 = 1 << 3;
+pub const STATUS_NEEDS_RESET: u8 = 1 << 6;
+pub const STATUS_FAILED: u8 = 1 << 7;
+
+pub const F_INDIRECT_DESC: u64 = 1 << 28;
+pub const F_EVENT_IDX: u64 = 1 << 29;
+pub const F_VERSION_1: u64 = 1 << 32;
+
+pub const ISR_QUEUE: u8 = 1 << 0;
+pub const ISR_CONFIG: u8 = 1 << 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceType {
+    Net,
+    Block,
+    Console,
+    Entropy,
+    Gpu,
+    Input,
+}
+
+impl DeviceType {
+    pub fn from_id(id: u16) -> Option<Self> {
+        match id {
+            1 => Some(Self::Net),
+            2 => Some(Self::Block),
+            3 => Some(Self::Console),
+            4 => Some(Self::Entropy),
+            16 => Some(Self::Gpu),
+            18 => Some(Self::Input),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DeviceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Net => "net",
+            Self::Block => "block",
+            Self::Console => "console",
+            Self::Entropy => "entropy",
+            Self::Gpu => "gpu",
+            Self::Input => "input",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VirtioError {
+    Unsupported,
+    MissingCapability(u8),
+    FeaturesRejected,
+    QueueUnavailable(u16),
+    QueueFull,
+    Vector(VectorError),
+    Msi(MsiError),
+    Map(::memory::AllocError),
+}
+
+impl fmt::Display for VirtioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported => f.write_str("unsupported device"),
+            Self::MissingCapability(kind) => write!(f, "missing capability {}", kind),
+            Self::FeaturesRejected => f.write_str("device rejected features"),
+            Self::QueueUnavailable(idx) => write!(f, "queue {} unavailable", idx),
+            Self::QueueFull => f.write_str("queue full"),
+            Self::Vector(err) => write!(f, "{}", err),
+            Self::Msi(err) => write!(f, "msi-x: {}", err),
+            Self::Map(err) => write!(f, "mapping: {}", err),
+        }
+    }
+}
+
+impl From<VectorError> for VirtioError {
+    fn from(err: VectorError) -> Self {
+        Self::Vector(err)
+    }
+}
+
+impl From<MsiError> for VirtioError {
+    fn from(err: MsiError) -> Self {
+        Self::Msi(err)
+    }
+}
+
+impl From<::memory::AllocError> for VirtioError {
+    fn from(err: ::memory::AllocError) -> Self {
+        Self::Map(err)
+    }
+}
+
+impl From<VirtioError> for ProbeError {
+    fn from(err: VirtioError) -> Self {
+        match err {
+            VirtioError::Unsupported => Self::Unsupported,
+            VirtioError::Map(err) => Self::Map(err),
+            VirtioError::MissingCapability(_) => Self::Device("virtio: missing capability"),
+            VirtioError::FeaturesRejected => Self::Device("virtio: features rejected"),
+            VirtioError::QueueUnavailable(_) => Self::Device("virtio: queue unavailable"),
+            VirtioError::QueueFull => Self::Device("virtio: queue full"),
+            VirtioError::Vector(_) | VirtioError::Msi(_) => {
+                Self::Device("virtio: interrupt setup failed")
+            }
+        }
+    }
+}
+
+// Config space accesses must match the width of the field being accessed.
+// Interrupts are enabled after `negotiate` and before the queues are created,
+// since a reset clears the queue vectors and they are assigned in
+// `setup_queue`. The handler is shared by all queues and should call
+// `ack_interrupt` before polling them.
+pub trait Transport: Sync {
+    fn device_type(&self) -> DeviceType;
+    fn is_legacy(&self) -> bool;
+    fn status(&self) -> u8;
+    fn set_status(&self, status: u8);
+    fn device_features(&self) -> u64;
+    fn set_driver_features(&self, features: u64);
+    fn max_queue_size(&self, queue: u16) -> u16;
+    fn setup_queue(&self, queue: &VirtQueue) -> Result<(), VirtioError>;
+    fn notify(&self, queue: u16);
+    fn enable_interrupts(&self, handler: VectorFn, data: usize) -> Result<(), VirtioError>;
+    fn ack_interrupt(&self) -> u8;
+    fn read_config_u8(&self, offset: u16) -> u8;
+    fn read_config_u16(&self, offset: u16) -> u16;
+    fn read_config_u32(&self, offset: u16) -> u32;
+    fn write_config_u8(&self, offset: u16, val: u8);
+    fn write_config_u16(&self, offset: u16, val: u16);
+    fn write_config_u32(&self, offset: u16, val: u32);
+
+    fn read_config_u64(&self, offset: u16) -> u64 {
+        loop {
+            let high = self.read_config_u32(offset + 4);
+            let low = self.read_config_u32(offset);
+            if self.read_config_u32(offset + 4) == high {
+                return ((high as u64) << 32) | low as u64;
+            }
+        }
+    }
+}
+
+pub fn init() {
+    blk::init();
+}
+
+pub fn device_type(device: &Device) -> Option<DeviceType> {
+    if device.vendor() != VENDOR {
+        return None;
+    }
+    match device.device() {
+        TRANSITIONAL_FIRST..=TRANSITIONAL_LAST => {
+            DeviceType::from_id(device.read_u16(REG_SUBSYSTEM + 2))
+        }
+        id @ MODERN_FIRST..=MODERN_LAST => DeviceType::from_id(id - MODERN_FIRST),
+        _ => None,
+    }
+}
+
+// Resets the device and agrees on the subset of `supported` it offers.
+// Modern devices always get VERSION_1. Queues are set up afterwards and the
+// device is started with `start`.
+pub fn negotiate(transport: &dyn Transport, supported: u64) -> Result<u64, VirtioError> {
+    transport.set_status(0);
+    transport.set_status(STATUS_ACKNOWLEDGE);
+    transport.set_status(STATUS_ACKNOWLEDGE | STATUS_DRIVER);
+
+    let supported = if transport.is_legacy() {
+        supported & !F_VERSION_1
+    } else {
+        supported | F_VERSION_1
+    };
+    let features = transport.device_features() & supported;
+    if !transport.is_legacy() && features & F_VERSION_1 == 0 {
+        fail(transport);
+        return Err(VirtioError::FeaturesRejected);
+    }
+    transport.set_driver_features(features);
+
+    if !transport.is_legacy() {
+        transport.set_status(STATUS_ACKNOWLEDGE | STATUS_DRIVER | STATUS_FEATURES_OK);
+        if transport.status() & STATUS_FEATURES_OK == 0 {
+            fail(transport);
+            return Err(VirtioError::FeaturesRejected);
+        }
+    }
+    Ok(features)
+}
+
+pub fn create_queue(transport: &dyn Transport, idx: u16) -> Result<VirtQueue, VirtioError> {
+    let max = transport.max_queue_size(idx);
+    if max == 0 {
+        return Err(VirtioError::QueueUnavailable(idx));
+    }
+    // Legacy devices have a fixed queue size.
+    let size = if transport.is_legacy() {
+        max
+    } else {
+        max.min(MAX_QUEUE_SIZE)
+    };
+    let queue = VirtQueue::new(idx, size)?;
+    transport.setup_queue(&queue)?;
+    Ok(queue)
+}
+
+#[inline]
+pub fn start(transport: &dyn Transport) {
+    transport.set_status(transport.status() | STATUS_DRIVER_OK);
+}
+
+#[inline]
+pub fn fail(transport: &dyn Transport) {
+    transport.set_status(transport.status() | STATUS_FAILED);
+}
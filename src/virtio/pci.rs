@@ -0,0 +1,399 @@
+use core::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+
+use crate::{
+    arch::{self, Mmio, Pio, PortInOut, VectorFn},
+    pci::{Bar, Command, Device, MsiX, CAP_VENDOR},
+};
+
+use super::{device_type, DeviceType, Transport, VirtQueue, VirtioError, MODERN_FIRST};
+
+const MAX_QUEUES: usize = 16;
+const NO_VECTOR: u16 = 0xffff;
+
+const CFG_TYPE_COMMON: u8 = 1;
+const CFG_TYPE_NOTIFY: u8 = 2;
+const CFG_TYPE_ISR: u8 = 3;
+const CFG_TYPE_DEVICE: u8 = 4;
+
+const CAP_CFG_TYPE: u16 = 0x3;
+const CAP_BAR: u16 = 0x4;
+const CAP_OFFSET: u16 = 0x8;
+const CAP_LENGTH: u16 = 0xc;
+const CAP_NOTIFY_MULTIPLIER: u16 = 0x10;
+
+const LEGACY_DEVICE_FEATURES: u16 = 0x00;
+const LEGACY_DRIVER_FEATURES: u16 = 0x04;
+const LEGACY_QUEUE_PFN: u16 = 0x08;
+const LEGACY_QUEUE_SIZE: u16 = 0x0c;
+const LEGACY_QUEUE_SELECT: u16 = 0x0e;
+const LEGACY_QUEUE_NOTIFY: u16 = 0x10;
+const LEGACY_STATUS: u16 = 0x12;
+const LEGACY_ISR: u16 = 0x13;
+const LEGACY_CONFIG_VECTOR: u16 = 0x14;
+const LEGACY_QUEUE_VECTOR: u16 = 0x16;
+const LEGACY_CONFIG: u16 = 0x14;
+const LEGACY_CONFIG_MSIX: u16 = 0x18;
+const LEGACY_PFN_SHIFT: usize = 12;
+
+const COMMON_DEVICE_FEATURE_SELECT: usize = 0x00;
+const COMMON_DEVICE_FEATURE: usize = 0x04;
+const COMMON_DRIVER_FEATURE_SELECT: usize = 0x08;
+const COMMON_DRIVER_FEATURE: usize = 0x0c;
+const COMMON_CONFIG_VECTOR: usize = 0x10;
+const COMMON_STATUS: usize = 0x14;
+const COMMON_QUEUE_SELECT: usize = 0x16;
+const COMMON_QUEUE_SIZE: usize = 0x18;
+const COMMON_QUEUE_VECTOR: usize = 0x1a;
+const COMMON_QUEUE_ENABLE: usize = 0x1c;
+const COMMON_QUEUE_NOTIFY_OFF: usize = 0x1e;
+const COMMON_QUEUE_DESC: usize = 0x20;
+const COMMON_QUEUE_DRIVER: usize = 0x28;
+const COMMON_QUEUE_DEVICE: usize = 0x30;
+
+#[derive(Clone, Copy)]
+struct Modern {
+    common: usize,
+    notify: usize,
+    notify_multiplier: u32,
+    isr: usize,
+    config: usize,
+}
+
+#[derive(Clone, Copy)]
+enum Registers {
+    Legacy(u16),
+    Modern(Modern),
+}
+
+pub struct PciTransport {
+    device: &'static Device,
+    kind: DeviceType,
+    regs: Registers,
+    msix: AtomicBool,
+    notify_offsets: [AtomicU16; MAX_QUEUES],
+}
+
+impl PciTransport {
+    // Modern capabilities are preferred, transitional devices without them
+    // fall back to the legacy IO BAR.
+    pub fn new(device: &'static Device) -> Result<Self, VirtioError> {
+        let kind = device_type(device).ok_or(VirtioError::Unsupported)?;
+        let regs = match find_modern(device)? {
+            Some(modern) => Registers::Modern(modern),
+            None if device.device() < MODERN_FIRST => match device.bar(0) {
+                Some(Bar::Io { port, .. }) => Registers::Legacy(port),
+                _ => return Err(VirtioError::MissingCapability(CFG_TYPE_COMMON)),
+            },
+            None => return Err(VirtioError::MissingCapability(CFG_TYPE_COMMON)),
+        };
+        device.enable(Command::IO | Command::MEMORY | Command::BUS_MASTER);
+
+        Ok(Self {
+            device,
+            kind,
+            regs,
+            msix: AtomicBool::new(false),
+            notify_offsets: [const { AtomicU16::new(0) }; MAX_QUEUES],
+        })
+    }
+
+    #[inline]
+    pub fn device(&self) -> &'static Device {
+        self.device
+    }
+
+    #[inline]
+    fn legacy<T: PortInOut>(base: u16, reg: u16) -> Pio<T> {
+        Pio::new(base + reg)
+    }
+
+    #[inline]
+    fn common<T>(modern: &Modern, reg: usize) -> Mmio<T> {
+        Mmio::new(modern.common + reg)
+    }
+
+    fn legacy_config(&self, base: u16, offset: u16) -> u16 {
+        if self.msix.load(Ordering::Relaxed) {
+            base + LEGACY_CONFIG_MSIX + offset
+        } else {
+            base + LEGACY_CONFIG + offset
+        }
+    }
+
+    // All queues share entry 0, config change interrupts are left unrouted.
+    fn enable_msix(&self, handler: VectorFn, data: usize) -> Result<(), VirtioError> {
+        let msix = MsiX::new(self.device)?;
+        msix.route(0, handler, data, arch::cpu_id())?;
+        // The legacy register only appears once MSI-X is enabled.
+        msix.enable();
+        match self.regs {
+            Registers::Legacy(base) => {
+                Self::legacy::<u16>(base, LEGACY_CONFIG_VECTOR).write(NO_VECTOR)
+            }
+            Registers::Modern(ref modern) => {
+                Self::common::<u16>(modern, COMMON_CONFIG_VECTOR).write(NO_VECTOR)
+            }
+        }
+        self.msix.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+fn find_modern(device: &Device) -> Result<Option<Modern>, VirtioError> {
+    let mut common = None;
+    let mut notify = None;
+    let mut isr = None;
+    let mut config = None;
+    let mut notify_multiplier = 0;
+
+    for cap in device.capabilities().filter(|cap| cap.id == CAP_VENDOR) {
+        let kind = device.read_u8(cap.offset + CAP_CFG_TYPE);
+        let slot = match kind {
+            CFG_TYPE_COMMON => &mut common,
+            CFG_TYPE_NOTIFY => &mut notify,
+            CFG_TYPE_ISR => &mut isr,
+            CFG_TYPE_DEVICE => &mut config,
+            _ => continue,
+        };
+        // The first usable capability of each type wins.
+        if slot.is_some() {
+            continue;
+        }
+        let bar = device.read_u8(cap.offset + CAP_BAR) as usize;
+        let base = match device.bar(bar) {
+            Some(Bar::Memory { addr, .. }) => addr as usize,
+            _ => continue,
+        };
+        let addr = base + device.read_u32(cap.offset + CAP_OFFSET) as usize;
+        let len = device.read_u32(cap.offset + CAP_LENGTH) as usize;
+        arch::map_mmio(addr, len)?;
+        *slot = Some(addr);
+        if kind == CFG_TYPE_NOTIFY {
+            notify_multiplier = device.read_u32(cap.offset + CAP_NOTIFY_MULTIPLIER);
+        }
+    }
+
+    match (common, notify, isr) {
+        (Some(common), Some(notify), Some(isr)) => Ok(Some(Modern {
+            common,
+            notify,
+            notify_multiplier,
+            isr,
+            // Devices without configuration fields, like entropy, have no
+            // device capability and never read it.
+            config: config.unwrap_or(0),
+        })),
+        _ => Ok(None),
+    }
+}
+
+impl Transport for PciTransport {
+    #[inline]
+    fn device_type(&self) -> DeviceType {
+        self.kind
+    }
+
+    #[inline]
+    fn is_legacy(&self) -> bool {
+        matches!(self.regs, Registers::Legacy(_))
+    }
+
+    fn status(&self) -> u8 {
+        match self.regs {
+            Registers::Legacy(base) => Self::legacy::<u8>(base, LEGACY_STATUS).read(),
+            Registers::Modern(ref modern) => Self::common::<u8>(modern, COMMON_STATUS).read(),
+        }
+    }
+
+    fn set_status(&self, status: u8) {
+        match self.regs {
+            Registers::Legacy(base) => Self::legacy::<u8>(base, LEGACY_STATUS).write(status),
+            Registers::Modern(ref modern) => {
+                Self::common::<u8>(modern, COMMON_STATUS).write(status)
+            }
+        }
+    }
+
+    fn device_features(&self) -> u64 {
+        match self.regs {
+            Registers::Legacy(base) => {
+                Self::legacy::<u32>(base, LEGACY_DEVICE_FEATURES).read() as u64
+            }
+            Registers::Modern(ref modern) => {
+                let select = Self::common::<u32>(modern, COMMON_DEVICE_FEATURE_SELECT);
+                let feature = Self::common::<u32>(modern, COMMON_DEVICE_FEATURE);
+                select.write(0);
+                let low = feature.read();
+                select.write(1);
+                let high = feature.read();
+                ((high as u64) << 32) | low as u64
+            }
+        }
+    }
+
+    fn set_driver_features(&self, features: u64) {
+        match self.regs {
+            Registers::Legacy(base) => {
+                Self::legacy::<u32>(base, LEGACY_DRIVER_FEATURES).write(features as u32)
+            }
+            Registers::Modern(ref modern) => {
+                let select = Self::common::<u32>(modern, COMMON_DRIVER_FEATURE_SELECT);
+                let feature = Self::common::<u32>(modern, COMMON_DRIVER_FEATURE);
+                select.write(0);
+                feature.write(features as u32);
+                select.write(1);
+                feature.write((features >> 32) as u32);
+            }
+        }
+    }
+
+    fn max_queue_size(&self, queue: u16) -> u16 {
+        if queue as usize >= MAX_QUEUES {
+            return 0;
+        }
+        match self.regs {
+            Registers::Legacy(base) => {
+                Self::legacy::<u16>(base, LEGACY_QUEUE_SELECT).write(queue);
+                Self::legacy::<u16>(base, LEGACY_QUEUE_SIZE).read()
+            }
+            Registers::Modern(ref modern) => {
+                Self::common::<u16>(modern, COMMON_QUEUE_SELECT).write(queue);
+                Self::common::<u16>(modern, COMMON_QUEUE_SIZE).read()
+            }
+        }
+    }
+
+    fn setup_queue(&self, queue: &VirtQueue) -> Result<(), VirtioError> {
+        let idx = queue.index();
+        if idx as usize >= MAX_QUEUES {
+            return Err(VirtioError::QueueUnavailable(idx));
+        }
+        let msix = self.msix.load(Ordering::Relaxed);
+        match self.regs {
+            Registers::Legacy(base) => {
+                Self::legacy::<u16>(base, LEGACY_QUEUE_SELECT).write(idx);
+                if Self::legacy::<u16>(base, LEGACY_QUEUE_SIZE).read() != queue.size() {
+                    return Err(VirtioError::QueueUnavailable(idx));
+                }
+                if msix {
+                    let vector = Self::legacy::<u16>(base, LEGACY_QUEUE_VECTOR);
+                    vector.write(0);
+                    if vector.read() == NO_VECTOR {
+                        return Err(VirtioError::QueueUnavailable(idx));
+                    }
+                }
+                Self::legacy::<u32>(base, LEGACY_QUEUE_PFN)
+                    .write((queue.desc_addr() >> LEGACY_PFN_SHIFT) as u32);
+            }
+            Registers::Modern(ref modern) => {
+                Self::common::<u16>(modern, COMMON_QUEUE_SELECT).write(idx);
+                Self::common::<u16>(modern, COMMON_QUEUE_SIZE).write(queue.size());
+                if msix {
+                    let vector = Self::common::<u16>(modern, COMMON_QUEUE_VECTOR);
+                    vector.write(0);
+                    if vector.read() == NO_VECTOR {
+                        return Err(VirtioError::QueueUnavailable(idx));
+                    }
+                }
+                for (reg, addr) in [
+                    (COMMON_QUEUE_DESC, queue.desc_addr()),
+                    (COMMON_QUEUE_DRIVER, queue.avail_addr()),
+                    (COMMON_QUEUE_DEVICE, queue.used_addr()),
+                ] {
+                    Self::common::<u32>(modern, reg).write(addr as u32);
+                    Self::common::<u32>(modern, reg + 4).write((addr as u64 >> 32) as u32);
+                }
+                let offset = Self::common::<u16>(modern, COMMON_QUEUE_NOTIFY_OFF).read();
+                self.notify_offsets[idx as usize].store(offset, Ordering::Relaxed);
+                Self::common::<u16>(modern, COMMON_QUEUE_ENABLE).write(1);
+            }
+        }
+        Ok(())
+    }
+
+    fn notify(&self, queue: u16) {
+        match self.regs {
+            Registers::Legacy(base) => Self::legacy::<u16>(base, LEGACY_QUEUE_NOTIFY).write(queue),
+            Registers::Modern(ref modern) => {
+                let offset = self.notify_offsets[queue as usize].load(Ordering::Relaxed);
+                let addr = modern.notify + offset as usize * modern.notify_multiplier as usize;
+                Mmio::<u16>::new(addr).write(queue);
+            }
+        }
+    }
+
+    // MSI-X is used when the device has it, otherwise the handler is shared
+    // on the legacy interrupt line.
+    fn enable_interrupts(&self, handler: VectorFn, data: usize) -> Result<(), VirtioError> {
+        match self.enable_msix(handler, data) {
+            Ok(()) => Ok(()),
+            Err(VirtioError::Msi(_)) => {
+                arch::register_irq(self.device.interrupt_line(), handler, data)?;
+                Ok(())
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    // Reading the ISR status also deasserts the legacy interrupt.
+    fn ack_interrupt(&self) -> u8 {
+        match self.regs {
+            Registers::Legacy(base) => Self::legacy::<u8>(base, LEGACY_ISR).read(),
+            Registers::Modern(ref modern) => Mmio::<u8>::new(modern.isr).read(),
+        }
+    }
+
+    fn read_config_u8(&self, offset: u16) -> u8 {
+        match self.regs {
+            Registers::Legacy(base) => Pio::<u8>::new(self.legacy_config(base, offset)).read(),
+            Registers::Modern(ref modern) => {
+                Mmio::<u8>::new(modern.config + offset as usize).read()
+            }
+        }
+    }
+
+    fn read_config_u16(&self, offset: u16) -> u16 {
+        match self.regs {
+            Registers::Legacy(base) => Pio::<u16>::new(self.legacy_config(base, offset)).read(),
+            Registers::Modern(ref modern) => {
+                Mmio::<u16>::new(modern.config + offset as usize).read()
+            }
+        }
+    }
+
+    fn read_config_u32(&self, offset: u16) -> u32 {
+        match self.regs {
+            Registers::Legacy(base) => Pio::<u32>::new(self.legacy_config(base, offset)).read(),
+            Registers::Modern(ref modern) => {
+                Mmio::<u32>::new(modern.config + offset as usize).read()
+            }
+        }
+    }
+
+    fn write_config_u8(&self, offset: u16, val: u8) {
+        match self.regs {
+            Registers::Legacy(base) => Pio::<u8>::new(self.legacy_config(base, offset)).write(val),
+            Registers::Modern(ref modern) => {
+                Mmio::<u8>::new(modern.config + offset as usize).write(val)
+            }
+        }
+    }
+
+    fn write_config_u16(&self, offset: u16, val: u16) {
+        match self.regs {
+            Registers::Legacy(base) => Pio::<u16>::new(self.legacy_config(base, offset)).write(val),
+            Registers::Modern(ref modern) => {
+                Mmio::<u16>::new(modern.config + offset as usize).write(val)
+            }
+        }
+    }
+
+    fn write_config_u32(&self, offset: u16, val: u32) {
+        match self.regs {
+            Registers::Legacy(base) => Pio::<u32>::new(self.legacy_config(base, offset)).write(val),
+            Registers::Modern(ref modern) => {
+                Mmio::<u32>::new(modern.config + offset as usize).write(val)
+            }
+        }
+    }
+}
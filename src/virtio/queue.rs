@@ -0,0 +1,245 @@
+use core::{
+    ptr,
+    sync::atomic::{fence, Ordering},
+};
+
+use ::memory::PAGE_SIZE;
+
+use crate::{arch, sync::IrqSafeMutex};
+
+use super::{Transport, VirtioError};
+
+pub const MAX_QUEUE_SIZE: u16 = 256;
+
+const DESC_NEXT: u16 = 1 << 0;
+const DESC_WRITE: u16 = 1 << 1;
+
+const USED_NO_NOTIFY: u16 = 1 << 0;
+
+// Legacy devices need the used ring on its own page, the same layout is
+// valid for modern devices.
+const QUEUE_ALIGN: usize = PAGE_SIZE;
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct Descriptor {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct UsedElem {
+    id: u32,
+    len: u32,
+}
+
+// `writable` buffers are written by the device, the rest are read by it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Buffer {
+    pub addr: usize,
+    pub len: u32,
+    pub writable: bool,
+}
+
+impl Buffer {
+    #[inline]
+    pub const fn read_only(addr: usize, len: u32) -> Self {
+        Self {
+            addr,
+            len,
+            writable: false,
+        }
+    }
+
+    #[inline]
+    pub const fn write_only(addr: usize, len: u32) -> Self {
+        Self {
+            addr,
+            len,
+            writable: true,
+        }
+    }
+}
+
+struct QueueState {
+    free_head: u16,
+    num_free: u16,
+    avail_idx: u16,
+    last_used: u16,
+}
+
+pub struct VirtQueue {
+    index: u16,
+    size: u16,
+    addr: usize,
+    pages: usize,
+    used_offset: usize,
+    state: IrqSafeMutex<QueueState>,
+}
+
+impl VirtQueue {
+    pub fn new(index: u16, size: u16) -> Result<Self, VirtioError> {
+        let n = size as usize;
+        let used_offset = (16 * n + 6 + 2 * n).next_multiple_of(QUEUE_ALIGN);
+        let pages = (used_offset + 6 + 8 * n).div_ceil(PAGE_SIZE);
+        let addr = arch::alloc_dma(pages)?;
+
+        let queue = Self {
+            index,
+            size,
+            addr,
+            pages,
+            used_offset,
+            state: IrqSafeMutex::new(QueueState {
+                free_head: 0,
+                num_free: size,
+                avail_idx: 0,
+                last_used: 0,
+            }),
+        };
+        for idx in 0..size {
+            let desc = queue.desc(idx);
+            unsafe { ptr::addr_of_mut!((*desc).next).write_volatile(idx.wrapping_add(1)) };
+        }
+        Ok(queue)
+    }
+
+    #[inline]
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    #[inline]
+    pub fn size(&self) -> u16 {
+        self.size
+    }
+
+    #[inline]
+    pub fn desc_addr(&self) -> usize {
+        self.addr
+    }
+
+    #[inline]
+    pub fn avail_addr(&self) -> usize {
+        self.addr + 16 * self.size as usize
+    }
+
+    #[inline]
+    pub fn used_addr(&self) -> usize {
+        self.addr + self.used_offset
+    }
+
+    #[inline]
+    pub fn num_free(&self) -> u16 {
+        self.state.lock().num_free
+    }
+
+    fn desc(&self, idx: u16) -> *mut Descriptor {
+        (self.desc_addr() as *mut Descriptor).wrapping_add(idx as usize)
+    }
+
+    fn avail_idx(&self) -> *mut u16 {
+        (self.avail_addr() + 2) as *mut u16
+    }
+
+    fn avail_ring(&self, slot: u16) -> *mut u16 {
+        ((self.avail_addr() + 4) as *mut u16).wrapping_add((slot % self.size) as usize)
+    }
+
+    fn used_flags(&self) -> *mut u16 {
+        self.used_addr() as *mut u16
+    }
+
+    fn used_idx(&self) -> *mut u16 {
+        (self.used_addr() + 2) as *mut u16
+    }
+
+    fn used_ring(&self, slot: u16) -> *mut UsedElem {
+        ((self.used_addr() + 4) as *mut UsedElem).wrapping_add((slot % self.size) as usize)
+    }
+
+    // Chains the buffers and makes them available to the device, returning
+    // the head descriptor that `pop_used` reports once the device is done.
+    // The device is not notified until `kick`.
+    pub fn add(&self, buffers: &[Buffer]) -> Result<u16, VirtioError> {
+        assert!(!buffers.is_empty());
+        let mut state = self.state.lock();
+        if (state.num_free as usize) < buffers.len() {
+            return Err(VirtioError::QueueFull);
+        }
+
+        let head = state.free_head;
+        let mut idx = head;
+        for (i, buffer) in buffers.iter().enumerate() {
+            let desc = self.desc(idx);
+            let mut entry = unsafe { desc.read_volatile() };
+            entry.addr = buffer.addr as u64;
+            entry.len = buffer.len;
+            entry.flags = if buffer.writable { DESC_WRITE } else { 0 };
+            if i + 1 < buffers.len() {
+                entry.flags |= DESC_NEXT;
+            }
+            unsafe { desc.write_volatile(entry) };
+            state.free_head = entry.next;
+            idx = entry.next;
+        }
+        state.num_free -= buffers.len() as u16;
+
+        unsafe { self.avail_ring(state.avail_idx).write_volatile(head) };
+        state.avail_idx = state.avail_idx.wrapping_add(1);
+        fence(Ordering::SeqCst);
+        unsafe { self.avail_idx().write_volatile(state.avail_idx) };
+        Ok(head)
+    }
+
+    pub fn kick(&self, transport: &dyn Transport) {
+        fence(Ordering::SeqCst);
+        if unsafe { self.used_flags().read_volatile() } & USED_NO_NOTIFY == 0 {
+            transport.notify(self.index);
+        }
+    }
+
+    #[inline]
+    pub fn has_used(&self) -> bool {
+        let used = unsafe { self.used_idx().read_volatile() };
+        used != self.state.lock().last_used
+    }
+
+    // Returns the head of a completed chain and the number of bytes the
+    // device wrote, releasing its descriptors.
+    pub fn pop_used(&self) -> Option<(u16, u32)> {
+        let mut state = self.state.lock();
+        if unsafe { self.used_idx().read_volatile() } == state.last_used {
+            return None;
+        }
+        fence(Ordering::SeqCst);
+        let elem = unsafe { self.used_ring(state.last_used).read_volatile() };
+        state.last_used = state.last_used.wrapping_add(1);
+
+        let head = elem.id as u16;
+        let mut idx = head;
+        loop {
+            let desc = self.desc(idx);
+            let mut entry = unsafe { desc.read_volatile() };
+            state.num_free += 1;
+            if entry.flags & DESC_NEXT == 0 {
+                entry.next = state.free_head;
+                unsafe { desc.write_volatile(entry) };
+                break;
+            }
+            idx = entry.next;
+        }
+        state.free_head = head;
+        Some((head, elem.len))
+    }
+}
+
+// The device must have been reset before its queues are dropped.
+impl Drop for VirtQueue {
+    fn drop(&mut self) {
+        arch::free_dma(self.addr, self.pages);
+    }
+}